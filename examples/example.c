@@ -1,3 +1,5 @@
+/* Generated by argen 1.1.0 from example_spec.toml. */
+/* spec sha256: 7c1f1a768293c27cdaed5dfbd17dbdc9b99c0496837364b033aadac1d6373117 */
 #include<stdlib.h>
 #include<stdio.h>
 #include<string.h>
@@ -6,22 +8,16 @@
 
 static void usage(const char *progname) {
 	printf("usage: %s [options] IN_FILE [OUT_FILE [WORD...]]\n%s", progname,
-	       "  IN_FILE\n"
-	       "        an input file for this example program\n"
-	       "  OUT_FILE\n"
-	       "        where we'll put some output\n"
-	       "  WORD\n"
-	       "        word(s) of interest\n"
-	       "  -h  --help\n"
-	       "        print this usage and exit\n"
-	       "  -b  --block-size <num>  (aliased: --blocksize --bs)\n"
-	       "        set the block size, defaults to 12.\n"
-	       "      --fav-number <num>\n"
-	       "        your favorite number\n"
-	       "  -q  --quiet\n"
-	       "        disable output\n"
-	       "      --name <arg>\n"
-	       "        your name\n"
+	       "\nArguments:\n"
+	       "  IN_FILE                 an input file for this example program\n"
+	       "  OUT_FILE                where we'll put some output\n"
+	       "  WORD...                 word(s) of interest\n"
+	       "\nOptions:\n"
+	       "  -h, --help              print this usage and exit\n"
+	       "  -b, --block-size <num>  set the block size (default: 12) (aliased: --blocksize --bs)\n"
+	       "      --fav-number <num>  your favorite number (default: 0xDEADBEEF)\n"
+	       "  -q, --quiet             disable output\n"
+	       "      --name <arg>        your name (default: John Smith)\n"
 	       );
 }
 
@@ -91,9 +87,11 @@ void parse_args(int argc, char **argv, int *block_size, int *fave_number, int *q
 		*in_file = argv[0];
 		argv++; argc--;
 	}
+	*words = NULL;
+	*words__size = 0;
 	if (argc > 0) {
 		*words = argv;
-		*words__size = argc;
+		*words__size = (size_t)argc;
 	}
 }
 
@@ -109,6 +107,8 @@ int main(int argc, char **argv) {
 
 	parse_args(argc, argv, &block_size, &fave_number, &quiet, &username, &out_file, &in_file, &words, &words__size);
 
+	/* argen:begin user */
 	/* call your code here */
 	return 0;
+	/* argen:end user */
 }
@@ -0,0 +1,342 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Diagnostics as argen prints them: `file:line: error: message`, as
+//! compilers print theirs, then the lines of the spec at fault, underlined,
+//! in color on a terminal; or with `--message-format json`, each as a line of
+//! JSON, for editors and CI to read.
+
+use crate::codegen::Label;
+use crate::json::Json;
+use crate::log::Level;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether diagnostics are in color, as --color and the terminal say.
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Whether diagnostics are JSON, as --message-format says.
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// What --color can be.
+pub const WHEN: [&str; 3] = ["auto", "always", "never"];
+
+/// What --message-format can be.
+pub const FORMATS: [&str; 2] = ["human", "json"];
+
+/// Sets whether diagnostics are in color.
+pub fn set_color(color: bool) {
+    COLOR.store(color, Ordering::Relaxed);
+}
+
+/// Sets whether diagnostics are JSON.
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Whether diagnostics, and reports like them, are JSON.
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Text in an SGR color, when diagnostics are in color.
+fn paint(sgr: &str, text: &str) -> String {
+    match COLOR.load(Ordering::Relaxed) {
+        true => format!("\x1b[{}m{}\x1b[0m", sgr, text),
+        false => text.to_owned(),
+    }
+}
+
+const BOLD: &str = "1";
+const BLUE: &str = "1;34";
+
+/// How bad a diagnostic is, or for a log line, how much argen was asked to
+/// say.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+            Severity::Debug => "debug",
+            Severity::Trace => "trace",
+        }
+    }
+    fn sgr(self) -> &'static str {
+        match self {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+            Severity::Info => "1;32",
+            Severity::Debug => "1;36",
+            Severity::Trace => "1;35",
+        }
+    }
+}
+
+/// Prints a log line on stderr, as diagnostics are printed.
+pub fn log(level: Level, message: fmt::Arguments) {
+    let severity = match level {
+        Level::Error => Severity::Error,
+        Level::Warn => Severity::Warning,
+        Level::Info => Severity::Info,
+        Level::Debug => Severity::Debug,
+        Level::Trace => Severity::Trace,
+    };
+    let line = Diagnostic::new(severity, message.to_string());
+    writeln!(&mut io::stderr(), "{}", line.render()).unwrap();
+}
+
+/// Something argen reports: how bad it is, its code, if it has one, what it
+/// says, and where it is: in the file named, at the labels of its source.
+pub struct Diagnostic<'a> {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub file: Option<&'a str>,
+    pub source: &'a str,
+    pub labels: Vec<Label>,
+    /// What to do about it, on a line of its own.
+    pub hint: Option<&'a str>,
+}
+
+impl Diagnostic<'_> {
+    /// A diagnostic about nothing in particular.
+    pub fn new(severity: Severity, message: String) -> Diagnostic<'static> {
+        Diagnostic {
+            severity,
+            code: None,
+            message,
+            file: None,
+            source: "",
+            labels: vec![],
+            hint: None,
+        }
+    }
+
+    /// The diagnostic as it is printed, in the format asked for, without a
+    /// newline at the end.
+    pub fn render(&self) -> String {
+        match JSON.load(Ordering::Relaxed) {
+            true => self.json().line(),
+            false => self.human(),
+        }
+    }
+
+    fn human(&self) -> String {
+        let at = match (self.file, self.labels.first()) {
+            (Some(file), Some(label)) => Some(format!("{}:{}", file, label.line)),
+            (file, _) => file.map(str::to_owned),
+        };
+        let message = match self.code {
+            Some(code) => format!("{} [{}]", self.message, code),
+            None => self.message.clone(),
+        };
+        let mut out = vec![header(at.as_deref(), self.severity, &message)];
+        out.push(snippet(self.source, &self.labels, self.severity));
+        out.extend(self.hint.map(str::to_owned));
+        out.retain(|line| !line.is_empty());
+        out.join("\n")
+    }
+
+    fn json(&self) -> Json {
+        let lines: Vec<&str> = self.source.lines().collect();
+        let span = |label: &Label| {
+            // columns are counted in characters, from 1
+            let text = lines
+                .get(label.line.wrapping_sub(1))
+                .copied()
+                .unwrap_or_default();
+            let column =
+                |offset: usize| Json::Num(text[..clamp(text, offset)].chars().count() as i64 + 1);
+            Json::obj(vec![
+                ("line", Json::Num(label.line as i64)),
+                ("column", column(label.columns.start)),
+                (
+                    "end_column",
+                    column(label.columns.end.max(label.columns.start)),
+                ),
+                ("note", Json::opt_str(label.note.as_deref())),
+            ])
+        };
+        Json::obj(vec![
+            ("severity", Json::Str(self.severity.name().to_owned())),
+            ("code", Json::opt_str(self.code)),
+            ("message", Json::Str(self.message.clone())),
+            ("file", Json::opt_str(self.file)),
+            ("span", self.labels.first().map_or(Json::Null, span)),
+            (
+                "related",
+                Json::Arr(self.labels.iter().skip(1).map(span).collect()),
+            ),
+        ])
+    }
+}
+
+/// A diagnostic's first line, with where it is, if anywhere.
+fn header(at: Option<&str>, severity: Severity, message: &str) -> String {
+    let kind = paint(severity.sgr(), &format!("{}:", severity.name()));
+    match at {
+        Some(at) => format!("{} {} {}", paint(BOLD, &format!("{}:", at)), kind, message),
+        None => format!("{} {}", kind, message),
+    }
+}
+
+/// How wide text is on a terminal, with tabs as four spaces.
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
+
+/// The byte offset, clamped to the text and moved back to a character.
+fn clamp(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// The labelled lines of the source, each underlined: with ^ where it is the
+/// first label, at fault, and with - for the others, which it is related to.
+/// Lines between them are left out, with ... in their place.
+pub fn snippet(source: &str, labels: &[Label], severity: Severity) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut order: Vec<usize> = (0..labels.len())
+        .filter(|&n| (1..=lines.len()).contains(&labels[n].line))
+        .collect();
+    if order.is_empty() {
+        return String::new();
+    }
+    order.sort_by_key(|&n| (labels[n].line, n));
+    let gutter = order
+        .iter()
+        .map(|&n| labels[n].line.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let bar = |number: &str| paint(BLUE, &format!("{:>1$} |", number, gutter));
+    let mut out = vec![bar("")];
+    let mut last = None;
+    for &n in &order {
+        let label = &labels[n];
+        let text = lines[label.line - 1].trim_end();
+        if last != Some(label.line) {
+            if last.is_some_and(|last| label.line > last + 1) {
+                out.push(paint(BLUE, "..."));
+            }
+            out.push(format!(
+                "{} {}",
+                bar(&label.line.to_string()),
+                text.replace('\t', "    ")
+            ));
+            last = Some(label.line);
+        }
+        let start = clamp(text, label.columns.start);
+        let end = clamp(text, label.columns.end.max(start));
+        let (mark, sgr) = match n {
+            0 => ("^", severity.sgr()),
+            _ => ("-", BLUE),
+        };
+        let marks = mark.repeat(width(&text[start..end]).max(1));
+        let note = label
+            .note
+            .as_ref()
+            .map_or_else(String::new, |note| format!(" {}", note));
+        out.push(format!(
+            "{} {}{}",
+            bar(""),
+            " ".repeat(width(&text[..start])),
+            paint(sgr, &format!("{}{}", marks, note))
+        ));
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{header, snippet, Diagnostic, Severity};
+    use crate::codegen::Label;
+
+    #[test]
+    fn annotate() {
+        let source =
+            "[[non_positional]]\nlong = \"output\"\n\n[[non_positional]]\n\tlong = \"output\"\n";
+        let labels = [
+            Label {
+                line: 5,
+                columns: 8..16,
+                note: Some(String::from("given again here")),
+            },
+            Label {
+                line: 2,
+                columns: 7..15,
+                note: Some(String::from("first given here")),
+            },
+        ];
+        assert_eq!(
+            snippet(source, &labels, Severity::Error),
+            "  |\n\
+             2 | long = \"output\"\n  \
+             |        -------- first given here\n\
+             ...\n\
+             5 |     long = \"output\"\n  \
+             |            ^^^^^^^^ given again here"
+        );
+        let at_end = [Label {
+            line: 1,
+            columns: 9..9,
+            note: None,
+        }];
+        assert_eq!(
+            snippet("parser = \n", &at_end, Severity::Error),
+            "  |\n1 | parser =\n  |         ^"
+        );
+        assert_eq!(snippet("", &at_end, Severity::Error), "");
+        assert_eq!(
+            header(Some("spec.toml:2"), Severity::Warning, "no help"),
+            "spec.toml:2: warning: no help"
+        );
+        let diagnostic = Diagnostic {
+            code: Some("E041"),
+            file: Some("spec.toml"),
+            source,
+            labels: labels.to_vec(),
+            ..Diagnostic::new(Severity::Error, String::from("duplicate long"))
+        };
+        assert_eq!(
+            diagnostic.json().line(),
+            "{\"severity\":\"error\",\"code\":\"E041\",\"message\":\"duplicate long\",\"file\":\"spec.toml\",\
+             \"span\":{\"line\":5,\"column\":9,\"end_column\":17,\"note\":\"given again here\"},\
+             \"related\":[{\"line\":2,\"column\":8,\"end_column\":16,\"note\":\"first given here\"}]}"
+        );
+        assert_eq!(
+            Diagnostic {
+                hint: Some("run with --help"),
+                ..Diagnostic::new(Severity::Error, String::from("bad"))
+            }
+            .human(),
+            "error: bad\nrun with --help"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Parsers generated by a crate's build script, into `OUT_DIR`, for a
+//! program part Rust and part C to parse its command line in C. Cargo is
+//! told to run the build script again when the spec, or a file it
+//! includes, changes, and warned of the spec's lints.
+
+use crate::codegen::{Diagnostics, Spec};
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Why a parser couldn't be generated.
+#[derive(Debug)]
+pub enum BuildError {
+    /// OUT_DIR isn't set, as it is when cargo runs a build script.
+    NoOutDir,
+    /// A file couldn't be read or written.
+    Io(PathBuf, io::Error),
+    /// Everything wrong with the spec.
+    Spec(PathBuf, Diagnostics),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::NoOutDir => write!(f, "OUT_DIR is not set, as it is for a build script"),
+            BuildError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            BuildError::Spec(path, diagnostics) => {
+                write!(f, "in spec {}:\n{}", path.display(), diagnostics)
+            }
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BuildError::NoOutDir => None,
+            BuildError::Io(_, e) => Some(e),
+            BuildError::Spec(_, diagnostics) => Some(diagnostics),
+        }
+    }
+}
+
+/// Generates the C parser for a spec, read as JSON if its extension says so
+/// and as TOML otherwise, into OUT_DIR, and returns the file written: the
+/// spec's c_file, or one named after the spec. The parser has no main, as
+/// the program's own calls parse_args.
+pub fn generate<P: AsRef<Path>>(spec: P) -> Result<PathBuf, BuildError> {
+    let out_dir = std::env::var_os("OUT_DIR").ok_or(BuildError::NoOutDir)?;
+    generate_in(spec.as_ref(), Path::new(&out_dir), &mut io::stdout())
+}
+
+/// Generates the C parser for a spec as generate does, and compiles it with
+/// cc into a static library of the given name, linked into the crate. A
+/// build script needing other compiler flags passes the file generate
+/// returns to its own `cc::Build`.
+#[cfg(feature = "cc")]
+pub fn compile<P: AsRef<Path>>(spec: P, lib: &str) -> Result<PathBuf, BuildError> {
+    let c = generate(spec)?;
+    cc::Build::new().file(&c).compile(lib);
+    Ok(c)
+}
+
+/// Generates the parser into out_dir, telling cargo, through its
+/// instructions, what the build depends on.
+fn generate_in(spec: &Path, out_dir: &Path, cargo: &mut dyn Write) -> Result<PathBuf, BuildError> {
+    let io_error = |path: &Path| {
+        let path = path.to_owned();
+        move |e| BuildError::Io(path, e)
+    };
+    let source = std::fs::read_to_string(spec).map_err(io_error(spec))?;
+    let dir = spec.parent().unwrap_or_else(|| Path::new("."));
+    let read = match spec.extension().and_then(|e| e.to_str()) {
+        Some("json") => Spec::from_json_in(&source, dir),
+        _ => Spec::from_str_in(&source, dir),
+    };
+    let mut s = read.map_err(|diagnostics| BuildError::Spec(spec.to_owned(), diagnostics))?;
+    let mut instructions = format!("cargo:rerun-if-changed={}\n", spec.display());
+    for file in s.included_files() {
+        instructions.push_str(&format!("cargo:rerun-if-changed={}\n", file.display()));
+    }
+    // cargo says it is a warning, and takes it on one line, notes and all
+    for warning in s.diagnostics(&source).iter() {
+        instructions.push_str(&format!("cargo:warning={}", spec.display()));
+        if let Some(label) = warning.labels.first() {
+            instructions.push_str(&format!(":{}", label.line));
+        }
+        instructions.push_str(&format!(": {} [{}]", warning.message, warning.code));
+        for note in &warning.notes {
+            instructions.push_str(&format!("; note: {}", note));
+        }
+        instructions.push('\n');
+    }
+    cargo
+        .write_all(instructions.as_bytes())
+        .map_err(io_error(Path::new("stdout")))?;
+    s.set_emit_main(false);
+    s.set_spec_hash(&source);
+    let name = match s.c_file().and_then(|c_file| Path::new(c_file).file_name()) {
+        Some(name) => PathBuf::from(name),
+        None => Path::new(spec.file_name().unwrap_or_default()).with_extension("c"),
+    };
+    let c = out_dir.join(name);
+    let mut file = BufWriter::new(File::create(&c).map_err(io_error(&c))?);
+    s.writeout(&mut file)
+        .and_then(|()| file.flush())
+        .map_err(io_error(&c))?;
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_in, BuildError};
+
+    #[test]
+    fn build() {
+        let dir = std::env::temp_dir().join("argen_build");
+        let out_dir = dir.join("out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        std::fs::write(
+            dir.join("common.toml"),
+            "[[non_positional]]\nc_var = \"verbose\"\nc_type = \"int\"\nlong = \"verbose\"\nflag = true\nhelp_descr = \"say more\"\n",
+        )
+        .unwrap();
+        let spec = dir.join("app.toml");
+        std::fs::write(
+            &spec,
+            "include = [\"common.toml\"]\n\n[[positional]]\nc_var = \"input\"\nc_type = \"char*\"\nhelp_name = \"INPUT\"\n",
+        )
+        .unwrap();
+        let mut cargo = vec![];
+        let c = generate_in(&spec, &out_dir, &mut cargo).unwrap();
+        assert_eq!(c, out_dir.join("app.c"));
+        let code = std::fs::read_to_string(&c).unwrap();
+        assert!(code.contains("void parse_args(int argc, char **argv, int *verbose, char* *input"));
+        assert!(!code.contains("int main("));
+        let cargo = String::from_utf8(cargo).unwrap();
+        let common = dir.join("common.toml").canonicalize().unwrap();
+        assert_eq!(
+            cargo,
+            format!(
+                "cargo:rerun-if-changed={}\ncargo:rerun-if-changed={}\ncargo:warning={}: INPUT has no help text [W001]\n",
+                spec.display(),
+                common.display(),
+                spec.display()
+            )
+        );
+        // a spec in error isn't written
+        std::fs::write(&spec, "[[positional]]\nc_var = \"1\"\n").unwrap();
+        assert!(matches!(
+            generate_in(&spec, &out_dir, &mut vec![]),
+            Err(BuildError::Spec(_, _))
+        ));
+    }
+}
@@ -14,43 +14,367 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use regex::Regex;
-use serde::Deserialize;
-use std::collections::HashSet;
+mod argp;
+pub mod backend;
+mod build;
+mod canonical;
+mod compare;
+mod cpp;
+mod ctest;
+mod define;
+mod describe;
+mod diagnostics;
+mod eval;
+mod explain;
+mod ffi;
+mod fuzz;
+mod go;
+#[cfg(feature = "cli")]
+mod import;
+mod include;
+mod ir;
+mod lint;
+mod locate;
+mod migrate;
+mod powershell;
+mod readme;
+mod rust;
+mod schema;
+mod subcommand;
+mod template;
+mod upgrade;
+mod vars;
+pub mod visit;
+mod zig;
+
+use crate::json::{self, Json};
+use crate::sha256;
+pub use backend::CodeGenerator;
+pub use compare::{Change, Impact};
+pub use define::Define;
+pub use diagnostics::{Diagnostic, Diagnostics, Severity};
+#[cfg(feature = "cli")]
+pub use import::SOURCES as IMPORT_SOURCES;
+use ir::Ir;
+pub use lint::{Warning, CODES as WARNING_CODES};
+pub use locate::Label;
+use serde::{Deserialize, Serialize};
 use std::convert::From;
 use std::error::Error;
 use std::fmt;
-use std::io::Write;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use subcommand::Global;
+pub use subcommand::Subcommand;
+pub use template::{Template, TemplateError};
+pub use upgrade::SCHEMA;
+pub use visit::SpecVisitor;
 
 const INCLUDES: [&str; 4] = ["stdlib", "stdio", "string", "getopt"];
 
-const HELP_PREFIX: &str = "\t       \"  ";
+const HELP_PREFIX: &str = "\t       \"";
+
+/// Markers around hand-written code that survives regeneration.
+const USER_BEGIN: &str = "/* argen:begin user */";
+const USER_END: &str = "/* argen:end user */";
+/// Marks both ends of the generated code in a file updated in place.
+const GENERATED_MARKER: &str = "/* argen:generated */";
+
+/// The keywords of C, through C11, and of C23 that were macros before.
+const C_KEYWORDS: [&str; 47] = [
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_Bool",
+    "_Complex",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+    "bool",
+    "true",
+    "false",
+];
+
+/// Names parse_args and main use themselves, which a variable would clash
+/// with or hide.
+const GENERATED_NAMES: [&str; 8] = [
+    "argc", "argv", "ch", "longopts", "optarg", "optind", "opterr", "optopt",
+];
+
+/// What the generated code appends to a variable's name for the variables
+/// it keeps alongside, such as `words__size` for the count of `words`.
+const GENERATED_SUFFIXES: [&str; 5] = ["__size", "__isset", "__isset_out", "__default", "__i"];
+
+/// is_identifier checks that a string is usable as a C identifier.
+fn is_identifier(i: &str) -> bool {
+    let mut chars = i.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// What is wrong with a variable name, if anything: it must be an
+/// identifier, and not one C or the generated code has taken.
+fn c_var_problem(param: &str, c_var: &str) -> Option<ValidationError> {
+    if !is_identifier(c_var) {
+        Some(ValidationError::BadIdent(
+            param.to_owned(),
+            c_var.to_owned(),
+        ))
+    } else if C_KEYWORDS.contains(&c_var) {
+        Some(ValidationError::ReservedWord(
+            param.to_owned(),
+            c_var.to_owned(),
+        ))
+    } else if GENERATED_NAMES.contains(&c_var) {
+        Some(ValidationError::GeneratedName(
+            param.to_owned(),
+            c_var.to_owned(),
+        ))
+    } else {
+        None
+    }
+}
 
 /// c_quote takes a string and quotes it suitably for use in a char* literal in C.
 fn c_quote(i: &str) -> String {
     i.replace("\"", "\\\"").replace("\n", "\\n")
 }
 
-/// Error type for sanity checks
+/// A snake_case name such as a c_var in CamelCase, for the backends whose
+/// conventions call for it.
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// The value of a C integer literal such as 12, -3, 0x1F or 017u, as the
+/// C int it is assigned to, if it is one.
+fn int_literal(lit: &str) -> Option<i32> {
+    let lit = lit.trim().trim_end_matches(|c| "uUlL".contains(c));
+    let (negative, digits) = match lit.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, lit),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse::<i64>().ok()?
+    };
+    // out of range values wrap, as they do on the usual C compilers
+    Some(if negative { -value } else { value } as i32)
+}
+
+/// Appends a "(default: ...)" note to a help description.
+fn help_with_default(mut text: String, default: &Option<String>, show: Option<bool>) -> String {
+    if let Some(default) = default {
+        if show.unwrap_or(true) {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(&format!("(default: {})", default));
+        }
+    }
+    text
+}
+
+/// The label and description of an item in the usage.
+type HelpEntry = (String, String);
+
+/// A line of help text as a C string literal, with the description column padded
+/// to width. Continuation lines of a multi-line description stay in that column.
+fn help_line(label: &str, descr: &str, width: usize) -> String {
+    if descr.is_empty() {
+        return format!("{}  {}\\n\"\n", HELP_PREFIX, c_quote(label));
+    }
+    let indent = " ".repeat(width + 2);
+    let descr = descr
+        .lines()
+        .map(c_quote)
+        .collect::<Vec<_>>()
+        .join(&format!("\\n\"\n{}{}", HELP_PREFIX, indent));
+    format!(
+        "{}  {:<width$}{}\\n\"\n",
+        HELP_PREFIX,
+        c_quote(label),
+        descr,
+        width = width
+    )
+}
+
+/// Generates the C parser for a TOML spec, or says everything wrong with it.
+/// Nothing is read but the spec, so it can't include other files, and
+/// nothing is written, so this works wherever the library is built, as in
+/// a browser.
+pub fn generate(spec: &str) -> Result<String, Diagnostics> {
+    let s = toml::from_str(spec)
+        .map_err(ValidationError::from)
+        .and_then(|value| Spec::from_value(value, None, &[]))
+        .map_err(|e| Diagnostics::new(spec, vec![e]))?;
+    Ok(s.checked(spec)?.gen())
+}
+
+/// Error type for sanity checks. Each kind of error has a code that stays the
+/// same from one release to the next; more kinds may come in any release.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ValidationError {
+    /// The spec is not valid TOML, or not a spec.
     TomlError(toml::de::Error),
+    /// The spec is not valid JSON.
+    JsonError(json::ParseError),
+    /// An item's c_var, or a name given to C, is not a C identifier.
     BadIdent(String, String),
+    /// A required item has a default, which could never be used.
     RequiredHasDefault(String),
+    /// A positional taking several values is not a char*.
     MultiNotChars(String),
+    /// An option's long name is not one getopt_long takes.
     InvalidLong(String),
+    /// An option's short name is not a single letter or digit.
     InvalidShort(String, String),
+    /// An option's alias is not a valid long name.
     InvalidAlias(String, String),
+    /// A flag is not an int.
     FlagMustBeInt(String),
+    /// A flag has a default.
     FlagHasDefault(String),
+    /// A flag is required.
     FlagCannotBeRequired(String),
+    /// A required positional comes after one that isn't.
     RequiredPositionalGoesBeforeOptionPositional(String),
+    /// A positional taking several values is not the last.
     MultiMustBeLast(String),
+    /// Portable output is asked to use getopt.
+    PortableNeedsScanner,
+    /// An option has no short name, with parser = "posix".
+    PosixNeedsShort(String),
+    /// case_insensitive is set, with parser = "posix".
+    PosixCaseInsensitive,
+    /// indent is not spaces or tabs.
+    BadIndent(String),
+    /// A formatting style argen doesn't know.
+    UnknownStyle(String),
+    /// An extra include is not a header name.
+    BadInclude(String),
+    /// A spec file included can't be read or merged, with why.
+    SpecInclude(String, String),
+    /// Two spec files included define the same item.
+    IncludeConflict(String, String, String, String),
+    /// An option set used can't be, with why.
+    OptionSet(String, String),
+    /// A variable used is not defined.
+    UnknownVar(String),
+    /// A variable is not a string.
+    BadVar(String),
+    /// A spec with subcommands uses what they can't have.
+    SubcommandsUnsupported(String),
+    /// A subcommand's name is not one a command line can give.
+    BadSubcommand(String),
+    /// Two subcommands have the same name.
+    DuplicateSubcommand(String),
+    /// A subcommand with subcommands has items or a handler.
+    SubcommandHasItems(String),
+    /// A subcommand has a handler, without an args_struct to give it.
+    HandlerNeedsStruct(String),
+    /// A subcommand's option has a global option's name.
+    GlobalConflict(String, String),
+    /// A spec has global options, but no subcommands.
+    GlobalNeedsSubcommands,
+    /// thread_safe output is asked to use getopt.
+    ThreadSafeNeedsScanner,
+    /// thread_safe output is asked for accessors.
+    ThreadSafeAccessors,
+    /// thread_safe output is asked for what keeps static state.
+    ThreadSafeKeeps(String),
+    /// An option's env is not an environment variable name.
+    InvalidEnv(String, String),
+    /// config = true, with parser = "posix".
+    PosixConfigOption,
+    /// dump_args, with parser = "posix".
+    PosixDumpArgs,
+    /// A backend can't generate what the spec asks for.
+    BackendUnsupported(String, String),
+    /// parser = "argp" can't do what the spec asks for.
+    ArgpUnsupported(String),
+    /// Two items have the same c_var.
+    DuplicateCVar(String),
+    /// Two options have the same long name.
+    DuplicateLong(String),
+    /// Two options have the same short name.
+    DuplicateShort(String, String, String),
+    /// An alias is another option's long name or alias.
+    AliasConflict(String, String, String),
+    /// An option has the name of one the parser adds itself.
+    BuiltinConflict(String, String),
+    /// A c_var is a C keyword.
+    ReservedWord(String, String),
+    /// A c_var is a name the generated parser uses.
+    GeneratedName(String, String),
+    /// A c_var is a name argen generates for another item.
+    SuffixCollision(String, String),
+    /// A value set from the command line with -D can't be set.
+    BadDefine(String, String),
+    /// The spec names a schema argen doesn't read.
+    UnknownSchema(String),
+    /// More options lack a short name than getopt has vals for: how many
+    /// need one, and how many there are.
+    TooManyOptions(usize, usize),
 }
 impl fmt::Display for ValidationError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ValidationError::TomlError(e) => e.fmt(f),
+            ValidationError::JsonError(e) => e.fmt(f),
             ValidationError::BadIdent(param, ident) =>
                 write!(f, "in param {}: invalid c variable \"{}\"", param, ident),
             ValidationError::RequiredHasDefault(param) =>
@@ -73,20 +397,180 @@ impl fmt::Display for ValidationError {
                 write!(f, "in param {}: required positional argument cannot come after a non-required one", param),
             ValidationError::MultiMustBeLast(param) =>
                 write!(f, "in param {}: only the last positional argument can take multiple values", param),
+            ValidationError::PortableNeedsScanner =>
+                write!(f, "portable output cannot use getopt, use parser = \"reentrant\""),
+            ValidationError::PosixNeedsShort(param) =>
+                write!(f, "in param {}: options must have a short name with parser = \"posix\"", param),
+            ValidationError::PosixCaseInsensitive =>
+                write!(f, "case_insensitive needs long options, which parser = \"posix\" lacks"),
+            ValidationError::BadIndent(indent) =>
+                write!(f, "indent must be spaces or tabs: \"{}\"", indent.escape_default()),
+            ValidationError::UnknownStyle(style) =>
+                write!(f, "unknown formatting style: \"{}\"", style),
+            ValidationError::ThreadSafeNeedsScanner =>
+                write!(f, "thread_safe output cannot use getopt's global state, use parser = \"reentrant\""),
+            ValidationError::ThreadSafeAccessors =>
+                write!(f, "thread_safe output cannot keep values in a static struct, use args_struct instead of accessors"),
+            ValidationError::ThreadSafeKeeps(feature) =>
+                write!(f, "thread_safe output cannot use {}, which keeps what it reads in a static list", feature),
+            ValidationError::InvalidEnv(param, env) =>
+                write!(f, "in param {}: invalid environment variable name: \"{}\"", param, env),
+            ValidationError::PosixConfigOption =>
+                write!(f, "config = true adds a --config option, which parser = \"posix\" lacks; use config_file"),
+            ValidationError::PosixDumpArgs =>
+                write!(f, "dump_args adds a --dump-args option, which parser = \"posix\" lacks"),
+            ValidationError::BackendUnsupported(backend, feature) =>
+                write!(f, "the {} backend does not support {}", backend, feature),
+            ValidationError::ArgpUnsupported(feature) =>
+                write!(f, "parser = \"argp\" does not support {}", feature),
+            ValidationError::BadInclude(include) =>
+                write!(f, "invalid extra include: \"{}\"", include.escape_default()),
+            ValidationError::SpecInclude(include, reason) =>
+                write!(f, "in include {}: {}", include, reason),
+            ValidationError::IncludeConflict(field, name, first, second) =>
+                write!(f, "{} \"{}\" is in both {} and {}", field, name, first, second),
+            ValidationError::OptionSet(set, reason) =>
+                write!(f, "in option set {}: {}", set, reason),
+            ValidationError::UnknownVar(name) =>
+                write!(f, "undefined variable ${{{}}}", name),
+            ValidationError::BadVar(name) =>
+                write!(f, "in {}: variables must be strings, in a [vars] table", name),
+            ValidationError::SubcommandsUnsupported(feature) =>
+                write!(f, "a spec with subcommands cannot use {}", feature),
+            ValidationError::BadSubcommand(name) =>
+                write!(f, "invalid subcommand name: \"{}\"", name),
+            ValidationError::DuplicateSubcommand(name) =>
+                write!(f, "duplicate subcommand: \"{}\"", name),
+            ValidationError::SubcommandHasItems(name) =>
+                write!(f, "subcommand \"{}\" has subcommands, so cannot have items or a handler of its own", name),
+            ValidationError::GlobalConflict(name, option) =>
+                write!(f, "subcommand \"{}\" has an option named {}, as a global option is", name, option),
+            ValidationError::GlobalNeedsSubcommands =>
+                write!(f, "global options are for specs with subcommands"),
+            ValidationError::HandlerNeedsStruct(name) =>
+                write!(f, "subcommand \"{}\" has a handler, which is given its args_struct, but there is none", name),
+            ValidationError::DuplicateCVar(c_var) =>
+                write!(f, "duplicate c variable: \"{}\"", c_var),
+            ValidationError::DuplicateLong(long) =>
+                write!(f, "duplicate argument long: \"{}\"", long),
+            ValidationError::DuplicateShort(param, short, other) =>
+                write!(f, "in param {}: short name \"{}\" is already the short name of param {}", param, short, other),
+            ValidationError::AliasConflict(param, alias, other) =>
+                write!(f, "in param {}: alias \"{}\" is already a long name of param {}", param, alias, other),
+            ValidationError::ReservedWord(param, ident) =>
+                write!(f, "in param {}: c variable \"{}\" is a C keyword", param, ident),
+            ValidationError::GeneratedName(param, ident) =>
+                write!(f, "in param {}: c variable \"{}\" is a name the generated parser uses itself", param, ident),
+            ValidationError::SuffixCollision(var, base) =>
+                write!(f, "c variable \"{}\" is also the name argen generates to go with \"{}\"", var, base),
+            ValidationError::BuiltinConflict(param, name) =>
+                write!(f, "in param {}: {} is taken by the built-in option of that name", param, name),
+            ValidationError::BadDefine(define, reason) =>
+                write!(f, "in -D {}: {}", define, reason),
+            ValidationError::UnknownSchema(schema) =>
+                write!(f, "schema {} is not one this argen reads, which are 1 to {}; a newer spec needs a newer argen", schema, upgrade::SCHEMA),
+            ValidationError::TooManyOptions(needed, free) =>
+                write!(f, "{} options have no short name, but getopt has only {} values to return for them", needed, free),
         }
     }
 }
 impl Error for ValidationError {}
+impl ValidationError {
+    /// The error's code, which stays the same from one version of argen to
+    /// the next, for tools to tell errors apart by.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::TomlError(_) => "E001",
+            ValidationError::JsonError(_) => "E002",
+            ValidationError::BadIdent(_, _) => "E003",
+            ValidationError::RequiredHasDefault(_) => "E004",
+            ValidationError::MultiNotChars(_) => "E005",
+            ValidationError::InvalidLong(_) => "E006",
+            ValidationError::InvalidShort(_, _) => "E007",
+            ValidationError::InvalidAlias(_, _) => "E008",
+            ValidationError::FlagMustBeInt(_) => "E009",
+            ValidationError::FlagHasDefault(_) => "E010",
+            ValidationError::FlagCannotBeRequired(_) => "E011",
+            ValidationError::RequiredPositionalGoesBeforeOptionPositional(_) => "E012",
+            ValidationError::MultiMustBeLast(_) => "E013",
+            ValidationError::PortableNeedsScanner => "E014",
+            ValidationError::PosixNeedsShort(_) => "E015",
+            ValidationError::PosixCaseInsensitive => "E016",
+            ValidationError::BadIndent(_) => "E017",
+            ValidationError::UnknownStyle(_) => "E018",
+            ValidationError::BadInclude(_) => "E019",
+            ValidationError::SpecInclude(_, _) => "E020",
+            ValidationError::IncludeConflict(_, _, _, _) => "E021",
+            ValidationError::OptionSet(_, _) => "E022",
+            ValidationError::UnknownVar(_) => "E023",
+            ValidationError::BadVar(_) => "E024",
+            ValidationError::SubcommandsUnsupported(_) => "E025",
+            ValidationError::BadSubcommand(_) => "E026",
+            ValidationError::DuplicateSubcommand(_) => "E027",
+            ValidationError::SubcommandHasItems(_) => "E028",
+            ValidationError::HandlerNeedsStruct(_) => "E029",
+            ValidationError::GlobalConflict(_, _) => "E030",
+            ValidationError::GlobalNeedsSubcommands => "E031",
+            ValidationError::ThreadSafeNeedsScanner => "E032",
+            ValidationError::ThreadSafeAccessors => "E033",
+            ValidationError::ThreadSafeKeeps(_) => "E034",
+            ValidationError::InvalidEnv(_, _) => "E035",
+            ValidationError::PosixConfigOption => "E036",
+            ValidationError::PosixDumpArgs => "E037",
+            ValidationError::BackendUnsupported(_, _) => "E038",
+            ValidationError::ArgpUnsupported(_) => "E039",
+            ValidationError::DuplicateCVar(_) => "E040",
+            ValidationError::DuplicateLong(_) => "E041",
+            ValidationError::DuplicateShort(_, _, _) => "E042",
+            ValidationError::AliasConflict(_, _, _) => "E043",
+            ValidationError::BuiltinConflict(_, _) => "E044",
+            ValidationError::ReservedWord(_, _) => "E045",
+            ValidationError::GeneratedName(_, _) => "E046",
+            ValidationError::SuffixCollision(_, _) => "E047",
+            ValidationError::BadDefine(_, _) => "E048",
+            ValidationError::UnknownSchema(_) => "E049",
+            ValidationError::TooManyOptions(_, _) => "E050",
+        }
+    }
+}
 impl From<toml::de::Error> for ValidationError {
     fn from(err: toml::de::Error) -> ValidationError {
         ValidationError::TomlError(err)
     }
 }
+impl From<json::ParseError> for ValidationError {
+    fn from(err: json::ParseError) -> ValidationError {
+        ValidationError::JsonError(err)
+    }
+}
+
+/// The TOML equivalent of a JSON value, so that a JSON spec deserializes
+/// just as TOML does. A null is as good as leaving the field out.
+fn json_to_toml(value: Json) -> Option<toml::Value> {
+    Some(match value {
+        Json::Null => return None,
+        Json::Bool(b) => toml::Value::Boolean(b),
+        Json::Num(n) => toml::Value::Integer(n),
+        Json::Str(s) => toml::Value::String(s),
+        Json::Arr(items) => {
+            toml::Value::Array(items.into_iter().filter_map(json_to_toml).collect())
+        }
+        Json::Obj(pairs) => toml::Value::Table(
+            pairs
+                .into_iter()
+                .filter_map(|(k, v)| Some((k, json_to_toml(v)?)))
+                .collect(),
+        ),
+    })
+}
 
-#[derive(Clone, Copy, Deserialize)]
-enum CType {
+/// The C type a value is parsed into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum CType {
+    /// A string, as given.
     #[serde(rename = "char*")]
     Chars,
+    /// A number, converted with atoi.
     #[serde(rename = "int")]
     Int,
 }
@@ -99,8 +583,343 @@ impl fmt::Display for CType {
     }
 }
 
-#[derive(Deserialize)]
-struct PositionalItem {
+/// What the generated parser does when the command line is wrong.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum OnError {
+    /// Print usage and exit(1).
+    Exit,
+    /// Print usage and return 1 from parse_args, which returns 0 otherwise.
+    Return,
+}
+
+/// How options are sorted in the help, among those of the same order.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum SortHelp {
+    /// By long name.
+    Alpha,
+    /// As the spec has them.
+    Spec,
+}
+
+/// Where the opening brace of a block goes.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Braces {
+    SameLine,
+    NextLine,
+}
+
+/// Spec-wide settings that change how each item's C is written.
+struct Cgen {
+    /// Strings are const char*, so that string literals and argv can be
+    /// assigned to them without discarding qualifiers.
+    const_strings: bool,
+    /// One level of indentation.
+    indent: String,
+    braces: Braces,
+    /// Longer lines are wrapped after a comma, where one can be found.
+    max_line_length: Option<usize>,
+    /// Statics inside functions are const, so nothing is shared mutably
+    /// between concurrent calls.
+    const_statics: bool,
+}
+impl Cgen {
+    /// The C spelling of a type.
+    fn ty(&self, c_type: CType) -> &'static str {
+        match c_type {
+            CType::Chars if self.const_strings => "const char*",
+            CType::Chars => "char*",
+            CType::Int => "int",
+        }
+    }
+    /// The C spelling of a type for a function-local static.
+    fn static_ty(&self, c_type: CType) -> String {
+        if self.const_statics {
+            format!("{} const", self.ty(c_type))
+        } else {
+            self.ty(c_type).to_owned()
+        }
+    }
+    /// argv, converted for assignment to an array of strings.
+    fn argv(&self, argv: &str) -> String {
+        if self.const_strings {
+            format!("(const char **){}", argv)
+        } else {
+            argv.to_owned()
+        }
+    }
+    /// Lays out generated code, which is written with a tab per level of
+    /// indentation and braces on the same line, in the configured style.
+    fn layout(&self, code: &str) -> String {
+        let mut out = String::with_capacity(code.len());
+        for line in code.lines() {
+            let body = line.trim_start_matches('\t');
+            let level = line.len() - body.len();
+            let mut lines = Vec::new();
+            if self.braces == Braces::NextLine
+                && body.ends_with(" {")
+                && !body.ends_with("= {")
+                && !body.starts_with('#')
+            {
+                let head = &body[..body.len() - 2];
+                match head.strip_prefix("} ") {
+                    Some(rest) => {
+                        lines.push("}");
+                        lines.push(rest);
+                    }
+                    None => lines.push(head),
+                }
+                lines.push("{");
+            } else {
+                lines.push(body);
+            }
+            for l in lines {
+                if l.starts_with('#') {
+                    out.push_str(l);
+                } else {
+                    out.push_str(&self.wrap(l, level));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+    /// Indents a line, breaking it after commas outside of literals if it
+    /// runs past the maximum length. Continuations are indented twice more.
+    fn wrap(&self, line: &str, level: usize) -> String {
+        let indent = self.indent.repeat(level);
+        let max = match self.max_line_length {
+            Some(max) if columns(&indent) + line.len() > max => max,
+            _ => return format!("{}{}", indent, line),
+        };
+        // split just after every ", " that sits inside parentheses
+        let mut pieces = Vec::new();
+        let (mut start, mut depth, mut quote, mut escaped) = (0, 0, None, false);
+        let bytes = line.as_bytes();
+        for (i, &b) in bytes.iter().enumerate() {
+            match quote {
+                Some(_) if escaped => escaped = false,
+                Some(_) if b == b'\\' => escaped = true,
+                Some(q) if b == q => quote = None,
+                Some(_) => {}
+                None => match b {
+                    b'"' | b'\'' => quote = Some(b),
+                    b'(' => depth += 1,
+                    b')' => depth -= 1,
+                    b',' if depth > 0 && bytes.get(i + 1) == Some(&b' ') => {
+                        pieces.push(&line[start..=i]);
+                        start = i + 2;
+                    }
+                    _ => {}
+                },
+            }
+        }
+        pieces.push(&line[start..]);
+        let cont = self.indent.repeat(level + 2);
+        let mut wrapped = indent.clone();
+        let mut width = columns(&indent);
+        for (i, piece) in pieces.iter().enumerate() {
+            if i > 0 {
+                if width + 1 + piece.len() > max {
+                    wrapped.push('\n');
+                    wrapped.push_str(&cont);
+                    width = columns(&cont);
+                } else {
+                    wrapped.push(' ');
+                    width += 1;
+                }
+            }
+            wrapped.push_str(piece);
+            width += piece.len();
+        }
+        wrapped
+    }
+}
+
+/// Generated code laid out as Cgen::layout does, a line at a time as it is
+/// written, so it goes out as it is generated rather than kept whole.
+struct LaidOut<'a, W: Write + ?Sized> {
+    cg: Cgen,
+    out: &'a mut W,
+    /// The start of a line not yet ended.
+    partial: String,
+}
+impl<W: Write + ?Sized> LaidOut<'_, W> {
+    fn new(cg: Cgen, out: &mut W) -> LaidOut<'_, W> {
+        LaidOut {
+            cg,
+            out,
+            partial: String::new(),
+        }
+    }
+    /// Lays out and writes the lines the code ends, keeping the rest.
+    fn write(&mut self, code: &str) -> io::Result<()> {
+        self.partial.push_str(code);
+        if let Some(end) = self.partial.rfind('\n') {
+            let lines: String = self.partial.drain(..=end).collect();
+            self.out.write_all(self.cg.layout(&lines).as_bytes())?;
+        }
+        Ok(())
+    }
+    /// Writes the last line, if it wasn't ended.
+    fn finish(self) -> io::Result<()> {
+        self.out.write_all(self.cg.layout(&self.partial).as_bytes())
+    }
+}
+
+/// The end of main, where the user's code goes.
+fn main_body() -> String {
+    format!(
+        "\t{}\n\t/* call your code here */\n\treturn 0;\n\t{}\n}}\n",
+        USER_BEGIN, USER_END
+    )
+}
+
+/// The text of a marker comment, in whichever language's comment syntax,
+/// or the trimmed line if it isn't a comment.
+fn marker_text(line: &str) -> &str {
+    let line = line.trim();
+    let delimiters = [
+        ("/*", "*/"),
+        ("<!--", "-->"),
+        ("<#", "#>"),
+        ("//", ""),
+        ("#", ""),
+    ];
+    for (open, close) in delimiters.iter() {
+        if let Some(text) = line.strip_prefix(open).and_then(|l| l.strip_suffix(close)) {
+            return text.trim();
+        }
+    }
+    line
+}
+
+/// Carries the hand-written regions of a previously generated file over to
+/// freshly generated code, in order. Regions beyond those in the existing
+/// file keep their generated contents.
+pub fn splice_user_regions(generated: &str, existing: &str) -> String {
+    let (begin, end) = (marker_text(USER_BEGIN), marker_text(USER_END));
+    let mut regions = Vec::new();
+    let mut region: Option<String> = None;
+    for line in existing.split_inclusive('\n') {
+        match marker_text(line) {
+            text if text == begin => region = Some(String::new()),
+            text if text == end => regions.extend(region.take()),
+            _ => {
+                if let Some(r) = region.as_mut() {
+                    r.push_str(line)
+                }
+            }
+        }
+    }
+    let mut regions = regions.into_iter();
+    let mut out = String::with_capacity(generated.len());
+    let mut skipping = false;
+    for line in generated.split_inclusive('\n') {
+        match marker_text(line) {
+            text if text == begin => {
+                out.push_str(line);
+                if let Some(r) = regions.next() {
+                    out.push_str(&r);
+                    skipping = true;
+                }
+            }
+            text if text == end => {
+                out.push_str(line);
+                skipping = false;
+            }
+            _ if skipping => {}
+            _ => out.push_str(line),
+        }
+    }
+    out
+}
+
+/// Replaces what lies between the first two generated markers in a file with
+/// freshly generated code, or returns None if the file lacks them.
+pub fn replace_generated(existing: &str, generated: &str) -> Option<String> {
+    let marker = marker_text(GENERATED_MARKER);
+    let mut lines = existing.split_inclusive('\n');
+    let mut out = String::with_capacity(existing.len() + generated.len());
+    for line in lines.by_ref() {
+        out.push_str(line);
+        if marker_text(line) == marker {
+            break;
+        }
+    }
+    let mut found = false;
+    for line in lines.by_ref() {
+        if marker_text(line) == marker {
+            out.push_str(generated);
+            out.push_str(line);
+            found = true;
+            break;
+        }
+    }
+    if !found {
+        return None;
+    }
+    out.extend(lines);
+    Some(out)
+}
+
+/// Text made safe to put in a C comment.
+fn doc_text(text: &str) -> String {
+    text.replace("*/", "* /")
+}
+
+/// The width of leading whitespace, with tabs as 8 columns.
+fn columns(indent: &str) -> usize {
+    indent.chars().map(|c| if c == '\t' { 8 } else { 1 }).sum()
+}
+
+/// Linkage of a generated function.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Linkage {
+    Static,
+    Extern,
+}
+impl Linkage {
+    /// The storage-class specifier to put before a function, if any.
+    fn specifier(self) -> &'static str {
+        match self {
+            Linkage::Static => "static ",
+            Linkage::Extern => "",
+        }
+    }
+}
+
+/// The C standard generated code must conform to.
+#[derive(Clone, Copy, PartialEq, PartialOrd, Deserialize, Serialize)]
+enum CStd {
+    #[serde(rename = "c89")]
+    C89,
+    #[serde(rename = "c99")]
+    C99,
+    #[serde(rename = "c11")]
+    C11,
+}
+
+/// How the generated parse_args scans argv for options.
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Parser {
+    /// GNU getopt_long, which keeps its state in the optind/optarg globals.
+    Getopt,
+    /// A scanner generated into the output, with all state local to parse_args.
+    Reentrant,
+    /// POSIX getopt from unistd.h, which has short options only.
+    Posix,
+    /// glibc's argp_parse, which adds --usage and --version to --help.
+    Argp,
+}
+
+/// A positional argument, by its place on the command line.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct PositionalItem {
     c_var: String,
     c_type: CType,
     help_name: String,
@@ -109,6 +928,7 @@ struct PositionalItem {
     default: Option<String>,
     //multi: c_var will be c_type*, and c_var__size will be size_t. default occupies first entry.
     multi: Option<bool>,
+    show_default: Option<bool>,
 }
 
 impl PositionalItem {
@@ -122,11 +942,15 @@ impl PositionalItem {
         self.default.is_some()
     }
     /// A suitable string to go into the parse_args declaration. Starts with ',' if anything.
-    fn cgen_decl_arg(&self) -> String {
+    fn cgen_decl_arg(&self, cg: &Cgen) -> String {
         if self.is_multi() {
-            format!(", {} **{}, size_t *{1}__size", self.c_type, self.c_var)
+            format!(
+                ", {} **{}, size_t *{1}__size",
+                cg.ty(self.c_type),
+                self.c_var
+            )
         } else {
-            format!(", {} *{}", self.c_type, self.c_var)
+            format!(", {} *{}", cg.ty(self.c_type), self.c_var)
         }
     }
     /// A suitable string to go into the parse_args function call. Starts with ',' if anything.
@@ -137,51 +961,58 @@ impl PositionalItem {
             format!(", &{}", self.c_var)
         }
     }
+    /// Pointers into the args struct, standing in for parse_args parameters.
+    fn cgen_struct_ptrs(&self, cg: &Cgen) -> String {
+        if self.is_multi() {
+            format!(
+                "\t{} **{} = &out->{1};\n\tsize_t *{1}__size = &out->{1}__size;\n",
+                cg.ty(self.c_type),
+                self.c_var
+            )
+        } else {
+            format!("\t{} *{} = &out->{1};\n", cg.ty(self.c_type), self.c_var)
+        }
+    }
     /// Declarations for the main function.
-    fn cgen_main_decls(&self) -> String {
+    fn cgen_main_decls(&self, cg: &Cgen) -> String {
         if self.is_multi() {
-            format!("\t{} *{};\n\tsize_t {1}__size;\n", self.c_type, self.c_var)
+            format!(
+                "\t{} *{};\n\tsize_t {1}__size;\n",
+                cg.ty(self.c_type),
+                self.c_var
+            )
         } else {
-            format!("\t{} {};\n", self.c_type, self.c_var)
+            format!("\t{} {};\n", cg.ty(self.c_type), self.c_var)
         }
     }
+    /// Whether parse_args keeps an __isset variable for this argument. With
+    /// exposed isset, every optional argument needs one.
+    fn tracks_isset(&self, exposed: bool) -> bool {
+        self.has_default() || (exposed && !self.is_required())
+    }
     /// Declaration of __isset variables for the parse_args (not main) function.
-    fn cgen_isset_decl(&self) -> String {
-        if self.has_default() {
+    fn cgen_isset_decl(&self, exposed: bool) -> String {
+        if self.tracks_isset(exposed) {
             format!("\tint {}__isset = 0;\n", self.c_var)
         } else {
             String::new()
         }
     }
-    /// Definition of __default variables for the parse_args (not main) function.
-    fn cgen_default_decl(&self) -> String {
-        match &self.default {
-            Some(default) => {
-                let quoted = format!("\"{}\"", c_quote(default));
-                let default = match self.c_type {
-                    CType::Chars => &quoted,
-                    CType::Int => default,
-                };
-                format!(
-                    "\tstatic {} {}__default = {};\n",
-                    self.c_type, self.c_var, default
-                )
-            }
-            _ => String::new(),
-        }
-    }
     /// Assigns value to c_var using argv[0].
-    fn cgen_assign_argv0(&self) -> String {
+    fn cgen_assign_argv0(&self, cg: &Cgen, exposed: bool) -> String {
         let indent = if self.is_required() { "\t" } else { "\t\t" };
-        let set_isset = if self.has_default() {
+        let set_isset = if self.tracks_isset(exposed) {
             format!("{}{}__isset = 1;\n", indent, self.c_var)
         } else {
             String::new()
         };
         if self.is_multi() {
             format!(
-                "{}*{} = argv;\n{0}*{1}__size = argc;\n{}",
-                indent, self.c_var, set_isset
+                "{}*{} = {};\n{0}*{1}__size = (size_t)argc;\n{}",
+                indent,
+                self.c_var,
+                cg.argv("argv"),
+                set_isset
             )
         } else {
             match self.c_type {
@@ -206,43 +1037,43 @@ impl PositionalItem {
             String::new()
         }
     }
-    /// Error if self is invalid.
-    fn validate(&self) -> Result<(), ValidationError> {
-        let identifier_re = Regex::new(r"^[_a-zA-Z][_a-zA-Z0-9]*$").unwrap();
-        if !identifier_re.is_match(&self.c_var) {
-            return Err(ValidationError::BadIdent(
-                self.help_name.to_owned(),
-                self.c_var.to_owned(),
-            ));
-        }
+    /// Every way self is invalid.
+    fn problems(&self) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        errors.extend(c_var_problem(&self.help_name, &self.c_var));
         if self.is_required() && self.has_default() {
-            return Err(ValidationError::RequiredHasDefault(
+            errors.push(ValidationError::RequiredHasDefault(
                 self.help_name.to_owned(),
             ));
         }
         if self.is_multi() {
             if let CType::Int = self.c_type {
-                return Err(ValidationError::MultiNotChars(self.help_name.to_owned()));
+                errors.push(ValidationError::MultiNotChars(self.help_name.to_owned()));
             }
         }
-        Ok(())
+        errors
     }
-    fn help(&self) -> String {
-        if let Some(d) = &self.help_descr {
-            format!(
-                "{}{}\\n\"\n{0}      {}\\n\"\n",
-                HELP_PREFIX,
-                self.help_name,
-                &c_quote(&d)
-            )
+    /// The left column of this argument's help line.
+    fn help_label(&self) -> String {
+        if self.is_multi() {
+            format!("{}...", self.help_name)
         } else {
-            format!("{}{}\\n\"\n", HELP_PREFIX, self.help_name)
+            self.help_name.to_owned()
         }
     }
+    /// The right column of this argument's help line.
+    fn help_text(&self) -> String {
+        help_with_default(
+            self.help_descr.clone().unwrap_or_default(),
+            &self.default,
+            self.show_default,
+        )
+    }
 }
 
-#[derive(Deserialize)]
-struct NonPositionalItem {
+/// An option, by its names.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct NonPositionalItem {
     c_var: String,
     c_type: CType,
     long: String,
@@ -253,6 +1084,12 @@ struct NonPositionalItem {
     required: Option<bool>,
     default: Option<String>,
     flag: Option<bool>,
+    show_default: Option<bool>,
+    /// Environment variable to take the value from when the option isn't given.
+    env: Option<String>,
+    /// Where the option goes in the help, before those of higher order.
+    /// Options without one count as 0.
+    order: Option<i64>,
 }
 
 impl NonPositionalItem {
@@ -265,46 +1102,41 @@ impl NonPositionalItem {
     fn has_default(&self) -> bool {
         self.default.is_some()
     }
+    /// Whether the value may also come from the environment or, if config is
+    /// set, a config file.
+    fn is_layered(&self, config: bool) -> bool {
+        config || self.env.is_some()
+    }
     /// A suitable string to go into the parse_args declaration. Starts with ',' if anything.
-    fn cgen_decl_arg(&self) -> String {
-        format!(", {} *{}", self.c_type, self.c_var)
+    fn cgen_decl_arg(&self, cg: &Cgen) -> String {
+        format!(", {} *{}", cg.ty(self.c_type), self.c_var)
     }
     /// A suitable string to go into the parse_args function call. Starts with ',' if anything.
     fn cgen_call_arg(&self) -> String {
         format!(", &{}", self.c_var)
     }
+    /// Pointer into the args struct, standing in for a parse_args parameter.
+    fn cgen_struct_ptr(&self, cg: &Cgen) -> String {
+        format!("\t{} *{} = &out->{1};\n", cg.ty(self.c_type), self.c_var)
+    }
     /// Declarations for the main function.
-    fn cgen_main_decl(&self) -> String {
-        format!("\t{} {};\n", self.c_type, self.c_var)
+    fn cgen_main_decl(&self, cg: &Cgen) -> String {
+        format!("\t{} {};\n", cg.ty(self.c_type), self.c_var)
     }
     /// Declaration of __isset variables for the parse_args (not main) function.
-    fn cgen_isset_decl(&self) -> String {
-        if !self.is_flag() {
+    /// Flags only need one when they are layered.
+    fn cgen_isset_decl(&self, config: bool) -> String {
+        if !self.is_flag() || self.is_layered(config) {
             format!("\tint {}__isset = 0;\n", self.c_var)
         } else {
             String::new()
         }
     }
-    /// Definition of __default variables for the parse_args (not main) function.
-    fn cgen_default_decl(&self) -> String {
-        match &self.default {
-            Some(default) => {
-                let quoted = format!("\"{}\"", c_quote(default));
-                let default = match self.c_type {
-                    CType::Chars => &quoted,
-                    CType::Int => default,
-                };
-                format!(
-                    "\tstatic {} {}__default = {};\n",
-                    self.c_type, self.c_var, default
-                )
-            }
-            _ => String::new(),
-        }
-    }
     /// Assigns value to the c_var in parse loop.
-    fn cgen_assign_optarg(&self) -> String {
-        if self.is_flag() {
+    fn cgen_assign_optarg(&self, config: bool) -> String {
+        if self.is_flag() && self.is_layered(config) {
+            format!("\t\t\t*{} = 1;\n\t\t\t{0}__isset = 1;\n", self.c_var)
+        } else if self.is_flag() {
             format!("\t\t\t*{} = 1;\n", self.c_var)
         } else {
             let set_isset = format!("\t\t\t{}__isset = 1;\n", self.c_var);
@@ -327,406 +1159,3690 @@ impl NonPositionalItem {
             uniq
         )
     }
+    /// Takes the value from the environment, then the config file if config is
+    /// set, when it wasn't given on the command line.
+    fn cgen_layers(&self, config: bool) -> String {
+        let assign = match self.c_type {
+            CType::Int if self.is_flag() => format!("*{} = args_truthy(args__value);", self.c_var),
+            CType::Int => format!("*{} = atoi(args__value);", self.c_var),
+            CType::Chars => format!("*{} = args__value;", self.c_var),
+        };
+        let mut sources = Vec::new();
+        if let Some(env) = &self.env {
+            sources.push(format!("getenv(\"{}\")", env));
+        }
+        if config {
+            sources.push(format!("args_config_get(args__config, \"{}\")", self.long));
+        }
+        sources
+            .iter()
+            .map(|source| {
+                format!(
+                    "\tif (!{0}__isset && (args__value = {1}) != NULL) {{\n\
+                     \t\t{2}\n\
+                     \t\t{0}__isset = 1;\n\
+                     \t}}\n",
+                    self.c_var, source, assign
+                )
+            })
+            .collect()
+    }
     /// Performs checks and conditional assignments after the parse loop.
-    fn cgen_post_loop(&self) -> String {
+    /// usage is the name of the generated usage function, and fail the
+    /// statement that gives up.
+    fn cgen_post_loop(&self, usage: &str, fail: &str, config: bool) -> String {
+        let layers = self.cgen_layers(config);
         if self.is_required() {
             format!(
-                "\tif (!{}__isset) {{\n\t\tusage(argv[0]);\n\t\texit(1);\n\t}}\n",
-                self.c_var
+                "{}\tif (!{}__isset) {{\n\t\t{}(argv[0]);\n\t\t{}\n\t}}\n",
+                layers, self.c_var, usage, fail
             )
         } else if self.default.is_none() {
-            String::new()
+            layers
         } else {
             format!(
-                "\tif (!{}__isset) {{\n\t\t*{0} = {0}__default;\n\t}}\n",
-                self.c_var
+                "{}\tif (!{}__isset) {{\n\t\t*{1} = {1}__default;\n\t}}\n",
+                layers, self.c_var
             )
         }
     }
-    /// Error if self is invalid.
-    fn validate(&self) -> Result<(), ValidationError> {
-        let identifier_re = Regex::new(r"^[_a-zA-Z][_a-zA-Z0-9]*$").unwrap();
-        if !identifier_re.is_match(&self.c_var) {
-            return Err(ValidationError::BadIdent(
-                self.long.to_owned(),
-                self.c_var.to_owned(),
-            ));
-        }
+    /// Every way self is invalid.
+    fn problems(&self) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        errors.extend(c_var_problem(&self.long, &self.c_var));
         if self.long.find(' ').is_some() {
-            return Err(ValidationError::InvalidLong(self.long.to_owned()));
+            errors.push(ValidationError::InvalidLong(self.long.to_owned()));
         }
         if self.is_flag() {
             if let CType::Chars = self.c_type {
-                return Err(ValidationError::FlagMustBeInt(self.long.to_owned()));
+                errors.push(ValidationError::FlagMustBeInt(self.long.to_owned()));
             }
             if self.has_default() {
-                return Err(ValidationError::FlagHasDefault(self.long.to_owned()));
+                errors.push(ValidationError::FlagHasDefault(self.long.to_owned()));
             }
             if self.is_required() {
-                return Err(ValidationError::FlagCannotBeRequired(self.long.to_owned()));
+                errors.push(ValidationError::FlagCannotBeRequired(self.long.to_owned()));
             }
         }
         if self.has_default() && self.is_required() {
-            return Err(ValidationError::RequiredHasDefault(self.long.to_owned()));
+            errors.push(ValidationError::RequiredHasDefault(self.long.to_owned()));
         }
         if let Some(short_name) = &self.short {
             if short_name.len() != 1 {
-                return Err(ValidationError::InvalidShort(
+                errors.push(ValidationError::InvalidShort(
                     self.long.to_owned(),
                     short_name.to_owned(),
                 ));
             }
         }
+        if let Some(env) = &self.env {
+            if !is_identifier(env) {
+                errors.push(ValidationError::InvalidEnv(
+                    self.long.to_owned(),
+                    env.to_owned(),
+                ));
+            }
+        }
         if let Some(aliases) = &self.aliases {
             for alias in aliases {
                 if alias.find(' ').is_some() {
-                    return Err(ValidationError::InvalidAlias(
+                    errors.push(ValidationError::InvalidAlias(
                         self.long.to_owned(),
                         alias.to_owned(),
                     ));
                 }
             }
         }
-        Ok(())
+        errors
     }
-    fn help(&self) -> String {
-        let mut long = String::from("  --");
-        long.push_str(&self.long);
+    /// The left column of this option's help line. Without long options, only
+    /// the short name is shown.
+    fn help_label(&self, long: bool) -> String {
+        let mut label = match &self.short {
+            Some(short) if !long => format!("-{}", short),
+            Some(short) => format!("-{}, --{}", short, self.long),
+            None => format!("    --{}", self.long),
+        };
         if !self.is_flag() {
             if let Some(help_name) = &self.help_name {
-                long.push_str(&format!(" <{}>", help_name));
+                label.push_str(&format!(" <{}>", help_name));
             } else {
-                long.push_str(" <arg>")
+                label.push_str(" <arg>")
             }
         }
-        if let Some(aliases) = &self.aliases {
-            long.push_str("  (aliased:");
-            for alias in aliases {
-                long.push_str(" --");
-                long.push_str(alias);
+        label
+    }
+    /// The right column of this option's help line. Aliases are long options,
+    /// so they are left out without long options.
+    fn help_text(&self, long: bool) -> String {
+        let mut text = help_with_default(
+            self.help_descr.clone().unwrap_or_default(),
+            &self.default,
+            self.show_default,
+        );
+        if let Some(aliases) = self.aliases.as_ref().filter(|_| long) {
+            if !text.is_empty() {
+                text.push(' ');
             }
-            long.push_str(")");
-        }
-        let descr = match &self.help_descr {
-            Some(h) => {
-                let mut hm = String::from("\\n\"\n\t       \"        ");
-                hm.push_str(&c_quote(&h));
-                hm
+            text.push_str("(aliased:");
+            for alias in aliases {
+                text.push_str(" --");
+                text.push_str(alias);
             }
-            _ => String::new(),
-        };
-        if let Some(short) = &self.short {
-            format!("{}-{}{}{}\\n\"\n", HELP_PREFIX, short, long, descr)
-        } else {
-            format!("{}  {}{}\\n\"\n", HELP_PREFIX, long, descr)
+            text.push(')');
         }
+        text
     }
 }
 
-#[derive(Deserialize)]
+/// A program's command line, as a spec describes it, from which argen
+/// generates a parser.
+#[derive(Clone, Deserialize, Serialize)]
 pub struct Spec {
+    /// The version of the spec format the spec is written in; 1 when left
+    /// out, from before there were versions.
+    schema: Option<u32>,
     /// Positional must be ordered: required, then optional.
     /// Only the last PositionalItem can be multi.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     positional: Vec<PositionalItem>,
     /// Non-positional is unordered.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     non_positional: Vec<NonPositionalItem>,
+    /// Commands chosen by the first argument, each with its own items.
+    subcommand: Option<Vec<Subcommand>>,
+    /// Options taken by every subcommand.
+    global: Option<Global>,
+    /// Everything after a `--` separator is captured into a char** of this name
+    /// (with a size_t of the same name suffixed by __size).
+    capture_rest: Option<String>,
+    /// Stop option parsing at the first non-option, leaving operands untouched.
+    strict_posix: Option<bool>,
+    /// Accept unambiguous abbreviations of long options, as getopt_long does.
+    allow_abbrev: Option<bool>,
+    /// Match long option names regardless of case.
+    case_insensitive: Option<bool>,
+    /// Results go into a struct of this name rather than one out-parameter per item.
+    args_struct: Option<String>,
+    /// Keep the parsed values private, exposing args_parse and getter functions.
+    accessors: Option<bool>,
+    /// Prepended, with an underscore, to the names of generated functions.
+    prefix: Option<String>,
+    /// Also hand back an __isset for every optional item, saying whether it was given.
+    expose_isset: Option<bool>,
+    /// Generate a main function calling parse_args.
+    emit_main: Option<bool>,
+    /// Generate the usage function; otherwise it is only declared.
+    emit_usage: Option<bool>,
+    /// How options of the same order are sorted in the help.
+    sort_help: Option<SortHelp>,
+    /// Generate the parse_args function; otherwise it is only declared.
+    emit_parse_args: Option<bool>,
+    usage_linkage: Option<Linkage>,
+    parse_args_linkage: Option<Linkage>,
+    parser: Option<Parser>,
+    /// Only use the standard C library, so output also builds on Windows.
+    portable: Option<bool>,
+    std: Option<CStd>,
+    /// Generate code that compiles cleanly with -Wall -Wextra -Wpedantic
+    /// -Wwrite-strings -Wsign-conversion.
+    pedantic: Option<bool>,
+    /// One level of indentation in the generated code.
+    indent: Option<String>,
+    braces: Option<Braces>,
+    max_line_length: Option<usize>,
+    /// Put Doxygen comments, taken from the help metadata, on parse_args,
+    /// the args struct and its fields.
+    doxygen: Option<bool>,
+    /// Included after the standard headers; quoted unless given in <...>.
+    extra_includes: Option<Vec<String>>,
+    /// Declarations emitted after the includes, one per entry.
+    extra_decls: Option<Vec<String>>,
+    /// Called with argc and argv at the start of parse_args.
+    pre_parse_fn: Option<String>,
+    /// Called with the original argc and argv, and the args struct if there
+    /// is one, at the end of parse_args.
+    post_parse_fn: Option<String>,
+    on_error: Option<OnError>,
+    /// Generate parse_args_cleanup, releasing whatever parse_args allocated.
+    cleanup: Option<bool>,
+    /// Generate args_dump, printing every parsed value to a FILE *.
+    dump: Option<bool>,
+    /// Add a hidden --dump-args option printing the parsed values as JSON.
+    dump_args: Option<bool>,
+    /// parse_args may be called from several threads at once.
+    thread_safe: Option<bool>,
+    /// Expand @file arguments into the whitespace-separated words of the file.
+    response_files: Option<bool>,
+    /// Add a --config option naming a key=value file of option defaults.
+    config: Option<bool>,
+    /// The config file read when --config isn't given, if it exists.
+    config_file: Option<String>,
+    /// Printed by --version, with parser = "argp".
+    version: Option<String>,
+    /// Where to report bugs, shown in --help with parser = "argp".
+    bug_address: Option<String>,
+    /// The file `argen --auto-name` writes the C parser to, rather than one
+    /// named after the spec.
+    c_file: Option<String>,
+    /// Says what generated the output, in a comment at the top of each file.
+    #[serde(skip)]
+    provenance: Option<String>,
+    /// SHA-256 of the spec's source, noted in a comment at the top of each file.
+    #[serde(skip)]
+    spec_hash: Option<String>,
+    /// The subcommand a subcommand's parser is for, named in its usage.
+    #[serde(skip)]
+    command: Option<String>,
+    /// What reading the spec warned of: fields of its tables that aren't in
+    /// the spec format, and settings given with -D that its parser ignores.
+    #[serde(skip)]
+    read_warnings: Vec<Warning>,
+    /// The files included, and those they include.
+    #[serde(skip)]
+    included: Vec<PathBuf>,
 }
 
 impl Spec {
-    /// Deserializes toml from a string into a Spec.
-    pub fn from_str(toml: &str) -> Result<Spec, ValidationError> {
-        let s: Spec = toml::from_str(toml)?;
-        s.validate()?;
+    /// Deserializes toml from a string into a Spec, with its includes found
+    /// relative to the current directory, or says everything wrong with it.
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(toml: &str) -> Result<Spec, Diagnostics> {
+        Spec::from_str_in(toml, Path::new("."))
+    }
+    /// Deserializes toml into a Spec, with its includes found relative to
+    /// dir, or says everything wrong with it.
+    pub fn from_str_in(toml: &str, dir: &Path) -> Result<Spec, Diagnostics> {
+        Spec::parse_str_in(toml, dir)
+            .map_err(|e| Diagnostics::new(toml, vec![e]))?
+            .checked(toml)
+    }
+    /// Deserializes toml into a Spec without validating it, so its problems
+    /// can all be listed.
+    pub fn parse_str_in(toml: &str, dir: &Path) -> Result<Spec, ValidationError> {
+        Spec::parse_str_with(toml, dir, &[])
+    }
+    /// Deserializes toml into a Spec without validating it, with values set
+    /// from the command line.
+    pub fn parse_str_with(
+        toml: &str,
+        dir: &Path,
+        defines: &[Define],
+    ) -> Result<Spec, ValidationError> {
+        Spec::from_value(toml::from_str(toml)?, Some(dir), defines)
+    }
+    /// Deserializes JSON, with the same fields as the TOML, into a Spec, with
+    /// its includes found relative to the current directory, or says
+    /// everything wrong with it.
+    pub fn from_json(json: &str) -> Result<Spec, Diagnostics> {
+        Spec::from_json_in(json, Path::new("."))
+    }
+    /// Deserializes JSON into a Spec, with its includes found relative to
+    /// dir, or says everything wrong with it.
+    pub fn from_json_in(json: &str, dir: &Path) -> Result<Spec, Diagnostics> {
+        Spec::parse_json_in(json, dir)
+            .map_err(|e| Diagnostics::new(json, vec![e]))?
+            .checked(json)
+    }
+    /// Deserializes JSON into a Spec without validating it.
+    pub fn parse_json_in(json: &str, dir: &Path) -> Result<Spec, ValidationError> {
+        Spec::parse_json_with(json, dir, &[])
+    }
+    /// Deserializes JSON into a Spec without validating it, with values set
+    /// from the command line.
+    pub fn parse_json_with(
+        json: &str,
+        dir: &Path,
+        defines: &[Define],
+    ) -> Result<Spec, ValidationError> {
+        let value = json_to_toml(json::parse(json)?)
+            .unwrap_or_else(|| toml::Value::Table(Default::default()));
+        Spec::from_value(value, Some(dir), defines)
+    }
+    /// Deserializes a spec's value, with its includes found relative to dir,
+    /// or without one, refused.
+    fn from_value(
+        value: toml::Value,
+        dir: Option<&Path>,
+        defines: &[Define],
+    ) -> Result<Spec, ValidationError> {
+        let (value, included) = include::resolve(value, dir)?;
+        let value = define::apply(value, defines)?;
+        let value = vars::interpolate(value)?;
+        upgrade::schema(&value)?;
+        let read_warnings = lint::unused_fields(&value);
+        let mut s: Spec = value.try_into()?;
+        s.read_warnings = read_warnings;
+        s.read_warnings.extend(define::ignored(defines, &s));
+        s.included = included;
         Ok(s)
     }
-    /// Check all items in the spec to make sure they are valid.
-    fn validate(&self) -> Result<(), ValidationError> {
-        let mut saw_optional = false;
-        for (i, pi) in self.positional.iter().enumerate() {
-            pi.validate()?;
-            if pi.is_required() && saw_optional {
-                return Err(
-                    ValidationError::RequiredPositionalGoesBeforeOptionPositional(
-                        pi.help_name.to_owned(),
-                    ),
-                );
-            }
-            if pi.is_multi() && i != self.positional.len() - 1 {
-                return Err(ValidationError::MultiMustBeLast(pi.help_name.to_owned()));
-            }
-            if !pi.is_required() {
-                saw_optional = true
-            }
-        }
-        for npi in &self.non_positional {
-            npi.validate()?
+    fn is_strict_posix(&self) -> bool {
+        self.strict_posix.unwrap_or(false)
+    }
+    fn allows_abbrev(&self) -> bool {
+        self.allow_abbrev.unwrap_or(true)
+    }
+    fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive.unwrap_or(false)
+    }
+    fn has_accessors(&self) -> bool {
+        self.accessors.unwrap_or(false)
+    }
+    fn is_isset_exposed(&self) -> bool {
+        self.expose_isset.unwrap_or(false)
+    }
+    /// The name of a generated function, after applying any prefix.
+    fn sym(&self, name: &str) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}_{}", prefix, name),
+            None => name.to_owned(),
         }
-        Ok(())
     }
-    /// Creates the necessary headers in C.
-    fn cgen_headers(&self) -> String {
-        INCLUDES
-            .iter()
-            .map(|s| format!("#include<{}.h>\n", s))
-            .collect()
+    fn emits_main(&self) -> bool {
+        self.emit_main.unwrap_or(true)
     }
-    /// Creates the usage function in C.
-    fn cgen_usage(&self) -> String {
-        let positional_usage = {
-            let mut pos = String::new();
-            let mut noptional = 0;
-            for pi in &self.positional {
-                pos.push(' ');
-                if !pi.is_required() {
-                    pos.push('[');
-                    noptional += 1;
-                }
-                pos.push_str(&pi.help_name);
-                if pi.is_multi() {
-                    pos.push_str("...");
-                }
+    fn emits_usage(&self) -> bool {
+        self.emit_usage.unwrap_or(true)
+    }
+    /// The indices of options in the order the help shows them: by their
+    /// order, and then alphabetically or as the spec has them.
+    fn help_order(&self, options: &[NonPositionalItem]) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..options.len()).collect();
+        match self.sort_help.unwrap_or(SortHelp::Spec) {
+            SortHelp::Alpha => {
+                order.sort_by_key(|&i| (options[i].order.unwrap_or(0), &options[i].long))
             }
-            pos.push_str(&(0..noptional).map(|_| ']').collect::<String>());
-            pos
-        };
-        let mut help = String::new();
-        for pi_usage in self.positional.iter().map(PositionalItem::help) {
-            help.push_str(&pi_usage)
-        }
-        help.push_str(&format!(
-            "{0}-h  --help\\n\"\n\
-             {0}      print this usage and exit\\n\"\n",
-            HELP_PREFIX
-        ));
-        for npi_usage in self.non_positional.iter().map(NonPositionalItem::help) {
-            help.push_str(&npi_usage)
+            SortHelp::Spec => order.sort_by_key(|&i| options[i].order.unwrap_or(0)),
         }
-        format!(
-            "static void usage(const char *progname) {{\n\
-             \tprintf(\"usage: %s [options]{}\\n%s\", progname,\n\
-             {}\t       );\n\
-             }}\n",
-            positional_usage, help
-        )
+        order
     }
-    /// Creates the parse_args function in C.
-    fn cgen_decl(&self) -> String {
-        let mut body = String::new();
-        body.push_str("void parse_args(int argc, char **argv");
-        for npi in &self.non_positional {
-            body.push_str(&npi.cgen_decl_arg())
+    /// The options in the order the help shows them.
+    fn help_options(&self) -> Vec<&NonPositionalItem> {
+        self.help_order(&self.non_positional)
+            .into_iter()
+            .map(|i| &self.non_positional[i])
+            .collect()
+    }
+    fn emits_parse_args(&self) -> bool {
+        self.emit_parse_args.unwrap_or(true)
+    }
+    fn usage_linkage(&self) -> Linkage {
+        self.usage_linkage.unwrap_or(Linkage::Static)
+    }
+    /// parse_args is private by default when only reached through accessors.
+    fn parse_args_linkage(&self) -> Linkage {
+        match self.parse_args_linkage {
+            Some(linkage) => linkage,
+            None if self.has_accessors() => Linkage::Static,
+            None => Linkage::Extern,
         }
-        for pi in &self.positional {
-            body.push_str(&pi.cgen_decl_arg())
+    }
+    fn is_portable(&self) -> bool {
+        self.portable.unwrap_or(false)
+    }
+    /// Portable output has no getopt.h to rely on, so it scans options itself.
+    fn parser(&self) -> Parser {
+        match self.parser {
+            Some(parser) => parser,
+            None if self.is_portable() || self.is_thread_safe() => Parser::Reentrant,
+            None => Parser::Getopt,
         }
-        body.push_str(") {\n");
-
-        // decls for __isset
-        for npi in &self.non_positional {
-            body.push_str(&npi.cgen_isset_decl());
+    }
+    fn is_pedantic(&self) -> bool {
+        self.pedantic.unwrap_or(false)
+    }
+    /// Settings for generating each item.
+    fn cgen(&self) -> Cgen {
+        Cgen {
+            const_strings: self.is_pedantic(),
+            indent: self.indent.clone().unwrap_or_else(|| String::from("\t")),
+            braces: self.braces.unwrap_or(Braces::SameLine),
+            max_line_length: self.max_line_length,
+            const_statics: self.is_thread_safe(),
         }
-        for pi in &self.positional {
-            body.push_str(&pi.cgen_isset_decl());
+    }
+    fn std(&self) -> CStd {
+        self.std.unwrap_or(CStd::C99)
+    }
+    fn has_long_options(&self) -> bool {
+        self.parser() != Parser::Posix
+    }
+    /// The C type of entries in longopts. The reentrant parser brings its own,
+    /// so it can't clash with getopt.h if both end up in one translation unit.
+    fn option_type(&self) -> &'static str {
+        match self.parser() {
+            Parser::Getopt | Parser::Posix => "struct option",
+            Parser::Reentrant => "struct args_option",
+            Parser::Argp => "struct argp_option",
         }
-        // defs for __default
-        for npi in &self.non_positional {
-            body.push_str(&npi.cgen_default_decl());
+    }
+    /// The file the spec names for its C parser.
+    pub fn c_file(&self) -> Option<&str> {
+        self.c_file.as_deref()
+    }
+    /// The files the spec includes, and those they include in turn, each
+    /// once, as they were read.
+    pub fn included_files(&self) -> &[PathBuf] {
+        &self.included
+    }
+    /// Overrides whether a main function is generated.
+    pub fn set_emit_main(&mut self, emit_main: bool) {
+        self.emit_main = Some(emit_main)
+    }
+    /// Notes the hash of the spec's source text in every generated file, so
+    /// build tools can tell whether it is up to date.
+    pub fn set_spec_hash(&mut self, source: &str) {
+        self.spec_hash = Some(sha256::hex_digest(source.as_bytes()))
+    }
+    /// The comments that start every generated file.
+    fn cgen_stamp(&self) -> String {
+        let mut stamp = self.provenance.clone().unwrap_or_default();
+        if let Some(hash) = &self.spec_hash {
+            stamp.push_str(&format!("/* spec sha256: {} */\n", hash));
         }
-        for pi in &self.positional {
-            body.push_str(&pi.cgen_default_decl());
+        stamp
+    }
+    /// The stamp, with each comment written between open and close instead,
+    /// for languages other than C.
+    fn stamp_with(&self, open: &str, close: &str) -> String {
+        self.cgen_stamp()
+            .lines()
+            .map(|line| {
+                let text = line.trim_start_matches("/* ").trim_end_matches(" */");
+                format!("{} {}{}\n", open, text, close)
+            })
+            .collect()
+    }
+    /// Starts every generated file with a comment saying it was generated by
+    /// the given generator from the given spec file.
+    pub fn set_provenance(&mut self, generator: &str, spec: &str) {
+        self.provenance = Some(format!(
+            "/* Generated by {} from {}. */\n",
+            generator,
+            doc_text(spec)
+        ))
+    }
+    /// Approximates one of clang-format's predefined styles with the
+    /// indent, braces and max_line_length settings.
+    pub fn set_style(&mut self, style: &str) -> Result<(), ValidationError> {
+        let (indent, braces, max) = match style.to_ascii_lowercase().as_str() {
+            "llvm" | "google" | "chromium" | "mozilla" => (2, Braces::SameLine, Some(80)),
+            "webkit" => (4, Braces::SameLine, None),
+            "gnu" => (2, Braces::NextLine, Some(79)),
+            "microsoft" => (4, Braces::NextLine, Some(120)),
+            _ => return Err(ValidationError::UnknownStyle(style.to_owned())),
+        };
+        self.indent = Some(" ".repeat(indent));
+        self.braces = Some(braces);
+        self.max_line_length = max;
+        Ok(())
+    }
+    /// The c_var of every item whose __isset is handed back to the caller.
+    fn exposed_issets(&self) -> Vec<&str> {
+        if !self.is_isset_exposed() {
+            return Vec::new();
+        }
+        let npis = self
+            .non_positional
+            .iter()
+            .filter(|npi| !npi.is_flag() && !npi.is_required())
+            .map(|npi| npi.c_var.as_str());
+        let pis = self
+            .positional
+            .iter()
+            .filter(|pi| !pi.is_required())
+            .map(|pi| pi.c_var.as_str());
+        npis.chain(pis).collect()
+    }
+    /// The name of the struct parse_args fills in, if it uses one.
+    fn on_error(&self) -> OnError {
+        self.on_error.unwrap_or(OnError::Exit)
+    }
+    /// The return type of parse_args and args_parse.
+    fn parse_args_type(&self) -> &'static str {
+        match self.on_error() {
+            OnError::Exit => "void",
+            OnError::Return => "int",
+        }
+    }
+    /// The statement that gives up on a bad command line.
+    fn cgen_fail(&self) -> &'static str {
+        match self.on_error() {
+            OnError::Exit => "exit(1);",
+            OnError::Return => "return 1;",
+        }
+    }
+    fn is_thread_safe(&self) -> bool {
+        self.thread_safe.unwrap_or(false)
+    }
+    fn has_response_files(&self) -> bool {
+        self.response_files.unwrap_or(false)
+    }
+    fn has_config_option(&self) -> bool {
+        self.config.unwrap_or(false)
+    }
+    /// Whether option defaults can come from a config file.
+    fn has_config(&self) -> bool {
+        self.has_config_option() || self.config_file.is_some()
+    }
+    /// Whether parse_args keeps what it allocates for parse_args_cleanup. A
+    /// config file is only read if there are options to configure.
+    fn keeps_allocations(&self) -> bool {
+        self.has_response_files() || (self.has_config() && !self.non_positional.is_empty())
+    }
+    fn has_cleanup(&self) -> bool {
+        self.cleanup.unwrap_or(false)
+    }
+    fn has_dump(&self) -> bool {
+        self.dump.unwrap_or(false)
+    }
+    fn has_dump_args(&self) -> bool {
+        self.dump_args.unwrap_or(false)
+    }
+    fn has_doxygen(&self) -> bool {
+        self.doxygen.unwrap_or(false)
+    }
+    fn struct_name(&self) -> Option<&str> {
+        match &self.args_struct {
+            Some(name) => Some(name),
+            None if self.has_accessors() => Some("args"),
+            None => None,
+        }
+    }
+    /// Check all items in the spec to make sure they are valid.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self.problems().into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+    /// Every way the spec is invalid, where validate stops at the first.
+    pub fn problems(&self) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        let mut saw_optional = false;
+        for (i, pi) in self.positional.iter().enumerate() {
+            errors.extend(pi.problems());
+            if pi.is_required() && saw_optional {
+                errors.push(
+                    ValidationError::RequiredPositionalGoesBeforeOptionPositional(
+                        pi.help_name.to_owned(),
+                    ),
+                );
+            }
+            if pi.is_multi() && i != self.positional.len() - 1 {
+                errors.push(ValidationError::MultiMustBeLast(pi.help_name.to_owned()));
+            }
+            if !pi.is_required() {
+                saw_optional = true
+            }
         }
-
-        // longopts
-        // unique chars for each longopt
-        let mut all_bytes: HashSet<u8> = (2..255).collect();
-        // remove chars that are used for small opts
         for npi in &self.non_positional {
-            if let Some(s) = &npi.short {
-                all_bytes.remove(&s.as_bytes()[0]);
+            errors.extend(npi.problems());
+        }
+        errors.extend(self.collisions());
+        errors.extend(self.vals_problem());
+        if let Some(rest) = &self.capture_rest {
+            errors.extend(c_var_problem("capture_rest", rest));
+        }
+        if self.is_portable() && self.parser() != Parser::Reentrant {
+            errors.push(ValidationError::PortableNeedsScanner);
+        }
+        if self.is_thread_safe() {
+            if self.parser() != Parser::Reentrant {
+                errors.push(ValidationError::ThreadSafeNeedsScanner);
+            }
+            if self.has_accessors() {
+                errors.push(ValidationError::ThreadSafeAccessors);
+            }
+            if self.has_response_files() {
+                errors.push(ValidationError::ThreadSafeKeeps(String::from(
+                    "response_files",
+                )));
+            }
+            if self.has_config() {
+                errors.push(ValidationError::ThreadSafeKeeps(String::from(
+                    "a config file",
+                )));
             }
         }
-        let mut unused_bytes = all_bytes.drain().collect::<Vec<_>>();
-        unused_bytes.sort();
-        unused_bytes.reverse();
-        let mut next_free_shortname = unused_bytes.into_iter();
-        let uniqs: Vec<u8> = self
-            .non_positional
-            .iter()
-            .map(|npi| {
-                if let Some(s) = &npi.short {
-                    s.as_bytes()[0]
-                } else {
-                    next_free_shortname
-                        .next()
-                        .expect("too many non-positional arguments")
+        if !self.has_long_options() {
+            if let Some(npi) = self.non_positional.iter().find(|npi| npi.short.is_none()) {
+                errors.push(ValidationError::PosixNeedsShort(npi.long.to_owned()));
+            }
+            if self.is_case_insensitive() {
+                errors.push(ValidationError::PosixCaseInsensitive);
+            }
+            if self.has_config_option() {
+                errors.push(ValidationError::PosixConfigOption);
+            }
+            if self.has_dump_args() {
+                errors.push(ValidationError::PosixDumpArgs);
+            }
+        }
+        if self.parser() == Parser::Argp {
+            // argp matches long options with getopt_long's rules, always
+            if !self.allows_abbrev() {
+                errors.push(ValidationError::ArgpUnsupported(String::from(
+                    "allow_abbrev = false",
+                )));
+            }
+            if self.is_case_insensitive() {
+                errors.push(ValidationError::ArgpUnsupported(String::from(
+                    "case_insensitive",
+                )));
+            }
+        }
+        if let Some(prefix) = &self.prefix {
+            if !is_identifier(prefix) {
+                errors.push(ValidationError::BadIdent(
+                    String::from("prefix"),
+                    prefix.to_owned(),
+                ));
+            }
+        }
+        if let Some(name) = &self.args_struct {
+            if !is_identifier(name) {
+                errors.push(ValidationError::BadIdent(
+                    String::from("args_struct"),
+                    name.to_owned(),
+                ));
+            }
+        }
+        for (param, hook) in &[
+            ("pre_parse_fn", &self.pre_parse_fn),
+            ("post_parse_fn", &self.post_parse_fn),
+        ] {
+            if let Some(hook) = hook {
+                if !is_identifier(hook) {
+                    errors.push(ValidationError::BadIdent(
+                        String::from(*param),
+                        hook.to_owned(),
+                    ));
                 }
-            })
-            .collect();
-        body.push_str("\tstatic struct option longopts[] = {\n");
-        for (i, npi) in self.non_positional.iter().enumerate() {
-            body.push_str(&npi.cgen_getopt(uniqs[i]));
+            }
         }
-        body.push_str(
-            "\t\t{\"help\", 0, 0, 'h'},\n\
-             \t\t{0, 0, 0, 0}\n\t};\n",
-        );
-
-        // shortopts
-        let mut optstring = String::from_utf8(
-            self.non_positional
-                .iter()
-                .filter(|npi| npi.short.is_some())
-                .flat_map(|npi| {
-                    let s = npi.short.clone();
-                    let mut v = Vec::new();
-                    v.push(s.unwrap().as_bytes()[0]);
-                    if !npi.is_flag() {
-                        v.push(b':');
-                    }
-                    v.into_iter().collect::<Vec<u8>>()
-                })
-                .collect(),
-        )
-        .unwrap();
-        optstring.push('h');
-
-        // parse loop, optional
-        body.push_str(&format!(
-            "\tint ch;\n\
-             \twhile ((ch = getopt_long(argc, argv, \"{}\", longopts, NULL)) != -1) {{\n\
-             \t\tswitch (ch) {{\n",
-            optstring
-        ));
-        for (i, uniq) in uniqs.iter().enumerate() {
-            body.push_str(&format!(
-                "\t\tcase {}:\n{}\t\t\tbreak;\n",
-                uniq,
-                self.non_positional[i].cgen_assign_optarg()
-            ));
+        for include in self.extra_includes.iter().flatten() {
+            let bracketed = include.starts_with('<') && include.ends_with('>');
+            if include.is_empty()
+                || include.contains(['"', '\n'])
+                || (include.contains(['<', '>']) && !bracketed)
+            {
+                errors.push(ValidationError::BadInclude(include.to_owned()));
+            }
         }
-        body.push_str(
-            "\t\tcase 0:\n\t\t\tbreak;\n\
-             \t\tcase 'h':\n\
-             \t\tdefault:\n\t\t\tusage(argv[0]);\n\t\t\texit(1);\n\
-             \t\t}\n\t}\n",
-        );
-
-        // post loop, optional
-        for npi in &self.non_positional {
-            body.push_str(&npi.cgen_post_loop());
+        if let Some(indent) = &self.indent {
+            if indent.is_empty() || !indent.chars().all(|c| c == ' ' || c == '\t') {
+                errors.push(ValidationError::BadIndent(indent.to_owned()));
+            }
         }
-
-        // parse+post loop, positional
-        let required: Vec<&PositionalItem> = self
+        if self.has_subcommands() {
+            errors.extend(self.subcommand_problems());
+        } else if self.global.is_some() {
+            errors.push(ValidationError::GlobalNeedsSubcommands);
+        }
+        errors
+    }
+    /// The short and long names of the options the parser adds itself.
+    fn builtin_names(&self) -> (Vec<&'static str>, Vec<&'static str>) {
+        match (self.parser(), &self.version) {
+            (Parser::Argp, Some(_)) => (vec!["?", "V"], vec!["help", "usage", "version"]),
+            (Parser::Argp, None) => (vec!["?"], vec!["help", "usage"]),
+            _ => (vec!["h"], vec!["help"]),
+        }
+    }
+    /// Names given to more than one item, or taken by the parser's own
+    /// options, each reported once.
+    fn collisions(&self) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        let mut c_vars: Vec<&str> = vec![];
+        let all_c_vars = self
             .positional
             .iter()
-            .filter(|p| p.is_required() && !p.is_multi())
+            .map(|pi| &pi.c_var)
+            .chain(self.non_positional.iter().map(|npi| &npi.c_var));
+        for c_var in all_c_vars {
+            if c_vars.contains(&c_var.as_str()) {
+                errors.push(ValidationError::DuplicateCVar(c_var.to_owned()));
+            }
+            c_vars.push(c_var);
+        }
+        // words__size is generated for words, so can't be a variable too
+        let bases: Vec<&str> = c_vars
+            .iter()
+            .copied()
+            .chain(self.capture_rest.as_deref())
             .collect();
-        let nrequired = required.len()
-            + if self
-                .positional
-                .iter()
-                .any(|p| p.is_required() && p.is_multi())
-            {
-                1
-            } else {
-                0
+        for c_var in &c_vars {
+            let base = GENERATED_SUFFIXES.iter().find_map(|suffix| {
+                let base = c_var.strip_suffix(suffix)?;
+                bases.iter().find(|b| **b == base)
+            });
+            if let Some(base) = base {
+                errors.push(ValidationError::SuffixCollision(
+                    (*c_var).to_owned(),
+                    (*base).to_owned(),
+                ));
+            }
+        }
+        let (builtin_shorts, builtin_longs) = self.builtin_names();
+        let mut shorts: Vec<(&str, &str)> = vec![];
+        for npi in &self.non_positional {
+            let short = match &npi.short {
+                Some(short) => short,
+                None => continue,
             };
-        if nrequired > 0 {
-            body.push_str(&format!(
-                "\n\tif (argc-optind < {}) {{\n\
-                   \t\tusage(argv[0]);\n\
-                   \t\texit(1);\n\
-                   \t}}\n\
-                   \targv += optind;\n\targc -= optind;\n\n",
-                nrequired
-            ));
-            if !required.is_empty() {
-                for pi in &required {
-                    body.push_str(&format!("{}\targv++;\n", pi.cgen_assign_argv0()));
+            if builtin_shorts.contains(&short.as_str()) {
+                errors.push(ValidationError::BuiltinConflict(
+                    npi.long.to_owned(),
+                    format!("-{}", short),
+                ));
+            } else if let Some((_, other)) = shorts.iter().find(|(s, _)| s == short) {
+                errors.push(ValidationError::DuplicateShort(
+                    npi.long.to_owned(),
+                    short.to_owned(),
+                    (*other).to_owned(),
+                ));
+            }
+            shorts.push((short, &npi.long));
+        }
+        // without long options, longs and aliases are only names in the spec
+        if !self.has_long_options() {
+            return errors;
+        }
+        let fold = |name: &str| match self.is_case_insensitive() {
+            true => name.to_lowercase(),
+            false => name.to_owned(),
+        };
+        let mut longs: Vec<(String, &str)> = vec![];
+        for npi in &self.non_positional {
+            let long = fold(&npi.long);
+            if builtin_longs.contains(&long.as_str()) {
+                errors.push(ValidationError::BuiltinConflict(
+                    npi.long.to_owned(),
+                    format!("--{}", long),
+                ));
+            } else if longs.iter().any(|(l, _)| *l == long) {
+                errors.push(ValidationError::DuplicateLong(npi.long.to_owned()));
+            }
+            longs.push((long, &npi.long));
+        }
+        // aliases can't be another option's long, wherever it is, or an
+        // alias already given
+        let mut aliases: Vec<(String, &str)> = vec![];
+        for npi in &self.non_positional {
+            for alias in npi.aliases.iter().flatten() {
+                let folded = fold(alias);
+                let other = longs
+                    .iter()
+                    .chain(aliases.iter())
+                    .find(|(l, _)| *l == folded);
+                if builtin_longs.contains(&folded.as_str()) {
+                    errors.push(ValidationError::BuiltinConflict(
+                        npi.long.to_owned(),
+                        format!("--{}", folded),
+                    ));
+                } else if let Some((_, other)) = other {
+                    errors.push(ValidationError::AliasConflict(
+                        npi.long.to_owned(),
+                        alias.to_owned(),
+                        (*other).to_owned(),
+                    ));
                 }
-                if required.len() == 1 {
-                    body.push_str("\targc--;\n\n");
+                aliases.push((folded, &npi.long));
+            }
+        }
+        errors
+    }
+    /// Creates the necessary headers in C.
+    fn cgen_headers(&self) -> String {
+        let mut includes = INCLUDES.to_vec();
+        match self.parser() {
+            Parser::Getopt => {}
+            Parser::Reentrant => includes.retain(|&s| s != "getopt"),
+            Parser::Posix => {
+                includes.retain(|&s| s != "getopt");
+                includes.push("unistd");
+            }
+            Parser::Argp => {
+                includes.retain(|&s| s != "getopt");
+                includes.push("argp");
+            }
+        }
+        if self.std() >= CStd::C11 && !self.int_defaults().is_empty() {
+            includes.push("limits");
+        }
+        if self.is_case_insensitive() || self.has_response_files() || self.has_config() {
+            includes.push("ctype");
+        }
+        let mut headers = String::new();
+        if self.parser() == Parser::Posix {
+            // getopt is hidden under strict ISO C modes unless POSIX is asked for
+            headers.push_str("#ifndef _POSIX_C_SOURCE\n#define _POSIX_C_SOURCE 2\n#endif\n");
+        }
+        headers.extend(includes.iter().map(|s| format!("#include<{}.h>\n", s)));
+        for include in self.extra_includes.iter().flatten() {
+            if include.starts_with('<') {
+                headers.push_str(&format!("#include {}\n", include));
+            } else {
+                headers.push_str(&format!("#include \"{}\"\n", include));
+            }
+        }
+        if self.parser() == Parser::Reentrant {
+            headers.push_str(
+                "\n\
+                 struct args_option {\n\
+                 \tconst char *name;\n\
+                 \tint has_arg;\n\
+                 \tint *flag;\n\
+                 \tint val;\n\
+                 };\n\
+                 #ifndef no_argument\n\
+                 #define no_argument 0\n\
+                 #define required_argument 1\n\
+                 #endif\n",
+            );
+        }
+        if let Some(decls) = self.extra_decls.as_ref().filter(|d| !d.is_empty()) {
+            headers.push('\n');
+            for decl in decls {
+                headers.push_str(decl.trim_end());
+                headers.push('\n');
+            }
+        }
+        if self.pre_parse_fn.is_some() || self.post_parse_fn.is_some() {
+            headers.push('\n');
+        }
+        if let Some(pre) = &self.pre_parse_fn {
+            headers.push_str(&format!("void {}(int argc, char **argv);\n", pre));
+        }
+        if let Some(post) = &self.post_parse_fn {
+            match self.struct_name() {
+                Some(name) => headers.push_str(&format!(
+                    "struct {};\nvoid {}(int argc, char **argv, struct {0} *args);\n",
+                    name, post
+                )),
+                None => headers.push_str(&format!("void {}(int argc, char **argv);\n", post)),
+            }
+        }
+        headers
+    }
+    /// Creates the reentrant option scanner. It behaves like getopt_long, except
+    /// that its state lives in a struct and operands are moved to the front of
+    /// argv (after argv[0]) rather than permuted to the back.
+    fn cgen_scanner(&self) -> String {
+        let mut scanner = format!(
+            "struct args_scan {{\n\
+             \tint argc;\n\
+             \tchar **argv;\n\
+             \tint next;\n\
+             \tint operands;\n\
+             \tchar *cluster;\n\
+             \tchar *optarg;\n\
+             }};\n\
+             \n\
+             static int args_scan_long(struct args_scan *s, char *name, const {0} *longopts) {{\n\
+             \tconst {0} *opt, *match = NULL;\n\
+             \tsize_t len = strcspn(name, \"=\");\n\
+             \tint ambiguous = 0;\n\
+             \tfor (opt = longopts; opt->name; opt++) {{\n\
+             \t\tif (strncmp(opt->name, name, len) != 0) {{\n\
+             \t\t\tcontinue;\n\
+             \t\t}}\n\
+             \t\tif (strlen(opt->name) == len) {{\n\
+             \t\t\tmatch = opt;\n\
+             \t\t\tambiguous = 0;\n\
+             \t\t\tbreak;\n\
+             \t\t}}\n",
+            self.option_type()
+        );
+        if self.allows_abbrev() {
+            scanner.push_str(
+                "\t\tif (match) {\n\
+                 \t\t\tambiguous = 1;\n\
+                 \t\t} else {\n\
+                 \t\t\tmatch = opt;\n\
+                 \t\t}\n",
+            );
+        }
+        scanner.push_str(
+            "\t}\n\
+             \tif (ambiguous) {\n\
+             \t\tfprintf(stderr, \"%s: option '--%.*s' is ambiguous\\n\", s->argv[0], (int)len, name);\n\
+             \t\treturn '?';\n\
+             \t}\n\
+             \tif (!match) {\n\
+             \t\tfprintf(stderr, \"%s: unrecognized option '--%s'\\n\", s->argv[0], name);\n\
+             \t\treturn '?';\n\
+             \t}\n\
+             \tif (match->has_arg == required_argument) {\n\
+             \t\tif (name[len] == '=') {\n\
+             \t\t\ts->optarg = name + len + 1;\n\
+             \t\t} else if (s->next < s->argc) {\n\
+             \t\t\ts->optarg = s->argv[s->next++];\n\
+             \t\t} else {\n\
+             \t\t\tfprintf(stderr, \"%s: option '--%s' requires an argument\\n\", s->argv[0], match->name);\n\
+             \t\t\treturn '?';\n\
+             \t\t}\n\
+             \t} else if (name[len] == '=') {\n\
+             \t\tfprintf(stderr, \"%s: option '--%s' doesn't allow an argument\\n\", s->argv[0], match->name);\n\
+             \t\treturn '?';\n\
+             \t}\n\
+             \treturn match->val;\n\
+             }\n\n",
+        );
+        scanner.push_str(&format!(
+            "static int args_scan_next(struct args_scan *s, const char *shortopts, const {} *longopts) {{\n\
+             \tchar *arg;\n\
+             \tconst char *spec;\n\
+             \tint c;\n\
+             \ts->optarg = NULL;\n\
+             \tif (s->cluster == NULL || *s->cluster == '\\0') {{\n\
+             \t\ts->cluster = NULL;\n\
+             \t\tfor (;;) {{\n\
+             \t\t\tif (s->next >= s->argc) {{\n\
+             \t\t\t\treturn -1;\n\
+             \t\t\t}}\n\
+             \t\t\targ = s->argv[s->next];\n\
+             \t\t\tif (arg[0] == '-' && arg[1] != '\\0') {{\n\
+             \t\t\t\tbreak;\n\
+             \t\t\t}}\n\
+             {}\
+             \t\t\ts->argv[s->operands++] = s->argv[s->next++];\n\
+             \t\t}}\n\
+             \t\ts->next++;\n\
+             \t\tif (arg[1] == '-') {{\n\
+             \t\t\tif (arg[2] == '\\0') {{\n\
+             \t\t\t\twhile (s->next < s->argc) {{\n\
+             \t\t\t\t\ts->argv[s->operands++] = s->argv[s->next++];\n\
+             \t\t\t\t}}\n\
+             \t\t\t\treturn -1;\n\
+             \t\t\t}}\n\
+             \t\t\treturn args_scan_long(s, arg + 2, longopts);\n\
+             \t\t}}\n\
+             \t\ts->cluster = arg + 1;\n\
+             \t}}\n\
+             \tc = (unsigned char)*s->cluster++;\n\
+             \tspec = c == ':' || c == '+' ? NULL : strchr(shortopts, c);\n\
+             \tif (spec == NULL) {{\n\
+             \t\tfprintf(stderr, \"%s: invalid option -- '%c'\\n\", s->argv[0], c);\n\
+             \t\treturn '?';\n\
+             \t}}\n\
+             \tif (spec[1] == ':') {{\n\
+             \t\tif (*s->cluster != '\\0') {{\n\
+             \t\t\ts->optarg = s->cluster;\n\
+             \t\t}} else if (s->next < s->argc) {{\n\
+             \t\t\ts->optarg = s->argv[s->next++];\n\
+             \t\t}} else {{\n\
+             \t\t\tfprintf(stderr, \"%s: option requires an argument -- '%c'\\n\", s->argv[0], c);\n\
+             \t\t\treturn '?';\n\
+             \t\t}}\n\
+             \t\ts->cluster = NULL;\n\
+             \t}}\n\
+             \treturn c;\n\
+             }}\n\n",
+            self.option_type(),
+            if self.is_strict_posix() {
+                // the first operand ends option parsing, like a "--"
+                "\t\t\twhile (s->next < s->argc) {\n\
+                 \t\t\t\ts->argv[s->operands++] = s->argv[s->next++];\n\
+                 \t\t\t}\n\
+                 \t\t\treturn -1;\n"
+            } else {
+                ""
+            }
+        ));
+        scanner
+    }
+    /// Creates static helper functions used by parse_args, if any are needed.
+    fn cgen_helpers(&self) -> String {
+        let mut helpers = String::new();
+        if self.parser() == Parser::Reentrant {
+            helpers.push_str(&self.cgen_scanner());
+        }
+        if self.keeps_allocations() {
+            // everything allocated is kept in args__kept for
+            // parse_args_cleanup, since parsed values point into it
+            helpers.push_str(
+                "static void **args__kept = NULL;\n\
+                 static size_t args__nkept = 0;\n\
+                 \n\
+                 static int args_keep(void *p) {\n\
+                 \tvoid **kept = realloc(args__kept, (args__nkept + 1) * sizeof *kept);\n\
+                 \tif (kept == NULL) {\n\
+                 \t\treturn -1;\n\
+                 \t}\n\
+                 \targs__kept = kept;\n\
+                 \targs__kept[args__nkept++] = p;\n\
+                 \treturn 0;\n\
+                 }\n\n",
+            );
+        }
+        if self.has_response_files() {
+            helpers.push_str(
+                "static int args_push(char ***out, size_t *n, char *arg) {\n\
+                 \tchar **grown = realloc(*out, (*n + 2) * sizeof *grown);\n\
+                 \tif (grown == NULL) {\n\
+                 \t\treturn -1;\n\
+                 \t}\n\
+                 \tgrown[(*n)++] = arg;\n\
+                 \tgrown[*n] = NULL;\n\
+                 \t*out = grown;\n\
+                 \treturn 0;\n\
+                 }\n\
+                 \n\
+                 /* Replaces every @file before any \"--\" with the whitespace-separated\n\
+                 \x20* words in file. An @file that can't be opened is left as it is. */\n\
+                 static int args_expand_response_files(int *argc, char ***argv) {\n\
+                 \tchar **out = NULL;\n\
+                 \tsize_t n = 0;\n\
+                 \tint i, rest = 0;\n\
+                 \tfor (i = 0; i < *argc; i++) {\n\
+                 \t\tchar *arg = (*argv)[i], *text, *p;\n\
+                 \t\tFILE *f;\n\
+                 \t\tlong len;\n\
+                 \t\tif (i > 0 && strcmp(arg, \"--\") == 0) {\n\
+                 \t\t\trest = 1;\n\
+                 \t\t}\n\
+                 \t\tif (i == 0 || rest || arg[0] != '@' || (f = fopen(arg + 1, \"rb\")) == NULL) {\n\
+                 \t\t\tif (args_push(&out, &n, arg) != 0) {\n\
+                 \t\t\t\tgoto fail;\n\
+                 \t\t\t}\n\
+                 \t\t\tcontinue;\n\
+                 \t\t}\n\
+                 \t\tif (fseek(f, 0, SEEK_END) != 0 || (len = ftell(f)) < 0 || fseek(f, 0, SEEK_SET) != 0\n\
+                 \t\t\t\t|| (text = malloc((size_t)len + 1)) == NULL) {\n\
+                 \t\t\tfclose(f);\n\
+                 \t\t\tgoto fail;\n\
+                 \t\t}\n\
+                 \t\ttext[fread(text, 1, (size_t)len, f)] = '\\0';\n\
+                 \t\tfclose(f);\n\
+                 \t\tif (args_keep(text) != 0) {\n\
+                 \t\t\tfree(text);\n\
+                 \t\t\tgoto fail;\n\
+                 \t\t}\n\
+                 \t\tfor (p = text; *p; ) {\n\
+                 \t\t\tchar *word;\n\
+                 \t\t\twhile (isspace((unsigned char)*p)) {\n\
+                 \t\t\t\tp++;\n\
+                 \t\t\t}\n\
+                 \t\t\tif (*p == '\\0') {\n\
+                 \t\t\t\tbreak;\n\
+                 \t\t\t}\n\
+                 \t\t\tword = p;\n\
+                 \t\t\twhile (*p && !isspace((unsigned char)*p)) {\n\
+                 \t\t\t\tp++;\n\
+                 \t\t\t}\n\
+                 \t\t\tif (*p) {\n\
+                 \t\t\t\t*p++ = '\\0';\n\
+                 \t\t\t}\n\
+                 \t\t\tif (args_push(&out, &n, word) != 0) {\n\
+                 \t\t\t\tgoto fail;\n\
+                 \t\t\t}\n\
+                 \t\t}\n\
+                 \t}\n\
+                 \tif (out == NULL || args_keep(out) != 0) {\n\
+                 \t\tgoto fail;\n\
+                 \t}\n\
+                 \t*argc = (int)n;\n\
+                 \t*argv = out;\n\
+                 \treturn 0;\n\
+                 fail:\n\
+                 \tfree(out);\n\
+                 \treturn -1;\n\
+                 }\n\n",
+            );
+        }
+        if self.has_config() && !self.non_positional.is_empty() {
+            helpers.push_str(
+                "/* Reads the key = value lines of a config file, skipping blank lines and\n\
+                 \x20* # comments, and packs them as key\\0value\\0 pairs ending with an\n\
+                 \x20* empty key. Returns NULL if the file can't be read. */\n\
+                 static char *args_config_load(const char *path) {\n\
+                 \tFILE *f = fopen(path, \"rb\");\n\
+                 \tchar *text, *in, *out;\n\
+                 \tlong len;\n\
+                 \tif (f == NULL) {\n\
+                 \t\treturn NULL;\n\
+                 \t}\n\
+                 \tif (fseek(f, 0, SEEK_END) != 0 || (len = ftell(f)) < 0 || fseek(f, 0, SEEK_SET) != 0\n\
+                 \t\t\t|| (text = malloc((size_t)len + 2)) == NULL) {\n\
+                 \t\tfclose(f);\n\
+                 \t\treturn NULL;\n\
+                 \t}\n\
+                 \ttext[fread(text, 1, (size_t)len, f)] = '\\0';\n\
+                 \tfclose(f);\n\
+                 \tfor (in = out = text; *in; ) {\n\
+                 \t\tchar *line = in, *end, *eq, *key_end, *value, *value_end;\n\
+                 \t\twhile (*in && *in != '\\n') {\n\
+                 \t\t\tin++;\n\
+                 \t\t}\n\
+                 \t\tend = in;\n\
+                 \t\tif (*in) {\n\
+                 \t\t\tin++;\n\
+                 \t\t}\n\
+                 \t\twhile (line < end && isspace((unsigned char)*line)) {\n\
+                 \t\t\tline++;\n\
+                 \t\t}\n\
+                 \t\tfor (eq = line; eq < end && *eq != '='; eq++) {\n\
+                 \t\t}\n\
+                 \t\tif (line == end || *line == '#' || eq == end) {\n\
+                 \t\t\tcontinue;\n\
+                 \t\t}\n\
+                 \t\tfor (key_end = eq; key_end > line && isspace((unsigned char)key_end[-1]); key_end--) {\n\
+                 \t\t}\n\
+                 \t\tfor (value = eq + 1; value < end && isspace((unsigned char)*value); value++) {\n\
+                 \t\t}\n\
+                 \t\tfor (value_end = end; value_end > value && isspace((unsigned char)value_end[-1]); value_end--) {\n\
+                 \t\t}\n\
+                 \t\tmemmove(out, line, (size_t)(key_end - line));\n\
+                 \t\tout += key_end - line;\n\
+                 \t\t*out++ = '\\0';\n\
+                 \t\tmemmove(out, value, (size_t)(value_end - value));\n\
+                 \t\tout += value_end - value;\n\
+                 \t\t*out++ = '\\0';\n\
+                 \t}\n\
+                 \t*out = '\\0';\n\
+                 \tif (args_keep(text) != 0) {\n\
+                 \t\tfree(text);\n\
+                 \t\treturn NULL;\n\
+                 \t}\n\
+                 \treturn text;\n\
+                 }\n\
+                 \n\
+                 /* The value of the last line in a loaded config file with the key. */\n\
+                 static char *args_config_get(char *config, const char *key) {\n\
+                 \tchar *found = NULL;\n\
+                 \twhile (config != NULL && *config) {\n\
+                 \t\tchar *value = config + strlen(config) + 1;\n\
+                 \t\tif (strcmp(config, key) == 0) {\n\
+                 \t\t\tfound = value;\n\
+                 \t\t}\n\
+                 \t\tconfig = value + strlen(value) + 1;\n\
+                 \t}\n\
+                 \treturn found;\n\
+                 }\n\n",
+            );
+        }
+        if self.has_dump_args() {
+            helpers.push_str(
+                "static void args_json_string(FILE *out, const char *s) {\n\
+                 \tif (s == NULL) {\n\
+                 \t\tfputs(\"null\", out);\n\
+                 \t\treturn;\n\
+                 \t}\n\
+                 \tfputc('\"', out);\n\
+                 \tfor (; *s; s++) {\n\
+                 \t\tunsigned char c = (unsigned char)*s;\n\
+                 \t\tif (c == '\"' || c == '\\\\') {\n\
+                 \t\t\tfprintf(out, \"\\\\%c\", c);\n\
+                 \t\t} else if (c < 0x20) {\n\
+                 \t\t\tfprintf(out, \"\\\\u%04x\", c);\n\
+                 \t\t} else {\n\
+                 \t\t\tfputc(c, out);\n\
+                 \t\t}\n\
+                 \t}\n\
+                 \tfputc('\"', out);\n\
+                 }\n\n",
+            );
+        }
+        let config = self.has_config();
+        if self
+            .non_positional
+            .iter()
+            .any(|npi| npi.is_flag() && npi.is_layered(config))
+        {
+            helpers.push_str(
+                "static int args_truthy(const char *value) {\n\
+                 \treturn strcmp(value, \"1\") == 0 || strcmp(value, \"true\") == 0\n\
+                 \t\t|| strcmp(value, \"yes\") == 0 || strcmp(value, \"on\") == 0;\n\
+                 }\n\n",
+            );
+        }
+        if !self.allows_abbrev() && self.parser() == Parser::Getopt {
+            helpers.push_str(
+                "static const char *longopt_abbrev(char **argv, const struct option *opt) {\n\
+                 \tconst char *arg = (optarg && optarg == argv[optind-1]) ? argv[optind-2] : argv[optind-1];\n\
+                 \tsize_t len = strlen(opt->name);\n\
+                 \tif (strncmp(arg + 2, opt->name, len) == 0 && (arg[len+2] == '\\0' || arg[len+2] == '=')) {\n\
+                 \t\treturn NULL;\n\
+                 \t}\n\
+                 \treturn arg;\n\
+                 }\n\n",
+            );
+        }
+        if self.is_case_insensitive() {
+            // rewrite each long option in argv to the spelling used in longopts,
+            // so getopt_long's case-sensitive matching sees the canonical name
+            helpers.push_str(
+                "static int longopt_casematch(const char *arg, const char *name, size_t len) {\n\
+                 \tsize_t i;\n\
+                 \tfor (i = 0; i < len; i++) {\n\
+                 \t\tif (tolower((unsigned char)arg[i]) != tolower((unsigned char)name[i])) {\n\
+                 \t\t\treturn 0;\n\
+                 \t\t}\n\
+                 \t}\n\
+                 \treturn 1;\n\
+                 }\n\n",
+            );
+            helpers.push_str(&format!(
+                "static void longopt_normalize(int argc, char **argv, const {0} *longopts) {{\n\
+                 \tint i;\n\
+                 \tfor (i = 1; i < argc; i++) {{\n\
+                 \t\tchar *arg = argv[i];\n\
+                 \t\tconst {0} *opt, *match = NULL;\n\
+                 \t\tsize_t len;\n\
+                 {1}\
+                 \t\tif (arg[2] == '\\0') {{\n\
+                 \t\t\tbreak;\n\
+                 \t\t}}\n\
+                 \t\targ += 2;\n\
+                 \t\tlen = strcspn(arg, \"=\");\n\
+                 \t\tfor (opt = longopts; opt->name; opt++) {{\n\
+                 \t\t\tif (strlen(opt->name) < len || !longopt_casematch(arg, opt->name, len)) {{\n\
+                 \t\t\t\tcontinue;\n\
+                 \t\t\t}}\n\
+                 \t\t\tif (!match || strlen(opt->name) == len) {{\n\
+                 \t\t\t\tmatch = opt;\n\
+                 \t\t\t}}\n\
+                 \t\t}}\n\
+                 \t\tif (match) {{\n\
+                 \t\t\tmemcpy(arg, match->name, len);\n\
+                 \t\t\tif (arg[len] == '\\0' && match->has_arg == required_argument) {{\n\
+                 \t\t\t\ti++;\n\
+                 \t\t\t}}\n\
+                 \t\t}}\n\
+                 \t}}\n\
+                 }}\n\n",
+                self.option_type(),
+                if self.is_strict_posix() {
+                    // options stop at the first operand
+                    "\t\tif (arg[0] != '-') {\n\t\t\tbreak;\n\t\t}\n\
+                     \t\tif (arg[1] != '-') {\n\t\t\tcontinue;\n\t\t}\n"
                 } else {
-                    body.push_str(&format!("\targc -= {};\n\n", required.len()));
-                }
-                for pi in &required {
-                    body.push_str(&pi.cgen_post_loop());
+                    "\t\tif (arg[0] != '-' || arg[1] != '-') {\n\t\t\tcontinue;\n\t\t}\n"
                 }
-            }
+            ));
         }
-
-        // parse+post loop, positional optional
-        let optional: Vec<&PositionalItem> = self
-            .positional
+        helpers
+    }
+    /// Whether any option takes a value.
+    fn takes_values(&self) -> bool {
+        self.has_config_option() || self.non_positional.iter().any(|npi| !npi.is_flag())
+    }
+    /// Creates args_value_follows, telling the search for capture_rest's
+    /// "--" which arguments are an option's value, matching long options as
+    /// the parser does. Each subcommand's parser has its own.
+    fn cgen_value_follows(&self) -> String {
+        let shorts: String = self
+            .non_positional
             .iter()
-            .filter(|p| !p.is_required() && !p.is_multi())
+            .filter(|npi| !npi.is_flag())
+            .filter_map(|npi| npi.short.as_deref())
             .collect();
-        for pi in &optional {
-            body.push_str("\tif (argc > 0) {\n");
-            body.push_str(&pi.cgen_assign_argv0());
-            body.push_str("\t\targv++; argc--;\n\t}\n");
+        let mut longs = Vec::new();
+        if self.has_long_options() {
+            for npi in &self.non_positional {
+                longs.push((npi.long.to_owned(), !npi.is_flag()));
+                for alias in npi.aliases.iter().flatten() {
+                    longs.push((alias.to_owned(), !npi.is_flag()));
+                }
+            }
+            if self.has_config_option() {
+                longs.push((String::from("config"), true));
+            }
         }
-        for pi in &optional {
-            body.push_str(&pi.cgen_post_loop());
+        let mut f = format!(
+            "/* Whether the next argument is the value of the option arg, so no \"--\". */\n\
+             static int {}(const char *arg) {{\n",
+            self.sym("args_value_follows")
+        );
+        if !longs.is_empty() {
+            f.push_str("\tstatic const struct {\n\t\tconst char *name;\n\t\tint takes_value;\n\t} longs[] = {\n");
+            for (name, takes_value) in &longs {
+                f.push_str(&format!("\t\t{{\"{}\", {}}},\n", name, *takes_value as u8));
+            }
+            f.push_str("\t\t{NULL, 0}\n\t};\n\tsize_t i, len;\n");
         }
-
-        // multi item
-        let multi: Option<&PositionalItem> = self.positional.iter().find(|p| p.is_multi());
-        if let Some(pi) = multi {
-            if pi.is_required() {
-                body.push_str(&pi.cgen_assign_argv0());
+        f.push_str("\tif (arg[0] != '-' || arg[1] == '\\0') {\n\t\treturn 0;\n\t}\n\tif (arg[1] != '-') {\n");
+        if !shorts.is_empty() {
+            f.push_str(&format!(
+                "\t\t/* a value is the rest of the cluster, or the next argument */\n\
+                 \t\tfor (arg++; *arg; arg++) {{\n\
+                 \t\t\tif (strchr(\"{}\", *arg) != NULL) {{\n\
+                 \t\t\t\treturn arg[1] == '\\0';\n\
+                 \t\t\t}}\n\
+                 \t\t}}\n",
+                shorts
+            ));
+        }
+        f.push_str("\t\treturn 0;\n\t}\n");
+        if !longs.is_empty() {
+            let prefix = if self.is_case_insensitive() {
+                "strlen(longs[i].name) >= len && longopt_casematch(arg, longs[i].name, len)"
             } else {
-                body.push_str("\tif (argc > 0) {\n");
-                body.push_str(&pi.cgen_assign_argv0());
-                body.push_str("\t}\n");
+                "strncmp(arg, longs[i].name, len) == 0"
+            };
+            f.push_str(&format!(
+                "\targ += 2;\n\
+                 \tif (strchr(arg, '=') != NULL) {{\n\
+                 \t\treturn 0;\n\
+                 \t}}\n\
+                 \tlen = strlen(arg);\n\
+                 \tfor (i = 0; longs[i].name != NULL; i++) {{\n\
+                 \t\tif ({} && longs[i].name[len] == '\\0') {{\n\
+                 \t\t\treturn longs[i].takes_value;\n\
+                 \t\t}}\n\
+                 \t}}\n",
+                prefix
+            ));
+            if self.allows_abbrev() {
+                f.push_str(&format!(
+                    "\t/* or an abbreviation of it */\n\
+                     \tfor (i = 0; longs[i].name != NULL; i++) {{\n\
+                     \t\tif ({} && longs[i].takes_value) {{\n\
+                     \t\t\treturn 1;\n\
+                     \t\t}}\n\
+                     \t}}\n",
+                    prefix
+                ));
             }
-            body.push_str(&pi.cgen_post_loop());
         }
-
-        body.push_str("}\n");
-        body
+        f.push_str("\treturn 0;\n}\n\n");
+        f
     }
-    /// Creates the main function in C.
-    fn cgen_main(&self) -> String {
-        let mut main = String::new();
-        main.push_str("int main(int argc, char **argv) {\n");
-
-        for npi in &self.non_positional {
-            main.push_str(&npi.cgen_main_decl())
+    /// The subcommand named after the program in the usage line, starting
+    /// with a space if there is one.
+    fn usage_command(&self) -> String {
+        match &self.command {
+            Some(command) => format!(" {}", command),
+            None => String::new(),
         }
+    }
+    /// The positional part of the usage line, starting with a space if anything.
+    fn usage_synopsis(&self) -> String {
+        let mut pos = String::new();
+        let mut noptional = 0;
         for pi in &self.positional {
-            main.push_str(&pi.cgen_main_decls())
+            pos.push(' ');
+            if !pi.is_required() {
+                pos.push('[');
+                noptional += 1;
+            }
+            pos.push_str(&pi.help_name);
+            if pi.is_multi() {
+                pos.push_str("...");
+            }
         }
-
-        main.push_str("\n\tparse_args(argc, argv");
-        for npi in &self.non_positional {
-            main.push_str(&npi.cgen_call_arg())
+        pos.push_str(&(0..noptional).map(|_| ']').collect::<String>());
+        if self.capture_rest.is_some() {
+            pos.push_str(" [-- ARG...]");
         }
-        for pi in &self.positional {
-            main.push_str(&pi.cgen_call_arg())
+        pos
+    }
+    fn cgen_usage(&self, ir: &Ir) -> String {
+        if self.parser() == Parser::Argp {
+            // argp lays out the help itself
+            return format!(
+                "{}{} {{\n\
+                 \targp_help(&args__argp, stdout, ARGP_HELP_STD_HELP, (char *)progname);\n\
+                 }}\n",
+                self.usage_linkage().specifier(),
+                self.cgen_usage_signature()
+            );
         }
-        main.push_str(
-            ");\n\n\
-                      \t/* call your code here */\n\
-                      \treturn 0;\n}\n",
-        );
-        main
+        let positional_usage = &ir.synopsis;
+        let help = ir.usage_help();
+        if self.std() == CStd::C89 {
+            // C89 compilers need only support 509 characters in a string
+            // literal, so print each line of help on its own.
+            let help: String = help
+                .lines()
+                .map(|line| format!("\tfputs({}, stdout);\n", line.trim_start()))
+                .collect();
+            return format!(
+                "{}{} {{\n\
+                 \tprintf(\"usage: %s{} [options]{}\\n\", progname);\n\
+                 {}\
+                 }}\n",
+                self.usage_linkage().specifier(),
+                self.cgen_usage_signature(),
+                self.usage_command(),
+                positional_usage,
+                help
+            );
+        }
+        format!(
+            "{}{} {{\n\
+             \tprintf(\"usage: %s{} [options]{}\\n%s\", progname,\n\
+             {}\t       );\n\
+             }}\n",
+            self.usage_linkage().specifier(),
+            self.cgen_usage_signature(),
+            self.usage_command(),
+            positional_usage,
+            help
+        )
     }
-    /// Generates everything
-    pub fn gen(&self) -> String {
-        let h = self.cgen_headers();
-        let usage = self.cgen_usage();
-        let body = self.cgen_decl();
-        let main = self.cgen_main();
-        format!("{}\n\n{}\n{}\n{}", h, usage, body, main)
-    }
-    /// Writes generate C code to a writer.
-    pub fn writeout<W>(&self, wrt: &mut W)
-    where
-        W: Write,
-    {
-        wrt.write_all(self.gen().as_bytes())
-            .expect("write generated code to file")
+    /// The c_var and default of every int item with a default.
+    fn int_defaults(&self) -> Vec<(&str, &str)> {
+        let npis = self
+            .non_positional
+            .iter()
+            .filter(|npi| matches!(npi.c_type, CType::Int))
+            .filter_map(|npi| Some((npi.c_var.as_str(), npi.default.as_deref()?)));
+        let pis = self
+            .positional
+            .iter()
+            .filter(|pi| matches!(pi.c_type, CType::Int))
+            .filter_map(|pi| Some((pi.c_var.as_str(), pi.default.as_deref()?)));
+        npis.chain(pis).collect()
+    }
+    /// Compile-time checks of the spec, where the C standard allows them.
+    fn cgen_static_asserts(&self) -> String {
+        if self.std() < CStd::C11 {
+            return String::new();
+        }
+        self.int_defaults()
+            .into_iter()
+            .map(|(var, default)| {
+                format!(
+                    "_Static_assert((long long)({1}) >= INT_MIN && (long long)({1}) <= INT_MAX, \"default for {0} does not fit in an int\");\n",
+                    var, default
+                )
+            })
+            .collect()
+    }
+    /// The usage signature, without a trailing semicolon or body.
+    fn cgen_usage_signature(&self) -> String {
+        format!("void {}(const char *progname)", self.sym("usage"))
+    }
+    /// The parse_args signature, without a trailing semicolon or body.
+    fn cgen_signature(&self) -> String {
+        let cg = self.cgen();
+        if let Some(name) = self.struct_name() {
+            return format!(
+                "{} {}(int argc, char **argv, struct {} *out)",
+                self.parse_args_type(),
+                self.sym("parse_args"),
+                name
+            );
+        }
+        let mut sig = format!(
+            "{} {}(int argc, char **argv",
+            self.parse_args_type(),
+            self.sym("parse_args")
+        );
+        for npi in &self.non_positional {
+            sig.push_str(&npi.cgen_decl_arg(&cg))
+        }
+        for pi in &self.positional {
+            sig.push_str(&pi.cgen_decl_arg(&cg))
+        }
+        if let Some(rest) = &self.capture_rest {
+            sig.push_str(&format!(
+                ", {} **{}, size_t *{1}__size",
+                cg.ty(CType::Chars),
+                rest
+            ))
+        }
+        for var in self.exposed_issets() {
+            sig.push_str(&format!(", int *{}__isset_out", var))
+        }
+        sig.push(')');
+        sig
+    }
+    /// A description of every parsed value, named as in cgen_vars and in
+    /// the same order.
+    fn doc_entries(&self) -> Vec<(String, String)> {
+        fn describe(label: String, text: String) -> String {
+            if text.is_empty() {
+                label
+            } else {
+                format!("{}: {}", label, text)
+            }
+        }
+        let mut docs = Vec::new();
+        for npi in &self.non_positional {
+            docs.push((
+                npi.c_var.to_owned(),
+                describe(
+                    npi.help_label(true).trim_start().to_owned(),
+                    npi.help_text(true),
+                ),
+            ));
+        }
+        for pi in &self.positional {
+            docs.push((
+                pi.c_var.to_owned(),
+                describe(pi.help_label(), pi.help_text()),
+            ));
+            if pi.is_multi() {
+                docs.push((
+                    format!("{}__size", pi.c_var),
+                    format!("number of {} given", pi.help_name),
+                ));
+            }
+        }
+        if let Some(rest) = &self.capture_rest {
+            docs.push((rest.to_owned(), String::from("arguments after \"--\"")));
+            docs.push((
+                format!("{}__size", rest),
+                String::from("number of arguments after \"--\""),
+            ));
+        }
+        for var in self.exposed_issets() {
+            docs.push((
+                format!("{}__isset", var),
+                format!("1 if {} was given, 0 otherwise", var),
+            ));
+        }
+        docs
+    }
+    /// The Doxygen comment for parse_args, if wanted.
+    fn cgen_signature_doc(&self) -> String {
+        if !self.has_doxygen() {
+            return String::new();
+        }
+        let mut doc = String::from(
+            "/**\n * Parses the command line, printing usage on error.\n *\n\
+             \x20* @param argc argument count, as passed to main\n\
+             \x20* @param argv argument vector, as passed to main\n",
+        );
+        if self.struct_name().is_some() {
+            doc.push_str(" * @param out receives the parsed arguments\n");
+        } else {
+            for (var, descr) in self.doc_entries() {
+                let var = if var.ends_with("__isset") {
+                    format!("{}_out", var)
+                } else {
+                    var
+                };
+                doc.push_str(&format!(" * @param {} {}\n", var, doc_text(&descr)));
+            }
+        }
+        if self.on_error() == OnError::Return {
+            doc.push_str(" * @return 0 on success, or 1 if the command line was wrong\n");
+        }
+        doc.push_str(" */\n");
+        doc
+    }
+    /// Creates the parse_args function in C.
+    fn cgen_decl(&self, ir: &Ir) -> String {
+        let cg = self.cgen();
+        let mut body = String::new();
+        if self.capture_rest.is_some() && self.takes_values() {
+            body.push_str(&self.cgen_value_follows());
+        }
+        body.push_str(&self.cgen_signature_doc());
+        body.push_str(self.parse_args_linkage().specifier());
+        body.push_str(&self.cgen_signature());
+        body.push_str(" {\n");
+
+        // with a struct, the rest of the body writes through these pointers
+        // just as it would through out-parameters
+        if self.struct_name().is_some() {
+            for npi in &self.non_positional {
+                body.push_str(&npi.cgen_struct_ptr(&cg));
+            }
+            for pi in &self.positional {
+                body.push_str(&pi.cgen_struct_ptrs(&cg));
+            }
+            if let Some(rest) = &self.capture_rest {
+                body.push_str(&format!(
+                    "\t{} **{} = &out->{1};\n\tsize_t *{1}__size = &out->{1}__size;\n",
+                    cg.ty(CType::Chars),
+                    rest
+                ));
+            }
+            for var in self.exposed_issets() {
+                body.push_str(&format!("\tint *{}__isset_out = &out->{0}__isset;\n", var));
+            }
+        }
+
+        // decls for __isset
+        for npi in &self.non_positional {
+            body.push_str(&npi.cgen_isset_decl(self.has_config()));
+        }
+        for pi in &self.positional {
+            body.push_str(&pi.cgen_isset_decl(self.is_isset_exposed()));
+        }
+        // defs for __default
+        let defaults = ir
+            .options
+            .iter()
+            .map(|opt| (opt.item.c_type, &opt.item.c_var, &opt.default))
+            .chain(
+                ir.arguments
+                    .iter()
+                    .map(|arg| (arg.item.c_type, &arg.item.c_var, &arg.default)),
+            );
+        for (c_type, c_var, default) in defaults {
+            if let Some(default) = default {
+                body.push_str(&format!(
+                    "\tstatic {} {}__default = {};\n",
+                    cg.static_ty(c_type),
+                    c_var,
+                    default
+                ));
+            }
+        }
+
+        // longopts
+        if self.has_long_options() && self.parser() != Parser::Argp {
+            body.push_str(&format!(
+                "\tstatic {}{} longopts[] = {{\n",
+                if self.is_thread_safe() { "const " } else { "" },
+                self.option_type()
+            ));
+            for opt in &ir.options {
+                body.push_str(&opt.item.cgen_getopt(opt.val));
+            }
+            if self.has_config_option() {
+                body.push_str(&format!(
+                    "\t\t{{\"config\", required_argument, 0, {}}},\n",
+                    ir.config_val
+                ));
+            }
+            if self.has_dump_args() {
+                body.push_str(&format!(
+                    "\t\t{{\"dump-args\", no_argument, 0, {}}},\n",
+                    ir.dump_val
+                ));
+            }
+            body.push_str(
+                "\t\t{\"help\", 0, 0, 'h'},\n\
+                 \t\t{0, 0, 0, 0}\n\t};\n",
+            );
+        }
+
+        let optstring = &ir.optstring;
+        if self.parser() != Parser::Argp {
+            body.push_str("\tint ch;\n");
+        }
+        match self.parser() {
+            Parser::Getopt if !self.allows_abbrev() => {
+                body.push_str("\tint longindex;\n\tconst char *abbrev;\n");
+            }
+            Parser::Getopt | Parser::Posix => {}
+            // these shadow nothing, since getopt.h isn't included
+            Parser::Reentrant => {
+                body.push_str("\tstruct args_scan scan;\n\tchar *optarg;\n\tint optind;\n");
+            }
+            Parser::Argp => {
+                if self.argp_has_parser() {
+                    body.push_str("\tstruct args__argp_input args__in;\n");
+                }
+                body.push_str("\tint optind;\n");
+            }
+        }
+        if let Some(rest) = &self.capture_rest {
+            body.push_str(&format!("\tint {}__i;\n", rest));
+        }
+        if self.has_dump_args() {
+            body.push_str("\tint args__dump = 0;\n");
+            if self.has_arrays() {
+                body.push_str("\tsize_t args__i;\n");
+            }
+        }
+        let config = self.has_config() && !self.non_positional.is_empty();
+        if config {
+            body.push_str(&format!(
+                "\tconst char *args__config_path = {};\n\tchar *args__config = NULL;\n",
+                match &self.config_file {
+                    Some(path) => format!("\"{}\"", c_quote(path)),
+                    None => String::from("NULL"),
+                }
+            ));
+            if self.has_config_option() {
+                body.push_str("\tint args__config_given = 0;\n");
+            }
+        }
+        if self
+            .non_positional
+            .iter()
+            .any(|npi| npi.is_layered(self.has_config()))
+        {
+            body.push_str("\tchar *args__value;\n");
+        }
+        if self.post_parse_fn.is_some() {
+            // parsing moves argv along, so keep what the hook should see
+            body.push_str("\tint argc__orig = argc;\n\tchar **argv__orig = argv;\n");
+        }
+        if self.has_response_files() {
+            body.push_str(&format!(
+                "\tif (args_expand_response_files(&argc, &argv) != 0) {{\n\
+                 \t\tfprintf(stderr, \"%s: cannot expand response files\\n\", argv[0]);\n\
+                 \t\t{}\n\
+                 \t}}\n",
+                self.cgen_fail()
+            ));
+        }
+        if let Some(pre) = &self.pre_parse_fn {
+            body.push_str(&format!("\t{}(argc, argv);\n", pre));
+        }
+
+        // everything after "--" is hidden from getopt and handed back as-is
+        if let Some(rest) = &self.capture_rest {
+            body.push_str(&format!(
+                "\t*{0} = NULL;\n\
+                 \t*{0}__size = 0;\n\
+                 \tfor ({0}__i = 1; {0}__i < argc; {0}__i++) {{\n\
+                 \t\tif (strcmp(argv[{0}__i], \"--\") == 0) {{\n\
+                 \t\t\t*{0} = {1};\n\
+                 \t\t\t*{0}__size = (size_t)(argc - {0}__i - 1);\n\
+                 \t\t\targc = {0}__i;\n\
+                 \t\t\tbreak;\n\
+                 \t\t}}\n{2}\
+                 \t}}\n",
+                rest,
+                cg.argv(&format!("(argv + {}__i + 1)", rest)),
+                if self.takes_values() {
+                    format!(
+                        "\t\tif ({1}(argv[{0}__i])) {{\n\t\t\t{0}__i++;\n\t\t}}\n",
+                        rest,
+                        self.sym("args_value_follows")
+                    )
+                } else {
+                    String::new()
+                }
+            ));
+        }
+        if self.is_case_insensitive() {
+            body.push_str("\tlongopt_normalize(argc, argv, longopts);\n");
+        }
+
+        // parse loop, optional
+        if self.parser() == Parser::Argp {
+            body.push_str(&self.cgen_argp_parse());
+        } else if self.parser() == Parser::Reentrant {
+            body.push_str(&format!(
+                "\tscan.argc = argc;\n\
+                 \tscan.argv = argv;\n\
+                 \tscan.next = 1;\n\
+                 \tscan.operands = 1;\n\
+                 \tscan.cluster = NULL;\n\
+                 \twhile ((ch = args_scan_next(&scan, \"{}\", longopts)) != -1) {{\n\
+                 \t\toptarg = scan.optarg;\n",
+                optstring
+            ));
+        } else if self.parser() == Parser::Posix {
+            body.push_str(&format!(
+                "\twhile ((ch = getopt(argc, argv, \"{}\")) != -1) {{\n",
+                optstring
+            ));
+        } else if self.allows_abbrev() {
+            body.push_str(&format!(
+                "\twhile ((ch = getopt_long(argc, argv, \"{}\", longopts, NULL)) != -1) {{\n",
+                optstring
+            ));
+        } else {
+            // getopt_long reports which entry matched, so we can reject any
+            // spelling that isn't the full option name.
+            body.push_str(&format!(
+                "\twhile (longindex = -1, (ch = getopt_long(argc, argv, \"{}\", longopts, &longindex)) != -1) {{\n\
+                 \t\tif (longindex >= 0 && (abbrev = longopt_abbrev(argv, &longopts[longindex])) != NULL) {{\n\
+                 \t\t\tfprintf(stderr, \"%s: unrecognized option '%s'\\n\", argv[0], abbrev);\n\
+                 \t\t\t{}(argv[0]);\n\
+                 \t\t\t{}\n\
+                 \t\t}}\n",
+                optstring,
+                self.sym("usage"),
+                self.cgen_fail()
+            ));
+        }
+        if self.parser() != Parser::Argp {
+            body.push_str("\t\tswitch (ch) {\n");
+            for opt in &ir.options {
+                body.push_str(&format!(
+                    "\t\tcase {}:\n{}\t\t\tbreak;\n",
+                    opt.val,
+                    opt.item.cgen_assign_optarg(self.has_config())
+                ));
+            }
+            if self.has_config_option() {
+                body.push_str(&format!(
+                    "\t\tcase {}:\n\
+                     \t\t\t{}\n\
+                     \t\t\tbreak;\n",
+                    ir.config_val,
+                    if config {
+                        "args__config_path = optarg;\n\t\t\targs__config_given = 1;"
+                    } else {
+                        // with no options to configure, the file is never read
+                        "(void)optarg;"
+                    }
+                ));
+            }
+            if self.has_dump_args() {
+                body.push_str(&format!(
+                    "\t\tcase {}:\n\t\t\targs__dump = 1;\n\t\t\tbreak;\n",
+                    ir.dump_val
+                ));
+            }
+            body.push_str(&format!(
+                "\t\tcase 0:\n\t\t\tbreak;\n\
+                 \t\tcase 'h':\n\
+                 \t\tdefault:\n\t\t\t{}(argv[0]);\n\t\t\t{}\n\
+                 \t\t}}\n\t}}\n",
+                self.sym("usage"),
+                self.cgen_fail()
+            ));
+        }
+        if self.parser() == Parser::Reentrant {
+            // operands now sit at the front of argv, where getopt would leave optind
+            body.push_str("\targc = scan.operands;\n\toptind = 1;\n");
+            if cg.const_strings {
+                // a spec with no options or positionals never reads these
+                body.push_str("\t(void)optarg;\n\t(void)optind;\n");
+            }
+        }
+
+        // config file, read only once the command line has been seen
+        if config && self.has_config_option() {
+            // only a file named on the command line has to exist
+            body.push_str(&format!(
+                "\tif (args__config_path != NULL && (args__config = args_config_load(args__config_path)) == NULL\n\
+                 \t\t\t&& args__config_given) {{\n\
+                 \t\tfprintf(stderr, \"%s: cannot read config file %s\\n\", argv[0], args__config_path);\n\
+                 \t\t{}\n\
+                 \t}}\n",
+                self.cgen_fail()
+            ));
+        } else if config {
+            body.push_str("\targs__config = args_config_load(args__config_path);\n");
+        }
+
+        // post loop, optional: the environment, then the config file,
+        // then defaults for whatever wasn't on the command line
+        for npi in &self.non_positional {
+            body.push_str(&npi.cgen_post_loop(
+                &self.sym("usage"),
+                self.cgen_fail(),
+                self.has_config(),
+            ));
+        }
+
+        // parse+post loop, positional
+        let required: Vec<&PositionalItem> = self
+            .positional
+            .iter()
+            .filter(|p| p.is_required() && !p.is_multi())
+            .collect();
+        let nrequired = required.len()
+            + if self
+                .positional
+                .iter()
+                .any(|p| p.is_required() && p.is_multi())
+            {
+                1
+            } else {
+                0
+            };
+        if nrequired > 0 {
+            body.push_str(&format!(
+                "\n\tif (argc-optind < {}) {{\n\
+                   \t\t{}(argv[0]);\n\
+                   \t\t{}\n\
+                   \t}}\n\
+                   \targv += optind;\n\targc -= optind;\n\n",
+                nrequired,
+                self.sym("usage"),
+                self.cgen_fail()
+            ));
+            if !required.is_empty() {
+                for pi in &required {
+                    body.push_str(&format!(
+                        "{}\targv++;\n",
+                        pi.cgen_assign_argv0(&cg, self.is_isset_exposed())
+                    ));
+                }
+                if required.len() == 1 {
+                    body.push_str("\targc--;\n\n");
+                } else {
+                    body.push_str(&format!("\targc -= {};\n\n", required.len()));
+                }
+                for pi in &required {
+                    body.push_str(&pi.cgen_post_loop());
+                }
+            }
+        }
+
+        // parse+post loop, positional optional
+        let optional: Vec<&PositionalItem> = self
+            .positional
+            .iter()
+            .filter(|p| !p.is_required() && !p.is_multi())
+            .collect();
+        for pi in &optional {
+            body.push_str("\tif (argc > 0) {\n");
+            body.push_str(&pi.cgen_assign_argv0(&cg, self.is_isset_exposed()));
+            body.push_str("\t\targv++; argc--;\n\t}\n");
+        }
+        for pi in &optional {
+            body.push_str(&pi.cgen_post_loop());
+        }
+
+        // multi item
+        let multi: Option<&PositionalItem> = self.positional.iter().find(|p| p.is_multi());
+        if let Some(pi) = multi {
+            if pi.is_required() {
+                body.push_str(&pi.cgen_assign_argv0(&cg, self.is_isset_exposed()));
+            } else {
+                // left empty, rather than as the caller had them, when absent
+                body.push_str(&format!("\t*{} = NULL;\n\t*{0}__size = 0;\n", pi.c_var));
+                body.push_str("\tif (argc > 0) {\n");
+                body.push_str(&pi.cgen_assign_argv0(&cg, self.is_isset_exposed()));
+                body.push_str("\t}\n");
+            }
+            body.push_str(&pi.cgen_post_loop());
+        }
+
+        for var in self.exposed_issets() {
+            body.push_str(&format!("\t*{}__isset_out = {0}__isset;\n", var));
+        }
+        if self.has_dump_args() {
+            body.push_str(&self.cgen_dump_json());
+        }
+        if let Some(post) = &self.post_parse_fn {
+            match self.struct_name() {
+                Some(_) => body.push_str(&format!("\t{}(argc__orig, argv__orig, out);\n", post)),
+                None => body.push_str(&format!("\t{}(argc__orig, argv__orig);\n", post)),
+            }
+        }
+        if cg.const_strings && self.struct_name().is_some() {
+            body.push_str("\t(void)out;\n");
+        }
+        if self.on_error() == OnError::Return {
+            body.push_str("\treturn 0;\n");
+        }
+
+        body.push_str("}\n");
+        body
+    }
+    /// Declarations of a variable for every parsed value.
+    fn cgen_vars(&self) -> String {
+        let cg = self.cgen();
+        let mut vars = String::new();
+        for npi in &self.non_positional {
+            vars.push_str(&npi.cgen_main_decl(&cg))
+        }
+        for pi in &self.positional {
+            vars.push_str(&pi.cgen_main_decls(&cg))
+        }
+        if let Some(rest) = &self.capture_rest {
+            vars.push_str(&format!(
+                "\t{} *{};\n\tsize_t {1}__size;\n",
+                cg.ty(CType::Chars),
+                rest
+            ))
+        }
+        for var in self.exposed_issets() {
+            vars.push_str(&format!("\tint {}__isset;\n", var))
+        }
+        vars
+    }
+    /// Creates the args struct definition in C, if one is used.
+    fn cgen_struct(&self) -> String {
+        match self.struct_name() {
+            Some(name) => {
+                let mut vars = self.cgen_vars();
+                if vars.is_empty() && self.is_pedantic() {
+                    // ISO C forbids a struct with no members
+                    vars.push_str("\tchar unused__;\n");
+                }
+                if !self.has_doxygen() {
+                    return format!("struct {} {{\n{}}};\n", name, vars);
+                }
+                // cgen_vars declares one variable per line, in doc_entries order
+                let mut fields = String::new();
+                for (line, (_, descr)) in vars.lines().zip(self.doc_entries()) {
+                    fields.push_str(&format!("\t/** {} */\n{}\n", doc_text(&descr), line));
+                }
+                format!(
+                    "/** Arguments parsed from the command line. */\nstruct {} {{\n{}}};\n",
+                    name, fields
+                )
+            }
+            None => String::new(),
+        }
+    }
+    /// The (return type, field) of each getter in accessor mode.
+    fn accessor_fields(&self) -> Vec<(&'static str, String)> {
+        fn getter_type(c_type: CType) -> &'static str {
+            match c_type {
+                CType::Chars => "const char *",
+                CType::Int => "int ",
+            }
+        }
+        let strings = if self.is_pedantic() {
+            "const char **"
+        } else {
+            "char **"
+        };
+        let mut fields = Vec::new();
+        for npi in &self.non_positional {
+            fields.push((getter_type(npi.c_type), npi.c_var.to_owned()));
+        }
+        for pi in &self.positional {
+            if pi.is_multi() {
+                fields.push((strings, pi.c_var.to_owned()));
+                fields.push(("size_t ", format!("{}__size", pi.c_var)));
+            } else {
+                fields.push((getter_type(pi.c_type), pi.c_var.to_owned()));
+            }
+        }
+        if let Some(rest) = &self.capture_rest {
+            fields.push((strings, rest.to_owned()));
+            fields.push(("size_t ", format!("{}__size", rest)));
+        }
+        for var in self.exposed_issets() {
+            fields.push(("int ", format!("{}__isset", var)));
+        }
+        fields
+    }
+    /// Prototypes for args_parse and the getters in accessor mode.
+    fn cgen_accessor_decls(&self) -> String {
+        let mut decls = format!(
+            "{} {}(int argc, char **argv);\n",
+            self.parse_args_type(),
+            self.sym("args_parse")
+        );
+        for (ty, field) in self.accessor_fields() {
+            decls.push_str(&format!(
+                "{}{}(void);\n",
+                ty,
+                self.sym(&format!("args_get_{}", field))
+            ));
+        }
+        decls
+    }
+    /// Creates args_parse and the getters in accessor mode, which wrap a private
+    /// instance of the args struct.
+    fn cgen_accessors(&self) -> String {
+        let mut acc = format!(
+            "static struct {} args__values;\n\n\
+             {} {}(int argc, char **argv) {{\n\
+             \t{}{}(argc, argv, &args__values);\n\
+             }}\n",
+            self.struct_name().unwrap_or_default(),
+            self.parse_args_type(),
+            self.sym("args_parse"),
+            if self.on_error() == OnError::Return {
+                "return "
+            } else {
+                ""
+            },
+            self.sym("parse_args")
+        );
+        for (ty, field) in self.accessor_fields() {
+            acc.push_str(&format!(
+                "\n{}{}(void) {{\n\treturn args__values.{};\n}}\n",
+                ty,
+                self.sym(&format!("args_get_{}", field)),
+                field
+            ));
+        }
+        acc
+    }
+    /// Like parse_args, except that with accessors it is part of the interface.
+    fn cleanup_linkage(&self) -> Linkage {
+        if self.has_accessors() {
+            Linkage::Extern
+        } else {
+            self.parse_args_linkage()
+        }
+    }
+    /// The parse_args_cleanup signature, without a trailing semicolon or body.
+    fn cgen_cleanup_signature(&self) -> String {
+        format!("void {}(void)", self.sym("parse_args_cleanup"))
+    }
+    /// Creates parse_args_cleanup, which callers can rely on to release anything
+    /// parse_args allocated. Only response files allocate; otherwise parsed
+    /// values point into argv or at static defaults.
+    fn cgen_cleanup(&self) -> String {
+        let linkage = self.cleanup_linkage().specifier();
+        if !self.emits_parse_args() {
+            return format!("{}{};\n", linkage, self.cgen_cleanup_signature());
+        }
+        let body = if self.keeps_allocations() {
+            "\twhile (args__nkept > 0) {\n\
+             \t\tfree(args__kept[--args__nkept]);\n\
+             \t}\n\
+             \tfree(args__kept);\n\
+             \targs__kept = NULL;\n"
+        } else {
+            "\t/* nothing allocated: values point into argv or at defaults */\n"
+        };
+        format!(
+            "{}{} {{\n{}}}\n",
+            linkage,
+            self.cgen_cleanup_signature(),
+            body
+        )
+    }
+    /// The args_dump signature, without a trailing semicolon or body. It takes
+    /// the args struct if there is one, or else every value in the order
+    /// parse_args hands them back.
+    fn cgen_dump_signature(&self) -> String {
+        let mut sig = format!("void {}(FILE *out", self.sym("args_dump"));
+        match self.struct_name() {
+            _ if self.has_accessors() => {}
+            Some(name) => sig.push_str(&format!(", const struct {} *args", name)),
+            None => {
+                for (ty, field) in self.accessor_fields() {
+                    sig.push_str(&format!(", {}{}", ty, field));
+                }
+            }
+        }
+        sig.push(')');
+        sig
+    }
+    /// Creates args_dump, which prints each parsed value on a line of its own
+    /// as name = value, with strings quoted and arrays in brackets.
+    fn cgen_dump(&self) -> String {
+        let value = |field: &str| match self.struct_name() {
+            _ if self.has_accessors() => format!("args__values.{}", field),
+            Some(_) => format!("args->{}", field),
+            None => field.to_owned(),
+        };
+        let mut body = String::new();
+        let mut arrays = false;
+        for (ty, field) in self.accessor_fields() {
+            let v = value(&field);
+            match ty {
+                "size_t " => {}
+                "int " => {
+                    body.push_str(&format!("\tfprintf(out, \"{} = %d\\n\", {});\n", field, v))
+                }
+                "const char *" => body.push_str(&format!(
+                    "\tif ({1} != NULL) {{\n\
+                     \t\tfprintf(out, \"{0} = \\\"%s\\\"\\n\", {1});\n\
+                     \t}} else {{\n\
+                     \t\tfprintf(out, \"{0} = NULL\\n\");\n\
+                     \t}}\n",
+                    field, v
+                )),
+                // an array of strings, followed by its size
+                _ => {
+                    arrays = true;
+                    body.push_str(&format!(
+                        "\tfprintf(out, \"{0} = [\");\n\
+                         \tfor (i = 0; i < {2}; i++) {{\n\
+                         \t\tfprintf(out, \"%s\\\"%s\\\"\", i > 0 ? \", \" : \"\", {1}[i]);\n\
+                         \t}}\n\
+                         \tfprintf(out, \"]\\n\");\n",
+                        field,
+                        v,
+                        value(&format!("{}__size", field))
+                    ));
+                }
+            }
+        }
+        format!(
+            "{}{} {{\n{}{}}}\n",
+            self.cleanup_linkage().specifier(),
+            self.cgen_dump_signature(),
+            if arrays { "\tsize_t i;\n" } else { "" },
+            if body.is_empty() {
+                String::from("\t(void)out;\n")
+            } else {
+                body
+            }
+        )
+    }
+    /// Whether any parsed value is an array of strings.
+    fn has_arrays(&self) -> bool {
+        self.capture_rest.is_some() || self.positional.iter().any(|pi| pi.is_multi())
+    }
+    /// The end of parse_args with --dump-args given, printing the parsed
+    /// values as one JSON object on stdout and exiting, or returning 2.
+    fn cgen_dump_json(&self) -> String {
+        let mut dump = String::from("\tif (args__dump) {\n");
+        let mut sep = "{";
+        for (ty, field) in self.accessor_fields() {
+            if ty == "size_t " || field.ends_with("__isset") {
+                continue;
+            }
+            dump.push_str(&format!(
+                "\t\tfputs(\"{}\\\"{}\\\": \", stdout);\n",
+                sep, field
+            ));
+            sep = ", ";
+            match ty {
+                "int " => dump.push_str(&format!("\t\tprintf(\"%d\", *{});\n", field)),
+                "const char *" => {
+                    dump.push_str(&format!("\t\targs_json_string(stdout, *{});\n", field))
+                }
+                _ => dump.push_str(&format!(
+                    "\t\tfputs(\"[\", stdout);\n\
+                     \t\tfor (args__i = 0; args__i < *{0}__size; args__i++) {{\n\
+                     \t\t\tfputs(args__i > 0 ? \", \" : \"\", stdout);\n\
+                     \t\t\targs_json_string(stdout, (*{0})[args__i]);\n\
+                     \t\t}}\n\
+                     \t\tfputs(\"]\", stdout);\n",
+                    field
+                )),
+            }
+        }
+        if sep == "{" {
+            dump.push_str("\t\tfputs(\"{}\\n\", stdout);\n");
+        } else {
+            dump.push_str("\t\tfputs(\"}\\n\", stdout);\n");
+        }
+        match self.on_error() {
+            OnError::Exit => dump.push_str("\t\texit(0);\n\t}\n"),
+            // neither success nor failure, so the caller knows nothing was parsed
+            OnError::Return => dump.push_str("\t\treturn 2;\n\t}\n"),
+        }
+        dump
+    }
+    /// Creates the main function in C.
+    fn cgen_main(&self) -> String {
+        let mut main = String::new();
+        main.push_str("int main(int argc, char **argv) {\n");
+
+        // accessors keep the values to themselves
+        match self.struct_name() {
+            _ if self.has_accessors() => {}
+            // zeroed, so args_dump never reads what parse_args left unset
+            Some(name) => main.push_str(&format!(
+                "\tstruct {} args;\n\tmemset(&args, 0, sizeof args);\n\n",
+                name
+            )),
+            None => {
+                main.push_str(&self.cgen_vars());
+                main.push('\n');
+            }
+        }
+        main.push_str(&self.cgen_main_call(self.cgen_call()));
+        main.push_str(&main_body());
+        main
+    }
+    /// The variables cgen_call parses into, zeroed, for callers other than
+    /// main. May end with a statement, so goes after any other declarations.
+    fn cgen_call_vars(&self) -> String {
+        match self.struct_name() {
+            _ if self.has_accessors() => String::new(),
+            Some(name) => format!(
+                "\tstruct {} args;\n\tmemset(&args, 0, sizeof args);\n",
+                name
+            ),
+            None => self
+                .cgen_vars()
+                .lines()
+                .map(|var| format!("{} = 0;\n", var.trim_end_matches(';')))
+                .collect(),
+        }
+    }
+    /// The call parsing argc and argv into the variables main declares.
+    fn cgen_call(&self) -> String {
+        if self.has_accessors() {
+            return format!("{}(argc, argv)", self.sym("args_parse"));
+        }
+        if self.struct_name().is_some() {
+            return format!("{}(argc, argv, &args)", self.sym("parse_args"));
+        }
+        let mut call = format!("{}(argc, argv", self.sym("parse_args"));
+        for npi in &self.non_positional {
+            call.push_str(&npi.cgen_call_arg())
+        }
+        for pi in &self.positional {
+            call.push_str(&pi.cgen_call_arg())
+        }
+        if let Some(rest) = &self.capture_rest {
+            call.push_str(&format!(", &{}, &{0}__size", rest))
+        }
+        for var in self.exposed_issets() {
+            call.push_str(&format!(", &{}__isset", var))
+        }
+        call.push(')');
+        call
+    }
+    /// The statement in main that parses the command line, given the call.
+    fn cgen_main_call(&self, call: String) -> String {
+        match self.on_error() {
+            OnError::Exit => format!("\t{};\n\n", call),
+            OnError::Return if self.has_dump_args() => format!(
+                "\tswitch ({}) {{\n\
+                 \tcase 0:\n\t\tbreak;\n\
+                 \tcase 2:\n\t\t/* --dump-args printed the values */\n\t\treturn 0;\n\
+                 \tdefault:\n\t\treturn 1;\n\t}}\n\n",
+                call
+            ),
+            OnError::Return => format!("\tif ({} != 0) {{\n\t\treturn 1;\n\t}}\n\n", call),
+        }
+    }
+    /// Generates everything
+    pub fn gen(&self) -> String {
+        self.gen_source(None)
+    }
+    /// Generates the C code for use alongside the companion header of the given
+    /// file name (see gen_header), which it includes.
+    pub fn gen_with_header(&self, header: &str) -> String {
+        self.gen_source(Some(header))
+    }
+    fn gen_source(&self, header: Option<&str>) -> String {
+        let mut code = vec![];
+        self.write_source(header, &mut code)
+            .expect("generate into memory");
+        String::from_utf8(code).expect("generated code is UTF-8")
+    }
+    /// Writes the C code a part at a time, each laid out as it is generated.
+    fn write_source<W: Write + ?Sized>(&self, header: Option<&str>, out: &mut W) -> io::Result<()> {
+        if self.has_subcommands() {
+            return self.write_commands(header, out);
+        }
+        let mut out = LaidOut::new(self.cgen(), out);
+        out.write(&self.cgen_stamp())?;
+        out.write(&self.cgen_headers())?;
+        if let Some(header) = header {
+            out.write(&format!("#include \"{}\"\n", header))?;
+        }
+        let asserts = self.cgen_static_asserts();
+        if !asserts.is_empty() {
+            out.write(&format!("\n{}", asserts))?;
+        }
+        // the header carries the struct, unless it is private to this file
+        if header.is_none() || self.has_accessors() {
+            let st = self.cgen_struct();
+            if !st.is_empty() {
+                out.write(&format!("\n{}", st))?;
+            }
+        }
+        out.write("\n\n")?;
+        let ir = self.analyze();
+        // usage and parse_args share argp's tables
+        if self.parser() == Parser::Argp && (self.emits_usage() || self.emits_parse_args()) {
+            out.write(&format!("{}\n", self.cgen_argp()))?;
+        }
+        // anything not emitted is declared, to be defined by whoever includes this
+        if self.emits_usage() {
+            out.write(&self.cgen_usage(&ir))?;
+        } else {
+            out.write(&format!(
+                "{}{};\n",
+                self.usage_linkage().specifier(),
+                self.cgen_usage_signature()
+            ))?;
+        }
+        out.write("\n")?;
+        if self.emits_parse_args() {
+            out.write(&self.cgen_helpers())?;
+            out.write(&self.cgen_decl(&ir))?;
+        } else {
+            out.write(&format!(
+                "{}{}{};\n",
+                self.cgen_signature_doc(),
+                self.parse_args_linkage().specifier(),
+                self.cgen_signature()
+            ))?;
+        }
+        if self.has_cleanup() {
+            out.write(&format!("\n{}", self.cgen_cleanup()))?;
+        }
+        if self.has_accessors() {
+            out.write(&format!("\n{}", self.cgen_accessors()))?;
+        }
+        if self.has_dump() {
+            out.write(&format!("\n{}", self.cgen_dump()))?;
+        }
+        if self.emits_main() {
+            out.write(&format!("\n{}", self.cgen_main()))?;
+        }
+        out.finish()
+    }
+    /// Generates a header with include guards declaring what callers of the
+    /// generated C code need. The guard is derived from the header's file name.
+    pub fn gen_header(&self, name: &str) -> String {
+        let guard: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let header = format!(
+            "{3}#ifndef {0}\n\
+             #define {0}\n\
+             \n\
+             #include<stddef.h>\n\
+             {4}\
+             \n\
+             {1}{2}\
+             \n\
+             #endif /* {0} */\n",
+            guard,
+            match self.cgen_struct() {
+                _ if self.has_subcommands() => String::new(),
+                st if st.is_empty() || self.has_accessors() => String::new(),
+                st => format!("{}\n", st),
+            },
+            if self.has_subcommands() {
+                self.cgen_command_header_decls()
+            } else {
+                self.cgen_header_decls()
+            },
+            self.cgen_stamp(),
+            // args_dump writes to a FILE *
+            if self.has_dump() {
+                "#include<stdio.h>\n"
+            } else {
+                ""
+            }
+        );
+        self.cgen().layout(&header)
+    }
+    /// Prototypes of every generated function with external linkage.
+    fn cgen_header_decls(&self) -> String {
+        let mut decls = String::new();
+        if self.usage_linkage() == Linkage::Extern {
+            decls.push_str(&format!("{};\n", self.cgen_usage_signature()));
+        }
+        if self.parse_args_linkage() == Linkage::Extern {
+            decls.push_str(&self.cgen_signature_doc());
+            decls.push_str(&format!("{};\n", self.cgen_signature()));
+        }
+        if self.has_cleanup() && self.cleanup_linkage() == Linkage::Extern {
+            decls.push_str(&format!("{};\n", self.cgen_cleanup_signature()));
+        }
+        if self.has_accessors() {
+            decls.push_str(&self.cgen_accessor_decls());
+        }
+        if self.has_dump() && self.cleanup_linkage() == Linkage::Extern {
+            decls.push_str(&format!("{};\n", self.cgen_dump_signature()));
+        }
+        decls
+    }
+    /// Writes the C code to a writer a part at a time, as it is generated,
+    /// rather than whole as gen returns it. Writes are a line or so each, so
+    /// a file or pipe is best written through a BufWriter.
+    pub fn writeout<W>(&self, wrt: &mut W) -> io::Result<()>
+    where
+        W: Write + ?Sized,
+    {
+        self.write_source(None, wrt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{int_literal, Spec};
+
+    #[test]
+    fn capture_rest() {
+        let s = Spec::from_str(
+            r#"
+            capture_rest = "child_args"
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("char* **child_args, size_t *child_args__size"));
+        assert!(c.contains("strcmp(argv[child_args__i], \"--\") == 0"));
+        assert!(c.contains("&child_args, &child_args__size"));
+        // the value of an option, as in -o --, is no "--"
+        let s = Spec::from_str(
+            r#"
+            capture_rest = "child_args"
+            positional = []
+
+            [[non_positional]]
+            c_var = "output"
+            c_type = "char*"
+            long = "output"
+            short = "o"
+            aliases = ["out"]
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("static int args_value_follows(const char *arg) {\n"));
+        assert!(c.contains("\t\t{\"output\", 1},\n\t\t{\"out\", 1},\n"));
+        assert!(c.contains("\t\t\tif (strchr(\"o\", *arg) != NULL) {\n"));
+        assert!(c.contains(
+            "\t\tif (args_value_follows(argv[child_args__i])) {\n\t\t\tchild_args__i++;\n\t\t}\n"
+        ));
+    }
+
+    #[test]
+    fn strict_posix() {
+        let s = Spec::from_str(
+            r#"
+            strict_posix = true
+            positional = []
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            short = "q"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        assert!(s.gen().contains("getopt_long(argc, argv, \"+qh\""));
+    }
+
+    #[test]
+    fn disallow_abbrev() {
+        let s = Spec::from_str(
+            r#"
+            allow_abbrev = false
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("static const char *longopt_abbrev("));
+        assert!(c.contains("longopts, &longindex)"));
+    }
+
+    #[test]
+    fn help_sections_aligned() {
+        let s = Spec::from_str(
+            r#"
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            help_descr = "input file"
+
+            [[non_positional]]
+            c_var = "block_size"
+            c_type = "int"
+            long = "block-size"
+            short = "b"
+            help_name = "num"
+            help_descr = "block size\nin bytes"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("\"\\nArguments:\\n\"\n"));
+        assert!(c.contains("\"  FILE                    input file\\n\"\n"));
+        assert!(c.contains("\"\\nOptions:\\n\"\n"));
+        assert!(c.contains("\"  -h, --help              print this usage and exit\\n\"\n"));
+        assert!(c.contains(
+            "\"  -b, --block-size <num>  block size\\n\"\n\
+             \t       \"                          in bytes\\n\"\n"
+        ));
+    }
+
+    #[test]
+    fn help_shows_default() {
+        let s = Spec::from_str(
+            r#"
+            positional = []
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            help_descr = "port to listen on"
+            default = "8080"
+
+            [[non_positional]]
+            c_var = "host"
+            c_type = "char*"
+            long = "host"
+            help_descr = "host to bind"
+            default = "localhost"
+            show_default = false
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("port to listen on (default: 8080)\\n"));
+        assert!(c.contains("host to bind\\n"));
+    }
+
+    #[test]
+    fn companion_header() {
+        let s = Spec::from_str(
+            r#"
+            positional = []
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            "#,
+        )
+        .unwrap();
+        let h = s.gen_header("my-args.h");
+        assert!(h.starts_with("#ifndef MY_ARGS_H\n#define MY_ARGS_H\n"));
+        assert!(h.contains("void parse_args(int argc, char **argv, int *port);\n"));
+        assert!(h.ends_with("#endif /* MY_ARGS_H */\n"));
+        assert!(s
+            .gen_with_header("my-args.h")
+            .contains("#include \"my-args.h\"\n"));
+    }
+
+    #[test]
+    fn args_struct() {
+        let s = Spec::from_str(
+            r#"
+            args_struct = "prog_args"
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains(
+            "struct prog_args {\n\tint port;\n\tchar* *words;\n\tsize_t words__size;\n};\n"
+        ));
+        assert!(c.contains("void parse_args(int argc, char **argv, struct prog_args *out) {\n"));
+        assert!(c.contains("\tint *port = &out->port;\n"));
+        assert!(c.contains("\tparse_args(argc, argv, &args);\n"));
+        assert!(s.gen_header("args.h").contains("struct prog_args {\n"));
+        assert!(!s.gen_with_header("args.h").contains("struct prog_args {\n"));
+    }
+
+    #[test]
+    fn accessors() {
+        let s = Spec::from_str(
+            r#"
+            accessors = true
+            positional = []
+
+            [[non_positional]]
+            c_var = "output"
+            c_type = "char*"
+            long = "output"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen_with_header("args.h");
+        assert!(c.contains("struct args {\n\tchar* output;\n};\n"));
+        assert!(c.contains("static void parse_args(int argc, char **argv, struct args *out) {\n"));
+        assert!(
+            c.contains("const char *args_get_output(void) {\n\treturn args__values.output;\n}\n")
+        );
+        assert!(c.contains("\targs_parse(argc, argv);\n"));
+        let h = s.gen_header("args.h");
+        assert!(!h.contains("struct args {"));
+        assert!(h.contains(
+            "void args_parse(int argc, char **argv);\nconst char *args_get_output(void);\n"
+        ));
+    }
+
+    #[test]
+    fn expose_isset() {
+        let s = Spec::from_str(
+            r#"
+            expose_isset = true
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            default = "8080"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains(", int *port__isset_out, int *input__isset_out) {\n"));
+        assert!(c.contains("\tint input__isset = 0;\n"));
+        assert!(c.contains("\t\tinput__isset = 1;\n"));
+        assert!(c.contains("\t*port__isset_out = port__isset;\n"));
+        assert!(c.contains("\tint port__isset;\n"));
+        assert!(c.contains(", &port__isset, &input__isset);\n"));
+    }
+
+    #[test]
+    fn no_main() {
+        let mut s = Spec::from_str(
+            r#"
+            emit_main = false
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        assert!(!s.gen().contains("int main("));
+        s.set_emit_main(true);
+        assert!(s.gen().contains("int main("));
+    }
+
+    #[test]
+    fn prefix() {
+        let s = Spec::from_str(
+            r#"
+            prefix = "myprog"
+            positional = []
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            required = true
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("static void myprog_usage(const char *progname) {\n"));
+        assert!(c.contains("void myprog_parse_args(int argc, char **argv, int *port) {\n"));
+        assert!(c.contains("\t\tmyprog_usage(argv[0]);\n"));
+        assert!(!c.contains(" usage(argv[0]);"));
+        assert!(c.contains("\tmyprog_parse_args(argc, argv, &port);\n"));
+    }
+
+    #[test]
+    fn linkage() {
+        let s = Spec::from_str(
+            r#"
+            usage_linkage = "extern"
+            parse_args_linkage = "static"
+            emit_usage = false
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("\nvoid usage(const char *progname);\n"));
+        assert!(!c.contains("printf(\"usage:"));
+        assert!(c.contains("\nstatic void parse_args(int argc, char **argv) {\n"));
+        let h = s.gen_header("args.h");
+        assert!(h.contains("void usage(const char *progname);\n"));
+        assert!(!h.contains("parse_args"));
+    }
+
+    #[test]
+    fn reentrant_parser() {
+        let s = Spec::from_str(
+            r#"
+            parser = "reentrant"
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(!c.contains("getopt"));
+        assert!(c.contains("static struct args_option longopts[] = {\n"));
+        assert!(c.contains("while ((ch = args_scan_next(&scan, \"h\", longopts)) != -1) {\n"));
+    }
+
+    #[test]
+    fn c_standard() {
+        let spec = r#"
+            positional = []
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            help_descr = "port to listen on"
+            default = "8080"
+            "#;
+        let c89 = Spec::from_str(&format!("std = \"c89\"\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(c89.contains("\tprintf(\"usage: %s [options]\\n\", progname);\n"));
+        assert!(c89.contains("\tfputs(\"\\nOptions:\\n\", stdout);\n"));
+        assert!(!c89.contains("_Static_assert"));
+        let c11 = Spec::from_str(&format!("std = \"c11\"\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(c11.contains("#include<limits.h>\n"));
+        assert!(c11.contains(
+            "_Static_assert((long long)(8080) >= INT_MIN && (long long)(8080) <= INT_MAX, "
+        ));
+    }
+
+    #[test]
+    fn portable() {
+        let s = Spec::from_str(
+            r#"
+            portable = true
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(!c.contains("getopt"));
+        assert!(c.contains("args_scan_next(&scan, "));
+        assert!(Spec::from_str(
+            r#"
+            portable = true
+            parser = "getopt"
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn posix_parser() {
+        let s = Spec::from_str(
+            r#"
+            parser = "posix"
+            positional = []
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            short = "q"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("#include<unistd.h>\n"));
+        assert!(!c.contains("getopt.h"));
+        assert!(!c.contains("longopts"));
+        assert!(c.contains("while ((ch = getopt(argc, argv, \"qh\")) != -1) {\n"));
+        assert!(c.contains("\"  -q\\n\""));
+        assert!(Spec::from_str(
+            r#"
+            parser = "posix"
+            positional = []
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+            "#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let s = Spec::from_str(
+            r#"
+            case_insensitive = true
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("#include<ctype.h>"));
+        assert!(c.contains("\tlongopt_normalize(argc, argv, longopts);\n"));
+    }
+
+    #[test]
+    fn pedantic() {
+        let s = Spec::from_str(
+            r#"
+            pedantic = true
+            capture_rest = "rest"
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+
+            [[non_positional]]
+            c_var = "name"
+            c_type = "char*"
+            long = "name"
+            default = "x"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("const char* *name, const char* **words"));
+        assert!(c.contains("\tstatic const char* name__default = \"x\";\n"));
+        assert!(c.contains("\t\t\t*rest = (const char **)(argv + rest__i + 1);\n"));
+        assert!(c.contains("\t\t\t*rest__size = (size_t)(argc - rest__i - 1);\n"));
+        assert!(c.contains("\t\t*words__size = (size_t)argc;\n"));
+    }
+
+    /// Compiles pedantic output with warnings as errors, if a C compiler
+    /// is around to do it.
+    #[test]
+    fn pedantic_compiles() {
+        use std::process::Command;
+
+        let base = r#"
+            pedantic = true
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+
+            [[non_positional]]
+            c_var = "count"
+            c_type = "int"
+            long = "count"
+            short = "c"
+            default = "3"
+
+            [[non_positional]]
+            c_var = "name"
+            c_type = "char*"
+            long = "name"
+            short = "n"
+            default = "x"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            short = "q"
+            flag = true
+            "#;
+        let modes = [
+            ("", "c99"),
+            ("capture_rest = \"rest\"\nallow_abbrev = false\n", "c99"),
+            ("parser = \"reentrant\"\ncase_insensitive = true\n", "c99"),
+            ("args_struct = \"a\"\nexpose_isset = true\n", "c11"),
+            ("accessors = true\nstd = \"c89\"\n", "c89"),
+            ("parser = \"posix\"\n", "c99"),
+            (
+                "config = true\nresponse_files = true\ncleanup = true\n",
+                "c99",
+            ),
+        ];
+        let dir = std::env::temp_dir();
+        for (i, (extra, std)) in modes.iter().enumerate() {
+            let s = Spec::from_str(&format!("{}{}", extra, base)).unwrap();
+            let src = dir.join(format!("argen_pedantic_{}.c", i));
+            std::fs::write(&src, s.gen()).unwrap();
+            let status = Command::new("cc")
+                .arg(format!("-std={}", std))
+                .args(["-Wall", "-Wextra", "-Wpedantic", "-Werror", "-c", "-o"])
+                .arg(dir.join(format!("argen_pedantic_{}.o", i)))
+                .arg(&src)
+                .status();
+            match status {
+                Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+                Err(_) => return, // no compiler to check against
+            }
+        }
+    }
+
+    #[test]
+    fn formatting() {
+        let s = Spec::from_str(
+            r#"
+            indent = "    "
+            braces = "next_line"
+            max_line_length = 60
+            positional = []
+
+            [[non_positional]]
+            c_var = "block_size"
+            c_type = "int"
+            long = "block-size"
+
+            [[non_positional]]
+            c_var = "fave_number"
+            c_type = "int"
+            long = "fave-number"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(!c.contains('\t'));
+        assert!(c.contains(
+            "void parse_args(int argc, char **argv, int *block_size,\n\
+             \x20       int *fave_number)\n{\n    int block_size__isset = 0;\n"
+        ));
+        assert!(c.contains("        switch (ch)\n        {\n        case 254:\n"));
+        assert!(c.contains("    static struct option longopts[] = {\n"));
+        assert!(Spec::from_str("indent = \"--\"\npositional = []\nnon_positional = []").is_err());
+    }
+
+    #[test]
+    fn style() {
+        let mut s = Spec::from_str("positional = []\nnon_positional = []").unwrap();
+        s.set_style("GNU").unwrap();
+        assert!(s
+            .gen()
+            .contains("static void usage(const char *progname)\n{\n  printf("));
+        assert!(s.set_style("nonsense").is_err());
+    }
+
+    #[test]
+    fn writeout() {
+        use std::io::{self, Write};
+
+        /// Takes the first bytes it is given, then fails as a closed pipe does.
+        struct Pipe(Vec<Vec<u8>>, usize);
+        impl Write for Pipe {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.0.iter().map(Vec::len).sum::<usize>() >= self.1 {
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                self.0.push(buf.to_vec());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut s =
+            Spec::from_str("[[non_positional]]\nc_var = \"x\"\nc_type = \"int\"\nlong = \"x\"\n")
+                .unwrap();
+        s.set_style("GNU").unwrap();
+        let mut pipe = Pipe(vec![], usize::MAX);
+        s.writeout(&mut pipe).unwrap();
+        // written a part at a time, the code is what gen lays out whole
+        assert!(pipe.0.len() > 1);
+        assert_eq!(pipe.0.concat(), s.gen().into_bytes());
+        let e = s.writeout(&mut Pipe(vec![], 100)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        // and so are the parsers of subcommands
+        let s = Spec::from_str("[[subcommand]]\nname = \"a\"\n\n[[subcommand]]\nname = \"b\"\n")
+            .unwrap();
+        let mut pipe = Pipe(vec![], usize::MAX);
+        s.writeout(&mut pipe).unwrap();
+        assert!(pipe.0.len() > 1);
+        assert_eq!(pipe.0.concat(), s.gen().into_bytes());
+    }
+
+    #[test]
+    fn doxygen() {
+        let spec = r#"
+            doxygen = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            help_descr = "words */ to use"
+            multi = true
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            short = "s"
+            help_name = "num"
+            "#;
+        let c = Spec::from_str(spec).unwrap().gen();
+        assert!(c.contains(" * @param argv argument vector, as passed to main\n"));
+        assert!(c.contains(" * @param size -s, --size <num>\n"));
+        assert!(c.contains(" * @param words WORD...: words * / to use\n"));
+        assert!(c.contains(" * @param words__size number of WORD given\n */\nvoid parse_args("));
+        let c = Spec::from_str(&format!("args_struct = \"a\"\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(c.contains(" * @param out receives the parsed arguments\n"));
+        assert!(c.contains(
+            "/** Arguments parsed from the command line. */\nstruct a {\n\
+             \t/** -s, --size <num> */\n\tint size;\n"
+        ));
+    }
+
+    #[test]
+    fn extra_includes() {
+        let s = Spec::from_str(
+            r#"
+            extra_includes = ["myproj/log.h", "<sys/types.h>"]
+            extra_decls = ["int check_port(int port);"]
+            positional = []
+            non_positional = []
+            "#,
+        )
+        .unwrap();
+        assert!(s.gen().contains(
+            "#include<getopt.h>\n\
+             #include \"myproj/log.h\"\n\
+             #include <sys/types.h>\n\
+             \n\
+             int check_port(int port);\n"
+        ));
+        assert!(Spec::from_str(
+            "extra_includes = [\"<a.h\"]\npositional = []\nnon_positional = []"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn parse_hooks() {
+        let spec = r#"
+            pre_parse_fn = "load_config"
+            post_parse_fn = "log_args"
+            positional = []
+            non_positional = []
+            "#;
+        let c = Spec::from_str(spec).unwrap().gen();
+        assert!(c.contains("void load_config(int argc, char **argv);\n"));
+        assert!(c.contains("\tchar **argv__orig = argv;\n\tload_config(argc, argv);\n"));
+        assert!(c.contains("\tlog_args(argc__orig, argv__orig);\n}\n"));
+        let c = Spec::from_str(&format!("args_struct = \"a\"\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(c.contains("void log_args(int argc, char **argv, struct a *args);\n"));
+        assert!(c.contains("\tlog_args(argc__orig, argv__orig, out);\n"));
+        assert!(
+            Spec::from_str("pre_parse_fn = \"a b\"\npositional = []\nnon_positional = []").is_err()
+        );
+    }
+
+    #[test]
+    fn user_regions() {
+        let s = Spec::from_str("positional = []\nnon_positional = []").unwrap();
+        let c = s.gen();
+        assert!(c.contains(
+            "\t/* argen:begin user */\n\
+             \t/* call your code here */\n\
+             \treturn 0;\n\
+             \t/* argen:end user */\n}\n"
+        ));
+        let existing = c.replace(
+            "\t/* call your code here */\n\treturn 0;\n",
+            "\tputs(\"hi\");\n\treturn 2;\n",
+        );
+        let spliced = super::splice_user_regions(&c, &existing);
+        assert_eq!(spliced, existing);
+        assert_eq!(super::splice_user_regions(&c, "int x;\n"), c);
+    }
+
+    #[test]
+    fn replace_generated() {
+        let existing = "#include \"log.h\"\n\
+                        /* argen:generated */\n\
+                        old code\n\
+                        /* argen:generated */\n\
+                        int main(void) { return 0; }\n";
+        assert_eq!(
+            super::replace_generated(existing, "new code\n").unwrap(),
+            existing.replace("old code", "new code")
+        );
+        assert!(super::replace_generated("/* argen:generated */\n", "new\n").is_none());
+    }
+
+    #[test]
+    fn on_error_return() {
+        let spec = r#"
+            on_error = "return"
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            required = true
+            "#;
+        let c = Spec::from_str(spec).unwrap().gen();
+        assert!(!c.contains("exit("));
+        assert!(c.contains("int parse_args(int argc, char **argv, int *port, char* *input) {\n"));
+        assert!(c.contains("\tif (!port__isset) {\n\t\tusage(argv[0]);\n\t\treturn 1;\n\t}\n"));
+        assert!(c.contains("\treturn 0;\n}\n"));
+        assert!(
+            c.contains("\tif (parse_args(argc, argv, &port, &input) != 0) {\n\t\treturn 1;\n\t}\n")
+        );
+        let c = Spec::from_str(&format!("accessors = true\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(c.contains("int args_parse(int argc, char **argv) {\n\treturn parse_args("));
+    }
+
+    #[test]
+    fn cleanup() {
+        let s = Spec::from_str("cleanup = true\npositional = []\nnon_positional = []").unwrap();
+        assert!(s.gen().contains("\nvoid parse_args_cleanup(void) {\n"));
+        assert!(s
+            .gen_header("args.h")
+            .contains("void parse_args_cleanup(void);\n"));
+    }
+
+    #[test]
+    fn thread_safe() {
+        let spec = r#"
+            positional = []
+
+            [[non_positional]]
+            c_var = "name"
+            c_type = "char*"
+            long = "name"
+            default = "x"
+            "#;
+        let c = Spec::from_str(&format!("thread_safe = true\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(!c.contains("getopt"));
+        assert!(c.contains("\tstatic char* const name__default = \"x\";\n"));
+        assert!(c.contains("\tstatic const struct args_option longopts[] = {\n"));
+        for extra in &[
+            "parser = \"getopt\"",
+            "parser = \"posix\"",
+            "accessors = true",
+        ] {
+            let s = format!("thread_safe = true\n{}\n{}", extra, spec);
+            assert!(Spec::from_str(&s).is_err());
+        }
+    }
+
+    #[test]
+    fn provenance() {
+        let mut s = Spec::from_str("positional = []\nnon_positional = []").unwrap();
+        assert!(s.gen().starts_with("#include<stdlib.h>\n"));
+        s.set_provenance("argen 1.0.0", "spec.toml");
+        let comment = "/* Generated by argen 1.0.0 from spec.toml. */\n";
+        assert!(s
+            .gen()
+            .starts_with(&format!("{}#include<stdlib.h>\n", comment)));
+        assert!(s
+            .gen_header("args.h")
+            .starts_with(&format!("{}#ifndef ARGS_H\n", comment)));
+    }
+
+    #[test]
+    fn spec_hash() {
+        let spec = r#"
+            positional = []
+
+            [[non_positional]]
+            c_var = "a"
+            c_type = "int"
+            long = "a"
+
+            [[non_positional]]
+            c_var = "b"
+            c_type = "int"
+            long = "b"
+            "#;
+        let mut s = Spec::from_str(spec).unwrap();
+        s.set_spec_hash("abc");
+        let c = s.gen();
+        assert!(c.starts_with(
+            "/* spec sha256: ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad */\n"
+        ));
+        assert!(c.contains(
+            "\t\t{\"a\", required_argument, 0, 254},\n\t\t{\"b\", required_argument, 0, 253},\n"
+        ));
+        assert_eq!(c, s.gen());
+    }
+
+    #[test]
+    fn response_files() {
+        let spec = "response_files = true\ncleanup = true\npositional = []\nnon_positional = []";
+        let c = Spec::from_str(spec).unwrap().gen();
+        assert!(c.contains("#include<ctype.h>\n"));
+        assert!(c.contains("static int args_expand_response_files(int *argc, char ***argv) {\n"));
+        assert!(c.contains("\tif (args_expand_response_files(&argc, &argv) != 0) {\n"));
+        assert!(c.contains("\t\tfree(args__kept[--args__nkept]);\n"));
+        assert!(Spec::from_str(&format!("thread_safe = true\n{}", spec)).is_err());
+    }
+
+    #[test]
+    fn config_layers() {
+        let s = Spec::from_str(
+            r#"
+            config = true
+            config_file = "/etc/prog.conf"
+            positional = []
+
+            [[non_positional]]
+            c_var = "name"
+            c_type = "char*"
+            long = "name"
+            env = "PROG_NAME"
+            default = "x"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("\tconst char *args__config_path = \"/etc/prog.conf\";\n"));
+        assert!(c.contains("\t\t{\"config\", required_argument, 0, 252},\n"));
+        assert!(c.contains("\t\t\t*quiet = 1;\n\t\t\tquiet__isset = 1;\n"));
+        assert!(c.contains(
+            "\tif (!name__isset && (args__value = getenv(\"PROG_NAME\")) != NULL) {\n\
+             \t\t*name = args__value;\n\
+             \t\tname__isset = 1;\n\
+             \t}\n\
+             \tif (!name__isset && (args__value = args_config_get(args__config, \"name\")) != NULL) {\n\
+             \t\t*name = args__value;\n\
+             \t\tname__isset = 1;\n\
+             \t}\n\
+             \tif (!name__isset) {\n\t\t*name = name__default;\n\t}\n"
+        ));
+        assert!(c.contains("\t\t*quiet = args_truthy(args__value);\n"));
+        assert!(
+            c.contains("--config <file>  read option defaults from file (default: /etc/prog.conf)")
+        );
+        assert!(Spec::from_str(
+            "config = true\nparser = \"posix\"\npositional = []\nnon_positional = []"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn dump() {
+        use std::process::Command;
+
+        let spec = r#"
+            dump = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            "#;
+        let c = Spec::from_str(spec).unwrap().gen();
+        assert!(c.contains(
+            "void args_dump(FILE *out, int port, char **words, size_t words__size) {\n\tsize_t i;\n"
+        ));
+        assert!(c.contains("\tfprintf(out, \"port = %d\\n\", port);\n"));
+        assert!(c.contains("\tfor (i = 0; i < words__size; i++) {\n"));
+        let s = Spec::from_str(&format!("args_struct = \"opts\"\n{}", spec)).unwrap();
+        assert!(s
+            .gen()
+            .contains("\tfprintf(out, \"port = %d\\n\", args->port);\n"));
+        assert!(s
+            .gen_header("args.h")
+            .contains("void args_dump(FILE *out, const struct opts *args);\n"));
+        // with WORD absent, main's struct dumps as parsed, an empty array
+        let s = Spec::from_str(
+            r#"
+            dump = true
+            args_struct = "opts"
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("\tstruct opts args;\n\tmemset(&args, 0, sizeof args);\n"));
+        let dir = std::env::temp_dir();
+        let src = dir.join("argen_dump.c");
+        let bin = dir.join("argen_dump");
+        let c = c.replace(
+            "\t/* call your code here */\n",
+            "\targs_dump(stdout, &args);\n",
+        );
+        std::fs::write(&src, c).unwrap();
+        let status = Command::new("cc")
+            .args([
+                "-Wall",
+                "-Wextra",
+                "-Werror",
+                "-fsanitize=address,undefined",
+                "-o",
+            ])
+            .arg(&bin)
+            .arg(&src)
+            .status();
+        match status {
+            Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+            Err(_) => return, // no compiler to run the parser with
+        }
+        let run = Command::new(&bin).arg("in").output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&run.stdout),
+            "input = \"in\"\nwords = []\n"
+        );
+    }
+
+    #[test]
+    fn dump_args() {
+        let s = Spec::from_str(
+            r#"
+            dump_args = true
+            capture_rest = "rest"
+            positional = []
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("\t\t{\"dump-args\", no_argument, 0, 253},\n"));
+        assert!(c.contains("\t\tcase 253:\n\t\t\targs__dump = 1;\n"));
+        assert!(c.contains("static void args_json_string(FILE *out, const char *s) {\n"));
+        assert!(c.contains(
+            "\t\tfputs(\"{\\\"port\\\": \", stdout);\n\t\tprintf(\"%d\", *port);\n\
+             \t\tfputs(\", \\\"rest\\\": \", stdout);\n"
+        ));
+        assert!(!c.contains("dump-args  "));
+        assert!(c.contains("\t\texit(0);\n"));
+        // a caller handling errors itself is returned to, not exited
+        let s = Spec::from_str(
+            r#"
+            dump_args = true
+            on_error = "return"
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            "#,
+        )
+        .unwrap();
+        let c = s.gen();
+        assert!(c.contains("\t*words = NULL;\n\t*words__size = 0;\n\tif (argc > 0) {\n"));
+        assert!(c.contains("\t\treturn 2;\n\t}\n"));
+        assert!(!c.contains("exit("));
+        assert!(c.contains("\tcase 2:\n\t\t/* --dump-args printed the values */\n\t\treturn 0;\n"));
+        assert!(Spec::from_str(
+            "dump_args = true\nparser = \"posix\"\npositional = []\nnon_positional = []"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn json_spec() {
+        let toml = Spec::from_str(
+            r#"
+            args_struct = "opts"
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            default = "3"
+            "#,
+        )
+        .unwrap();
+        let json = Spec::from_json(
+            r#"{
+                "args_struct": "opts",
+                "prefix": null,
+                "positional": [
+                    {"c_var": "input", "c_type": "char*", "help_name": "FILE", "required": true}
+                ],
+                "non_positional": [
+                    {"c_var": "size", "c_type": "int", "long": "size", "default": "3"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(json.gen(), toml.gen());
+        assert!(
+            Spec::from_json(r#"{"positional": [], "non_positional": [], "std": "c42"}"#).is_err()
+        );
+        assert!(Spec::from_json(r#"{"positional": []"#).is_err());
+    }
+
+    #[test]
+    fn problems() {
+        let s = Spec::parse_str_in(
+            r#"
+            prefix = "my prefix"
+
+            [[positional]]
+            c_var = "2in"
+            c_type = "int"
+            help_name = "IN"
+            multi = true
+
+            [[non_positional]]
+            c_var = "verbose"
+            c_type = "char*"
+            long = "verbose"
+            flag = true
+            "#,
+            std::path::Path::new("."),
+        )
+        .unwrap();
+        let problems: Vec<String> = s.problems().iter().map(|e| e.to_string()).collect();
+        assert_eq!(problems.len(), 4);
+        assert_eq!(problems[0], "in param IN: invalid c variable \"2in\"");
+        assert_eq!(
+            problems[3],
+            "in param prefix: invalid c variable \"my prefix\""
+        );
+        assert_eq!(s.validate().err().unwrap().to_string(), problems[0]);
+    }
+
+    #[test]
+    fn collisions() {
+        let s = Spec::parse_str_in(
+            r#"
+            case_insensitive = true
+
+            [[positional]]
+            c_var = "out"
+            c_type = "char*"
+            help_name = "OUT"
+
+            [[non_positional]]
+            c_var = "out"
+            c_type = "char*"
+            long = "output"
+            short = "o"
+            aliases = ["out", "Verbose"]
+
+            [[non_positional]]
+            c_var = "verbose"
+            c_type = "int"
+            long = "verbose"
+            short = "o"
+            flag = true
+
+            [[non_positional]]
+            c_var = "hostname"
+            c_type = "char*"
+            long = "OUTPUT"
+            short = "h"
+            aliases = ["help"]
+            "#,
+            std::path::Path::new("."),
+        )
+        .unwrap();
+        let problems: Vec<String> = s.problems().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            problems,
+            [
+                "duplicate c variable: \"out\"",
+                "in param verbose: short name \"o\" is already the short name of param output",
+                "in param OUTPUT: -h is taken by the built-in option of that name",
+                "duplicate argument long: \"OUTPUT\"",
+                "in param output: alias \"Verbose\" is already a long name of param verbose",
+                "in param OUTPUT: --help is taken by the built-in option of that name",
+            ]
+        );
+    }
+
+    #[test]
+    fn help_order() {
+        let spec = r#"
+            [[non_positional]]
+            c_var = "zeta"
+            c_type = "int"
+            long = "zeta"
+            flag = true
+
+            [[non_positional]]
+            c_var = "verbose"
+            c_type = "int"
+            long = "verbose"
+            flag = true
+            order = -1
+
+            [[non_positional]]
+            c_var = "alpha"
+            c_type = "int"
+            long = "alpha"
+            flag = true
+            "#;
+        let longs = |s: &Spec| -> Vec<String> {
+            s.help_options()
+                .iter()
+                .map(|npi| npi.long.to_owned())
+                .collect()
+        };
+        let s = Spec::from_str(spec).unwrap();
+        assert_eq!(longs(&s), ["verbose", "zeta", "alpha"]);
+        let s = Spec::from_str(&format!("sort_help = \"alpha\"\n{}", spec)).unwrap();
+        assert_eq!(longs(&s), ["verbose", "alpha", "zeta"]);
+        assert!(s
+            .analyze()
+            .usage_text("prog")
+            .ends_with("  -h, --help     print this usage and exit\n      --verbose\n      --alpha\n      --zeta\n"));
+    }
+
+    #[test]
+    fn reserved_names() {
+        let s = Spec::parse_str_in(
+            r#"
+            capture_rest = "words"
+
+            [[positional]]
+            c_var = "default"
+            c_type = "char*"
+            help_name = "DEFAULT"
+
+            [[non_positional]]
+            c_var = "argc"
+            c_type = "int"
+            long = "count"
+
+            [[non_positional]]
+            c_var = "words__size"
+            c_type = "int"
+            long = "size"
+            "#,
+            std::path::Path::new("."),
+        )
+        .unwrap();
+        let problems: Vec<String> = s.problems().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            problems,
+            [
+                "in param DEFAULT: c variable \"default\" is a C keyword",
+                "in param count: c variable \"argc\" is a name the generated parser uses itself",
+                "c variable \"words__size\" is also the name argen generates to go with \"words\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn int_literals() {
+        assert_eq!(int_literal("12"), Some(12));
+        assert_eq!(int_literal("-3"), Some(-3));
+        assert_eq!(int_literal("0x1F"), Some(31));
+        assert_eq!(int_literal("017u"), Some(15));
+        assert_eq!(int_literal("INT_MAX"), None);
     }
 }
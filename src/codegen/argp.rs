@@ -0,0 +1,394 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! parser = "argp": option scanning with glibc's argp_parse, which brings
+//! --help, --usage and --version of its own. The argp parser function
+//! writes through pointers to parse_args' variables, so everything after
+//! the scan is the same as with getopt.
+
+use super::{c_quote, CType, Cgen, Linkage, NonPositionalItem, OnError, Spec};
+
+/// The argp key of the nth option: its short name, or a value past any
+/// character for options with only a long name, which argp shows that way.
+fn argp_key(short: &Option<String>, n: usize) -> String {
+    match short {
+        Some(short) => format!("'{}'", short),
+        None => (256 + n).to_string(),
+    }
+}
+
+impl NonPositionalItem {
+    /// Whether parse_args keeps an __isset variable for this option, as
+    /// decided by cgen_isset_decl.
+    fn argp_tracks_isset(&self, config: bool) -> bool {
+        !self.is_flag() || self.is_layered(config)
+    }
+    /// This option's entries in the argp options table, aliases after it.
+    fn argp_options(&self, n: usize) -> String {
+        let key = argp_key(&self.short, n);
+        let arg = if self.is_flag() {
+            String::from("0")
+        } else {
+            format!(
+                "\"{}\"",
+                c_quote(self.help_name.as_deref().unwrap_or("ARG"))
+            )
+        };
+        // argp lists the aliases itself
+        let doc = super::help_with_default(
+            self.help_descr.clone().unwrap_or_default(),
+            &self.default,
+            self.show_default,
+        );
+        let doc = if doc.is_empty() {
+            String::from("0")
+        } else {
+            format!("\"{}\"", c_quote(&doc))
+        };
+        let mut options = format!(
+            "\t{{\"{}\", {}, {}, 0, {}, 0}},\n",
+            self.long, key, arg, doc
+        );
+        for alias in self.aliases.iter().flatten() {
+            options.push_str(&format!(
+                "\t{{\"{}\", {}, 0, OPTION_ALIAS, 0, 0}},\n",
+                alias, key
+            ));
+        }
+        options
+    }
+    /// The case of the argp parser function storing this option's value.
+    fn argp_case(&self, n: usize, config: bool) -> String {
+        let mut case = format!("\tcase {}:\n", argp_key(&self.short, n));
+        let value = match self.c_type {
+            _ if self.is_flag() => String::from("1"),
+            CType::Chars => String::from("arg"),
+            CType::Int => String::from("atoi(arg)"),
+        };
+        case.push_str(&format!("\t\t*in->{} = {};\n", self.c_var, value));
+        if self.argp_tracks_isset(config) {
+            case.push_str(&format!("\t\t*in->{}__isset = 1;\n", self.c_var));
+        }
+        case.push_str("\t\tbreak;\n");
+        case
+    }
+}
+
+impl Spec {
+    /// Whether argp is given a parser function, which it needs only when
+    /// there are options to store.
+    pub(super) fn argp_has_parser(&self) -> bool {
+        !self.non_positional.is_empty() || self.has_config_option() || self.has_dump_args()
+    }
+    /// The argp flags parse_args passes to argp_parse.
+    fn argp_flags(&self) -> String {
+        let mut flags = Vec::new();
+        if self.is_strict_posix() {
+            // operands come in order, and the first one ends the scan
+            flags.push("ARGP_IN_ORDER");
+        }
+        if self.on_error() == OnError::Return {
+            flags.push("ARGP_NO_EXIT");
+        }
+        if flags.is_empty() {
+            String::from("0")
+        } else {
+            flags.join(" | ")
+        }
+    }
+    /// The struct through which the argp parser function reaches
+    /// parse_args' variables.
+    fn argp_input(&self, cg: &Cgen) -> String {
+        let config = self.has_config();
+        let mut input = String::from("struct args__argp_input {\n");
+        for npi in &self.non_positional {
+            input.push_str(&format!("\t{} *{};\n", cg.ty(npi.c_type), npi.c_var));
+            if npi.argp_tracks_isset(config) {
+                input.push_str(&format!("\tint *{}__isset;\n", npi.c_var));
+            }
+        }
+        if self.argp_reads_config() {
+            input.push_str("\tconst char **config_path;\n\tint *config_given;\n");
+        }
+        if self.has_dump_args() {
+            input.push_str("\tint *dump;\n");
+        }
+        if input.ends_with("{\n") {
+            // ISO C forbids a struct with no members
+            input.push_str("\tchar unused__;\n");
+        }
+        input.push_str("};\n");
+        input
+    }
+    /// Whether --config names a file that is read.
+    fn argp_reads_config(&self) -> bool {
+        self.has_config_option() && !self.non_positional.is_empty()
+    }
+    /// The documentation argp prints before the options: the Arguments
+    /// section of the usage.
+    fn argp_doc(&self) -> String {
+        let arguments = self.analyze().help_arguments;
+        if arguments.is_empty() {
+            return String::from("0");
+        }
+        let width = arguments
+            .iter()
+            .map(|(label, _)| label.len() + 2)
+            .max()
+            .unwrap_or(0);
+        let mut doc = String::from("\"Arguments:\\n\"");
+        for (label, descr) in &arguments {
+            let indent = format!("\\n{}", " ".repeat(width + 2));
+            let descr = descr.lines().map(c_quote).collect::<Vec<_>>().join(&indent);
+            doc.push_str(&format!(
+                "\n\t\"  {:<width$}{}\\n\"",
+                c_quote(label),
+                descr,
+                width = width
+            ));
+        }
+        doc
+    }
+    /// Creates the argp options table, parser function and argp struct, which
+    /// usage and parse_args share.
+    pub(super) fn cgen_argp(&self) -> String {
+        let cg = self.cgen();
+        let config = self.has_config();
+        let mut argp = String::new();
+        if let Some(version) = &self.version {
+            argp.push_str(&format!(
+                "const char *argp_program_version = \"{}\";\n",
+                c_quote(version)
+            ));
+        }
+        if let Some(address) = &self.bug_address {
+            argp.push_str(&format!(
+                "const char *argp_program_bug_address = \"{}\";\n",
+                c_quote(address)
+            ));
+        }
+        if !argp.is_empty() {
+            argp.push('\n');
+        }
+        // a nameless entry heads a group, as Options heads the C usage
+        argp.push_str(
+            "static const struct argp_option args__options[] = {\n\
+             \t{0, 0, 0, 0, \"Options:\", 0},\n",
+        );
+        for n in self.help_order(&self.non_positional) {
+            argp.push_str(&self.non_positional[n].argp_options(n));
+        }
+        let config_key = 256 + self.non_positional.len();
+        if self.has_config_option() {
+            argp.push_str(&format!(
+                "\t{{\"config\", {}, \"file\", 0, \"{}\", 0}},\n",
+                config_key,
+                c_quote(&super::help_with_default(
+                    String::from("read option defaults from file"),
+                    &self.config_file,
+                    None,
+                ))
+            ));
+        }
+        if self.has_dump_args() {
+            argp.push_str(&format!(
+                "\t{{\"dump-args\", {}, 0, OPTION_HIDDEN, 0, 0}},\n",
+                config_key + 1
+            ));
+        }
+        argp.push_str("\t{0, 0, 0, 0, 0, 0}\n};\n\n");
+        if self.argp_has_parser() {
+            argp.push_str(&self.argp_input(&cg));
+            argp.push_str(
+                "\n\
+                 static error_t args__argp_parser(int key, char *arg, struct argp_state *state) {\n\
+                 \tstruct args__argp_input *in = state->input;\n",
+            );
+            if self.non_positional.iter().all(|npi| npi.is_flag()) && !self.argp_reads_config() {
+                argp.push_str("\t(void)arg;\n");
+            }
+            argp.push_str("\tswitch (key) {\n");
+            for (n, npi) in self.non_positional.iter().enumerate() {
+                argp.push_str(&npi.argp_case(n, config));
+            }
+            if self.has_config_option() {
+                argp.push_str(&format!("\tcase {}:\n", config_key));
+                if self.argp_reads_config() {
+                    argp.push_str("\t\t*in->config_path = arg;\n\t\t*in->config_given = 1;\n");
+                } else {
+                    // with no options to configure, the file is never read
+                    argp.push_str("\t\t(void)in;\n");
+                }
+                argp.push_str("\t\tbreak;\n");
+            }
+            if self.has_dump_args() {
+                argp.push_str(&format!(
+                    "\tcase {}:\n\t\t*in->dump = 1;\n\t\tbreak;\n",
+                    config_key + 1
+                ));
+            }
+            argp.push_str(
+                "\tdefault:\n\
+                 \t\treturn ARGP_ERR_UNKNOWN;\n\
+                 \t}\n\
+                 \treturn 0;\n\
+                 }\n\n",
+            );
+        }
+        let synopsis = self.usage_synopsis();
+        argp.push_str(&format!(
+            "static const struct argp args__argp = {{args__options, {}, \"{}\", {}, 0, 0, 0}};\n",
+            if self.argp_has_parser() {
+                "args__argp_parser"
+            } else {
+                "0"
+            },
+            c_quote(synopsis.trim_start()),
+            self.argp_doc()
+        ));
+        argp
+    }
+    /// The statements of parse_args that scan the options with argp_parse,
+    /// leaving optind at the first operand.
+    pub(super) fn cgen_argp_parse(&self) -> String {
+        let config = self.has_config();
+        let mut parse = String::new();
+        let input = if self.argp_has_parser() {
+            for npi in &self.non_positional {
+                parse.push_str(&format!("\targs__in.{0} = {0};\n", npi.c_var));
+                if npi.argp_tracks_isset(config) {
+                    parse.push_str(&format!(
+                        "\targs__in.{0}__isset = &{0}__isset;\n",
+                        npi.c_var
+                    ));
+                }
+            }
+            if self.argp_reads_config() {
+                parse.push_str(
+                    "\targs__in.config_path = &args__config_path;\n\
+                     \targs__in.config_given = &args__config_given;\n",
+                );
+            }
+            if self.has_dump_args() {
+                parse.push_str("\targs__in.dump = &args__dump;\n");
+            }
+            "&args__in"
+        } else {
+            "0"
+        };
+        let required = self.non_positional.iter().any(|npi| npi.is_required())
+            || self.positional.iter().any(|pi| pi.is_required());
+        if !required && self.usage_linkage() == Linkage::Static {
+            // argp prints its own usage, so nothing else calls this one
+            parse.push_str(&format!("\t(void){};\n", self.sym("usage")));
+        }
+        parse.push_str(&format!(
+            "\tif (argp_parse(&args__argp, argc, argv, {}, &optind, {}) != 0) {{\n\
+             \t\t{}\n\
+             \t}}\n",
+            self.argp_flags(),
+            input,
+            self.cgen_fail()
+        ));
+        parse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+    use std::process::Command;
+
+    const SPEC: &str = r#"
+        parser = "argp"
+        version = "prog 1.0"
+
+        [[positional]]
+        c_var = "input"
+        c_type = "char*"
+        help_name = "FILE"
+        help_descr = "file to read"
+        required = true
+
+        [[positional]]
+        c_var = "words"
+        c_type = "char*"
+        help_name = "WORD"
+        multi = true
+
+        [[non_positional]]
+        c_var = "size"
+        c_type = "int"
+        long = "size"
+        short = "s"
+        aliases = ["length"]
+        help_name = "NUM"
+        default = "3"
+
+        [[non_positional]]
+        c_var = "quiet"
+        c_type = "int"
+        long = "quiet"
+        flag = true
+        "#;
+
+    #[test]
+    fn argp() {
+        let c = Spec::from_str(SPEC).unwrap().gen();
+        assert!(c.contains("#include<argp.h>\n"));
+        assert!(!c.contains("getopt"));
+        assert!(c.contains("const char *argp_program_version = \"prog 1.0\";\n"));
+        assert!(c.contains("\t{\"size\", 's', \"NUM\", 0, \"(default: 3)\", 0},\n"));
+        assert!(c.contains("\t{\"length\", 's', 0, OPTION_ALIAS, 0, 0},\n"));
+        assert!(c.contains("\t{\"quiet\", 257, 0, 0, 0, 0},\n"));
+        assert!(c.contains("\tcase 257:\n\t\t*in->quiet = 1;\n\t\tbreak;\n"));
+        assert!(c.contains("\targs__in.size__isset = &size__isset;\n"));
+        assert!(c.contains("argp_parse(&args__argp, argc, argv, 0, &optind, &args__in)"));
+        assert!(
+            c.contains("\targp_help(&args__argp, stdout, ARGP_HELP_STD_HELP, (char *)progname);\n")
+        );
+    }
+
+    #[test]
+    fn argp_runs() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("argen_argp.c");
+        let bin = dir.join("argen_argp");
+        let main = "\tprintf(\"%d %d %s %d\\n\", size, quiet, input, (int)words__size);\n";
+        let c = Spec::from_str(SPEC)
+            .unwrap()
+            .gen()
+            .replace("\t/* call your code here */\n", main);
+        std::fs::write(&src, c).unwrap();
+        let status = Command::new("cc")
+            .args(["-Wall", "-Wextra", "-Werror", "-o"])
+            .arg(&bin)
+            .arg(&src)
+            .status();
+        match status {
+            Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+            Err(_) => return, // no compiler to run the parser with
+        }
+        let run = |args: &[&str]| Command::new(&bin).args(args).output().unwrap();
+        let out = run(&["f", "--length=5", "a", "--qu", "b"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "5 1 f 2\n");
+        let out = run(&["--usage"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("FILE [WORD...]"));
+        let out = run(&["--version"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "prog 1.0\n");
+        assert!(!run(&[]).status.success());
+    }
+}
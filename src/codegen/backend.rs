@@ -0,0 +1,171 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The languages argen generates parsers in, and other outputs, each a
+//! CodeGenerator, as a generator from another crate is too.
+
+use super::{Spec, ValidationError};
+use std::io::{self, Write};
+
+/// What generates code, or anything else, from a spec.
+pub trait CodeGenerator {
+    /// Checks that the spec only uses what the generator supports, so that
+    /// generate can be relied on. Everything is, unless a generator says
+    /// otherwise.
+    fn check(&self, _spec: &Spec) -> Result<(), ValidationError> {
+        Ok(())
+    }
+    /// Writes what is generated from a spec that checks out.
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()>;
+}
+
+/// The C parser, with getopt_long or without, as the spec says.
+pub struct C;
+
+impl CodeGenerator for C {
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        spec.writeout(out)
+    }
+}
+
+/// A header-only C++ parser, filling a struct.
+pub struct Cpp;
+
+impl CodeGenerator for Cpp {
+    fn check(&self, spec: &Spec) -> Result<(), ValidationError> {
+        spec.check_cpp()
+    }
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(spec.gen_cpp().as_bytes())
+    }
+}
+
+/// A struct deriving clap's Parser.
+pub struct Rust;
+
+impl CodeGenerator for Rust {
+    fn check(&self, spec: &Spec) -> Result<(), ValidationError> {
+        spec.check_rust()
+    }
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(spec.gen_rust().as_bytes())
+    }
+}
+
+/// A Go parser, with pflag.
+pub struct Go;
+
+impl CodeGenerator for Go {
+    fn check(&self, spec: &Spec) -> Result<(), ValidationError> {
+        spec.check_go()
+    }
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(spec.gen_go().as_bytes())
+    }
+}
+
+/// A Zig parser, for Zig 0.13.
+pub struct Zig;
+
+impl CodeGenerator for Zig {
+    fn check(&self, spec: &Spec) -> Result<(), ValidationError> {
+        spec.check_zig()
+    }
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(spec.gen_zig().as_bytes())
+    }
+}
+
+/// A PowerShell script of the program's name, taking its options in a param
+/// block.
+pub struct PowerShell {
+    /// The script's name, without .ps1.
+    pub program: String,
+}
+
+impl CodeGenerator for PowerShell {
+    fn check(&self, spec: &Spec) -> Result<(), ValidationError> {
+        spec.check_powershell()
+    }
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(spec.gen_powershell(&self.program).as_bytes())
+    }
+}
+
+/// Markdown for the program's README: its usage, arguments and options.
+pub struct Readme {
+    /// The program's name, as the usage shows it.
+    pub program: String,
+}
+
+impl CodeGenerator for Readme {
+    fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(spec.gen_readme(&self.program).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Spec, ValidationError};
+    use super::{CodeGenerator, Cpp, C};
+    use std::io::{self, Write};
+
+    /// A generator from outside the core, listing the options.
+    struct Longs;
+
+    impl CodeGenerator for Longs {
+        fn generate(&self, spec: &Spec, out: &mut dyn Write) -> io::Result<()> {
+            for option in &spec.non_positional {
+                writeln!(out, "--{}", option.long)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn backend() {
+        let s = Spec::from_str(
+            r#"
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+
+            [[non_positional]]
+            c_var = "name"
+            c_type = "char*"
+            long = "name"
+            "#,
+        )
+        .unwrap();
+        let generators: [&dyn CodeGenerator; 3] = [&C, &Cpp, &Longs];
+        let mut outputs = vec![];
+        for generator in generators {
+            generator.check(&s).unwrap();
+            let mut out = vec![];
+            generator.generate(&s, &mut out).unwrap();
+            outputs.push(String::from_utf8(out).unwrap());
+        }
+        assert_eq!(outputs[0], s.gen());
+        assert_eq!(outputs[1], s.gen_cpp());
+        assert_eq!(outputs[2], "--size\n--name\n");
+        let config = Spec::from_str("config = true").unwrap();
+        assert!(matches!(
+            Cpp.check(&config),
+            Err(ValidationError::BackendUnsupported(_, _))
+        ));
+    }
+}
@@ -0,0 +1,178 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Specs built in code rather than read from a file, item by item, to be
+//! generated from or written out with to_toml or to_json, or any serde
+//! serializer, as a spec argen reads back the same.
+
+use super::{upgrade, CType, NonPositionalItem, PositionalItem, Spec};
+
+impl PositionalItem {
+    /// A char* argument, optional, named help_name in the usage.
+    pub fn new(c_var: &str, help_name: &str) -> PositionalItem {
+        PositionalItem {
+            c_var: c_var.to_owned(),
+            c_type: CType::Chars,
+            help_name: help_name.to_owned(),
+            help_descr: None,
+            required: None,
+            default: None,
+            multi: None,
+            show_default: None,
+        }
+    }
+    /// Parses the argument into the C type.
+    pub fn set_c_type(&mut self, c_type: CType) {
+        self.c_type = c_type
+    }
+    /// Describes the argument in the help.
+    pub fn set_help(&mut self, help: &str) {
+        self.help_descr = Some(help.to_owned())
+    }
+    /// Makes the argument required, or not.
+    pub fn set_required(&mut self, required: bool) {
+        self.required = Some(required)
+    }
+    /// The value when the argument is left out, as C would write it.
+    pub fn set_default(&mut self, default: &str) {
+        self.default = Some(default.to_owned())
+    }
+    /// Makes the argument take the rest of the command line, or not.
+    pub fn set_multi(&mut self, multi: bool) {
+        self.multi = Some(multi)
+    }
+}
+
+impl NonPositionalItem {
+    /// A char* option given as --long, optional.
+    pub fn new(c_var: &str, long: &str) -> NonPositionalItem {
+        NonPositionalItem {
+            c_var: c_var.to_owned(),
+            c_type: CType::Chars,
+            long: long.to_owned(),
+            help_name: None,
+            help_descr: None,
+            aliases: None,
+            short: None,
+            required: None,
+            default: None,
+            flag: None,
+            show_default: None,
+            env: None,
+            order: None,
+        }
+    }
+    /// Parses the option's value into the C type.
+    pub fn set_c_type(&mut self, c_type: CType) {
+        self.c_type = c_type
+    }
+    /// Makes the option a flag, an int taking no value, or not.
+    pub fn set_flag(&mut self, flag: bool) {
+        if flag {
+            self.c_type = CType::Int;
+        }
+        self.flag = Some(flag)
+    }
+    /// Lets the option be given as -short, too.
+    pub fn set_short(&mut self, short: char) {
+        self.short = Some(short.to_string())
+    }
+    /// Lets the option be given by other long names, too.
+    pub fn set_aliases(&mut self, aliases: &[&str]) {
+        self.aliases = Some(aliases.iter().map(|alias| (*alias).to_owned()).collect())
+    }
+    /// Describes the option in the help.
+    pub fn set_help(&mut self, help: &str) {
+        self.help_descr = Some(help.to_owned())
+    }
+    /// Names the option's value in the help, as in --size=BYTES.
+    pub fn set_help_name(&mut self, help_name: &str) {
+        self.help_name = Some(help_name.to_owned())
+    }
+    /// Makes the option required, or not.
+    pub fn set_required(&mut self, required: bool) {
+        self.required = Some(required)
+    }
+    /// The value when the option isn't given, as C would write it.
+    pub fn set_default(&mut self, default: &str) {
+        self.default = Some(default.to_owned())
+    }
+    /// Takes the option's value from an environment variable when it isn't
+    /// given.
+    pub fn set_env(&mut self, env: &str) {
+        self.env = Some(env.to_owned())
+    }
+}
+
+impl Spec {
+    /// A spec with no items, in the current schema.
+    pub fn new() -> Spec {
+        let mut spec = Spec::from_str("").expect("an empty spec");
+        spec.schema = Some(upgrade::SCHEMA);
+        spec
+    }
+    /// Adds a positional argument, after those added before it.
+    pub fn add_positional(&mut self, item: PositionalItem) {
+        self.positional.push(item)
+    }
+    /// Adds an option.
+    pub fn add_non_positional(&mut self, item: NonPositionalItem) {
+        self.non_positional.push(item)
+    }
+}
+
+impl Default for Spec {
+    fn default() -> Spec {
+        Spec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{CType, NonPositionalItem, PositionalItem, Spec};
+
+    #[test]
+    fn build() {
+        let mut spec = Spec::new();
+        let mut size = NonPositionalItem::new("size", "size");
+        size.set_c_type(CType::Int);
+        size.set_short('s');
+        size.set_default("12");
+        size.set_help("block size");
+        spec.add_non_positional(size);
+        let mut verbose = NonPositionalItem::new("verbose", "verbose");
+        verbose.set_flag(true);
+        verbose.set_help("say more");
+        spec.add_non_positional(verbose);
+        let mut input = PositionalItem::new("input", "INPUT");
+        input.set_required(true);
+        input.set_help("file to read");
+        spec.add_positional(input);
+        assert!(spec.problems().is_empty());
+        assert!(spec.lints().is_empty());
+        // written out, it reads back the same
+        let toml = spec.to_toml();
+        assert!(toml.starts_with("schema = 2\n"));
+        let read = Spec::from_str(&toml).unwrap();
+        assert_eq!(read.to_toml(), toml);
+        assert_eq!(read.gen(), spec.gen());
+        assert_eq!(Spec::from_json(&spec.to_json()).unwrap().to_toml(), toml);
+        // as does an item alone
+        let item = toml::to_string(&spec.non_positional[0]).unwrap();
+        let item: NonPositionalItem = toml::from_str(&item).unwrap();
+        assert_eq!(item.default.as_deref(), Some("12"));
+    }
+}
@@ -0,0 +1,394 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Specs written out in canonical form, with their keys sorted and each
+//! value written the one way TOML writes it, so two specs saying the same
+//! thing read the same. A spec built in code is written with its settings
+//! before the tables of items and fields left unset dropped; `argen fmt`
+//! formats a spec as it is written, keeping its comments, and its includes,
+//! option sets and variables unexpanded.
+
+use super::locate::code;
+use super::{json_to_toml, Spec, ValidationError};
+use crate::json::{self, Json};
+use std::mem;
+
+pub(super) fn toml_to_json(value: toml::Value) -> Json {
+    match value {
+        toml::Value::Boolean(b) => Json::Bool(b),
+        toml::Value::Integer(n) => Json::Num(n),
+        toml::Value::Array(items) => Json::Arr(items.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Json::Obj(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect(),
+        ),
+        toml::Value::String(s) => Json::Str(s),
+        // specs have no floats or dates
+        value => Json::Str(value.to_string()),
+    }
+}
+
+/// A spec's table as TOML, with a blank line before each table.
+pub(super) fn write_toml(value: &toml::Value) -> String {
+    let toml = toml::to_string(value).expect("write spec as toml");
+    // the toml crate leaves out the blank line before a table after a
+    // nested one; every line is a header or a key, strings escaped
+    let mut out = String::with_capacity(toml.len());
+    for line in toml.lines() {
+        if line.starts_with('[') && !out.is_empty() && !out.ends_with("\n\n") {
+            out.push('\n');
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// A key of a table, as written, with the comments above and beside it.
+struct Entry {
+    above: Vec<String>,
+    key: String,
+    /// The value, on as many lines as it is written on.
+    value: String,
+    beside: Option<String>,
+}
+
+/// The top of a spec, or one of its tables: the header, with the comments
+/// above it, the comments set apart by a blank line from the keys they lead,
+/// and the keys.
+#[derive(Default)]
+struct Section {
+    above: Vec<String>,
+    header: Option<String>,
+    lead: Vec<String>,
+    entries: Vec<Entry>,
+}
+
+/// Where the `=` after a key is, outside of a quoted key.
+fn equals(line: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '=' => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+/// A value as TOML writes it, if that is on one line.
+fn canonical_value(value: &str) -> Option<String> {
+    let table: toml::value::Table = toml::from_str(&format!("v = {}", value)).ok()?;
+    let written = toml::to_string(&table).ok()?;
+    let written = written.strip_prefix("v = ")?.strip_suffix('\n')?;
+    match written.contains('\n') {
+        true => None,
+        false => Some(written.to_owned()),
+    }
+}
+
+/// A key and its value, from the lines they are written on. The value is
+/// written as TOML writes it, unless it is on more than one line with
+/// comments among them, which are kept.
+fn entry(above: Vec<String>, text: &str) -> Entry {
+    let lines = text.contains('\n');
+    let (text, beside) = match lines {
+        true => (text, None),
+        false => {
+            let code = code(text);
+            let comment = text[code.len()..].trim();
+            (code, Some(comment).filter(|c| !c.is_empty()))
+        }
+    };
+    // the spec has been read, so every key is set
+    let at = equals(text).unwrap_or(text.len());
+    let value = text[at..].trim_start_matches('=').trim();
+    Entry {
+        above,
+        key: text[..at].trim().to_owned(),
+        value: Some(value)
+            .filter(|value| !(lines && value.contains('#')))
+            .and_then(canonical_value)
+            .unwrap_or_else(|| value.to_owned()),
+        beside: beside.map(str::to_owned),
+    }
+}
+
+/// A table header, without spaces inside its brackets.
+fn header(line: &str) -> String {
+    let code = code(line).trim();
+    let comment = line.trim()[code.len()..].trim();
+    let brackets = if code.starts_with("[[") { 2 } else { 1 };
+    let name = code[brackets..code.len() - brackets].trim();
+    let (open, close) = if brackets == 2 {
+        ("[[", "]]")
+    } else {
+        ("[", "]")
+    };
+    match comment.is_empty() {
+        true => format!("{}{}{}", open, name, close),
+        false => format!("{}{}{} {}", open, name, close, comment),
+    }
+}
+
+/// A spec's sections, each comment with the key or header below it, and
+/// the comments after the last key.
+fn sections(toml: &str) -> (Vec<Section>, Vec<String>) {
+    let mut sections = vec![Section::default()];
+    let mut comments = vec![];
+    let mut lines = toml.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let section = sections.last_mut().expect("the top of the spec");
+        if trimmed.is_empty() {
+            if section.entries.is_empty() {
+                section.lead.append(&mut comments);
+            }
+        } else if trimmed.starts_with('#') {
+            comments.push(trimmed.to_owned());
+        } else if trimmed.starts_with('[') {
+            sections.push(Section {
+                above: mem::take(&mut comments),
+                header: Some(header(trimmed)),
+                ..Section::default()
+            });
+        } else {
+            // a value goes on until the lines so far are a key set
+            let mut text = trimmed.to_owned();
+            while toml::from_str::<toml::Value>(&text).is_err() {
+                match lines.next() {
+                    Some(more) => {
+                        text.push('\n');
+                        text.push_str(more);
+                    }
+                    None => break,
+                }
+            }
+            section.entries.push(entry(mem::take(&mut comments), &text));
+        }
+    }
+    (sections, comments)
+}
+
+fn push_lines(out: &mut String, lines: &[String]) {
+    for line in lines {
+        out.push_str(line);
+        out.push('\n');
+    }
+}
+
+/// A spec's TOML in canonical form, as it is written: the keys of each
+/// table sorted, with the comments above and beside them, the tables in
+/// the order they are written, a blank line before each, and each value
+/// written as TOML writes it.
+fn format_toml(toml: &str) -> String {
+    let (sections, trailing) = sections(toml);
+    let mut out = String::with_capacity(toml.len());
+    for mut section in sections {
+        if let Some(header) = &section.header {
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            push_lines(&mut out, &section.above);
+            out.push_str(header);
+            out.push('\n');
+        }
+        push_lines(&mut out, &section.lead);
+        if !section.lead.is_empty() && !section.entries.is_empty() {
+            out.push('\n');
+        }
+        section
+            .entries
+            .sort_by(|a, b| a.key.trim_matches('"').cmp(b.key.trim_matches('"')));
+        for entry in &section.entries {
+            push_lines(&mut out, &entry.above);
+            out.push_str(&format!("{} = {}", entry.key, entry.value));
+            if let Some(beside) = &entry.beside {
+                out.push(' ');
+                out.push_str(beside);
+            }
+            out.push('\n');
+        }
+    }
+    if !trailing.is_empty() && !out.is_empty() {
+        out.push('\n');
+    }
+    push_lines(&mut out, &trailing);
+    out
+}
+
+impl Spec {
+    /// A spec's TOML in canonical form, as `argen fmt` writes it: its keys
+    /// sorted and values written as TOML writes them, but otherwise as it is
+    /// written, with its comments, and its includes, option sets and
+    /// variables unexpanded.
+    pub fn format_toml(toml: &str) -> Result<String, ValidationError> {
+        toml::from_str::<toml::Value>(toml)?;
+        Ok(format_toml(toml))
+    }
+    /// A spec's JSON in canonical form, its keys sorted, as `argen fmt`
+    /// writes it.
+    pub fn format_json(json: &str) -> Result<String, ValidationError> {
+        let spec = json_to_toml(json::parse(json)?)
+            .unwrap_or_else(|| toml::Value::Table(Default::default()));
+        Ok(format!("{}\n", toml_to_json(spec)))
+    }
+    /// The spec as a table, keys sorted, with any includes, option sets and
+    /// variables already expanded.
+    fn to_value(&self) -> toml::Value {
+        toml::Value::try_from(self).expect("serialize spec")
+    }
+    /// The spec in canonical TOML.
+    pub fn to_toml(&self) -> String {
+        write_toml(&self.to_value())
+    }
+    /// The spec in canonical JSON.
+    pub fn to_json(&self) -> String {
+        format!("{}\n", toml_to_json(self.to_value()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    const SPEC: &str = r#"
+        positional = []
+        args_struct = "args"
+        allow_abbrev = true
+
+        [[non_positional]]
+        long = "verbose"
+        flag = true
+        c_type = "int"
+        c_var = "verbose"
+        short = "v"
+
+        [[non_positional]]
+        c_var = "name"
+        long = "name"
+        c_type = "char*"
+        default = "\"world\""
+        "#;
+
+    #[test]
+    fn canonical() {
+        let toml = Spec::from_str(SPEC).unwrap().to_toml();
+        assert_eq!(
+            toml,
+            r#"allow_abbrev = true
+args_struct = "args"
+
+[[non_positional]]
+c_type = "int"
+c_var = "verbose"
+flag = true
+long = "verbose"
+short = "v"
+
+[[non_positional]]
+c_type = "char*"
+c_var = "name"
+default = "\"world\""
+long = "name"
+"#
+        );
+        assert_eq!(Spec::from_str(&toml).unwrap().to_toml(), toml);
+
+        let json = Spec::from_str(SPEC).unwrap().to_json();
+        assert!(json.starts_with("{\n  \"allow_abbrev\": true,\n"));
+        assert_eq!(Spec::from_json(&json).unwrap().to_toml(), toml);
+    }
+
+    #[test]
+    fn format() {
+        let written = r#"# frob's command line
+
+include = ["common.toml"]
+  args_struct   =   'frob_args'   # the struct
+"allow_abbrev"=false
+
+[ vars ]
+product = "frob"
+
+# the input
+[[positional]]
+help_name = "FILE"
+c_var = "input"
+# TODO: make it optional
+required = true
+use = [
+  "logging", # for --log
+]
+
+[[option_sets.logging.non_positional]]
+long = "log"
+help_descr = "log to FILE, # of them"
+c_var = "log"
+c_type = "char*"
+default = """
+${product}.log"""
+# end of spec
+"#;
+        let formatted = Spec::format_toml(written).unwrap();
+        assert_eq!(
+            formatted,
+            r#"# frob's command line
+
+"allow_abbrev" = false
+args_struct = "frob_args" # the struct
+include = ["common.toml"]
+
+[vars]
+product = "frob"
+
+# the input
+[[positional]]
+c_var = "input"
+help_name = "FILE"
+# TODO: make it optional
+required = true
+use = [
+  "logging", # for --log
+]
+
+[[option_sets.logging.non_positional]]
+c_type = "char*"
+c_var = "log"
+default = "${product}.log"
+help_descr = "log to FILE, # of them"
+long = "log"
+
+# end of spec
+"#
+        );
+        let value = |toml: &str| toml.parse::<toml::Value>().unwrap();
+        assert_eq!(value(&formatted), value(written));
+        assert_eq!(Spec::format_toml(&formatted).unwrap(), formatted);
+        assert!(Spec::format_toml("[[positional]\n").is_err());
+
+        let json = Spec::format_json("{\"use\": [\"a\"], \"args_struct\": \"s\"}").unwrap();
+        assert_eq!(
+            json,
+            "{\n  \"args_struct\": \"s\",\n  \"use\": [\n    \"a\"\n  ]\n}\n"
+        );
+    }
+}
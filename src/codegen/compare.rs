@@ -0,0 +1,560 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `argen diff`: how a program's command line changes from one spec to the
+//! next, each change breaking if a command line that worked before may not
+//! work, or work differently, after it.
+
+use super::subcommand::Subcommand;
+use super::{NonPositionalItem, PositionalItem, Spec};
+use crate::json::Json;
+use std::fmt;
+
+/// Whether a change can break a command line written for the old spec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Impact {
+    /// Every command line that worked before works as it did.
+    Compatible,
+    /// Some command line that worked before may not, or may do otherwise.
+    Breaking,
+}
+
+impl Impact {
+    fn name(self) -> &'static str {
+        match self {
+            Impact::Compatible => "compatible",
+            Impact::Breaking => "breaking",
+        }
+    }
+}
+
+/// A change to the command line: to the program's own options and
+/// arguments, or to those of the subcommand named.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    /// Whether the change can break a command line.
+    pub impact: Impact,
+    /// The subcommand changed, as typed after the program's name, or empty
+    /// for the program itself.
+    pub command: String,
+    /// What changed.
+    pub message: String,
+}
+
+impl Change {
+    /// The change as a line of JSON.
+    pub fn json(&self) -> String {
+        Json::obj(vec![
+            ("impact", Json::Str(self.impact.name().to_owned())),
+            (
+                "command",
+                Json::opt_str(Some(&self.command[..]).filter(|c| !c.is_empty())),
+            ),
+            ("message", Json::Str(self.message.clone())),
+        ])
+        .line()
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.command[..] {
+            "" => write!(f, "{}: {}", self.impact.name(), self.message),
+            command => write!(f, "{}: {}: {}", self.impact.name(), command, self.message),
+        }
+    }
+}
+
+/// The options and arguments a command takes, and its subcommands.
+struct Command<'a> {
+    positional: &'a [PositionalItem],
+    non_positional: Vec<&'a NonPositionalItem>,
+    subcommands: &'a [Subcommand],
+}
+
+impl<'a> Command<'a> {
+    fn of_spec(spec: &'a Spec) -> Command<'a> {
+        // options every subcommand takes are the program's, as far as a
+        // command line is concerned
+        Command {
+            positional: &spec.positional,
+            non_positional: spec.non_positional.iter().chain(spec.globals()).collect(),
+            subcommands: spec.subcommands(),
+        }
+    }
+    fn of_subcommand(sub: &'a Subcommand) -> Command<'a> {
+        Command {
+            positional: sub.positional(),
+            non_positional: sub.non_positional().iter().collect(),
+            subcommands: sub.subcommands(),
+        }
+    }
+}
+
+/// The changes found so far, to the command being compared.
+struct Changes<'a> {
+    command: &'a str,
+    found: &'a mut Vec<Change>,
+}
+
+impl Changes<'_> {
+    fn push(&mut self, impact: Impact, message: String) {
+        self.found.push(Change {
+            impact,
+            command: self.command.to_owned(),
+            message,
+        })
+    }
+}
+
+/// How a default is shown: quoted, or none.
+fn default(default: &Option<String>) -> String {
+    match default {
+        Some(default) => format!("\"{}\"", default),
+        None => String::from("none"),
+    }
+}
+
+impl NonPositionalItem {
+    /// Whether the option can be given by the long name or alias.
+    fn is_named(&self, name: &str) -> bool {
+        self.long == name || self.aliases().iter().any(|alias| alias == name)
+    }
+}
+
+/// The changes to an option kept from one spec to the next.
+fn compare_option(old: &NonPositionalItem, new: &NonPositionalItem, changes: &mut Changes) {
+    let name = format!("--{}", new.long);
+    if old.long != new.long {
+        match new.is_named(&old.long) {
+            true => changes.push(
+                Impact::Compatible,
+                format!(
+                    "--{} is renamed {}, keeping --{0} as an alias",
+                    old.long, name
+                ),
+            ),
+            false => changes.push(
+                Impact::Breaking,
+                format!("--{} is renamed {}", old.long, name),
+            ),
+        }
+    }
+    match (&old.short, &new.short) {
+        (Some(old), Some(new)) if old != new => changes.push(
+            Impact::Breaking,
+            format!("{}'s short name is -{}, not -{}", name, new, old),
+        ),
+        (Some(old), None) => changes.push(
+            Impact::Breaking,
+            format!("{} can no longer be given as -{}", name, old),
+        ),
+        (None, Some(new)) => changes.push(
+            Impact::Compatible,
+            format!("{} can be given as -{}", name, new),
+        ),
+        _ => {}
+    }
+    for alias in old.aliases() {
+        if !new.is_named(alias) && *alias != old.long {
+            changes.push(
+                Impact::Breaking,
+                format!("{} can no longer be given as --{}", name, alias),
+            );
+        }
+    }
+    for alias in new.aliases() {
+        if !old.is_named(alias) {
+            changes.push(
+                Impact::Compatible,
+                format!("{} can be given as --{}", name, alias),
+            );
+        }
+    }
+    match (old.is_flag(), new.is_flag()) {
+        (true, false) => changes.push(Impact::Breaking, format!("{} takes a value", name)),
+        (false, true) => changes.push(
+            Impact::Breaking,
+            format!("{} no longer takes a value", name),
+        ),
+        (false, false) if old.c_type != new.c_type => changes.push(
+            Impact::Breaking,
+            format!("{}'s value is {}, not {}", name, new.c_type, old.c_type),
+        ),
+        _ => {}
+    }
+    match (old.is_required(), new.is_required()) {
+        (false, true) => changes.push(Impact::Breaking, format!("{} is required", name)),
+        (true, false) => changes.push(
+            Impact::Compatible,
+            format!("{} is no longer required", name),
+        ),
+        _ => {}
+    }
+    // a command line leaving the option out means something else
+    if old.default != new.default {
+        changes.push(
+            Impact::Breaking,
+            format!(
+                "{}'s default is {}, not {}",
+                name,
+                default(&new.default),
+                default(&old.default)
+            ),
+        );
+    }
+    match (&old.env, &new.env) {
+        (Some(old), Some(new)) if old != new => changes.push(
+            Impact::Breaking,
+            format!("{} is taken from ${}, not ${}", name, new, old),
+        ),
+        (Some(old), None) => changes.push(
+            Impact::Breaking,
+            format!("{} is no longer taken from ${}", name, old),
+        ),
+        (None, Some(new)) => changes.push(
+            Impact::Compatible,
+            format!("{} is taken from ${} when not given", name, new),
+        ),
+        _ => {}
+    }
+}
+
+/// The changes to an argument kept at the same position.
+fn compare_positional(old: &PositionalItem, new: &PositionalItem, changes: &mut Changes) {
+    let name = &new.help_name;
+    if old.help_name != new.help_name {
+        changes.push(
+            Impact::Compatible,
+            format!("argument {} is renamed {}", old.help_name, name),
+        );
+    }
+    if old.c_type != new.c_type {
+        changes.push(
+            Impact::Breaking,
+            format!("argument {} is {}, not {}", name, new.c_type, old.c_type),
+        );
+    }
+    match (old.is_required(), new.is_required()) {
+        (false, true) => changes.push(Impact::Breaking, format!("argument {} is required", name)),
+        (true, false) => changes.push(
+            Impact::Compatible,
+            format!("argument {} is no longer required", name),
+        ),
+        _ => {}
+    }
+    match (old.is_multi(), new.is_multi()) {
+        (true, false) => changes.push(
+            Impact::Breaking,
+            format!("argument {} takes one value, not several", name),
+        ),
+        (false, true) => changes.push(
+            Impact::Compatible,
+            format!("argument {} takes several values", name),
+        ),
+        _ => {}
+    }
+    if old.default != new.default {
+        changes.push(
+            Impact::Breaking,
+            format!(
+                "argument {}'s default is {}, not {}",
+                name,
+                default(&new.default),
+                default(&old.default)
+            ),
+        );
+    }
+}
+
+/// The changes from one command to another, and to their subcommands.
+fn compare(path: &str, old: &Command, new: &Command, found: &mut Vec<Change>) {
+    let mut changes = Changes {
+        command: path,
+        found,
+    };
+    // an option is the same one by its long name, then by an alias it
+    // keeps the old name as, then by an alias it had, then by its variable
+    let mut matched = vec![];
+    for old in &old.non_positional {
+        let same = new
+            .non_positional
+            .iter()
+            .find(|new| new.long == old.long)
+            .or_else(|| {
+                new.non_positional
+                    .iter()
+                    .find(|new| new.is_named(&old.long))
+            })
+            .or_else(|| {
+                new.non_positional
+                    .iter()
+                    .find(|new| old.is_named(&new.long))
+            })
+            .or_else(|| new.non_positional.iter().find(|new| new.c_var == old.c_var));
+        match same {
+            Some(new) if !matched.contains(&new.c_var) => {
+                matched.push(new.c_var.clone());
+                compare_option(old, new, &mut changes);
+            }
+            _ => changes.push(Impact::Breaking, format!("--{} is removed", old.long)),
+        }
+    }
+    for new in new
+        .non_positional
+        .iter()
+        .filter(|new| !matched.contains(&new.c_var))
+    {
+        match new.is_required() {
+            true => changes.push(
+                Impact::Breaking,
+                format!("--{} is added, and required", new.long),
+            ),
+            false => changes.push(Impact::Compatible, format!("--{} is added", new.long)),
+        }
+    }
+    // an argument is the same one by its position
+    for (old, new) in old.positional.iter().zip(new.positional) {
+        compare_positional(old, new, &mut changes);
+    }
+    for old in old.positional.iter().skip(new.positional.len()) {
+        changes.push(
+            Impact::Breaking,
+            format!("argument {} is removed", old.help_name),
+        );
+    }
+    for new in new.positional.iter().skip(old.positional.len()) {
+        match new.is_required() {
+            true => changes.push(
+                Impact::Breaking,
+                format!("argument {} is added, and required", new.help_name),
+            ),
+            false => changes.push(
+                Impact::Compatible,
+                format!("argument {} is added", new.help_name),
+            ),
+        }
+    }
+    for old_sub in old.subcommands {
+        match new
+            .subcommands
+            .iter()
+            .find(|new| new.name() == old_sub.name())
+        {
+            Some(new_sub) => {
+                let path = match path {
+                    "" => old_sub.name().to_owned(),
+                    path => format!("{} {}", path, old_sub.name()),
+                };
+                compare(
+                    &path,
+                    &Command::of_subcommand(old_sub),
+                    &Command::of_subcommand(new_sub),
+                    changes.found,
+                );
+            }
+            None => changes.push(
+                Impact::Breaking,
+                format!("subcommand {} is removed", old_sub.name()),
+            ),
+        }
+    }
+    for new_sub in new.subcommands {
+        if !old
+            .subcommands
+            .iter()
+            .any(|old| old.name() == new_sub.name())
+        {
+            changes.push(
+                Impact::Compatible,
+                format!("subcommand {} is added", new_sub.name()),
+            );
+        }
+    }
+}
+
+impl Spec {
+    /// How the command line changes from this spec to a new one, program
+    /// first, then each subcommand.
+    pub fn compare(&self, new: &Spec) -> Vec<Change> {
+        let mut found = vec![];
+        match (self.allows_abbrev(), new.allows_abbrev()) {
+            (true, false) => found.push(Change {
+                impact: Impact::Breaking,
+                command: String::new(),
+                message: String::from("long options can no longer be abbreviated"),
+            }),
+            (false, true) => found.push(Change {
+                impact: Impact::Compatible,
+                command: String::new(),
+                message: String::from("long options can be abbreviated"),
+            }),
+            _ => {}
+        }
+        compare(
+            "",
+            &Command::of_spec(self),
+            &Command::of_spec(new),
+            &mut found,
+        );
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+    use super::Impact;
+
+    const OLD: &str = r#"
+[[non_positional]]
+c_var = "size"
+c_type = "int"
+long = "size"
+short = "s"
+default = "12"
+
+[[non_positional]]
+c_var = "colour"
+c_type = "char*"
+long = "colour"
+
+[[non_positional]]
+c_var = "verbose"
+c_type = "int"
+long = "verbose"
+flag = true
+
+[[positional]]
+c_var = "input"
+c_type = "char*"
+help_name = "INPUT"
+required = true
+"#;
+
+    #[test]
+    fn compare() {
+        let old = Spec::from_str(OLD).unwrap();
+        let new = Spec::from_str(
+            r#"
+[[non_positional]]
+c_var = "size"
+c_type = "char*"
+long = "block-size"
+short = "b"
+default = "12"
+
+[[non_positional]]
+c_var = "color"
+c_type = "char*"
+long = "color"
+aliases = ["colour"]
+
+[[non_positional]]
+c_var = "quiet"
+c_type = "int"
+long = "quiet"
+flag = true
+
+[[positional]]
+c_var = "input"
+c_type = "char*"
+help_name = "FILE"
+
+[[positional]]
+c_var = "output"
+c_type = "char*"
+help_name = "OUT"
+"#,
+        )
+        .unwrap();
+        let changes: Vec<String> = old.compare(&new).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "breaking: --size is renamed --block-size",
+                "breaking: --block-size's short name is -b, not -s",
+                "breaking: --block-size's value is char*, not int",
+                "compatible: --colour is renamed --color, keeping --colour as an alias",
+                "breaking: --verbose is removed",
+                "compatible: --quiet is added",
+                "compatible: argument INPUT is renamed FILE",
+                "compatible: argument FILE is no longer required",
+                "compatible: argument OUT is added",
+            ]
+        );
+        let same = old.compare(&old);
+        assert!(same.is_empty());
+        let strict = Spec::from_str(&format!("allow_abbrev = false\n{}", OLD)).unwrap();
+        assert_eq!(
+            old.compare(&strict)[0].to_string(),
+            "breaking: long options can no longer be abbreviated"
+        );
+        let changes = new.compare(&old);
+        assert!(changes
+            .iter()
+            .any(|c| c.impact == Impact::Breaking && c.message == "--color is renamed --colour"));
+        assert_eq!(
+            changes[0].json(),
+            "{\"impact\":\"breaking\",\"command\":null,\"message\":\"--block-size is renamed --size\"}"
+        );
+        // a global option is every command's; subcommands match by name
+        let old = Spec::from_str(
+            r#"
+[[global.non_positional]]
+c_var = "verbose"
+c_type = "int"
+long = "verbose"
+flag = true
+
+[[subcommand]]
+name = "fetch"
+
+[[subcommand]]
+name = "push"
+"#,
+        )
+        .unwrap();
+        let new = Spec::from_str(
+            r#"
+[[subcommand]]
+name = "fetch"
+
+[[subcommand.non_positional]]
+c_var = "depth"
+c_type = "int"
+long = "depth"
+required = true
+
+[[subcommand]]
+name = "pull"
+"#,
+        )
+        .unwrap();
+        let changes = old.compare(&new);
+        let shown: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "breaking: --verbose is removed",
+                "breaking: fetch: --depth is added, and required",
+                "breaking: subcommand push is removed",
+                "compatible: subcommand pull is added",
+            ]
+        );
+        assert_eq!(changes[1].command, "fetch");
+    }
+}
@@ -0,0 +1,752 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The C++ backend: a header-only parser filling a struct of std::string,
+//! std::vector and std::optional fields, which throws on a bad command line
+//! or, with on_error = "return", returns an std::expected-style result.
+
+use super::{
+    c_quote, main_body, CType, NonPositionalItem, OnError, PositionalItem, Spec, ValidationError,
+};
+
+/// The C++ spelling of a type.
+fn cpp_type(c_type: CType) -> &'static str {
+    match c_type {
+        CType::Chars => "std::string",
+        CType::Int => "int",
+    }
+}
+
+/// A default as a C++ initializer, starting with " = ".
+fn cpp_default(c_type: CType, default: &str) -> String {
+    match c_type {
+        CType::Chars => format!(" = \"{}\"", c_quote(default)),
+        CType::Int => format!(" = {}", default),
+    }
+}
+
+/// An expression converting the std::string value to the given type.
+fn cpp_convert(c_type: CType, value: &str, what: &str) -> String {
+    match c_type {
+        CType::Chars => value.to_owned(),
+        CType::Int => format!("args_int({}, {})", value, what),
+    }
+}
+
+impl NonPositionalItem {
+    /// Whether parse_args needs to know if this option was given.
+    fn cpp_tracks_isset(&self) -> bool {
+        self.is_required() || self.env.is_some()
+    }
+    /// This option's member of the args struct. Options with neither a
+    /// default nor required are optional.
+    fn cpp_member(&self) -> String {
+        if self.is_flag() {
+            return format!("\tbool {} = false;\n", self.c_var);
+        }
+        let ty = cpp_type(self.c_type);
+        match &self.default {
+            Some(default) => format!(
+                "\t{} {}{};\n",
+                ty,
+                self.c_var,
+                cpp_default(self.c_type, default)
+            ),
+            None if self.is_required() => format!("\t{} {}{{}};\n", ty, self.c_var),
+            None => format!("\tstd::optional<{}> {};\n", ty, self.c_var),
+        }
+    }
+    /// The case setting this option, with id, from name and value.
+    fn cpp_case(&self, id: usize) -> String {
+        let mut case = format!("\t\tcase {}:\n", id);
+        if self.is_flag() {
+            case.push_str(&format!("\t\t\tout.{} = true;\n", self.c_var));
+        } else {
+            case.push_str(&format!(
+                "\t\t\tout.{} = {};\n",
+                self.c_var,
+                cpp_convert(self.c_type, "value", "name")
+            ));
+        }
+        if self.cpp_tracks_isset() {
+            case.push_str(&format!("\t\t\t{}__isset = true;\n", self.c_var));
+        }
+        case.push_str("\t\t\tbreak;\n");
+        case
+    }
+    /// Falls back on the environment, then checks the option was given if
+    /// it is required.
+    fn cpp_post_loop(&self) -> String {
+        let mut post = String::new();
+        if let Some(env) = &self.env {
+            let value = if self.is_flag() {
+                String::from("args_truthy(env)")
+            } else {
+                cpp_convert(self.c_type, "env", &format!("\"${}\"", env))
+            };
+            post.push_str(&format!(
+                "\tif (!{0}__isset) {{\n\
+                 \t\tif (const char *env = std::getenv(\"{1}\")) {{\n\
+                 \t\t\tout.{0} = {2};\n\
+                 \t\t\t{0}__isset = true;\n\
+                 \t\t}}\n\
+                 \t}}\n",
+                self.c_var, env, value
+            ));
+        }
+        if self.is_required() {
+            post.push_str(&format!(
+                "\tif (!{}__isset) {{\n\
+                 \t\tthrow args_error(\"missing required option '--{}'\");\n\
+                 \t}}\n",
+                self.c_var, self.long
+            ));
+        }
+        post
+    }
+}
+
+impl PositionalItem {
+    /// This argument's member of the args struct. Arguments with neither a
+    /// default nor required are optional.
+    fn cpp_member(&self) -> String {
+        let ty = cpp_type(self.c_type);
+        if self.is_multi() {
+            return format!("\tstd::vector<{}> {};\n", ty, self.c_var);
+        }
+        match &self.default {
+            Some(default) => format!(
+                "\t{} {}{};\n",
+                ty,
+                self.c_var,
+                cpp_default(self.c_type, default)
+            ),
+            None if self.is_required() => format!("\t{} {}{{}};\n", ty, self.c_var),
+            None => format!("\tstd::optional<{}> {};\n", ty, self.c_var),
+        }
+    }
+    /// Takes this argument from the operands, from index next on.
+    fn cpp_assign(&self) -> String {
+        let what = format!("\"{}\"", c_quote(&self.help_name));
+        let mut assign = String::new();
+        if self.is_required() {
+            assign.push_str(&format!(
+                "\tif (next >= operands.size()) {{\n\
+                 \t\tthrow args_error(\"missing {}\");\n\
+                 \t}}\n",
+                c_quote(&self.help_name)
+            ));
+        }
+        if self.is_multi() {
+            assign.push_str(&format!(
+                "\tfor (; next < operands.size(); next++) {{\n\
+                 \t\tout.{}.push_back(operands[next]);\n\
+                 \t}}\n",
+                self.c_var
+            ));
+            if let Some(default) = &self.default {
+                assign.push_str(&format!(
+                    "\tif (out.{0}.empty()) {{\n\
+                     \t\tout.{0}.push_back(\"{1}\");\n\
+                     \t}}\n",
+                    self.c_var,
+                    c_quote(default)
+                ));
+            }
+            return assign;
+        }
+        let value = cpp_convert(self.c_type, "operands[next++]", &what);
+        if self.is_required() {
+            assign.push_str(&format!("\tout.{} = {};\n", self.c_var, value));
+        } else {
+            assign.push_str(&format!(
+                "\tif (next < operands.size()) {{\n\
+                 \t\tout.{} = {};\n\
+                 \t}}\n",
+                self.c_var, value
+            ));
+        }
+        assign
+    }
+}
+
+impl Spec {
+    /// Checks that the spec only uses what the C++ backend supports.
+    pub fn check_cpp(&self) -> Result<(), ValidationError> {
+        let unsupported = [
+            ("response_files", self.has_response_files()),
+            ("config", self.has_config()),
+            ("pre_parse_fn", self.pre_parse_fn.is_some()),
+            ("post_parse_fn", self.post_parse_fn.is_some()),
+            ("subcommands", self.has_subcommands()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((feature, _)) => Err(ValidationError::BackendUnsupported(
+                String::from("c++"),
+                String::from(*feature),
+            )),
+            None => Ok(()),
+        }
+    }
+    fn cpp_struct_name(&self) -> &str {
+        self.struct_name().unwrap_or("args")
+    }
+    fn cpp_headers(&self) -> String {
+        let mut includes = vec![
+            "cstdlib",
+            "iostream",
+            "optional",
+            "stdexcept",
+            "string",
+            "vector",
+        ];
+        if self.is_case_insensitive() {
+            includes.push("cctype");
+        }
+        if self.on_error() == OnError::Return {
+            includes.push("utility");
+        }
+        includes.sort_unstable();
+        let mut headers: String = includes
+            .iter()
+            .map(|s| format!("#include <{}>\n", s))
+            .collect();
+        for include in self.extra_includes.iter().flatten() {
+            if include.starts_with('<') {
+                headers.push_str(&format!("#include {}\n", include));
+            } else {
+                headers.push_str(&format!("#include \"{}\"\n", include));
+            }
+        }
+        if let Some(decls) = self.extra_decls.as_ref().filter(|d| !d.is_empty()) {
+            headers.push('\n');
+            for decl in decls {
+                headers.push_str(decl.trim_end());
+                headers.push('\n');
+            }
+        }
+        headers
+    }
+    /// The struct of parsed values, and the types parse_args reports errors with.
+    fn cpp_types(&self) -> String {
+        let name = self.cpp_struct_name();
+        let mut types = format!("struct {} {{\n", name);
+        for npi in &self.non_positional {
+            types.push_str(&npi.cpp_member());
+        }
+        for pi in &self.positional {
+            types.push_str(&pi.cpp_member());
+        }
+        if let Some(rest) = &self.capture_rest {
+            types.push_str(&format!("\tstd::vector<std::string> {};\n", rest));
+        }
+        types.push_str("};\n\n");
+        types.push_str(
+            "// Why the command line could not be parsed.\n\
+             struct args_error : std::runtime_error {\n\
+             \tusing std::runtime_error::runtime_error;\n\
+             };\n",
+        );
+        if self.on_error() == OnError::Return {
+            types.push_str(&format!(
+                "\n\
+                 // The parsed arguments, or the error that stopped parsing, in the\n\
+                 // manner of std::expected.\n\
+                 class args_result {{\n\
+                 public:\n\
+                 \targs_result({0} value) : value_(std::move(value)) {{}}\n\
+                 \targs_result(args_error error) : error_(std::move(error)) {{}}\n\
+                 \tbool has_value() const {{\n\
+                 \t\treturn value_.has_value();\n\
+                 \t}}\n\
+                 \texplicit operator bool() const {{\n\
+                 \t\treturn has_value();\n\
+                 \t}}\n\
+                 \t{0} &value() {{\n\
+                 \t\tif (!value_) {{\n\
+                 \t\t\tthrow *error_;\n\
+                 \t\t}}\n\
+                 \t\treturn *value_;\n\
+                 \t}}\n\
+                 \t{0} &operator*() {{\n\
+                 \t\treturn *value_;\n\
+                 \t}}\n\
+                 \t{0} *operator->() {{\n\
+                 \t\treturn &*value_;\n\
+                 \t}}\n\
+                 \tconst args_error &error() const {{\n\
+                 \t\treturn *error_;\n\
+                 \t}}\n\
+                 \n\
+                 private:\n\
+                 \tstd::optional<{0}> value_;\n\
+                 \tstd::optional<args_error> error_;\n\
+                 }};\n",
+                name
+            ));
+        }
+        types
+    }
+    fn cpp_usage(&self) -> String {
+        let ir = self.analyze();
+        format!(
+            "inline void {}(std::ostream &out, const char *progname) {{\n\
+             \tout << \"usage: \" << progname << \" [options]{}\\n\"\n\
+             {}\t       ;\n\
+             }}\n",
+            self.sym("usage"),
+            ir.synopsis,
+            ir.usage_help()
+        )
+    }
+    /// Functions parse_args relies on, for those features that use them.
+    fn cpp_helpers(&self) -> String {
+        let mut helpers = String::from(
+            "struct args_option {\n\
+             \tconst char *name;\n\
+             \tint id;\n\
+             \tbool has_arg;\n\
+             };\n\n",
+        );
+        let equal = if self.is_case_insensitive() {
+            helpers.push_str(
+                "inline bool args_equal(const std::string &a, const std::string &b) {\n\
+                 \tif (a.size() != b.size()) {\n\
+                 \t\treturn false;\n\
+                 \t}\n\
+                 \tfor (std::string::size_type i = 0; i < a.size(); i++) {\n\
+                 \t\tif (std::tolower(static_cast<unsigned char>(a[i])) != \
+                 std::tolower(static_cast<unsigned char>(b[i]))) {\n\
+                 \t\t\treturn false;\n\
+                 \t\t}\n\
+                 \t}\n\
+                 \treturn true;\n\
+                 }\n\n",
+            );
+            "args_equal(candidate, name)"
+        } else {
+            "candidate == name"
+        };
+        helpers.push_str(
+            "inline const args_option *args_find_long(const args_option *opts, std::size_t n, \
+             const std::string &name) {\n",
+        );
+        if self.allows_abbrev() {
+            let prefix = if self.is_case_insensitive() {
+                "args_equal(candidate.substr(0, name.size()), name)"
+            } else {
+                "candidate.compare(0, name.size(), name) == 0"
+            };
+            helpers.push_str(&format!(
+                "\tconst args_option *match = nullptr;\n\
+                 \tbool ambiguous = false;\n\
+                 \tfor (std::size_t i = 0; i < n; i++) {{\n\
+                 \t\tstd::string candidate = opts[i].name;\n\
+                 \t\tif ({}) {{\n\
+                 \t\t\treturn &opts[i];\n\
+                 \t\t}}\n\
+                 \t\tif (name.size() < candidate.size() && {}) {{\n\
+                 \t\t\tif (match && match->id != opts[i].id) {{\n\
+                 \t\t\t\tambiguous = true;\n\
+                 \t\t\t}}\n\
+                 \t\t\tmatch = &opts[i];\n\
+                 \t\t}}\n\
+                 \t}}\n\
+                 \tif (ambiguous) {{\n\
+                 \t\tthrow args_error(\"option '--\" + name + \"' is ambiguous\");\n\
+                 \t}}\n\
+                 \treturn match;\n\
+                 }}\n\n",
+                equal, prefix
+            ));
+        } else {
+            helpers.push_str(&format!(
+                "\tfor (std::size_t i = 0; i < n; i++) {{\n\
+                 \t\tstd::string candidate = opts[i].name;\n\
+                 \t\tif ({}) {{\n\
+                 \t\t\treturn &opts[i];\n\
+                 \t\t}}\n\
+                 \t}}\n\
+                 \treturn nullptr;\n\
+                 }}\n\n",
+                equal
+            ));
+        }
+        let ints = self
+            .non_positional
+            .iter()
+            .any(|npi| !npi.is_flag() && matches!(npi.c_type, CType::Int))
+            || self
+                .positional
+                .iter()
+                .any(|pi| matches!(pi.c_type, CType::Int));
+        if ints {
+            helpers.push_str(
+                "inline int args_int(const std::string &value, const std::string &what) {\n\
+                 \tstd::size_t end = 0;\n\
+                 \tint n = 0;\n\
+                 \ttry {\n\
+                 \t\tn = std::stoi(value, &end);\n\
+                 \t} catch (const std::exception &) {\n\
+                 \t\tend = 0;\n\
+                 \t}\n\
+                 \tif (end == 0 || end != value.size()) {\n\
+                 \t\tthrow args_error(\"invalid number for \" + what + \": '\" + value + \"'\");\n\
+                 \t}\n\
+                 \treturn n;\n\
+                 }\n\n",
+            );
+        }
+        if self
+            .non_positional
+            .iter()
+            .any(|npi| npi.is_flag() && npi.env.is_some())
+        {
+            helpers.push_str(
+                "inline bool args_truthy(const std::string &value) {\n\
+                 \treturn value == \"1\" || value == \"true\" || value == \"yes\" || value == \"on\";\n\
+                 }\n\n",
+            );
+        }
+        helpers
+    }
+    /// The parse_args function, which throws args_error on a bad command line.
+    fn cpp_parse_args(&self, name: &str) -> String {
+        let mut longopts = String::from("\t\t{\"help\", -1, false},\n");
+        let mut shortopts = String::from("\t\t{\"h\", -1, false},\n");
+        for (id, npi) in self.non_positional.iter().enumerate() {
+            let has_arg = if npi.is_flag() { "false" } else { "true" };
+            for long in std::iter::once(&npi.long).chain(npi.aliases.iter().flatten()) {
+                longopts.push_str(&format!("\t\t{{\"{}\", {}, {}}},\n", long, id, has_arg));
+            }
+            if let Some(short) = &npi.short {
+                shortopts.push_str(&format!("\t\t{{\"{}\", {}, {}}},\n", short, id, has_arg));
+            }
+        }
+        let mut body = format!(
+            "inline {} {}(int argc, char **argv) {{\n\
+             \tstatic const args_option longopts[] = {{\n{}\t}};\n\
+             \tstatic const args_option shortopts[] = {{\n{}\t}};\n\
+             \t{0} out;\n",
+            self.cpp_struct_name(),
+            name,
+            longopts,
+            shortopts
+        );
+        for npi in self
+            .non_positional
+            .iter()
+            .filter(|npi| npi.cpp_tracks_isset())
+        {
+            body.push_str(&format!("\tbool {}__isset = false;\n", npi.c_var));
+        }
+        body.push_str("\tstd::vector<std::string> operands;\n\tint end = argc;\n");
+        if let Some(rest) = &self.capture_rest {
+            body.push_str(&format!(
+                "\tfor (int i = 1; i < argc; i++) {{\n\
+                 \t\tif (std::string(argv[i]) == \"--\") {{\n\
+                 \t\t\tout.{}.assign(argv + i + 1, argv + argc);\n\
+                 \t\t\tend = i;\n\
+                 \t\t\tbreak;\n\
+                 \t\t}}\n\
+                 \t}}\n",
+                rest
+            ));
+        }
+        body.push_str(
+            "\tauto set = [&](int id, const std::string &name, const std::string &value) {\n\
+             \t\t(void)name;\n\
+             \t\t(void)value;\n\
+             \t\tswitch (id) {\n",
+        );
+        for (id, npi) in self.non_positional.iter().enumerate() {
+            body.push_str(&npi.cpp_case(id));
+        }
+        body.push_str(&format!(
+            "\t\tdefault:\n\
+             \t\t\t{}(std::cout, argv[0]);\n\
+             \t\t\tstd::exit(0);\n\
+             \t\t}}\n\
+             \t}};\n",
+            self.sym("usage")
+        ));
+        body.push_str(
+            "\tint i = 1;\n\
+             \tfor (; i < end; i++) {\n\
+             \t\tstd::string arg = argv[i];\n\
+             \t\tif (arg == \"--\") {\n\
+             \t\t\ti++;\n\
+             \t\t\tbreak;\n\
+             \t\t}\n\
+             \t\tif (arg.size() > 2 && arg.compare(0, 2, \"--\") == 0) {\n\
+             \t\t\tstd::string name = arg.substr(2);\n\
+             \t\t\tstd::string value;\n\
+             \t\t\tstd::string::size_type eq = name.find('=');\n\
+             \t\t\tbool has_value = eq != std::string::npos;\n\
+             \t\t\tif (has_value) {\n\
+             \t\t\t\tvalue = name.substr(eq + 1);\n\
+             \t\t\t\tname.resize(eq);\n\
+             \t\t\t}\n\
+             \t\t\tconst args_option *opt = args_find_long(longopts, sizeof longopts / sizeof *longopts, name);\n\
+             \t\t\tif (!opt) {\n\
+             \t\t\t\tthrow args_error(\"unrecognized option '--\" + name + \"'\");\n\
+             \t\t\t}\n\
+             \t\t\tif (opt->has_arg && !has_value) {\n\
+             \t\t\t\tif (++i >= end) {\n\
+             \t\t\t\t\tthrow args_error(\"option '--\" + name + \"' requires an argument\");\n\
+             \t\t\t\t}\n\
+             \t\t\t\tvalue = argv[i];\n\
+             \t\t\t} else if (!opt->has_arg && has_value) {\n\
+             \t\t\t\tthrow args_error(\"option '--\" + name + \"' doesn't allow an argument\");\n\
+             \t\t\t}\n\
+             \t\t\tset(opt->id, \"--\" + name, value);\n\
+             \t\t} else if (arg.size() > 1 && arg[0] == '-') {\n\
+             \t\t\tfor (std::string::size_type j = 1; j < arg.size(); j++) {\n\
+             \t\t\t\tconst args_option *opt = nullptr;\n\
+             \t\t\t\tfor (const args_option &candidate : shortopts) {\n\
+             \t\t\t\t\tif (candidate.name[0] == arg[j]) {\n\
+             \t\t\t\t\t\topt = &candidate;\n\
+             \t\t\t\t\t}\n\
+             \t\t\t\t}\n\
+             \t\t\t\tstd::string name = std::string(\"-\") + arg[j];\n\
+             \t\t\t\tif (!opt) {\n\
+             \t\t\t\t\tthrow args_error(\"invalid option '\" + name + \"'\");\n\
+             \t\t\t\t}\n\
+             \t\t\t\tif (!opt->has_arg) {\n\
+             \t\t\t\t\tset(opt->id, name, \"\");\n\
+             \t\t\t\t} else if (j + 1 < arg.size()) {\n\
+             \t\t\t\t\tset(opt->id, name, arg.substr(j + 1));\n\
+             \t\t\t\t\tbreak;\n\
+             \t\t\t\t} else if (++i < end) {\n\
+             \t\t\t\t\tset(opt->id, name, argv[i]);\n\
+             \t\t\t\t} else {\n\
+             \t\t\t\t\tthrow args_error(\"option '\" + name + \"' requires an argument\");\n\
+             \t\t\t\t}\n\
+             \t\t\t}\n\
+             \t\t} else {\n",
+        );
+        if self.is_strict_posix() {
+            body.push_str("\t\t\tbreak;\n");
+        } else {
+            body.push_str("\t\t\toperands.push_back(arg);\n");
+        }
+        body.push_str("\t\t}\n\t}\n\toperands.insert(operands.end(), argv + i, argv + end);\n");
+        for npi in &self.non_positional {
+            body.push_str(&npi.cpp_post_loop());
+        }
+        if !self.positional.is_empty() {
+            body.push_str("\tstd::vector<std::string>::size_type next = 0;\n");
+            for pi in &self.positional {
+                body.push_str(&pi.cpp_assign());
+            }
+        }
+        body.push_str("\treturn out;\n}\n");
+        body
+    }
+    fn cpp_main(&self) -> String {
+        let name = self.cpp_struct_name();
+        let parse = self.sym("parse_args");
+        let usage = self.sym("usage");
+        let mut main = String::from("int main(int argc, char **argv) {\n");
+        match self.on_error() {
+            OnError::Exit => main.push_str(&format!(
+                "\t{} parsed;\n\
+                 \ttry {{\n\
+                 \t\tparsed = {}(argc, argv);\n\
+                 \t}} catch (const args_error &e) {{\n\
+                 \t\tstd::cerr << argv[0] << \": \" << e.what() << \"\\n\";\n\
+                 \t\t{}(std::cerr, argv[0]);\n\
+                 \t\treturn 1;\n\
+                 \t}}\n\n",
+                name, parse, usage
+            )),
+            OnError::Return => main.push_str(&format!(
+                "\targs_result parsed = {}(argc, argv);\n\
+                 \tif (!parsed) {{\n\
+                 \t\tstd::cerr << argv[0] << \": \" << parsed.error().what() << \"\\n\";\n\
+                 \t\t{}(std::cerr, argv[0]);\n\
+                 \t\treturn 1;\n\
+                 \t}}\n\n",
+                parse, usage
+            )),
+        }
+        main.push_str(&main_body());
+        main
+    }
+    /// Generates a C++17 parser for the spec. Everything is inline, so
+    /// without main the output can be used as a header.
+    pub fn gen_cpp(&self) -> String {
+        let parse = self.sym("parse_args");
+        let mut code = self.cgen_stamp();
+        code.push_str(&self.cpp_headers());
+        code.push('\n');
+        code.push_str(&self.cpp_types());
+        code.push('\n');
+        code.push_str(&self.cpp_usage());
+        code.push('\n');
+        code.push_str(&self.cpp_helpers());
+        match self.on_error() {
+            OnError::Exit => code.push_str(&self.cpp_parse_args(&parse)),
+            OnError::Return => {
+                let throwing = format!("{}__throwing", parse);
+                code.push_str(&self.cpp_parse_args(&throwing));
+                code.push_str(&format!(
+                    "\n\
+                     inline args_result {}(int argc, char **argv) {{\n\
+                     \ttry {{\n\
+                     \t\treturn {}(argc, argv);\n\
+                     \t}} catch (const args_error &e) {{\n\
+                     \t\treturn e;\n\
+                     \t}}\n\
+                     }}\n",
+                    parse, throwing
+                ));
+            }
+        }
+        if self.emits_main() {
+            code.push('\n');
+            code.push_str(&self.cpp_main());
+        }
+        self.cgen().layout(&code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    const SPEC: &str = r#"
+        [[positional]]
+        c_var = "input"
+        c_type = "char*"
+        help_name = "FILE"
+        required = true
+
+        [[positional]]
+        c_var = "count"
+        c_type = "int"
+        help_name = "N"
+
+        [[positional]]
+        c_var = "words"
+        c_type = "char*"
+        help_name = "WORD"
+        multi = true
+        default = "w"
+
+        [[non_positional]]
+        c_var = "size"
+        c_type = "int"
+        long = "size"
+        short = "s"
+        default = "3"
+
+        [[non_positional]]
+        c_var = "name"
+        c_type = "char*"
+        long = "name"
+        env = "PROG_NAME"
+
+        [[non_positional]]
+        c_var = "port"
+        c_type = "int"
+        long = "port"
+        required = true
+
+        [[non_positional]]
+        c_var = "quiet"
+        c_type = "int"
+        long = "quiet"
+        short = "q"
+        flag = true
+        "#;
+
+    #[test]
+    fn cpp_struct() {
+        let s = Spec::from_str(SPEC).unwrap();
+        let c = s.gen_cpp();
+        assert!(c.contains(
+            "struct args {\n\
+             \tint size = 3;\n\
+             \tstd::optional<std::string> name;\n\
+             \tint port{};\n\
+             \tbool quiet = false;\n\
+             \tstd::string input{};\n\
+             \tstd::optional<int> count;\n\
+             \tstd::vector<std::string> words;\n\
+             };\n"
+        ));
+        assert!(c.contains("\t\t{\"s\", 0, true},\n\t\t{\"q\", 3, false},\n"));
+        assert!(c.contains("\t\t\tout.size = args_int(value, name);\n"));
+        assert!(c.contains("\t\tif (const char *env = std::getenv(\"PROG_NAME\")) {\n"));
+        assert!(c.contains("\t\tthrow args_error(\"missing required option '--port'\");\n"));
+        assert!(c.contains("\t\tout.count = args_int(operands[next++], \"N\");\n"));
+        assert!(c.contains("\t\tout.words.push_back(\"w\");\n"));
+        assert!(c.contains("\t\tparsed = parse_args(argc, argv);\n"));
+        assert!(!c.contains("args_result"));
+    }
+
+    #[test]
+    fn cpp_on_error_return() {
+        let s = Spec::from_str(&format!("on_error = \"return\"\n{}", SPEC)).unwrap();
+        let c = s.gen_cpp();
+        assert!(c.contains("inline args parse_args__throwing(int argc, char **argv) {\n"));
+        assert!(c.contains("inline args_result parse_args(int argc, char **argv) {\n"));
+        assert!(c.contains("\targs_result parsed = parse_args(argc, argv);\n\tif (!parsed) {\n"));
+    }
+
+    #[test]
+    fn cpp_unsupported() {
+        let s = Spec::from_str(&format!("response_files = true\n{}", SPEC)).unwrap();
+        assert!(s.check_cpp().is_err());
+        assert!(Spec::from_str(SPEC).unwrap().check_cpp().is_ok());
+    }
+
+    #[test]
+    fn cpp_compiles() {
+        use std::process::Command;
+
+        let modes = [
+            "",
+            "on_error = \"return\"\ncapture_rest = \"rest\"\n",
+            "case_insensitive = true\nallow_abbrev = false\nstrict_posix = true\n",
+            "args_struct = \"opts\"\nprefix = \"my\"\nbraces = \"next_line\"\n",
+        ];
+        let dir = std::env::temp_dir();
+        for (i, extra) in modes.iter().enumerate() {
+            let s = Spec::from_str(&format!("{}{}", extra, SPEC)).unwrap();
+            let src = dir.join(format!("argen_cpp_{}.cpp", i));
+            std::fs::write(&src, s.gen_cpp()).unwrap();
+            let status = Command::new("c++")
+                .args([
+                    "-std=c++17",
+                    "-Wall",
+                    "-Wextra",
+                    "-Wpedantic",
+                    "-Werror",
+                    "-c",
+                    "-o",
+                ])
+                .arg(dir.join(format!("argen_cpp_{}.o", i)))
+                .arg(&src)
+                .status();
+            match status {
+                Ok(status) => assert!(status.success(), "c++ rejected {}", src.display()),
+                Err(_) => return, // no compiler to check against
+            }
+        }
+    }
+}
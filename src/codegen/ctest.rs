@@ -0,0 +1,521 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Unit tests for the generated C parser: a program that runs parse_args on
+//! canned command lines and checks what it makes of them.
+
+use super::{c_quote, int_literal, CType, NonPositionalItem, OnError, PositionalItem, Spec};
+
+/// A command line to parse, and what to check of the result.
+struct TestCase {
+    name: String,
+    args: Vec<String>,
+    /// C conditions that must hold after parsing.
+    checks: Vec<String>,
+    /// Whether parse_args should reject the command line.
+    fails: bool,
+}
+
+/// A value to give an item of the given type, other than its default.
+fn sample(c_type: CType, c_var: &str, default: Option<&str>) -> String {
+    match c_type {
+        CType::Int => default
+            .and_then(int_literal)
+            .map_or(42, |n| n.wrapping_add(1))
+            .to_string(),
+        CType::Chars => format!("{}_sample", c_var),
+    }
+}
+
+/// A C condition that value, an expression of the given type, is expected.
+fn equals(c_type: CType, value: &str, expected: &str) -> String {
+    match c_type {
+        CType::Int => format!(
+            "{} == {}",
+            value,
+            int_literal(expected).map_or_else(|| expected.to_owned(), |n| n.to_string())
+        ),
+        CType::Chars => format!("strcmp({}, \"{}\") == 0", value, c_quote(expected)),
+    }
+}
+
+impl NonPositionalItem {
+    fn ctest_sample(&self) -> String {
+        sample(self.c_type, &self.c_var, self.default.as_deref())
+    }
+    /// The arguments giving this option, by its long or short name.
+    fn ctest_args(&self, short: bool) -> Vec<String> {
+        let name = match &self.short {
+            Some(c) if short => format!("-{}", c),
+            _ => format!("--{}", self.long),
+        };
+        if self.is_flag() {
+            vec![name]
+        } else {
+            vec![name, self.ctest_sample()]
+        }
+    }
+}
+
+impl PositionalItem {
+    fn ctest_sample(&self) -> String {
+        sample(self.c_type, &self.c_var, self.default.as_deref())
+    }
+}
+
+impl Spec {
+    /// How a test reads a parsed value: a variable, a member of the args
+    /// struct, or a getter.
+    fn ctest_value(&self, field: &str) -> String {
+        if self.has_accessors() {
+            format!("{}()", self.sym(&format!("args_get_{}", field)))
+        } else if self.struct_name().is_some() {
+            format!("args.{}", field)
+        } else {
+            field.to_owned()
+        }
+    }
+    /// The arguments giving every required option.
+    fn ctest_required_options(&self) -> Vec<String> {
+        self.non_positional
+            .iter()
+            .filter(|npi| npi.is_required())
+            .flat_map(|npi| npi.ctest_args(false))
+            .collect()
+    }
+    /// The arguments giving every required positional argument.
+    fn ctest_required_positional(&self) -> Vec<String> {
+        self.positional
+            .iter()
+            .filter(|pi| pi.is_required())
+            .map(|pi| pi.ctest_sample())
+            .collect()
+    }
+    /// Checks that the required items got their samples.
+    fn ctest_required_checks(&self) -> Vec<String> {
+        let npis = self
+            .non_positional
+            .iter()
+            .filter(|npi| npi.is_required() && !npi.is_flag())
+            .map(|npi| {
+                equals(
+                    npi.c_type,
+                    &self.ctest_value(&npi.c_var),
+                    &npi.ctest_sample(),
+                )
+            });
+        let pis = self
+            .positional
+            .iter()
+            .filter(|pi| pi.is_required())
+            .map(|pi| {
+                if pi.is_multi() {
+                    format!("{} == 1", self.ctest_value(&format!("{}__size", pi.c_var)))
+                } else {
+                    equals(pi.c_type, &self.ctest_value(&pi.c_var), &pi.ctest_sample())
+                }
+            });
+        npis.chain(pis).collect()
+    }
+    /// Only the required items given: everything else takes its default.
+    fn ctest_defaults(&self) -> TestCase {
+        let mut args = self.ctest_required_options();
+        args.extend(self.ctest_required_positional());
+        let mut checks = self.ctest_required_checks();
+        for npi in &self.non_positional {
+            if let Some(default) = &npi.default {
+                checks.push(equals(npi.c_type, &self.ctest_value(&npi.c_var), default));
+            }
+        }
+        for pi in self.positional.iter().filter(|pi| !pi.is_required()) {
+            match &pi.default {
+                Some(default) if pi.is_multi() => {
+                    checks.push(format!(
+                        "{} == 1",
+                        self.ctest_value(&format!("{}__size", pi.c_var))
+                    ));
+                    checks.push(equals(
+                        pi.c_type,
+                        &format!("{}[0]", self.ctest_value(&pi.c_var)),
+                        default,
+                    ));
+                }
+                Some(default) => {
+                    checks.push(equals(pi.c_type, &self.ctest_value(&pi.c_var), default))
+                }
+                None => {}
+            }
+        }
+        for var in self.exposed_issets() {
+            checks.push(format!(
+                "{} == 0",
+                self.ctest_value(&format!("{}__isset", var))
+            ));
+        }
+        TestCase {
+            name: String::from("defaults"),
+            args,
+            checks,
+            fails: false,
+        }
+    }
+    /// Every item given, options by their long names, or their short names
+    /// where they have them.
+    fn ctest_everything(&self, short: bool) -> TestCase {
+        let mut args = Vec::new();
+        let mut checks = Vec::new();
+        let npis = self
+            .non_positional
+            .iter()
+            .filter(|npi| !short || npi.short.is_some());
+        for npi in npis {
+            args.extend(npi.ctest_args(short));
+            let value = self.ctest_value(&npi.c_var);
+            if npi.is_flag() {
+                checks.push(format!("{} == 1", value));
+            } else {
+                checks.push(equals(npi.c_type, &value, &npi.ctest_sample()));
+            }
+        }
+        if short {
+            // only what is needed to parse
+            args.extend(
+                self.non_positional
+                    .iter()
+                    .filter(|npi| npi.is_required() && npi.short.is_none())
+                    .flat_map(|npi| npi.ctest_args(false)),
+            );
+        }
+        for pi in &self.positional {
+            let value = self.ctest_value(&pi.c_var);
+            if pi.is_multi() {
+                let words = [format!("{}_1", pi.c_var), format!("{}_2", pi.c_var)];
+                checks.push(format!(
+                    "{} == 2",
+                    self.ctest_value(&format!("{}__size", pi.c_var))
+                ));
+                for (i, word) in words.iter().enumerate() {
+                    checks.push(equals(CType::Chars, &format!("{}[{}]", value, i), word));
+                }
+                args.extend(words.iter().cloned());
+            } else {
+                args.push(pi.ctest_sample());
+                checks.push(equals(pi.c_type, &value, &pi.ctest_sample()));
+            }
+        }
+        let exposed = self.exposed_issets();
+        let given = self
+            .non_positional
+            .iter()
+            .filter(|npi| !short || npi.short.is_some())
+            .map(|npi| &npi.c_var)
+            .chain(self.positional.iter().map(|pi| &pi.c_var));
+        for var in given.filter(|var| exposed.contains(&var.as_str())) {
+            checks.push(format!(
+                "{} == 1",
+                self.ctest_value(&format!("{}__isset", var))
+            ));
+        }
+        TestCase {
+            name: String::from(if short {
+                "short_options"
+            } else {
+                "long_options"
+            }),
+            args,
+            checks,
+            fails: false,
+        }
+    }
+    /// Command lines parse_args must reject: a required item left out, an
+    /// option without its value, and an option that doesn't exist.
+    fn ctest_failures(&self) -> Vec<TestCase> {
+        let mut cases = Vec::new();
+        let positional = self.ctest_required_positional();
+        for (i, npi) in self.non_positional.iter().enumerate() {
+            if npi.is_required() {
+                let mut args: Vec<String> = self
+                    .non_positional
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, other)| j != i && other.is_required())
+                    .flat_map(|(_, other)| other.ctest_args(false))
+                    .collect();
+                args.extend(positional.iter().cloned());
+                cases.push(TestCase {
+                    name: format!("missing_option_{}", npi.c_var),
+                    args,
+                    checks: Vec::new(),
+                    fails: true,
+                });
+            }
+        }
+        let required = self.positional.iter().filter(|pi| pi.is_required());
+        for (i, pi) in required.enumerate() {
+            let mut args = self.ctest_required_options();
+            args.extend(positional[..i].iter().cloned());
+            cases.push(TestCase {
+                name: format!("missing_argument_{}", pi.c_var),
+                args,
+                checks: Vec::new(),
+                fails: true,
+            });
+        }
+        // options must come before the operands to be seen with strict_posix
+        let operands = if self.is_strict_posix() {
+            Vec::new()
+        } else {
+            positional.clone()
+        };
+        for npi in self.non_positional.iter().filter(|npi| !npi.is_flag()) {
+            let mut args: Vec<String> = self
+                .non_positional
+                .iter()
+                .filter(|other| other.is_required() && other.c_var != npi.c_var)
+                .flat_map(|other| other.ctest_args(false))
+                .collect();
+            args.extend(operands.iter().cloned());
+            args.push(format!("--{}", npi.long));
+            cases.push(TestCase {
+                name: format!("missing_value_{}", npi.c_var),
+                args,
+                checks: Vec::new(),
+                fails: true,
+            });
+        }
+        let mut args = self.ctest_required_options();
+        args.push(String::from("--argen-no-such-option"));
+        args.extend(positional);
+        cases.push(TestCase {
+            name: String::from("unknown_option"),
+            args,
+            checks: Vec::new(),
+            fails: true,
+        });
+        cases
+    }
+    /// A test function, run in a child process by run_test.
+    fn ctest_function(&self, case: &TestCase) -> String {
+        let args: Vec<String> = std::iter::once(String::from("prog"))
+            .chain(case.args.iter().cloned())
+            .collect();
+        let mut f = format!("static void test_{}(void) {{\n", case.name);
+        // arrays rather than string literals, which aren't writable
+        for (i, arg) in args.iter().enumerate() {
+            f.push_str(&format!("\tchar arg{}[] = \"{}\";\n", i, c_quote(arg)));
+        }
+        let argv: Vec<String> = (0..args.len()).map(|i| format!("arg{}", i)).collect();
+        f.push_str(&format!(
+            "\tchar *argv[] = {{{}, NULL}};\n\tint argc = {};\n",
+            argv.join(", "),
+            args.len()
+        ));
+        f.push_str(&self.cgen_call_vars());
+        f.push('\n');
+        match self.on_error() {
+            OnError::Exit => f.push_str(&format!("\t{};\n", self.cgen_call())),
+            OnError::Return => f.push_str(&format!(
+                "\tif ({} != 0) {{\n\t\texit(1);\n\t}}\n",
+                self.cgen_call()
+            )),
+        }
+        for check in &case.checks {
+            f.push_str(&format!("\tcheck({}, \"{}\");\n", check, c_quote(check)));
+        }
+        f.push_str("}\n");
+        f
+    }
+    /// Generates a C program testing the parser generated for the spec,
+    /// which it contains: it parses happy paths, checks defaults, and makes
+    /// sure bad command lines are rejected, reporting each test on stdout
+    /// and exiting with 1 if any failed. Tests run in child processes, so
+    /// the program needs POSIX.
+    pub fn gen_tests(&self) -> String {
+        let stamp = self.cgen_stamp();
+        let parser = self.gen();
+        let parser = parser.strip_prefix(&stamp).unwrap_or(&parser);
+        let mut cases = vec![self.ctest_defaults(), self.ctest_everything(false)];
+        if self.non_positional.iter().any(|npi| npi.short.is_some()) {
+            cases.push(self.ctest_everything(true));
+        }
+        cases.extend(self.ctest_failures());
+        let mut tests = String::from(
+            "#include<sys/types.h>\n\
+             #include<sys/wait.h>\n\
+             #include<unistd.h>\n\
+             \n\
+             static int tests_failed = 0;\n\
+             /* where a test reports, the parser's own output being silenced */\n\
+             static FILE *report;\n\
+             \n\
+             static void check(int ok, const char *what) {\n\
+             \tif (!ok) {\n\
+             \t\tfprintf(report, \"\\tcheck failed: %s\\n\", what);\n\
+             \t\texit(2);\n\
+             \t}\n\
+             }\n\
+             \n\
+             /* Runs test in a child process, so a parser that exits can be tested,\n \
+             * and checks that it exits with the expected status. */\n\
+             static void run_test(const char *name, void (*test)(void), int expected) {\n\
+             \tint status;\n\
+             \tpid_t pid;\n\
+             \tfflush(stdout);\n\
+             \tpid = fork();\n\
+             \tif (pid < 0) {\n\
+             \t\tperror(\"fork\");\n\
+             \t\texit(1);\n\
+             \t}\n\
+             \tif (pid == 0) {\n\
+             \t\tint out = dup(1);\n\
+             \t\tif (out < 0 || !(report = fdopen(out, \"w\"))\n\
+             \t\t\t\t|| !freopen(\"/dev/null\", \"w\", stdout) || !freopen(\"/dev/null\", \"w\", stderr)) {\n\
+             \t\t\texit(3);\n\
+             \t\t}\n\
+             \t\ttest();\n\
+             \t\texit(0);\n\
+             \t}\n\
+             \tif (waitpid(pid, &status, 0) < 0 || !WIFEXITED(status) || WEXITSTATUS(status) != expected) {\n\
+             \t\tprintf(\"FAIL %s\\n\", name);\n\
+             \t\ttests_failed++;\n\
+             \t} else {\n\
+             \t\tprintf(\"ok   %s\\n\", name);\n\
+             \t}\n\
+             }\n",
+        );
+        for case in &cases {
+            tests.push('\n');
+            tests.push_str(&self.ctest_function(case));
+        }
+        tests.push_str("\nint main(void) {\n");
+        // values must come from the command line, not the environment
+        for npi in &self.non_positional {
+            if let Some(env) = &npi.env {
+                tests.push_str(&format!("\tunsetenv(\"{}\");\n", c_quote(env)));
+            }
+        }
+        for case in &cases {
+            tests.push_str(&format!(
+                "\trun_test(\"{0}\", test_{0}, {1});\n",
+                case.name,
+                if case.fails { 1 } else { 0 }
+            ));
+        }
+        tests.push_str("\treturn tests_failed != 0;\n}\n");
+        format!(
+            "{}#ifndef _POSIX_C_SOURCE\n#define _POSIX_C_SOURCE 200809L\n#endif\n{}\n{}",
+            stamp,
+            parser,
+            self.cgen().layout(&tests)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+    use std::process::Command;
+
+    const SPEC: &str = r#"
+        [[positional]]
+        c_var = "input"
+        c_type = "char*"
+        help_name = "FILE"
+        required = true
+
+        [[positional]]
+        c_var = "words"
+        c_type = "char*"
+        help_name = "WORD"
+        multi = true
+        default = "w"
+
+        [[non_positional]]
+        c_var = "size"
+        c_type = "int"
+        long = "size"
+        short = "s"
+        default = "3"
+
+        [[non_positional]]
+        c_var = "port"
+        c_type = "int"
+        long = "port"
+        required = true
+        env = "PROG_PORT"
+
+        [[non_positional]]
+        c_var = "quiet"
+        c_type = "int"
+        long = "quiet"
+        short = "q"
+        flag = true
+        "#;
+
+    #[test]
+    fn ctest_cases() {
+        let mut s = Spec::from_str(SPEC).unwrap();
+        s.set_emit_main(false);
+        let t = s.gen_tests();
+        assert!(t.contains(
+            "static void test_defaults(void) {\n\
+             \tchar arg0[] = \"prog\";\n\
+             \tchar arg1[] = \"--port\";\n\
+             \tchar arg2[] = \"42\";\n\
+             \tchar arg3[] = \"input_sample\";\n\
+             \tchar *argv[] = {arg0, arg1, arg2, arg3, NULL};\n\
+             \tint argc = 4;\n"
+        ));
+        assert!(t.contains("\tcheck(size == 3, \"size == 3\");\n"));
+        assert!(t.contains("\tcheck(strcmp(words[0], \"w\") == 0, "));
+        assert!(t.contains("\tchar arg1[] = \"-s\";\n\tchar arg2[] = \"4\";\n"));
+        assert!(t.contains("\trun_test(\"missing_option_port\", test_missing_option_port, 1);\n"));
+        assert!(t.contains("\trun_test(\"missing_value_size\", test_missing_value_size, 1);\n"));
+        assert!(t.contains("\tunsetenv(\"PROG_PORT\");\n"));
+        assert!(!t.contains("int main(int argc"));
+    }
+
+    #[test]
+    fn ctest_passes() {
+        let modes = [
+            "",
+            "args_struct = \"opts\"\non_error = \"return\"\n",
+            "accessors = true\nprefix = \"my\"\nexpose_isset = true\n",
+            "strict_posix = true\npedantic = true\nstd = \"c99\"\n",
+        ];
+        let dir = std::env::temp_dir();
+        for (i, extra) in modes.iter().enumerate() {
+            let mut s = Spec::from_str(&format!("{}{}", extra, SPEC)).unwrap();
+            s.set_emit_main(false);
+            let src = dir.join(format!("argen_ctest_{}.c", i));
+            let bin = dir.join(format!("argen_ctest_{}", i));
+            std::fs::write(&src, s.gen_tests()).unwrap();
+            let status = Command::new("cc")
+                .args(["-Wall", "-Wextra", "-Werror", "-o"])
+                .arg(&bin)
+                .arg(&src)
+                .status();
+            match status {
+                Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+                Err(_) => return, // no compiler to run the tests with
+            }
+            let run = Command::new(&bin).output().unwrap();
+            let out = String::from_utf8_lossy(&run.stdout);
+            assert!(run.status.success(), "{}:\n{}", src.display(), out);
+            assert!(out.contains("ok   unknown_option\n"));
+        }
+    }
+}
@@ -0,0 +1,216 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `-D key=value`: values of a spec set from the command line, once its
+//! includes are resolved and before its variables are substituted, so a
+//! build can set a version, a default or a variable without editing it.
+
+use super::{schema, Parser, Spec, ValidationError, Warning};
+use crate::log;
+use std::fmt;
+use std::str::FromStr;
+
+/// A value set from the command line: a setting, as in `version=1.4.2`, a
+/// variable, as in `vars.product=frob`, or a field of an item, by its c_var
+/// or long, as in `output.default=out.txt`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Define {
+    key: String,
+    value: String,
+}
+
+impl FromStr for Define {
+    type Err = String;
+    fn from_str(define: &str) -> Result<Define, String> {
+        match define.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok(Define {
+                key: key.trim().to_owned(),
+                value: value.to_owned(),
+            }),
+            _ => Err(format!("-D {}: expected KEY=VALUE", define)),
+        }
+    }
+}
+
+impl fmt::Display for Define {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+impl Define {
+    /// Whether the value is a setting of the spec, rather than a variable or
+    /// a field of an item.
+    pub fn is_setting(&self) -> bool {
+        !self.key.contains('.')
+    }
+    fn error(&self, reason: String) -> ValidationError {
+        ValidationError::BadDefine(self.to_string(), reason)
+    }
+
+    /// Sets the value in a spec's tables.
+    fn apply(&self, spec: &mut toml::value::Table) -> Result<(), ValidationError> {
+        let path: Vec<&str> = self.key.split('.').collect();
+        match path[..] {
+            ["vars", name] => {
+                let vars = spec
+                    .entry("vars")
+                    .or_insert_with(|| toml::Value::Table(Default::default()));
+                match vars {
+                    toml::Value::Table(vars) => {
+                        vars.insert(name.to_owned(), toml::Value::String(self.value.clone()));
+                        Ok(())
+                    }
+                    _ => Err(self.error(String::from("vars is not a table"))),
+                }
+            }
+            [setting] => {
+                let value = schema::parse_field("Spec", setting, &self.value)
+                    .map_err(|reason| self.error(reason))?;
+                spec.insert(setting.to_owned(), value);
+                Ok(())
+            }
+            [item, field] => {
+                for (kind, name) in [
+                    ("positional", "PositionalItem"),
+                    ("non_positional", "NonPositionalItem"),
+                ] {
+                    let items = match spec.get_mut(kind) {
+                        Some(toml::Value::Array(items)) => items,
+                        _ => continue,
+                    };
+                    for table in items.iter_mut().filter_map(toml::Value::as_table_mut) {
+                        if names(table, item) {
+                            let value = schema::parse_field(name, field, &self.value)
+                                .map_err(|reason| self.error(reason))?;
+                            table.insert(field.to_owned(), value);
+                            return Ok(());
+                        }
+                    }
+                }
+                Err(self.error(format!("no item has the c_var or long {}", item)))
+            }
+            _ => Err(self.error(String::from("expected a setting, vars.NAME, or ITEM.FIELD"))),
+        }
+    }
+}
+
+/// The settings only parser = "argp" uses.
+const ARGP_SETTINGS: [&str; 2] = ["version", "bug_address"];
+
+/// The defines setting what the spec's parser ignores, as warnings, since
+/// a build setting one expects it to show up.
+pub(super) fn ignored(defines: &[Define], spec: &Spec) -> Vec<Warning> {
+    if spec.parser() == Parser::Argp {
+        return vec![];
+    }
+    defines
+        .iter()
+        .filter(|define| ARGP_SETTINGS.contains(&define.key.as_str()))
+        .map(|define| Warning::IgnoredDefine(define.to_string()))
+        .collect()
+}
+
+/// Whether an item's table has the c_var or long.
+fn names(table: &toml::value::Table, name: &str) -> bool {
+    ["c_var", "long"]
+        .iter()
+        .any(|field| table.get(*field).and_then(toml::Value::as_str) == Some(name))
+}
+
+/// Sets each value, in order, in a spec.
+pub(super) fn apply(
+    value: toml::Value,
+    defines: &[Define],
+) -> Result<toml::Value, ValidationError> {
+    let mut spec = match value {
+        toml::Value::Table(spec) => spec,
+        // not a spec, which deserializing says
+        other => return Ok(other),
+    };
+    for define in defines {
+        log::debug(format_args!("setting -D {}", define));
+        define.apply(&mut spec)?;
+    }
+    Ok(toml::Value::Table(spec))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Spec, ValidationError};
+    use super::Define;
+    use std::path::Path;
+
+    #[test]
+    fn defines() {
+        let toml = r#"
+version = "${v}"
+
+[vars]
+v = "1.0"
+
+[[non_positional]]
+c_var = "size"
+long = "block-size"
+c_type = "int"
+default = "12"
+"#;
+        let parse = |defines: &[&str]| {
+            let defines: Vec<Define> = defines.iter().map(|d| d.parse().unwrap()).collect();
+            Spec::parse_str_with(toml, Path::new("."), &defines)
+        };
+        let s = parse(&["vars.v=1.4.2", "block-size.default=64", "emit_main=false"])
+            .ok()
+            .unwrap();
+        assert_eq!(
+            s.to_toml(),
+            "emit_main = false\n\
+             version = \"1.4.2\"\n\
+             \n\
+             [[non_positional]]\n\
+             c_type = \"int\"\n\
+             c_var = \"size\"\n\
+             default = \"64\"\n\
+             long = \"block-size\"\n"
+        );
+        let error = |defines: &[&str]| match parse(defines) {
+            Err(ValidationError::BadDefine(define, reason)) => (define, reason),
+            _ => panic!("expected a bad define"),
+        };
+        assert_eq!(
+            error(&["emit_main=yes"]),
+            (
+                String::from("emit_main=yes"),
+                String::from("emit_main is true or false")
+            )
+        );
+        assert_eq!(
+            error(&["out.default=x"]).1,
+            "no item has the c_var or long out"
+        );
+        assert_eq!(error(&["size.colour=red"]).1, "there is no field colour");
+        // only argp prints a version, so setting one for getopt warns
+        let lints = parse(&["version=1.4.2"]).ok().unwrap().lints();
+        assert_eq!(
+            lints[0].to_string(),
+            "-D version=1.4.2 is ignored, as only parser = \"argp\" uses it"
+        );
+        assert_eq!(lints[0].code(), "W006");
+        let argp = parse(&["version=1.4.2", "parser=argp"]).ok().unwrap();
+        assert!(argp.lints().iter().all(|w| w.code() != "W006"));
+        assert!("version".parse::<Define>().is_err());
+    }
+}
@@ -0,0 +1,239 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A JSON description of the command line a spec defines, with every
+//! default filled in, for tools other than a compiler to consume.
+
+use super::{CType, NonPositionalItem, OnError, PositionalItem, Spec};
+use crate::json::Json;
+
+/// The type of a value, as a JSON-minded consumer would think of it.
+fn value_type(c_type: CType) -> Json {
+    Json::Str(String::from(match c_type {
+        CType::Chars => "string",
+        CType::Int => "int",
+    }))
+}
+
+impl NonPositionalItem {
+    fn describe(&self) -> Json {
+        let value_name = if self.is_flag() {
+            None
+        } else {
+            Some(self.help_name.as_deref().unwrap_or("arg"))
+        };
+        Json::obj(vec![
+            ("c_var", Json::Str(self.c_var.clone())),
+            ("long", Json::Str(self.long.clone())),
+            ("short", Json::opt_str(self.short.as_deref())),
+            (
+                "aliases",
+                Json::Arr(
+                    self.aliases
+                        .iter()
+                        .flatten()
+                        .map(|a| Json::Str(a.clone()))
+                        .collect(),
+                ),
+            ),
+            (
+                "type",
+                if self.is_flag() {
+                    Json::Str(String::from("bool"))
+                } else {
+                    value_type(self.c_type)
+                },
+            ),
+            ("c_type", Json::Str(self.c_type.to_string())),
+            ("flag", Json::Bool(self.is_flag())),
+            ("value_name", Json::opt_str(value_name)),
+            (
+                "help",
+                Json::Str(self.help_descr.clone().unwrap_or_default()),
+            ),
+            ("default", Json::opt_str(self.default.as_deref())),
+            (
+                "show_default",
+                Json::Bool(self.show_default.unwrap_or(true)),
+            ),
+            ("required", Json::Bool(self.is_required())),
+            ("env", Json::opt_str(self.env.as_deref())),
+            ("builtin", Json::Bool(false)),
+        ])
+    }
+}
+
+impl PositionalItem {
+    fn describe(&self) -> Json {
+        Json::obj(vec![
+            ("c_var", Json::Str(self.c_var.clone())),
+            ("type", value_type(self.c_type)),
+            ("c_type", Json::Str(self.c_type.to_string())),
+            ("value_name", Json::Str(self.help_name.clone())),
+            (
+                "help",
+                Json::Str(self.help_descr.clone().unwrap_or_default()),
+            ),
+            ("default", Json::opt_str(self.default.as_deref())),
+            (
+                "show_default",
+                Json::Bool(self.show_default.unwrap_or(true)),
+            ),
+            ("required", Json::Bool(self.is_required())),
+            ("multi", Json::Bool(self.is_multi())),
+        ])
+    }
+}
+
+/// An option the generated parser adds by itself.
+fn builtin_option(
+    long: Option<&str>,
+    short: Option<&str>,
+    value_name: Option<&str>,
+    help: &str,
+) -> Json {
+    Json::obj(vec![
+        ("c_var", Json::Null),
+        ("long", Json::opt_str(long)),
+        ("short", Json::opt_str(short)),
+        ("aliases", Json::Arr(vec![])),
+        (
+            "type",
+            Json::Str(String::from(if value_name.is_some() {
+                "string"
+            } else {
+                "bool"
+            })),
+        ),
+        ("c_type", Json::Null),
+        ("flag", Json::Bool(value_name.is_none())),
+        ("value_name", Json::opt_str(value_name)),
+        ("help", Json::Str(help.to_owned())),
+        ("default", Json::Null),
+        ("show_default", Json::Bool(true)),
+        ("required", Json::Bool(false)),
+        ("env", Json::Null),
+        ("builtin", Json::Bool(true)),
+    ])
+}
+
+impl Spec {
+    /// Describes the command line the spec defines as JSON: its options,
+    /// positional arguments and parsing behaviour, with defaults resolved.
+    pub fn describe_json(&self) -> String {
+        format!("{}\n", self.describe())
+    }
+    /// The description describe_json writes out.
+    pub(super) fn describe(&self) -> Json {
+        let long = self.has_long_options();
+        let mut options = vec![builtin_option(
+            Some("help").filter(|_| long),
+            Some("h"),
+            None,
+            "print this usage and exit",
+        )];
+        options.extend(self.non_positional.iter().map(|npi| npi.describe()));
+        if self.has_config_option() {
+            options.push(builtin_option(
+                Some("config"),
+                None,
+                Some("file"),
+                "read option defaults from file",
+            ));
+        }
+        let settings = Json::obj(vec![
+            ("long_options", Json::Bool(long)),
+            ("strict_posix", Json::Bool(self.is_strict_posix())),
+            ("allow_abbrev", Json::Bool(self.allows_abbrev())),
+            ("case_insensitive", Json::Bool(self.is_case_insensitive())),
+            (
+                "on_error",
+                Json::Str(String::from(match self.on_error() {
+                    OnError::Exit => "exit",
+                    OnError::Return => "return",
+                })),
+            ),
+            ("capture_rest", Json::opt_str(self.capture_rest.as_deref())),
+            ("response_files", Json::Bool(self.has_response_files())),
+            ("config_file", Json::opt_str(self.config_file.as_deref())),
+        ]);
+        Json::obj(vec![
+            // bumped whenever a key changes meaning or goes away
+            ("format_version", Json::Num(1)),
+            (
+                "synopsis",
+                Json::Str(format!("[options]{}", self.usage_synopsis())),
+            ),
+            ("spec_sha256", Json::opt_str(self.spec_hash.as_deref())),
+            ("settings", settings),
+            ("options", Json::Arr(options)),
+            (
+                "positional",
+                Json::Arr(self.positional.iter().map(|pi| pi.describe()).collect()),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    #[test]
+    fn describe_json() {
+        let s = Spec::from_str(
+            r#"
+            capture_rest = "rest"
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            help_descr = "a \"word\""
+            multi = true
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            short = "s"
+            aliases = ["sz"]
+            default = "3"
+            env = "SIZE"
+            "#,
+        )
+        .unwrap();
+        let j = s.describe_json();
+        assert!(j.starts_with(
+            "{\n  \"format_version\": 1,\n  \"synopsis\": \"[options] [WORD...] [-- ARG...]\",\n"
+        ));
+        assert!(j.contains("    \"allow_abbrev\": true,\n"));
+        assert!(j.contains("    \"capture_rest\": \"rest\",\n"));
+        assert!(j.contains(
+            "      \"long\": \"size\",\n\
+             \x20     \"short\": \"s\",\n\
+             \x20     \"aliases\": [\n\
+             \x20       \"sz\"\n\
+             \x20     ],\n\
+             \x20     \"type\": \"int\",\n"
+        ));
+        assert!(j.contains("      \"env\": \"SIZE\",\n      \"builtin\": false\n"));
+        assert!(j.contains("      \"long\": \"help\",\n      \"short\": \"h\",\n"));
+        assert!(j.contains("      \"help\": \"a \\\"word\\\"\",\n"));
+        assert!(j.contains("      \"multi\": true\n"));
+        assert!(j.ends_with("}\n"));
+    }
+}
@@ -0,0 +1,234 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Everything wrong with a spec, each problem with how bad it is, its code,
+//! where in the spec's source it is and what more there is to know, for a
+//! caller with no terminal to print errors on.
+
+use super::{Label, Spec, ValidationError, Warning, SCHEMA};
+use std::error::Error;
+use std::fmt;
+
+/// How bad a problem is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The spec can't be generated from.
+    Error,
+    /// The spec generates, but probably not what was meant.
+    Warning,
+}
+
+/// A problem with a spec.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    /// How bad the problem is.
+    pub severity: Severity,
+    /// The problem's code, as ValidationError::code or Warning::code gives
+    /// it.
+    pub code: &'static str,
+    /// What the problem is.
+    pub message: String,
+    /// Where the problem is in the spec's source, the part at fault first;
+    /// none for a problem with no place in it.
+    pub labels: Vec<Label>,
+    /// What more there is to know, such as why it is a problem, or what is
+    /// allowed instead.
+    pub notes: Vec<String>,
+}
+
+/// The problems with a spec, in the order they were found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl ValidationError {
+    /// What more there is to know about the error than its message says.
+    fn notes(&self) -> Vec<String> {
+        let note = match self {
+            ValidationError::RequiredHasDefault(_) => {
+                "a required item is always given, so its default would never be used"
+            }
+            ValidationError::FlagMustBeInt(_)
+            | ValidationError::FlagHasDefault(_)
+            | ValidationError::FlagCannotBeRequired(_) => {
+                "a flag is an int, 1 when it is given and 0 when it isn't"
+            }
+            ValidationError::RequiredPositionalGoesBeforeOptionPositional(_) => {
+                "arguments are taken in order, so an optional one before a required one could never be left out"
+            }
+            ValidationError::MultiMustBeLast(_) => {
+                "an argument taking several values takes the rest of the command line"
+            }
+            ValidationError::PosixNeedsShort(_)
+            | ValidationError::PosixCaseInsensitive
+            | ValidationError::PosixConfigOption
+            | ValidationError::PosixDumpArgs => {
+                "POSIX getopt takes only short options; parser = \"getopt\" or \"reentrant\" takes long ones"
+            }
+            ValidationError::PortableNeedsScanner | ValidationError::ThreadSafeNeedsScanner => {
+                "parser = \"reentrant\" generates a scanner of its own, with no state kept between calls"
+            }
+            ValidationError::TooManyOptions(_, _) => {
+                "an option with a short name is returned as it, so needs no other value"
+            }
+            ValidationError::HandlerNeedsStruct(_) => {
+                "a handler is passed the parsed arguments as the struct args_struct names"
+            }
+            ValidationError::UnknownSchema(_) => {
+                return vec![format!("this argen reads schemas 1 to {}", SCHEMA)]
+            }
+            _ => return vec![],
+        };
+        vec![note.to_owned()]
+    }
+}
+
+impl Diagnostics {
+    /// The problems, found in the spec's source.
+    pub(super) fn new(source: &str, errors: Vec<ValidationError>) -> Diagnostics {
+        Diagnostics(
+            errors
+                .into_iter()
+                .map(|error| Diagnostic {
+                    severity: Severity::Error,
+                    code: error.code(),
+                    message: error.to_string(),
+                    labels: error.labels(source),
+                    notes: error.notes(),
+                })
+                .collect(),
+        )
+    }
+    /// The errors, which keep a spec from being generated from.
+    pub fn errors(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+    /// Whether there are any errors, rather than only warnings.
+    pub fn has_errors(&self) -> bool {
+        self.errors().next().is_some()
+    }
+    /// The problems, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.0.iter()
+    }
+    /// How many problems there are.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    /// Whether there are none.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Spec {
+    /// Everything wrong with a spec read from source: its problems, as
+    /// errors, then its lints, as warnings.
+    pub fn diagnostics(&self, source: &str) -> Diagnostics {
+        let mut diagnostics = Diagnostics::new(source, self.problems());
+        diagnostics
+            .0
+            .extend(self.lints().iter().map(|warning: &Warning| Diagnostic {
+                severity: Severity::Warning,
+                code: warning.code(),
+                message: warning.to_string(),
+                labels: vec![],
+                notes: vec![],
+            }));
+        diagnostics
+    }
+    /// The spec, unless it has problems, in which case all of them.
+    pub(super) fn checked(self, source: &str) -> Result<Spec, Diagnostics> {
+        let problems = self.problems();
+        match problems.is_empty() {
+            true => Ok(self),
+            false => Err(Diagnostics::new(source, problems)),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(label) = self.labels.first() {
+            write!(f, "line {}: ", label.line)?;
+        }
+        if self.severity == Severity::Warning {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{} [{}]", self.message, self.code)?;
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (n, diagnostic) in self.iter().enumerate() {
+            if n > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for Diagnostics {}
+
+#[cfg(test)]
+mod tests {
+    use super::Severity;
+    use crate::{generate, Spec};
+
+    #[test]
+    fn diagnostics() {
+        let code =
+            generate("[[positional]]\nc_var = \"x\"\nc_type = \"char*\"\nhelp_name = \"X\"\n")
+                .ok()
+                .unwrap();
+        assert!(code.contains("void parse_args(int argc, char **argv, char* *x) {\n"));
+        let spec = "[[non_positional]]\nc_var = \"2x\"\nc_type = \"int\"\nlong = \"x\"\n\n\
+                    [[non_positional]]\nc_var = \"y\"\nc_type = \"int\"\nlong = \"x\"\n";
+        let diagnostics = generate(spec).err().unwrap();
+        let codes: Vec<&str> = diagnostics.iter().map(|d| d.code).collect();
+        assert_eq!(codes, ["E003", "E041"]);
+        assert_eq!(diagnostics.0[0].labels[0].line, 2);
+        assert!(diagnostics
+            .to_string()
+            .starts_with("line 2: in param x: invalid c variable \"2x\" [E003]\n"));
+        assert_eq!(generate("c_var =").err().unwrap().0[0].code, "E001");
+        // nothing is read from files
+        let include = generate("include = [\"common.toml\"]\n").err().unwrap();
+        assert!(include.0[0].message.contains("common.toml"));
+        // reading a spec finds all its problems, not only the first
+        let all = Spec::from_str(spec).err().unwrap();
+        assert_eq!(all, diagnostics);
+        assert!(all.has_errors());
+        let posix = Spec::from_str("parser = \"posix\"\ncase_insensitive = true\n")
+            .err()
+            .unwrap();
+        assert_eq!(posix.0[0].notes.len(), 1);
+        assert!(posix.to_string().contains("\n  note: POSIX getopt"));
+        // warnings don't keep a spec from generating
+        let source = "[[non_positional]]\nc_var = \"x\"\nc_type = \"int\"\nlong = \"x\"\n";
+        let warnings = Spec::from_str(source).unwrap().diagnostics(source);
+        assert_eq!(warnings.0[0].severity, Severity::Warning);
+        assert!(!warnings.has_errors());
+        assert_eq!(warnings.to_string(), "warning: --x has no help text [W001]");
+    }
+}
@@ -0,0 +1,190 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A program around the generated C parser for shell scripts, in the manner
+//! of getopt(1): it parses its arguments and prints them as shell
+//! assignments, for the script to eval.
+
+use super::{OnError, Spec};
+
+impl Spec {
+    /// Readies the spec for an eval program: parse_args must return on a bad
+    /// command line, so the program can tell the script to exit.
+    pub fn set_eval(&mut self) {
+        self.on_error = Some(OnError::Return);
+        self.emit_main = Some(false);
+    }
+    /// The expression main reads a parsed value from.
+    fn eval_value(&self, field: &str) -> String {
+        match self.struct_name() {
+            _ if self.has_accessors() => format!("{}()", self.sym(&format!("args_get_{}", field))),
+            Some(_) => format!("args.{}", field),
+            None => field.to_owned(),
+        }
+    }
+    /// Generates a C program containing the parser generated for the spec,
+    /// which must be readied with set_eval. It prints a var='value' line for
+    /// every parsed value, named after its c_var, with arrays as var_0,
+    /// var_1 and so on, then var__size. Usage goes to stderr, and a bad
+    /// command line prints "exit 1" instead. The program needs POSIX.
+    pub fn gen_eval(&self) -> String {
+        let stamp = self.cgen_stamp();
+        let parser = self.gen();
+        let parser = parser.strip_prefix(&stamp).unwrap_or(&parser);
+        let mut eval = String::from(
+            "/* Prints value single-quoted for the shell, or '' for NULL. */\n\
+             static void eval_quote(const char *value) {\n\
+             \tputchar('\\'');\n\
+             \tfor (; value != NULL && *value; value++) {\n\
+             \t\tif (*value == '\\'') {\n\
+             \t\t\tfputs(\"'\\\\''\", stdout);\n\
+             \t\t} else {\n\
+             \t\t\tputchar(*value);\n\
+             \t\t}\n\
+             \t}\n\
+             \tputs(\"'\");\n\
+             }\n\
+             \n\
+             int main(int argc, char **argv) {\n\
+             \tint eval__stdout;\n\
+             \tsize_t eval__i;\n",
+        );
+        eval.push_str(&self.cgen_call_vars());
+        // the parser's usage must not reach the script's eval
+        eval.push_str(&format!(
+            "\tfflush(stdout);\n\
+             \tif ((eval__stdout = dup(1)) < 0 || dup2(2, 1) < 0) {{\n\
+             \t\tperror(argv[0]);\n\
+             \t\tputs(\"exit 1\");\n\
+             \t\treturn 1;\n\
+             \t}}\n\
+             \tif ({} != 0) {{\n\
+             \t\tfflush(stdout);\n\
+             \t\tdup2(eval__stdout, 1);\n\
+             \t\tputs(\"exit 1\");\n\
+             \t\treturn 1;\n\
+             \t}}\n\
+             \tfflush(stdout);\n\
+             \tdup2(eval__stdout, 1);\n\
+             \tclose(eval__stdout);\n",
+            self.cgen_call()
+        ));
+        let fields = self.accessor_fields();
+        for (ty, field) in &fields {
+            let value = self.eval_value(field);
+            match *ty {
+                "int " => eval.push_str(&format!("\tprintf(\"{}=%d\\n\", {});\n", field, value)),
+                "size_t " => eval.push_str(&format!(
+                    "\tprintf(\"{}=%lu\\n\", (unsigned long){});\n",
+                    field, value
+                )),
+                "const char *" => eval.push_str(&format!(
+                    "\tfputs(\"{}=\", stdout);\n\teval_quote({});\n",
+                    field, value
+                )),
+                // an array of strings, followed by its size
+                _ => eval.push_str(&format!(
+                    "\tfor (eval__i = 0; eval__i < {2}; eval__i++) {{\n\
+                     \t\tprintf(\"{0}_%lu=\", (unsigned long)eval__i);\n\
+                     \t\teval_quote({1}[eval__i]);\n\
+                     \t}}\n",
+                    field,
+                    value,
+                    self.eval_value(&format!("{}__size", field))
+                )),
+            }
+        }
+        if !fields.iter().any(|(ty, _)| *ty == "size_t ") {
+            eval.push_str("\t(void)eval__i;\n");
+        }
+        eval.push_str("\treturn 0;\n}\n");
+        format!(
+            "{}#ifndef _POSIX_C_SOURCE\n#define _POSIX_C_SOURCE 200809L\n#endif\n{}\n#include<unistd.h>\n\n{}",
+            stamp,
+            parser,
+            self.cgen().layout(&eval)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+    use std::process::Command;
+
+    const SPEC: &str = r#"
+        [[positional]]
+        c_var = "input"
+        c_type = "char*"
+        help_name = "FILE"
+        required = true
+
+        [[positional]]
+        c_var = "words"
+        c_type = "char*"
+        help_name = "WORD"
+        multi = true
+
+        [[non_positional]]
+        c_var = "size"
+        c_type = "int"
+        long = "size"
+        short = "s"
+        default = "3"
+        "#;
+
+    #[test]
+    fn eval_program() {
+        let mut s = Spec::from_str(SPEC).unwrap();
+        s.set_eval();
+        let c = s.gen_eval();
+        assert!(c.contains(
+            "\tif (parse_args(argc, argv, &size, &input, &words, &words__size) != 0) {\n"
+        ));
+        assert!(c.contains("\tprintf(\"size=%d\\n\", size);\n"));
+        assert!(c.contains("\tfputs(\"input=\", stdout);\n\teval_quote(input);\n"));
+        assert!(c.contains("\t\tprintf(\"words_%lu=\", (unsigned long)eval__i);\n"));
+        assert!(!c.contains("exit(1)"));
+    }
+
+    #[test]
+    fn eval_runs() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("argen_eval.c");
+        let bin = dir.join("argen_eval");
+        let mut s = Spec::from_str(&format!("args_struct = \"opts\"\n{}", SPEC)).unwrap();
+        s.set_eval();
+        std::fs::write(&src, s.gen_eval()).unwrap();
+        let status = Command::new("cc")
+            .args(["-Wall", "-Wextra", "-Werror", "-o"])
+            .arg(&bin)
+            .arg(&src)
+            .status();
+        match status {
+            Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+            Err(_) => return, // no compiler to run the program with
+        }
+        let run = |args: &[&str]| Command::new(&bin).args(args).output().unwrap();
+        let out = run(&["-s", "5", "it's", "a"]);
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout),
+            "size=5\ninput='it'\\''s'\nwords_0='a'\nwords__size=1\n"
+        );
+        let out = run(&["--help"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "exit 1\n");
+        assert!(String::from_utf8_lossy(&out.stderr).contains("usage: "));
+    }
+}
@@ -0,0 +1,257 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `argen explain`: what the generated parser does with each item, in
+//! words, for readers who would rather not read the C.
+
+use super::{CType, NonPositionalItem, OnError, Parser, PositionalItem, Spec};
+
+/// Numbers an operand: 1st, 2nd, 3rd, 4th...
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (1, 11) | (2, 12) | (3, 13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// A labelled line of an item's explanation.
+fn line(label: &str, text: &str) -> String {
+    format!("    {:<11}{}\n", format!("{}:", label), text)
+}
+
+impl NonPositionalItem {
+    fn explain(&self, spec: &Spec, val: u8, fails: &str) -> String {
+        let mut out = format!("  {}\n", self.help_label(spec.has_long_options()).trim());
+        if let Some(descr) = &self.help_descr {
+            out.push_str(&line("help", descr));
+        }
+        out.push_str(&line(
+            "variable",
+            &format!("{} {}", self.c_type, self.c_var),
+        ));
+        if spec.has_long_options() {
+            let entry = self.cgen_getopt(val);
+            out.push_str(&line("longopts", entry.trim().trim_end_matches(',')));
+        }
+        if let Some(short) = &self.short {
+            let colon = if self.is_flag() { "" } else { ":" };
+            out.push_str(&line("optstring", &format!("\"{}{}\"", short, colon)));
+        }
+        if let Some(aliases) = self.aliases.as_ref().filter(|a| !a.is_empty()) {
+            let aliases: Vec<String> = aliases.iter().map(|a| format!("--{}", a)).collect();
+            out.push_str(&line("aliases", &aliases.join(", ")));
+        }
+        let value = match self.c_type {
+            _ if self.is_flag() => "1 if given, otherwise 0",
+            CType::Int => "its argument, converted with atoi",
+            CType::Chars => "its argument, pointing into argv",
+        };
+        out.push_str(&line("value", value));
+        if let Some(env) = &self.env {
+            out.push_str(&line(
+                "env",
+                &format!("${} is used when the option isn't given", env),
+            ));
+        }
+        if spec.has_config() {
+            out.push_str(&line(
+                "config",
+                &format!(
+                    "\"{}\" in the config file is used when it isn't given",
+                    self.long
+                ),
+            ));
+        }
+        if let Some(default) = &self.default {
+            out.push_str(&line(
+                "default",
+                &format!("{}, when nothing else sets it", default),
+            ));
+        }
+        if self.is_required() {
+            out.push_str(&line("checks", &format!("required: without it, {}", fails)));
+        }
+        out
+    }
+}
+
+impl PositionalItem {
+    fn explain(&self, position: usize, fails: &str) -> String {
+        let label = if self.is_multi() {
+            format!("{}...", self.help_name)
+        } else {
+            self.help_name.to_owned()
+        };
+        let mut out = format!("  {}\n", label);
+        if let Some(descr) = &self.help_descr {
+            out.push_str(&line("help", descr));
+        }
+        if self.is_multi() {
+            out.push_str(&line(
+                "variable",
+                &format!("{} *{}, with size_t {1}__size", self.c_type, self.c_var),
+            ));
+            out.push_str(&line(
+                "value",
+                &format!(
+                    "the {} and every later operand, pointing into argv",
+                    ordinal(position)
+                ),
+            ));
+        } else {
+            out.push_str(&line(
+                "variable",
+                &format!("{} {}", self.c_type, self.c_var),
+            ));
+            let value = match self.c_type {
+                CType::Int => "converted with atoi",
+                CType::Chars => "pointing into argv",
+            };
+            out.push_str(&line(
+                "value",
+                &format!("the {} operand, {}", ordinal(position), value),
+            ));
+        }
+        if let Some(default) = &self.default {
+            out.push_str(&line(
+                "default",
+                &format!("{}, when the operand isn't given", default),
+            ));
+        }
+        if self.is_required() {
+            let what = if self.is_multi() {
+                "at least one"
+            } else {
+                "it"
+            };
+            out.push_str(&line(
+                "checks",
+                &format!("required: without {}, {}", what, fails),
+            ));
+        }
+        out
+    }
+}
+
+impl Spec {
+    /// Explains, item by item, what the generated parser does.
+    pub fn explain(&self) -> String {
+        if self.has_subcommands() {
+            return self
+                .leaf_paths()
+                .into_iter()
+                .map(|path| {
+                    let s = self.subcommand_spec(&path);
+                    format!(
+                        "Command: {}\n{}",
+                        s.command.as_deref().unwrap_or_default(),
+                        s.explain()
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join("\n");
+        }
+        let fails = match self.on_error() {
+            OnError::Exit => "usage is printed and the program exits with status 1",
+            OnError::Return => "usage is printed and parse_args returns 1",
+        };
+        let parser = match self.parser() {
+            Parser::Getopt => "GNU getopt_long",
+            Parser::Reentrant => "a reentrant scanner generated with it",
+            Parser::Posix => "POSIX getopt, with short options only",
+            Parser::Argp => "glibc's argp_parse",
+        };
+        let mut out = format!(
+            "Usage: [options]{}\nOptions are scanned with {}; -h{} prints usage and exits.\n",
+            self.usage_synopsis(),
+            parser,
+            if self.has_long_options() {
+                " or --help"
+            } else {
+                ""
+            }
+        );
+        if !self.non_positional.is_empty() {
+            out.push_str("\nOptions:\n");
+            for opt in self.analyze().options {
+                out.push_str(&opt.item.explain(self, opt.val, fails));
+            }
+        }
+        if !self.positional.is_empty() {
+            out.push_str("\nArguments:\n");
+            for (i, pi) in self.positional.iter().enumerate() {
+                out.push_str(&pi.explain(i + 1, fails));
+            }
+        }
+        if let Some(rest) = &self.capture_rest {
+            out.push_str(&format!(
+                "\nEverything after -- goes into char **{}, with size_t {0}__size.\n",
+                rest
+            ));
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    #[test]
+    fn explain() {
+        let s = Spec::from_str(
+            r#"
+            [[positional]]
+            c_var = "files"
+            c_type = "char*"
+            help_name = "FILE"
+            help_descr = "files to read"
+            multi = true
+            required = true
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            short = "s"
+            help_name = "n"
+            default = "12"
+            env = "SIZE"
+            "#,
+        )
+        .unwrap();
+        let text = s.explain();
+        assert!(text.contains(
+            "  -s, --size <n>\n\
+             \x20   variable:  int size\n\
+             \x20   longopts:  {\"size\", required_argument, 0, 115}\n\
+             \x20   optstring: \"s:\"\n"
+        ));
+        assert!(text.contains("    env:       $SIZE is used when the option isn't given\n"));
+        assert!(text.contains("    default:   12, when nothing else sets it\n"));
+        assert!(text.contains(
+            "  FILE...\n\
+             \x20   help:      files to read\n\
+             \x20   variable:  char* *files, with size_t files__size\n\
+             \x20   value:     the 1st and every later operand, pointing into argv\n\
+             \x20   checks:    required: without at least one, usage is printed and the program exits with status 1\n"
+        ));
+    }
+}
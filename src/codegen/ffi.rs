@@ -0,0 +1,369 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Rust bindings to the C parser: parse_args declared `extern "C"`, and a
+//! struct with a field for every c_var that parses into itself by calling
+//! it, so a Rust program links the parser of a C project and parses the
+//! same command line the same way.
+
+use super::rust::{rust_doc, rust_ident};
+use super::{CType, NonPositionalItem, OnError, PositionalItem, Spec, ValidationError};
+
+// The strings parse_args sets point into argv, or at its defaults, so they
+// are copied before argv is dropped.
+const STRING: &str = "
+/// A string parse_args set, if it set one.
+fn string(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    // a string parse_args set is NUL-terminated
+    Some(unsafe { CStr::from_ptr(s) }.to_string_lossy().into_owned())
+}
+";
+const STRINGS: &str = "
+/// The strings of an array parse_args set, of the length it set.
+fn strings(array: *const *mut c_char, len: usize) -> Vec<String> {
+    (0..len)
+        .filter_map(|i| string(unsafe { *array.add(i) }))
+        .collect()
+}
+";
+
+/// How a value goes between C and Rust: the C variable's declaration and
+/// the arguments passing it to parse_args, and the field's type and value.
+struct Binding {
+    var: String,
+    field_type: &'static str,
+    init: Vec<String>,
+    args: Vec<String>,
+    params: Vec<String>,
+    value: String,
+}
+
+impl Binding {
+    fn int(var: String, flag: bool) -> Binding {
+        let (field_type, value) = if flag {
+            ("bool", format!("{} != 0", var))
+        } else {
+            ("i32", var.clone())
+        };
+        Binding {
+            init: vec![format!("let mut {}: c_int = 0;", var)],
+            args: vec![format!("&mut {}", var)],
+            params: vec![format!("{}: *mut c_int", var)],
+            field_type,
+            value,
+            var,
+        }
+    }
+    fn string(var: String, optional: bool) -> Binding {
+        let (field_type, value) = if optional {
+            ("Option<String>", format!("string({})", var))
+        } else {
+            ("String", format!("string({}).unwrap_or_default()", var))
+        };
+        Binding {
+            init: vec![format!("let mut {}: *mut c_char = ptr::null_mut();", var)],
+            args: vec![format!("&mut {}", var)],
+            params: vec![format!("{}: *mut *mut c_char", var)],
+            field_type,
+            value,
+            var,
+        }
+    }
+    fn strings(var: String) -> Binding {
+        Binding {
+            init: vec![
+                format!("let mut {}: *mut *mut c_char = ptr::null_mut();", var),
+                format!("let mut {}_size: usize = 0;", var),
+            ],
+            args: vec![format!("&mut {}", var), format!("&mut {}_size", var)],
+            params: vec![
+                format!("{}: *mut *mut *mut c_char", var),
+                format!("{}_size: *mut usize", var),
+            ],
+            field_type: "Vec<String>",
+            value: format!("strings({0}, {0}_size)", var),
+            var,
+        }
+    }
+}
+
+impl NonPositionalItem {
+    fn rust_binding(&self) -> Binding {
+        let var = rust_ident(&self.c_var);
+        match self.c_type {
+            CType::Int => Binding::int(var, self.is_flag()),
+            CType::Chars => Binding::string(var, !self.is_required() && !self.has_default()),
+        }
+    }
+}
+
+impl PositionalItem {
+    fn rust_binding(&self) -> Binding {
+        let var = rust_ident(&self.c_var);
+        match self.c_type {
+            _ if self.is_multi() => Binding::strings(var),
+            CType::Int => Binding::int(var, false),
+            CType::Chars => Binding::string(var, !self.is_required() && !self.has_default()),
+        }
+    }
+}
+
+impl Spec {
+    /// Checks that parse_args takes only what the bindings pass it: a
+    /// pointer to each value, rather than a struct.
+    pub fn check_rust_ffi(&self) -> Result<(), ValidationError> {
+        let unsupported = [
+            ("args_struct", self.args_struct.is_some()),
+            ("accessors", self.has_accessors()),
+            ("expose_isset", self.is_isset_exposed()),
+            ("subcommands", self.has_subcommands()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((feature, _)) => Err(ValidationError::BackendUnsupported(
+                String::from("rust-ffi"),
+                String::from(*feature),
+            )),
+            None => Ok(()),
+        }
+    }
+    /// Generates a Rust module binding the C parser: parse_args declared
+    /// `extern "C"`, and a struct parsing the command line into itself with
+    /// it, so a Rust program links the parser of a C project. The extern
+    /// block is `unsafe`, as edition 2024 requires, so needs Rust 1.82.
+    pub fn gen_rust_ffi(&self) -> String {
+        let name = self.rust_struct_name();
+        let parse_args = self.sym("parse_args");
+        let docs: Vec<String> = self
+            .non_positional
+            .iter()
+            .map(|npi| rust_doc(&npi.help_descr))
+            .chain(self.positional.iter().map(|pi| rust_doc(&pi.help_descr)))
+            .chain(
+                self.capture_rest
+                    .iter()
+                    .map(|_| String::from("    /// Everything after \"--\"\n")),
+            )
+            .collect();
+        let bindings: Vec<Binding> = self
+            .non_positional
+            .iter()
+            .map(|npi| npi.rust_binding())
+            .chain(self.positional.iter().map(|pi| pi.rust_binding()))
+            .chain(
+                self.capture_rest
+                    .iter()
+                    .map(|rest| Binding::strings(rust_ident(rest))),
+            )
+            .collect();
+        let returns = self.on_error() == OnError::Return;
+        let uses_strings = bindings.iter().any(|b| b.field_type == "Vec<String>");
+        let uses_string = bindings.iter().any(|b| b.field_type.contains("String"));
+
+        let mut code = self.cgen_stamp();
+        code.push_str(if uses_string {
+            "use std::ffi::{CStr, CString};\n"
+        } else {
+            "use std::ffi::CString;\n"
+        });
+        code.push_str("use std::os::raw::{c_char, c_int};\nuse std::ptr;\n\n");
+        code.push_str("#[allow(non_snake_case)]\nunsafe extern \"C\" {\n");
+        code.push_str(&format!(
+            "    fn {}(\n        argc: c_int,\n        argv: *mut *mut c_char,\n",
+            parse_args
+        ));
+        for param in bindings.iter().flat_map(|b| &b.params) {
+            code.push_str(&format!("        {},\n", param));
+        }
+        code.push_str(if returns {
+            "    ) -> c_int;\n}\n\n"
+        } else {
+            "    );\n}\n\n"
+        });
+
+        code.push_str(&format!(
+            "/// The values {} parses the command line into.\n#[allow(non_snake_case)]\n#[derive(Clone, Debug)]\npub struct {} {{\n",
+            parse_args, name
+        ));
+        for (binding, doc) in bindings.iter().zip(&docs) {
+            code.push_str(&format!(
+                "{}    pub {}: {},\n",
+                doc, binding.var, binding.field_type
+            ));
+        }
+        code.push_str("}\n\n");
+
+        let (parsed, on_error) = if returns {
+            (
+                format!("Option<{}>", name),
+                "or None if it is bad, after printing the usage",
+            )
+        } else {
+            (name.clone(), "exiting with the usage if it is bad")
+        };
+        code.push_str(&format!("impl {} {{\n", name));
+        code.push_str(&format!(
+            "    /// Parses the program's command line with the C parser,\n    /// {}.\n    pub fn parse() -> {} {{\n        {}::parse_from(std::env::args())\n    }}\n\n",
+            on_error, parsed, name
+        ));
+        code.push_str(&format!(
+            "    /// Parses the given arguments, the program's name first, with the C\n    /// parser, {}.\n",
+            on_error
+        ));
+        code.push_str(&format!(
+            "    pub fn parse_from<I, T>(args: I) -> {}\n    where\n        I: IntoIterator<Item = T>,\n        T: Into<Vec<u8>>,\n    {{\n",
+            parsed
+        ));
+        // argv is the C's to reorder, and the arguments to write into, so
+        // they are handed over and taken back once the values are copied
+        code.push_str(
+            "        let args: Vec<*mut c_char> = args
+            .into_iter()
+            .map(|arg| {
+                CString::new(arg)
+                    .expect(\"an argument without a NUL byte\")
+                    .into_raw()
+            })
+            .collect();
+        let mut argv = args.clone();
+        argv.push(ptr::null_mut());
+",
+        );
+        for init in bindings.iter().flat_map(|b| &b.init) {
+            code.push_str(&format!("        {}\n", init));
+        }
+        code.push_str(if returns {
+            "        let failed = unsafe {\n"
+        } else {
+            "        unsafe {\n"
+        });
+        code.push_str(&format!(
+            "            {}(\n                args.len() as c_int,\n                argv.as_mut_ptr(),\n",
+            parse_args
+        ));
+        for arg in bindings.iter().flat_map(|b| &b.args) {
+            code.push_str(&format!("                {},\n", arg));
+        }
+        code.push_str("            )\n        };\n");
+        let indent = if returns { "            " } else { "        " };
+        let mut value = format!("{} {{\n", name);
+        for binding in &bindings {
+            if binding.value == binding.var {
+                value.push_str(&format!("{}    {},\n", indent, binding.var));
+            } else {
+                value.push_str(&format!(
+                    "{}    {}: {},\n",
+                    indent, binding.var, binding.value
+                ));
+            }
+        }
+        value.push_str(&format!("{}}}", indent));
+        if returns {
+            value = format!(
+                "if failed != 0 {{\n            None\n        }} else {{\n            Some({})\n        }}",
+                value
+            );
+        }
+        code.push_str(&format!(
+            "        let parsed = {};\n\
+             \x20       for arg in args {{\n\
+             \x20           drop(unsafe {{ CString::from_raw(arg) }});\n\
+             \x20       }}\n\
+             \x20       parsed\n    }}\n}}\n",
+            value
+        ));
+        if uses_string {
+            code.push_str(STRING);
+        }
+        if uses_strings {
+            code.push_str(STRINGS);
+        }
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    #[test]
+    fn rust_ffi() {
+        let s = Spec::from_str(
+            r#"
+            prefix = "tool"
+            on_error = "return"
+            capture_rest = "rest"
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            help_descr = "block size"
+            default = "12"
+
+            [[non_positional]]
+            c_var = "type"
+            c_type = "char*"
+            long = "type"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            "#,
+        )
+        .unwrap();
+        s.check_rust_ffi().unwrap();
+        let r = s.gen_rust_ffi();
+        // the declaration takes what the C signature does, in its order
+        assert!(r.contains(
+            "    fn tool_parse_args(\n        argc: c_int,\n        argv: *mut *mut c_char,\n        size: *mut c_int,\n        r#type: *mut *mut c_char,\n        quiet: *mut c_int,\n        input: *mut *mut c_char,\n        words: *mut *mut *mut c_char,\n        words_size: *mut usize,\n        rest: *mut *mut *mut c_char,\n        rest_size: *mut usize,\n    ) -> c_int;\n"
+        ));
+        assert!(r.contains("    /// block size\n    pub size: i32,\n"));
+        assert!(r.contains("    pub r#type: Option<String>,\n"));
+        assert!(r.contains("    pub quiet: bool,\n"));
+        assert!(r.contains("    pub input: String,\n"));
+        assert!(r.contains("    /// Everything after \"--\"\n    pub rest: Vec<String>,\n"));
+        assert!(r.contains("    pub fn parse() -> Option<Args> {\n"));
+        assert!(r.contains(
+            "        let failed = unsafe {\n            tool_parse_args(\n                args.len() as c_int,\n                argv.as_mut_ptr(),\n                &mut size,\n"
+        ));
+        assert!(r.contains("                &mut rest_size,\n            )\n        };\n        let parsed = if failed != 0 {\n"));
+        assert!(r.contains("                words: strings(words, words_size),\n"));
+        // the C may write into the arguments, so each is a buffer of its own
+        assert!(r.contains(".into_raw()\n"));
+        assert!(r.contains(
+            "        for arg in args {\n            drop(unsafe { CString::from_raw(arg) });\n        }\n        parsed\n"
+        ));
+        let with_struct = Spec::from_str("args_struct = \"opts\"").unwrap();
+        assert!(with_struct.check_rust_ffi().is_err());
+    }
+}
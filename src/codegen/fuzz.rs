@@ -0,0 +1,200 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A libFuzzer harness for the generated C parser, feeding it command lines
+//! made from the fuzzer's input.
+
+use super::{OnError, Parser, Spec};
+
+impl Spec {
+    /// Readies the spec for fuzzing: parse_args must return on a bad command
+    /// line rather than exit, and release whatever it allocates, as a fuzz
+    /// target runs it over and over in one process.
+    pub fn set_fuzzing(&mut self) {
+        self.on_error = Some(OnError::Return);
+        self.emit_main = Some(false);
+        if self.keeps_allocations() {
+            self.cleanup = Some(true);
+        }
+    }
+    /// Generates a libFuzzer target containing the parser generated for
+    /// the spec, which must be readied with set_fuzzing. The input is split
+    /// at NUL bytes into the arguments after the program name.
+    pub fn gen_fuzz(&self) -> String {
+        let stamp = self.cgen_stamp();
+        let parser = self.gen();
+        let parser = parser.strip_prefix(&stamp).unwrap_or(&parser);
+        let mut harness = String::from(
+            "#include<stdint.h>\n\
+             \n\
+             int LLVMFuzzerTestOneInput(const uint8_t *fuzz__data, size_t fuzz__size);\n\
+             \n\
+             int LLVMFuzzerTestOneInput(const uint8_t *fuzz__data, size_t fuzz__size) {\n\
+             \tstatic char fuzz__progname[] = \"fuzz\";\n\
+             \tchar *fuzz__buf, **argv;\n\
+             \tint argc = 1;\n\
+             \tsize_t fuzz__i, fuzz__nargs = 0;\n",
+        );
+        harness.push_str(&self.cgen_call_vars());
+        harness.push_str(
+            "\n\
+             \tfuzz__buf = malloc(fuzz__size + 1);\n\
+             \tif (!fuzz__buf) {\n\
+             \t\treturn 0;\n\
+             \t}\n\
+             \tmemcpy(fuzz__buf, fuzz__data, fuzz__size);\n\
+             \tfuzz__buf[fuzz__size] = '\\0';\n\
+             \tfor (fuzz__i = 0; fuzz__i < fuzz__size; fuzz__i++) {\n\
+             \t\tif (fuzz__buf[fuzz__i] == '\\0') {\n\
+             \t\t\tfuzz__nargs++;\n\
+             \t\t}\n\
+             \t}\n\
+             \targv = malloc((fuzz__nargs + 3) * sizeof *argv);\n\
+             \tif (!argv) {\n\
+             \t\tfree(fuzz__buf);\n\
+             \t\treturn 0;\n\
+             \t}\n\
+             \targv[0] = fuzz__progname;\n\
+             \t/* empty input is no arguments, not one empty one */\n\
+             \tif (fuzz__size > 0) {\n\
+             \t\targv[argc++] = fuzz__buf;\n\
+             \t\tfor (fuzz__i = 0; fuzz__i + 1 < fuzz__size; fuzz__i++) {\n\
+             \t\t\tif (fuzz__buf[fuzz__i] == '\\0') {\n\
+             \t\t\t\targv[argc++] = fuzz__buf + fuzz__i + 1;\n\
+             \t\t\t}\n\
+             \t\t}\n\
+             \t}\n\
+             \targv[argc] = NULL;\n",
+        );
+        match self.parser() {
+            // argp starts getopt afresh on every call
+            Parser::Reentrant | Parser::Argp => {}
+            // getopt keeps its place between calls; glibc only starts
+            // afresh, mid-cluster state and all, from 0
+            Parser::Getopt | Parser::Posix => harness.push_str(
+                "#ifdef __GLIBC__\n\
+                 \toptind = 0;\n\
+                 #else\n\
+                 \toptind = 1;\n\
+                 #endif\n",
+            ),
+        }
+        harness.push_str(&format!("\t(void){};\n", self.cgen_call()));
+        if self.has_cleanup() {
+            harness.push_str(&format!("\t{}();\n", self.sym("parse_args_cleanup")));
+        }
+        harness.push_str("\tfree(argv);\n\tfree(fuzz__buf);\n\treturn 0;\n}\n");
+        format!("{}{}\n{}", stamp, parser, self.cgen().layout(&harness))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+    use std::process::Command;
+
+    const SPEC: &str = r#"
+        response_files = true
+
+        [[positional]]
+        c_var = "input"
+        c_type = "char*"
+        help_name = "FILE"
+        required = true
+
+        [[positional]]
+        c_var = "words"
+        c_type = "char*"
+        help_name = "WORD"
+        multi = true
+
+        [[non_positional]]
+        c_var = "size"
+        c_type = "int"
+        long = "size"
+        short = "s"
+        default = "3"
+
+        [[non_positional]]
+        c_var = "quiet"
+        c_type = "int"
+        long = "quiet"
+        short = "q"
+        flag = true
+        "#;
+
+    #[test]
+    fn fuzz_harness() {
+        let mut s = Spec::from_str(SPEC).unwrap();
+        s.set_fuzzing();
+        let f = s.gen_fuzz();
+        assert!(f.contains(
+            "int LLVMFuzzerTestOneInput(const uint8_t *fuzz__data, size_t fuzz__size) {\n"
+        ));
+        assert!(f.contains("\toptind = 0;\n"));
+        assert!(f.contains(
+            "\t(void)parse_args(argc, argv, &size, &quiet, &input, &words, &words__size);\n"
+        ));
+        assert!(f.contains("\tparse_args_cleanup();\n"));
+        assert!(!f.contains("exit(1)"));
+        assert!(!f.contains("int main("));
+    }
+
+    #[test]
+    fn fuzz_runs() {
+        // without libFuzzer, a main can feed the target inputs itself
+        let driver = "\n\
+             int main(void) {\n\
+             \tstatic const char *inputs[] = {\"\", \"-s\", \"--size=4\\0f\\0a\\0b\", \"-qs\", \"--\\0-q\", \"--no\\0x\", \"@/nonexistent\"};\n\
+             \tsize_t i;\n\
+             \tfor (i = 0; i < sizeof inputs / sizeof *inputs; i++) {\n\
+             \t\tLLVMFuzzerTestOneInput((const uint8_t *)inputs[i], (i == 2 ? 14 : strlen(inputs[i])));\n\
+             \t}\n\
+             \treturn 0;\n\
+             }\n";
+        let modes = ["", "parser = \"reentrant\"\nargs_struct = \"opts\"\n"];
+        let dir = std::env::temp_dir();
+        for (i, extra) in modes.iter().enumerate() {
+            let mut s = Spec::from_str(&format!("{}{}", extra, SPEC)).unwrap();
+            s.set_fuzzing();
+            let src = dir.join(format!("argen_fuzz_{}.c", i));
+            let bin = dir.join(format!("argen_fuzz_{}", i));
+            std::fs::write(&src, s.gen_fuzz() + driver).unwrap();
+            let status = Command::new("cc")
+                .args([
+                    "-Wall",
+                    "-Wextra",
+                    "-Werror",
+                    "-fsanitize=address,undefined",
+                    "-o",
+                ])
+                .arg(&bin)
+                .arg(&src)
+                .status();
+            match status {
+                Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+                Err(_) => return, // no compiler to run the harness with
+            }
+            let run = Command::new(&bin).output().unwrap();
+            assert!(
+                run.status.success(),
+                "{}: {}",
+                src.display(),
+                String::from_utf8_lossy(&run.stderr)
+            );
+        }
+    }
+}
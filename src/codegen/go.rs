@@ -0,0 +1,576 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Go backend: an Args struct, and a ParseArgs function filling it in
+//! with github.com/spf13/pflag, which parses options the way getopt_long does.
+
+use super::{
+    camel_case, int_literal, CType, NonPositionalItem, PositionalItem, Spec, ValidationError,
+    USER_BEGIN, USER_END,
+};
+
+/// A string as a Go string literal.
+fn go_str(s: &str) -> String {
+    let mut lit = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => lit.push_str("\\\""),
+            '\\' => lit.push_str("\\\\"),
+            '\n' => lit.push_str("\\n"),
+            '\t' => lit.push_str("\\t"),
+            c => lit.push(c),
+        }
+    }
+    lit.push('"');
+    lit
+}
+
+/// A default as a Go value of the field's type.
+fn go_default(c_type: CType, default: &str) -> String {
+    match (c_type, int_literal(default)) {
+        (CType::Int, Some(n)) => n.to_string(),
+        (CType::Int, None) => default.to_owned(),
+        (CType::Chars, _) => go_str(default),
+    }
+}
+
+fn go_type(c_type: CType) -> &'static str {
+    match c_type {
+        CType::Chars => "string",
+        CType::Int => "int",
+    }
+}
+
+/// Assigns value, a Go string, to the field, converting it to an int if
+/// need be, in which case what names it in the error.
+fn go_assign(c_type: CType, field: &str, value: &str, what: &str, indent: &str) -> String {
+    match c_type {
+        CType::Chars => format!("{}args.{} = {}\n", indent, field, value),
+        CType::Int => format!(
+            "{0}if args.{1}, err = argsInt({2}, {3}); err != nil {{\n\
+             {0}\treturn args, err\n\
+             {0}}}\n",
+            indent,
+            field,
+            value,
+            go_str(what)
+        ),
+    }
+}
+
+impl NonPositionalItem {
+    fn go_field(&self) -> String {
+        camel_case(&self.c_var)
+    }
+    /// The pflag calls defining this option and its aliases.
+    fn go_define(&self) -> String {
+        let (kind, default) = if self.is_flag() {
+            ("Bool", String::from("false"))
+        } else {
+            let default = match &self.default {
+                Some(default) => go_default(self.c_type, default),
+                None if matches!(self.c_type, CType::Int) => String::from("0"),
+                None => String::from("\"\""),
+            };
+            (
+                match self.c_type {
+                    CType::Chars => "String",
+                    CType::Int => "Int",
+                },
+                default,
+            )
+        };
+        let mut define = match &self.short {
+            Some(short) => format!(
+                "\tfs.{}VarP(&args.{}, {}, {}, {}, {})\n",
+                kind,
+                self.go_field(),
+                go_str(&self.long),
+                go_str(short),
+                default,
+                go_str(self.help_descr.as_deref().unwrap_or(""))
+            ),
+            None => format!(
+                "\tfs.{}Var(&args.{}, {}, {}, {})\n",
+                kind,
+                self.go_field(),
+                go_str(&self.long),
+                default,
+                go_str(self.help_descr.as_deref().unwrap_or(""))
+            ),
+        };
+        for alias in self.aliases.iter().flatten() {
+            define.push_str(&format!(
+                "\tfs.{}Var(&args.{}, {}, {}, \"\")\n",
+                kind,
+                self.go_field(),
+                go_str(alias),
+                default
+            ));
+        }
+        define
+    }
+    /// Whether this option's value after parsing depends on whether it was given.
+    fn go_tracks_isset(&self, exposed: bool) -> bool {
+        self.is_required() || self.env.is_some() || exposed
+    }
+    /// Falls back on the environment, checks the option was given if it is
+    /// required, and hands back whether it was if that is exposed.
+    fn go_post_parse(&self, exposed: bool) -> String {
+        if !self.go_tracks_isset(exposed) {
+            return String::new();
+        }
+        // a local, so lowerCamelCase
+        let field = self.go_field();
+        let set = format!("{}{}Set", field[..1].to_ascii_lowercase(), &field[1..]);
+        let names = std::iter::once(&self.long).chain(self.aliases.iter().flatten());
+        let changed: Vec<String> = names
+            .map(|name| format!("fs.Changed({})", go_str(name)))
+            .collect();
+        let mut post = format!("\t{} := {}\n", set, changed.join(" || "));
+        if let Some(env) = &self.env {
+            post.push_str(&format!(
+                "\tif v, ok := os.LookupEnv({}); ok && !{} {{\n",
+                go_str(env),
+                set
+            ));
+            if self.is_flag() {
+                post.push_str(&format!("\t\targs.{} = argsTruthy(v)\n", self.go_field()));
+            } else {
+                post.push_str(&go_assign(
+                    self.c_type,
+                    &self.go_field(),
+                    "v",
+                    &format!("${}", env),
+                    "\t\t",
+                ));
+            }
+            if self.is_required() || exposed {
+                post.push_str(&format!("\t\t{} = true\n", set));
+            }
+            post.push_str("\t}\n");
+        }
+        if self.is_required() {
+            post.push_str(&format!(
+                "\tif !{} {{\n\
+                 \t\treturn args, errors.New({})\n\
+                 \t}}\n",
+                set,
+                go_str(&format!("missing required option '--{}'", self.long))
+            ));
+        }
+        if exposed {
+            post.push_str(&format!("\targs.{}IsSet = {}\n", self.go_field(), set));
+        }
+        post
+    }
+}
+
+impl PositionalItem {
+    fn go_field(&self) -> String {
+        camel_case(&self.c_var)
+    }
+    /// Takes this argument from the front of operands.
+    fn go_assign(&self, exposed: bool) -> String {
+        let field = self.go_field();
+        let mut assign = String::new();
+        if self.is_required() {
+            assign.push_str(&format!(
+                "\tif len(operands) == 0 {{\n\
+                 \t\treturn args, errors.New({})\n\
+                 \t}}\n",
+                go_str(&format!("missing {}", self.help_name))
+            ));
+        }
+        if self.is_multi() {
+            assign.push_str("\tif len(operands) > 0 {\n");
+            assign.push_str(&format!("\t\targs.{} = operands\n", field));
+            if exposed {
+                assign.push_str(&format!("\t\targs.{}IsSet = true\n", field));
+            }
+            assign.push_str("\t}\n");
+            if let Some(default) = &self.default {
+                assign.push_str(&format!(
+                    "\tif len(args.{0}) == 0 {{\n\
+                     \t\targs.{0} = []string{{{1}}}\n\
+                     \t}}\n",
+                    field,
+                    go_str(default)
+                ));
+            }
+            return assign;
+        }
+        let (open, indent, close) = if self.is_required() {
+            ("", "\t", "")
+        } else {
+            ("\tif len(operands) > 0 {\n", "\t\t", "\t}\n")
+        };
+        assign.push_str(open);
+        assign.push_str(&go_assign(
+            self.c_type,
+            &field,
+            "operands[0]",
+            &self.help_name,
+            indent,
+        ));
+        if exposed {
+            assign.push_str(&format!("{}args.{}IsSet = true\n", indent, field));
+        }
+        assign.push_str(&format!("{}operands = operands[1:]\n", indent));
+        assign.push_str(close);
+        assign
+    }
+}
+
+impl Spec {
+    /// Checks that the spec only uses what the Go backend supports.
+    pub fn check_go(&self) -> Result<(), ValidationError> {
+        let unsupported = [
+            ("response_files", self.has_response_files()),
+            ("config", self.has_config()),
+            ("pre_parse_fn", self.pre_parse_fn.is_some()),
+            ("post_parse_fn", self.post_parse_fn.is_some()),
+            ("subcommands", self.has_subcommands()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((feature, _)) => Err(ValidationError::BackendUnsupported(
+                String::from("go"),
+                String::from(*feature),
+            )),
+            None => Ok(()),
+        }
+    }
+    fn go_struct_name(&self) -> String {
+        camel_case(self.struct_name().unwrap_or("args"))
+    }
+    /// Whether any value is converted to an int after pflag is done.
+    fn go_converts_ints(&self) -> bool {
+        self.non_positional
+            .iter()
+            .any(|npi| npi.env.is_some() && !npi.is_flag() && matches!(npi.c_type, CType::Int))
+            || self
+                .positional
+                .iter()
+                .any(|pi| matches!(pi.c_type, CType::Int))
+    }
+    fn go_imports(&self) -> String {
+        let mut imports = vec!["fmt", "io", "os"];
+        let required = self.non_positional.iter().any(|npi| npi.is_required())
+            || self.positional.iter().any(|pi| pi.is_required());
+        if required {
+            imports.push("errors");
+        }
+        if self.go_converts_ints() {
+            imports.push("strconv");
+        }
+        if self.is_case_insensitive() {
+            imports.push("strings");
+        }
+        imports.sort_unstable();
+        let mut code = String::from("import (\n");
+        for import in imports {
+            code.push_str(&format!("\t\"{}\"\n", import));
+        }
+        code.push_str("\n\t\"github.com/spf13/pflag\"\n)\n");
+        code
+    }
+    fn go_struct(&self) -> String {
+        let exposed = self.exposed_issets();
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for npi in &self.non_positional {
+            let ty = if npi.is_flag() {
+                "bool"
+            } else {
+                go_type(npi.c_type)
+            };
+            fields.push((npi.go_field(), ty.to_owned()));
+        }
+        for pi in &self.positional {
+            let ty = if pi.is_multi() {
+                format!("[]{}", go_type(pi.c_type))
+            } else {
+                go_type(pi.c_type).to_owned()
+            };
+            fields.push((pi.go_field(), ty));
+        }
+        if let Some(rest) = &self.capture_rest {
+            fields.push((camel_case(rest), String::from("[]string")));
+        }
+        for var in exposed {
+            fields.push((format!("{}IsSet", camel_case(var)), String::from("bool")));
+        }
+        // gofmt lines up the types
+        let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let name = self.go_struct_name();
+        let mut code = format!(
+            "// {} holds the values parsed from the command line.\ntype {0} struct {{\n",
+            name
+        );
+        for (field, ty) in fields {
+            code.push_str(&format!("\t{:<width$} {}\n", field, ty, width = width));
+        }
+        code.push_str("}\n");
+        code
+    }
+    fn go_usage(&self) -> String {
+        let name = camel_case(&self.sym("usage"));
+        let mut code = format!(
+            "// {0} writes how to use the program to w.\n\
+             func {0}(w io.Writer, progname string) {{\n\
+             \tfmt.Fprint(w, \"usage: \"+progname+{1}",
+            name,
+            go_str(&format!(" [options]{}\n", self.usage_synopsis()))
+        );
+        for line in self.analyze().usage_help().lines() {
+            code.push_str(&format!("+\n\t\t{}", line.trim_start()));
+        }
+        code.push_str(")\n}\n");
+        code
+    }
+    fn go_helpers(&self) -> String {
+        let mut helpers = String::new();
+        if self.go_converts_ints() {
+            helpers.push_str(
+                "\nfunc argsInt(value, what string) (int, error) {\n\
+                 \tn, err := strconv.Atoi(value)\n\
+                 \tif err != nil {\n\
+                 \t\treturn 0, fmt.Errorf(\"invalid number for %s: %q\", what, value)\n\
+                 \t}\n\
+                 \treturn n, nil\n\
+                 }\n",
+            );
+        }
+        if self
+            .non_positional
+            .iter()
+            .any(|npi| npi.is_flag() && npi.env.is_some())
+        {
+            helpers.push_str(
+                "\nfunc argsTruthy(value string) bool {\n\
+                 \treturn value == \"1\" || value == \"true\" || value == \"yes\" || value == \"on\"\n\
+                 }\n",
+            );
+        }
+        helpers
+    }
+    fn go_parse_args(&self) -> String {
+        let name = camel_case(&self.sym("parse_args"));
+        let usage = camel_case(&self.sym("usage"));
+        let exposed = self.exposed_issets();
+        let mut code = format!(
+            "// {0} parses the command line, without the program name, into {1}.\n\
+             // It returns pflag.ErrHelp after printing the usage for --help.\n\
+             func {0}(progname string, argv []string) ({1}, error) {{\n\
+             \tvar args {1}\n\
+             \tfs := pflag.NewFlagSet(progname, pflag.ContinueOnError)\n\
+             \tfs.SetOutput(io.Discard)\n\
+             \tfs.Usage = func() {{ {2}(os.Stdout, progname) }}\n",
+            name,
+            self.go_struct_name(),
+            usage
+        );
+        if self.is_strict_posix() {
+            code.push_str("\tfs.SetInterspersed(false)\n");
+        }
+        if self.is_case_insensitive() {
+            code.push_str(
+                "\tfs.SetNormalizeFunc(func(f *pflag.FlagSet, name string) pflag.NormalizedName {\n\
+                 \t\treturn pflag.NormalizedName(strings.ToLower(name))\n\
+                 \t})\n",
+            );
+        }
+        for npi in &self.non_positional {
+            code.push_str(&npi.go_define());
+        }
+        for pi in self.positional.iter().filter(|pi| !pi.is_multi()) {
+            if let Some(default) = &pi.default {
+                code.push_str(&format!(
+                    "\targs.{} = {}\n",
+                    pi.go_field(),
+                    go_default(pi.c_type, default)
+                ));
+            }
+        }
+        code.push_str("\terr := fs.Parse(argv)\n\tif err != nil {\n\t\treturn args, err\n\t}\n");
+        for npi in &self.non_positional {
+            code.push_str(&npi.go_post_parse(exposed.contains(&npi.c_var.as_str())));
+        }
+        if !self.positional.is_empty() || self.capture_rest.is_some() {
+            code.push_str("\toperands := fs.Args()\n");
+        }
+        if let Some(rest) = &self.capture_rest {
+            code.push_str(&format!(
+                "\tif dash := fs.ArgsLenAtDash(); dash >= 0 {{\n\
+                 \t\targs.{} = operands[dash:]\n\
+                 \t\toperands = operands[:dash]\n\
+                 \t}}\n",
+                camel_case(rest)
+            ));
+        }
+        for pi in &self.positional {
+            code.push_str(&pi.go_assign(exposed.contains(&pi.c_var.as_str())));
+        }
+        code.push_str("\treturn args, nil\n}\n");
+        code
+    }
+    fn go_main(&self) -> String {
+        format!(
+            "func main() {{\n\
+             \targs, err := {}(os.Args[0], os.Args[1:])\n\
+             \tif err == pflag.ErrHelp {{\n\
+             \t\tos.Exit(0)\n\
+             \t}}\n\
+             \tif err != nil {{\n\
+             \t\tfmt.Fprintf(os.Stderr, \"%s: %v\\n\", os.Args[0], err)\n\
+             \t\t{}(os.Stderr, os.Args[0])\n\
+             \t\tos.Exit(1)\n\
+             \t}}\n\
+             \n\
+             \t{}\n\
+             \t/* call your code here */\n\
+             \t_ = args\n\
+             \t{}\n\
+             }}\n",
+            camel_case(&self.sym("parse_args")),
+            camel_case(&self.sym("usage")),
+            USER_BEGIN,
+            USER_END
+        )
+    }
+    /// Generates Go for the spec, in package main, parsing with pflag.
+    pub fn gen_go(&self) -> String {
+        let mut code = self.cgen_stamp();
+        code.push_str("package main\n\n");
+        code.push_str(&self.go_imports());
+        code.push('\n');
+        code.push_str(&self.go_struct());
+        code.push('\n');
+        code.push_str(&self.go_usage());
+        code.push_str(&self.go_helpers());
+        code.push('\n');
+        code.push_str(&self.go_parse_args());
+        if self.emits_main() {
+            code.push('\n');
+            code.push_str(&self.go_main());
+        }
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    #[test]
+    fn go_pflag() {
+        let s = Spec::from_str(
+            r#"
+            capture_rest = "rest"
+            expose_isset = true
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[positional]]
+            c_var = "count"
+            c_type = "int"
+            help_name = "N"
+            default = "2"
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            default = "w"
+
+            [[non_positional]]
+            c_var = "block_size"
+            c_type = "int"
+            long = "block-size"
+            short = "b"
+            aliases = ["bs"]
+            default = "0xDEADBEEF"
+            help_descr = "set the block size"
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            required = true
+            env = "PROG_PORT"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+            env = "PROG_QUIET"
+            "#,
+        )
+        .unwrap();
+        let g = s.gen_go();
+        assert!(g.contains("\t\"errors\"\n\t\"fmt\"\n\t\"io\"\n\t\"os\"\n\t\"strconv\"\n"));
+        assert!(g.contains(
+            "type Args struct {\n\
+             \tBlockSize      int\n\
+             \tPort           int\n\
+             \tQuiet          bool\n\
+             \tInput          string\n\
+             \tCount          int\n\
+             \tWords          []string\n\
+             \tRest           []string\n\
+             \tBlockSizeIsSet bool\n\
+             \tCountIsSet     bool\n\
+             \tWordsIsSet     bool\n\
+             }\n"
+        ));
+        assert!(g.contains(
+            "\tfs.IntVarP(&args.BlockSize, \"block-size\", \"b\", -559038737, \"set the block size\")\n\
+             \tfs.IntVar(&args.BlockSize, \"bs\", -559038737, \"\")\n"
+        ));
+        assert!(g.contains(
+            "\tportSet := fs.Changed(\"port\")\n\
+             \tif v, ok := os.LookupEnv(\"PROG_PORT\"); ok && !portSet {\n\
+             \t\tif args.Port, err = argsInt(v, \"$PROG_PORT\"); err != nil {\n\
+             \t\t\treturn args, err\n\
+             \t\t}\n\
+             \t\tportSet = true\n\
+             \t}\n\
+             \tif !portSet {\n\
+             \t\treturn args, errors.New(\"missing required option '--port'\")\n\
+             \t}\n"
+        ));
+        assert!(g.contains("\t\targs.Quiet = argsTruthy(v)\n"));
+        assert!(g.contains("\targs.BlockSizeIsSet = blockSizeSet\n"));
+        assert!(g.contains("\t\targs.Rest = operands[dash:]\n"));
+        assert!(g.contains(
+            "\tif len(operands) > 0 {\n\
+             \t\tif args.Count, err = argsInt(operands[0], \"N\"); err != nil {\n\
+             \t\t\treturn args, err\n\
+             \t\t}\n\
+             \t\targs.CountIsSet = true\n\
+             \t\toperands = operands[1:]\n\
+             \t}\n"
+        ));
+        assert!(g.contains("\t\targs.Words = []string{\"w\"}\n"));
+        assert!(g.contains("\targs, err := ParseArgs(os.Args[0], os.Args[1:])\n"));
+        assert!(s.check_go().is_ok());
+    }
+}
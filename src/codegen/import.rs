@@ -0,0 +1,99 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `argen import`: draft specs recovered from a program's existing command
+//! line definition, for a person to review. What an importer can't work
+//! out goes into the draft as a TODO comment.
+
+mod clap;
+mod docopt;
+mod getopt;
+mod help;
+
+use super::{CType, NonPositionalItem, PositionalItem, Spec};
+
+/// The command line definitions argen imports from, by `argen import` name.
+pub const SOURCES: &str = "clap, docopt, getopt, help";
+
+/// A spec recovered by an importer, with what it couldn't recover.
+pub struct Draft {
+    spec: Spec,
+    todos: Vec<String>,
+}
+
+impl Draft {
+    fn new(
+        positional: Vec<PositionalItem>,
+        non_positional: Vec<NonPositionalItem>,
+        mut todos: Vec<String>,
+    ) -> Draft {
+        let mut spec = Spec::from_str("").expect("an empty spec");
+        spec.positional = positional;
+        spec.non_positional = non_positional;
+        todos.extend(spec.problems().iter().map(|e| e.to_string()));
+        Draft { spec, todos }
+    }
+    /// The draft as TOML, its TODOs in comments at the top.
+    pub fn to_toml(&self) -> String {
+        let mut out: String = self
+            .todos
+            .iter()
+            .map(|todo| format!("# TODO: {}\n", todo.replace('\n', " ")))
+            .collect();
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&self.spec.to_toml());
+        out
+    }
+}
+
+/// A C variable for a name from another language, such as "dry-run".
+fn c_var(name: &str) -> String {
+    let mut var: String = name
+        .trim_start_matches('-')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !var.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        var.insert(0, '_');
+    }
+    var
+}
+
+/// An option with only its names set.
+fn option(name: &str, long: &str) -> NonPositionalItem {
+    NonPositionalItem::new(&c_var(name), long)
+}
+
+/// A positional argument with only its names set.
+fn positional(name: &str, help_name: &str) -> PositionalItem {
+    PositionalItem::new(&c_var(name), help_name)
+}
+
+impl Spec {
+    /// Drafts a spec from a command line definition of the named kind, or
+    /// None if argen doesn't import that kind.
+    pub fn import(from: &str, source: &str) -> Option<Draft> {
+        match from {
+            "clap" => Some(clap::import(source)),
+            "docopt" => Some(docopt::import(source)),
+            "getopt" => Some(getopt::import(source)),
+            "help" => Some(help::import(source)),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,508 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Drafts from clap: the builder (`Arg::new("name").long(...)`), the derive
+//! and structopt attributes (`#[arg(short, long)]` on a field), and clap 2's
+//! YAML definitions. None of it is compiled; the source is read for the
+//! calls and attributes that say what each argument is.
+
+use super::{c_var, option, positional, CType, Draft};
+use regex::Regex;
+
+/// What the definition says about one argument.
+#[derive(Default)]
+struct Arg {
+    name: String,
+    long: Option<String>,
+    short: Option<String>,
+    help: Option<String>,
+    value_name: Option<String>,
+    default: Option<String>,
+    env: Option<String>,
+    aliases: Vec<String>,
+    required: bool,
+    takes_value: bool,
+    flag: bool,
+    multi: bool,
+    int: bool,
+    index: bool,
+}
+
+/// The Rust types an int is read into.
+const INT_TYPES: [&str; 10] = [
+    "i8", "i16", "i32", "i64", "isize", "u8", "u16", "u32", "u64", "usize",
+];
+
+fn is_int_type(ty: &str) -> bool {
+    INT_TYPES.contains(&ty.trim())
+}
+
+/// The first string literal in code, unescaped.
+pub(super) fn string_literal(code: &str) -> Option<String> {
+    let re = Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap();
+    let lit = re.captures(code)?.get(1)?.as_str();
+    Some(
+        lit.replace("\\n", "\n")
+            .replace("\\\"", "\"")
+            .replace("\\\\", "\\"),
+    )
+}
+
+/// Every string literal in code, unescaped.
+pub(super) fn string_literals(code: &str) -> Vec<String> {
+    let re = Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap();
+    re.captures_iter(code)
+        .filter_map(|caps| string_literal(caps.get(0)?.as_str()))
+        .collect()
+}
+
+/// A short name, written as a char or a string literal.
+fn short_literal(code: &str) -> Option<String> {
+    let re = Regex::new(r#"'(.)'|"(.)""#).unwrap();
+    let caps = re.captures(code)?;
+    Some(caps.get(1).or_else(|| caps.get(2))?.as_str().to_owned())
+}
+
+/// The length of code up to and including the bracket closing the one it
+/// starts after, skipping string and char literals.
+pub(super) fn balanced(code: &str) -> usize {
+    let mut depth = 1;
+    let mut chars = code.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '\'' => {
+                // a char literal, not a lifetime
+                let rest = &code[i + 1..];
+                if rest.chars().nth(1) == Some('\'') || rest.starts_with('\\') {
+                    let end = rest[1..].find('\'').map_or(0, |e| e + 1);
+                    for _ in 0..rest[..=end].chars().count() {
+                        chars.next();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    code.len()
+}
+
+/// The method calls chained onto the start of code, each with the code of
+/// its arguments.
+fn method_chain(code: &str) -> Vec<(String, String)> {
+    let re = Regex::new(r"^\s*\.\s*([A-Za-z_][A-Za-z0-9_]*)\s*\(").unwrap();
+    let mut methods = vec![];
+    let mut rest = code;
+    while let Some(caps) = re.captures(rest) {
+        let start = caps.get(0).unwrap().end();
+        let len = balanced(&rest[start..]);
+        let args = &rest[start..start + len];
+        methods.push((
+            caps[1].to_owned(),
+            args.strip_suffix(')').unwrap_or(args).to_owned(),
+        ));
+        rest = &rest[start + len..];
+    }
+    methods
+}
+
+/// Args made with the builder, Arg::new("name") and the methods after it.
+fn builder_args(source: &str, todos: &mut Vec<String>) -> Vec<Arg> {
+    let re = Regex::new(r#"Arg::(?:new|with_name)\s*\(\s*"([^"]*)"\s*\)"#).unwrap();
+    let mut args = vec![];
+    for caps in re.captures_iter(source) {
+        let mut arg = Arg {
+            name: caps[1].to_owned(),
+            ..Default::default()
+        };
+        let chain = &source[caps.get(0).unwrap().end()..];
+        for (method, code) in method_chain(chain) {
+            let is_true = code.trim() == "true";
+            match method.as_str() {
+                "long" => arg.long = string_literal(&code),
+                "short" => arg.short = short_literal(&code),
+                "help" | "about" => arg.help = string_literal(&code),
+                "long_help" if arg.help.is_none() => arg.help = string_literal(&code),
+                "value_name" => arg.value_name = string_literal(&code),
+                "default_value" => arg.default = string_literal(&code),
+                "env" => arg.env = string_literal(&code),
+                "alias" | "visible_alias" | "aliases" | "visible_aliases" => {
+                    arg.aliases.extend(string_literals(&code))
+                }
+                "required" => arg.required = is_true,
+                "takes_value" => arg.takes_value = is_true,
+                "multiple" | "multiple_values" | "multiple_occurrences" => arg.multi = is_true,
+                "num_args" => arg.multi = code.contains(".."),
+                "index" => arg.index = true,
+                "action" => {
+                    if ["SetTrue", "SetFalse", "Count"]
+                        .iter()
+                        .any(|action| code.contains(action))
+                    {
+                        arg.flag = true;
+                    } else if code.contains("Append") {
+                        arg.multi = true;
+                    } else if code.contains("Set") {
+                        arg.takes_value = true;
+                    }
+                }
+                "value_parser" => {
+                    arg.int = INT_TYPES
+                        .iter()
+                        .any(|ty| Regex::new(&format!(r"\b{}\b", ty)).unwrap().is_match(&code))
+                }
+                "long_about" | "display_order" | "hide" | "hidden" => {}
+                _ => todos.push(format!(
+                    "{}: .{}({}) was not imported",
+                    arg.name,
+                    method,
+                    code.trim()
+                )),
+            }
+        }
+        args.push(arg);
+    }
+    args
+}
+
+/// The fields of a struct deriving Parser (or StructOpt, or Args), as args.
+fn derive_args(source: &str, todos: &mut Vec<String>) -> Vec<Arg> {
+    let derive = Regex::new(r"#\[derive\([^)]*\b(Parser|StructOpt|Args)\b[^)]*\)\]").unwrap();
+    let field =
+        Regex::new(r"^(?:pub(?:\([^)]*\))?\s+)?([A-Za-z_][A-Za-z0-9_]*)\s*:\s*(.+?),?$").unwrap();
+    let mut args = vec![];
+    for m in derive.find_iter(source) {
+        let rest = &source[m.end()..];
+        let open = match rest.find('{') {
+            Some(open) => open,
+            None => continue,
+        };
+        let body = &rest[open + 1..open + balanced(&rest[open + 1..])];
+        let body = body.strip_suffix('}').unwrap_or(body);
+        let mut docs = vec![];
+        let mut attrs = vec![];
+        let mut pending = String::new();
+        for line in body.lines().map(str::trim) {
+            if !pending.is_empty() || line.starts_with("#[") {
+                pending.push_str(line);
+                pending.push(' ');
+                if balanced(&pending[2..]) < pending.len() - 2 {
+                    attrs.push(pending.trim().to_owned());
+                    pending.clear();
+                }
+            } else if let Some(doc) = line.strip_prefix("///") {
+                docs.push(doc.trim().to_owned());
+            } else if let Some(caps) = field.captures(line) {
+                let arg = derive_arg(&caps[1], caps[2].trim(), &docs, &attrs, todos);
+                args.extend(arg);
+                docs.clear();
+                attrs.clear();
+            }
+        }
+    }
+    args
+}
+
+/// The arg for a field, from its type, doc comment and attributes.
+fn derive_arg(
+    name: &str,
+    ty: &str,
+    docs: &[String],
+    attrs: &[String],
+    todos: &mut Vec<String>,
+) -> Option<Arg> {
+    let mut arg = Arg {
+        name: name.to_owned(),
+        help: Some(docs.join(" ")).filter(|help| !help.is_empty()),
+        ..Default::default()
+    };
+    let attr = Regex::new(r"^#\[(?:arg|clap|structopt|command)\((.*)\)\]$").unwrap();
+    let settings: Vec<&str> = attrs
+        .iter()
+        .filter_map(|a| Some(attr.captures(a)?.get(1)?.as_str()))
+        .collect();
+    let settings = settings.join(", ");
+    let setting = Regex::new(r#"([a-z_]+)\s*(?:=\s*("(?:[^"\\]|\\.)*"|'.'|[^,]+))?"#).unwrap();
+    for caps in setting.captures_iter(&settings) {
+        let value = caps.get(2).map(|v| v.as_str().trim());
+        match (&caps[1], value) {
+            ("short", None) => arg.short = name.chars().next().map(String::from),
+            ("short", Some(v)) => arg.short = short_literal(v),
+            ("long", None) => arg.long = Some(name.replace('_', "-")),
+            ("long", Some(v)) => arg.long = string_literal(v),
+            ("help", Some(v)) => arg.help = string_literal(v),
+            ("value_name", Some(v)) => arg.value_name = string_literal(v),
+            ("env", Some(v)) => arg.env = string_literal(v),
+            ("env", None) => arg.env = Some(name.to_ascii_uppercase()),
+            ("default_value", Some(v)) => arg.default = string_literal(v),
+            ("default_value_t", Some(v)) => {
+                arg.default = Some(string_literal(v).unwrap_or_else(|| v.to_owned()))
+            }
+            ("required", v) => arg.required = v.is_none_or(|v| v == "true"),
+            ("alias" | "visible_alias", Some(v)) => arg.aliases.extend(string_literal(v)),
+            ("action", Some(v)) if v.contains("Count") || v.contains("SetTrue") => arg.flag = true,
+            ("subcommand" | "flatten", _) => {
+                todos.push(format!(
+                    "{}: #[{}] fields are not imported; give their options here",
+                    name, &caps[1]
+                ));
+                return None;
+            }
+            _ => {}
+        }
+    }
+    // the type says what the value is and whether it must be given
+    let (inner, optional, multi) = match ty {
+        _ if ty.starts_with("Option<") => (&ty[7..ty.len() - 1], true, false),
+        _ if ty.starts_with("Vec<") => (&ty[4..ty.len() - 1], true, true),
+        _ => (ty, false, false),
+    };
+    arg.multi = multi;
+    match inner.trim() {
+        "bool" => arg.flag = true,
+        inner if is_int_type(inner) => arg.int = true,
+        _ => {}
+    }
+    if !arg.flag {
+        arg.takes_value = true;
+        if !optional && arg.default.is_none() {
+            arg.required = true;
+        }
+    }
+    Some(arg)
+}
+
+/// Args under `args:` in a clap 2 YAML definition.
+fn yaml_args(source: &str, todos: &mut Vec<String>) -> Vec<Arg> {
+    let item = Regex::new(r"^(\s*)-\s*([A-Za-z0-9_-]+)\s*:\s*$").unwrap();
+    let pair = Regex::new(r"^\s*([a-z_]+)\s*:\s*(.*?)\s*$").unwrap();
+    let mut args: Vec<Arg> = vec![];
+    let mut in_args = false;
+    for line in source.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') && !line.starts_with('-') {
+            in_args = line.trim_end() == "args:";
+            continue;
+        }
+        if !in_args {
+            continue;
+        }
+        if let Some(caps) = item.captures(line) {
+            args.push(Arg {
+                name: caps[2].to_owned(),
+                ..Default::default()
+            });
+            continue;
+        }
+        let (arg, caps) = match (args.last_mut(), pair.captures(line)) {
+            (Some(arg), Some(caps)) => (arg, caps),
+            _ => continue,
+        };
+        let value = caps[2].trim_matches(|c| c == '"' || c == '\'').to_owned();
+        let is_true = value == "true";
+        match &caps[1] {
+            "long" => arg.long = Some(value),
+            "short" => arg.short = Some(value),
+            "help" | "about" => arg.help = Some(value),
+            "value_name" => arg.value_name = Some(value),
+            "default_value" => arg.default = Some(value),
+            "env" => arg.env = Some(value),
+            "required" => arg.required = is_true,
+            "takes_value" => arg.takes_value = is_true,
+            "multiple" => arg.multi = is_true,
+            "index" => arg.index = true,
+            key => todos.push(format!("{}: {} was not imported", arg.name, key)),
+        }
+    }
+    args
+}
+
+/// Drafts a spec from a clap definition in Rust or YAML.
+pub(super) fn import(source: &str) -> Draft {
+    let mut todos = vec![];
+    let args = if Regex::new(r"(?m)^args:\s*$").unwrap().is_match(source) {
+        yaml_args(source, &mut todos)
+    } else {
+        let mut args = builder_args(source, &mut todos);
+        args.extend(derive_args(source, &mut todos));
+        args
+    };
+    if args.is_empty() {
+        todos.push(String::from(
+            "no clap arguments were found: no Arg::new(...), #[derive(Parser)] struct or YAML args",
+        ));
+    }
+    let mut positionals = vec![];
+    let mut options = vec![];
+    for arg in args {
+        if arg.name == "help" || arg.name == "version" {
+            continue;
+        }
+        let c_type = if arg.int { CType::Int } else { CType::Chars };
+        if arg.index || (arg.long.is_none() && arg.short.is_none()) {
+            let help_name = arg
+                .value_name
+                .clone()
+                .unwrap_or_else(|| c_var(&arg.name).to_ascii_uppercase());
+            let mut pi = positional(&arg.name, &help_name);
+            pi.c_type = c_type;
+            pi.help_descr = arg.help;
+            pi.required = Some(arg.required).filter(|r| *r);
+            pi.default = arg.default;
+            pi.multi = Some(arg.multi).filter(|m| *m);
+            positionals.push(pi);
+            continue;
+        }
+        let long = arg
+            .long
+            .clone()
+            .unwrap_or_else(|| arg.name.replace('_', "-"));
+        let mut npi = option(&arg.name, &long);
+        let flag =
+            arg.flag || !(arg.takes_value || arg.value_name.is_some() || arg.default.is_some());
+        npi.c_type = if flag { CType::Int } else { c_type };
+        npi.flag = Some(flag).filter(|f| *f);
+        npi.short = arg.short;
+        npi.help_descr = arg.help;
+        npi.help_name = arg.value_name.filter(|_| !flag);
+        npi.default = arg.default.filter(|_| !flag);
+        npi.required = Some(arg.required).filter(|r| *r && !flag);
+        npi.env = arg.env;
+        npi.aliases = Some(arg.aliases).filter(|a| !a.is_empty());
+        if arg.multi && !flag {
+            todos.push(format!(
+                "--{} may be given more than once, but only its last value is kept",
+                long
+            ));
+        }
+        options.push(npi);
+    }
+    Draft::new(positionals, options, todos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::Spec;
+
+    #[test]
+    fn builder() {
+        let draft = Spec::import(
+            "clap",
+            r#"
+            let matches = Command::new("frob")
+                .arg(Arg::new("verbose").short('v').long("verbose")
+                    .action(ArgAction::SetTrue).help("say more"))
+                .arg(Arg::new("size").long("size").value_name("N")
+                    .value_parser(clap::value_parser!(u32)).default_value("12"))
+                .arg(Arg::new("input").help("the \"input\" file").required(true))
+                .get_matches();
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            draft.to_toml(),
+            r#"[[non_positional]]
+c_type = "int"
+c_var = "verbose"
+flag = true
+help_descr = "say more"
+long = "verbose"
+short = "v"
+
+[[non_positional]]
+c_type = "int"
+c_var = "size"
+default = "12"
+help_name = "N"
+long = "size"
+
+[[positional]]
+c_type = "char*"
+c_var = "input"
+help_descr = "the \"input\" file"
+help_name = "INPUT"
+required = true
+"#
+        );
+    }
+
+    #[test]
+    fn derive() {
+        let draft = Spec::import(
+            "clap",
+            r#"
+            #[derive(Parser, Debug)]
+            #[command(author, version)]
+            struct Cli {
+                /// Names to greet
+                names: Vec<String>,
+                /// Times to greet them
+                #[arg(short = 'n', long, default_value_t = 1)]
+                count: u8,
+                #[arg(long = "dry-run")]
+                dry_run: bool,
+                #[command(subcommand)]
+                command: Option<Commands>,
+            }
+            "#,
+        )
+        .unwrap();
+        let toml = draft.to_toml();
+        assert!(toml.starts_with(
+            "# TODO: command: #[subcommand] fields are not imported; give their options here\n"
+        ));
+        assert!(toml.contains(
+            "[[non_positional]]\nc_type = \"int\"\nc_var = \"count\"\ndefault = \"1\"\n\
+             help_descr = \"Times to greet them\"\nlong = \"count\"\nshort = \"n\"\n"
+        ));
+        assert!(toml.contains("c_var = \"dry_run\"\nflag = true\nlong = \"dry-run\"\n"));
+        assert!(toml.contains("help_name = \"NAMES\"\nmulti = true\n"));
+    }
+
+    #[test]
+    fn yaml() {
+        let draft = Spec::import(
+            "clap",
+            "name: frob\nargs:\n    - config:\n        short: c\n        long: config\n        \
+             value_name: FILE\n        help: Sets a custom config file\n        takes_value: true\n    \
+             - INPUT:\n        help: Sets the input file to use\n        required: true\n        \
+             index: 1\n",
+        )
+        .unwrap();
+        let toml = draft.to_toml();
+        assert!(toml.contains("c_var = \"config\"\nhelp_descr = \"Sets a custom config file\"\n"));
+        assert!(toml.contains("[[positional]]\nc_type = \"char*\"\nc_var = \"INPUT\"\n"));
+    }
+}
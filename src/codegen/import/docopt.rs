@@ -0,0 +1,338 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Drafts from docopt: a `Usage:` section of patterns, and an `Options:`
+//! section describing each option, its argument and its `[default: ...]`.
+
+use super::{c_var, option, positional, CType, Draft, NonPositionalItem, PositionalItem};
+use regex::Regex;
+
+/// An option, from the Options section or a usage pattern.
+pub(super) struct DocOption {
+    pub(super) long: Option<String>,
+    pub(super) short: Option<String>,
+    pub(super) arg: Option<String>,
+    pub(super) help: Option<String>,
+    pub(super) default: Option<String>,
+}
+
+impl DocOption {
+    /// The name it is known by: its long name, or else its short one.
+    fn name(&self) -> &str {
+        self.long
+            .as_deref()
+            .or(self.short.as_deref())
+            .unwrap_or_default()
+    }
+    fn matches(&self, token: &str) -> bool {
+        let name = token.split('=').next().unwrap_or_default();
+        match name.strip_prefix("--") {
+            Some(long) => self.long.as_deref() == Some(long),
+            None => self.short.as_deref() == name.strip_prefix('-'),
+        }
+    }
+}
+
+/// The lines of the section with the given title, case aside, up to the
+/// first blank line. Text after the title on its line is the first line.
+fn section<'a>(text: &'a str, title: &str) -> Vec<&'a str> {
+    let mut lines = text.lines();
+    let mut section = vec![];
+    for line in &mut lines {
+        let lower = line.trim_start().to_ascii_lowercase();
+        if lower.starts_with(title) {
+            let rest = line.trim_start()[title.len()..].trim();
+            if !rest.is_empty() {
+                section.push(rest);
+            }
+            break;
+        }
+    }
+    for line in lines {
+        if line.trim().is_empty() {
+            if section.is_empty() {
+                continue;
+            }
+            break;
+        }
+        section.push(line.trim());
+    }
+    section
+}
+
+/// An option line: names and argument, two spaces, then its description.
+pub(super) fn parse_option(line: &str) -> DocOption {
+    let (names, help) = match line.find("  ") {
+        Some(i) => (&line[..i], Some(line[i..].trim())),
+        None => (line, None),
+    };
+    let mut opt = DocOption {
+        long: None,
+        short: None,
+        arg: None,
+        help: help.map(str::to_owned),
+        default: None,
+    };
+    for token in names.split([' ', ',']).filter(|t| !t.is_empty()) {
+        let (name, arg) = match token.find('=') {
+            Some(i) => (&token[..i], Some(&token[i + 1..])),
+            None => (token, None),
+        };
+        if let Some(long) = name.strip_prefix("--") {
+            opt.long = Some(long.to_owned());
+        } else if let Some(short) = name.strip_prefix('-') {
+            // -o<file> runs the argument into the name
+            let mut chars = short.chars();
+            opt.short = chars.next().map(String::from);
+            let rest = chars.as_str();
+            if !rest.is_empty() {
+                opt.arg = Some(rest.to_owned());
+            }
+        } else {
+            opt.arg = Some(name.to_owned());
+        }
+        if let Some(arg) = arg {
+            opt.arg = Some(arg.to_owned());
+        }
+    }
+    let default = Regex::new(r"(?i)\s*\[default:\s*(.*?)\]").unwrap();
+    if let Some(caps) = opt.help.as_deref().and_then(|h| default.captures(h)) {
+        opt.default = Some(caps[1].to_owned());
+    }
+    opt.help = opt
+        .help
+        .map(|h| default.replace(&h, "").trim().to_owned())
+        .filter(|h| !h.is_empty());
+    opt
+}
+
+/// A usage pattern split into words, brackets, bars and ellipses.
+fn tokens(pattern: &str) -> Vec<String> {
+    let re = Regex::new(r"\.\.\.|[\[\]()|]|[^\s\[\]()|.]+(?:\.[^\s\[\]()|.]+)*").unwrap();
+    re.find_iter(pattern)
+        .map(|m| m.as_str().to_owned())
+        .collect()
+}
+
+fn is_argument(token: &str) -> bool {
+    (token.starts_with('<') && token.ends_with('>'))
+        || (token.chars().any(|c| c.is_ascii_uppercase())
+            && token.chars().all(|c| !c.is_ascii_lowercase()))
+}
+
+/// A positional argument across the usage patterns.
+struct DocArg {
+    name: String,
+    /// In how many patterns it is required.
+    required: usize,
+    multi: bool,
+}
+
+/// The positional arguments of usage patterns, each starting with the
+/// program name. Options in them not already known are added to options.
+pub(super) fn usage_positionals(
+    patterns: &[&str],
+    options: &mut Vec<DocOption>,
+    todos: &mut Vec<String>,
+) -> Vec<PositionalItem> {
+    let mut args: Vec<DocArg> = vec![];
+    let mut commands: Vec<String> = vec![];
+    let mut npatterns = 0;
+    for pattern in patterns {
+        let tokens = tokens(pattern);
+        // "prog -h | --help" and "prog --version" describe no arguments
+        if tokens[1..]
+            .iter()
+            .all(|t| t.starts_with('-') || t == "|" || t == "(" || t == ")")
+        {
+            continue;
+        }
+        npatterns += 1;
+        let mut brackets = vec![];
+        let mut last: Option<usize> = None;
+        // the argument of an option written as "--speed <kn>"
+        let mut option_arg = false;
+        for token in &tokens[1..] {
+            if std::mem::take(&mut option_arg) && is_argument(token) {
+                continue;
+            }
+            match token.as_str() {
+                "[" | "(" => brackets.push(token.to_owned()),
+                "]" | ")" => {
+                    brackets.pop();
+                }
+                "..." => {
+                    if let Some(i) = last {
+                        args[i].multi = true;
+                    }
+                }
+                "|" => {}
+                // the [options] shortcut, or [OPTION]... in GNU usage
+                "options" | "OPTION" | "OPTIONS" => last = None,
+                t if t.starts_with('-') && t.len() > 1 => {
+                    last = None;
+                    let known = options.iter().find(|o| o.matches(t));
+                    option_arg = !t.contains('=') && known.is_some_and(|o| o.arg.is_some());
+                    let known = known.is_some();
+                    if !known && !t.starts_with("--") && t.len() > 2 && !t.contains('<') {
+                        // stacked shorts, such as -abc
+                        for c in t[1..].chars() {
+                            if !options
+                                .iter()
+                                .any(|o| o.short.as_deref() == Some(&c.to_string()))
+                            {
+                                options.push(parse_option(&format!("-{}", c)));
+                            }
+                        }
+                    } else if !known {
+                        options.push(parse_option(t));
+                    }
+                }
+                t if is_argument(t) => {
+                    let optional = brackets.iter().any(|b| b == "[");
+                    let name = t.trim_start_matches('<').trim_end_matches('>').to_owned();
+                    let i = match args.iter().position(|a| a.name == name) {
+                        Some(i) => i,
+                        None => {
+                            args.push(DocArg {
+                                name,
+                                required: 0,
+                                multi: false,
+                            });
+                            args.len() - 1
+                        }
+                    };
+                    if !optional {
+                        args[i].required += 1;
+                    }
+                    last = Some(i);
+                }
+                t => {
+                    last = None;
+                    if !commands.iter().any(|c| c == t) {
+                        commands.push(t.to_owned());
+                    }
+                }
+            }
+        }
+    }
+    if !commands.is_empty() {
+        todos.push(format!(
+            "the commands {} were not imported; give each a [[subcommand]]",
+            commands.join(", ")
+        ));
+    }
+    args.into_iter()
+        .map(|arg| {
+            let mut pi = positional(&arg.name, &c_var(&arg.name).to_ascii_uppercase());
+            pi.required = Some(arg.required == npatterns).filter(|r| *r);
+            pi.multi = Some(arg.multi).filter(|m| *m);
+            pi
+        })
+        .collect()
+}
+
+/// The spec's options, but for the ones the parser adds itself.
+pub(super) fn draft_options(
+    options: Vec<DocOption>,
+    todos: &mut Vec<String>,
+) -> Vec<NonPositionalItem> {
+    options
+        .into_iter()
+        .filter(|opt| opt.name() != "help" && opt.name() != "h" && opt.name() != "version")
+        .map(|opt| {
+            let name = opt.name().to_owned();
+            let mut npi = option(&name, opt.long.as_deref().unwrap_or(&name));
+            if opt.long.is_none() {
+                todos.push(format!("-{} has no long name; one was made up", name));
+            }
+            npi.short = opt.short;
+            npi.help_descr = opt.help;
+            match opt.arg {
+                Some(arg) => {
+                    let arg = arg.trim_start_matches('<').trim_end_matches('>');
+                    npi.help_name = Some(arg.to_owned());
+                    npi.default = opt.default;
+                }
+                None => {
+                    npi.c_type = CType::Int;
+                    npi.flag = Some(true);
+                }
+            }
+            npi
+        })
+        .collect()
+}
+
+/// Drafts a spec from docopt usage text.
+pub(super) fn import(text: &str) -> Draft {
+    let mut todos = vec![];
+    let mut options: Vec<DocOption> = section(text, "options:")
+        .into_iter()
+        .filter(|line| line.starts_with('-'))
+        .map(parse_option)
+        .collect();
+    let patterns = section(text, "usage:");
+    if patterns.is_empty() {
+        todos.push(String::from("no Usage: section was found"));
+    }
+    let positionals = usage_positionals(&patterns, &mut options, &mut todos);
+    let non_positional = draft_options(options, &mut todos);
+    Draft::new(positionals, non_positional, todos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::Spec;
+
+    const NAVAL_FATE: &str = "Naval Fate.
+
+Usage:
+  naval_fate <name>... [--speed=<kn>] [-v]
+  naval_fate <name> <x> [<y>] --moored
+  naval_fate -h | --help
+
+Options:
+  -h --help     Show this screen.
+  --speed=<kn>  Speed in knots [default: 10].
+  --moored      Moored (anchored) mine.
+";
+
+    #[test]
+    fn docopt() {
+        let toml = Spec::import("docopt", NAVAL_FATE).unwrap().to_toml();
+        assert!(toml.starts_with("# TODO: -v has no long name; one was made up\n"));
+        assert!(toml.contains(
+            "[[non_positional]]\n\
+             c_type = \"char*\"\n\
+             c_var = \"speed\"\n\
+             default = \"10\"\n\
+             help_descr = \"Speed in knots.\"\n\
+             help_name = \"kn\"\n\
+             long = \"speed\"\n"
+        ));
+        assert!(toml.contains("c_var = \"moored\"\nflag = true\n"));
+        assert!(toml.contains(
+            "[[positional]]\n\
+             c_type = \"char*\"\n\
+             c_var = \"name\"\n\
+             help_name = \"NAME\"\n\
+             multi = true\n\
+             required = true\n"
+        ));
+        assert!(toml.contains("c_var = \"x\"\nhelp_name = \"X\"\n\n"));
+    }
+}
@@ -0,0 +1,510 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Drafts from C parsing its command line with getopt or getopt_long: the
+//! `struct option` array gives the long options, the optstring the short
+//! ones, and the cases of the switch on what getopt returned say where each
+//! value goes. None of it is compiled, and the preprocessor isn't run; the
+//! usage text in the file's string literals, if any, gives help text and
+//! the positional arguments.
+
+use super::clap::{balanced, string_literal, string_literals};
+use super::docopt::usage_positionals;
+use super::help::{help_options, usage_patterns};
+use super::{c_var, option, CType, Draft, NonPositionalItem};
+use regex::Regex;
+
+/// Whether an option takes an argument, as has_arg or the optstring says.
+#[derive(Clone, Copy, PartialEq)]
+enum HasArg {
+    No,
+    Required,
+    Optional,
+}
+
+/// An option, from the `struct option` array or the optstring.
+struct GetoptOption {
+    long: Option<String>,
+    short: Option<char>,
+    has_arg: HasArg,
+    /// What getopt returns for it, as a number where the C says so, or the
+    /// macro or enum constant it is written as.
+    val: String,
+    /// The variable given as the flag pointer, which getopt_long sets to
+    /// val itself.
+    flag_var: Option<String>,
+}
+
+impl GetoptOption {
+    fn name(&self) -> String {
+        match (&self.long, self.short) {
+            (Some(long), _) => format!("--{}", long),
+            (None, Some(short)) => format!("-{}", short),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// The code without its comments.
+fn strip_comments(code: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' | '\'' => {
+                out.push(c);
+                while let Some(d) = chars.next() {
+                    out.push(d);
+                    if d == '\\' {
+                        out.extend(chars.next());
+                    } else if d == c || d == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().is_some_and(|&d| d != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                for d in &mut chars {
+                    if last == '*' && d == '/' {
+                        break;
+                    }
+                    last = d;
+                }
+                out.push(' ');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A val or case label as a number where it is a char or int literal, so
+/// that 'b' and 98 are the same; anything else is kept as written.
+fn val_key(val: &str) -> String {
+    let val = val.trim();
+    let char_lit = Regex::new(r"^'(\\?.)'$").unwrap();
+    if let Some(caps) = char_lit.captures(val) {
+        let c = match &caps[1] {
+            "\\n" => '\n',
+            "\\t" => '\t',
+            "\\0" => '\0',
+            lit => lit.chars().last().unwrap_or_default(),
+        };
+        return (c as u32).to_string();
+    }
+    let parsed = match val.strip_prefix("0x").or_else(|| val.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok(),
+        None => val.parse().ok(),
+    };
+    parsed.map_or_else(|| val.to_owned(), |n| n.to_string())
+}
+
+/// The char a key stands for, where it is a printable one.
+fn key_char(key: &str) -> Option<char> {
+    let c = char::from_u32(key.parse().ok()?)?;
+    Some(c).filter(char::is_ascii_graphic)
+}
+
+/// The entries of the `struct option` array.
+fn longopts(code: &str, todos: &mut Vec<String>) -> Vec<GetoptOption> {
+    let array = Regex::new(r"struct\s+option\s+\w+\s*\[[^\]]*\]\s*=\s*\{").unwrap();
+    let start = match array.find(code) {
+        Some(m) => m.end(),
+        None => return vec![],
+    };
+    let body = &code[start..start + balanced(&code[start..])];
+    let entry = Regex::new(
+        r#"\{\s*("(?:[^"\\]|\\.)*"|0|NULL)\s*,\s*([^,{}]+?)\s*,\s*([^,{}]+?)\s*,\s*([^,{}]+?)\s*\}"#,
+    )
+    .unwrap();
+    let mut options = vec![];
+    for caps in entry.captures_iter(body) {
+        let long = match string_literal(&caps[1]) {
+            Some(long) => long,
+            // the all-zero entry ending the array
+            None => continue,
+        };
+        let has_arg = match &caps[2] {
+            "no_argument" | "0" => HasArg::No,
+            "required_argument" | "1" => HasArg::Required,
+            "optional_argument" | "2" => HasArg::Optional,
+            other => {
+                todos.push(format!(
+                    "--{} has has_arg = {}, which was read as required_argument",
+                    long, other
+                ));
+                HasArg::Required
+            }
+        };
+        let flag_var = match caps[3].trim() {
+            "0" | "NULL" | "nullptr" => None,
+            flag => Some(flag.trim_start_matches('&').trim().to_owned()),
+        };
+        options.push(GetoptOption {
+            long: Some(long),
+            short: None,
+            has_arg,
+            val: val_key(&caps[4]),
+            flag_var,
+        });
+    }
+    options
+}
+
+/// The short options of an optstring.
+fn optstring(optstring: &str) -> Vec<GetoptOption> {
+    // a leading + or - is how to scan, and a leading : how to report errors
+    let optstring = optstring
+        .trim_start_matches(['+', '-'])
+        .trim_start_matches(':');
+    let mut options = vec![];
+    let mut chars = optstring.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' || c == ';' {
+            continue;
+        }
+        let mut colons = 0;
+        while chars.peek() == Some(&':') {
+            chars.next();
+            colons += 1;
+        }
+        options.push(GetoptOption {
+            long: None,
+            short: Some(c),
+            has_arg: match colons {
+                0 => HasArg::No,
+                1 => HasArg::Required,
+                _ => HasArg::Optional,
+            },
+            val: (c as u32).to_string(),
+            flag_var: None,
+        });
+    }
+    options
+}
+
+/// The code of each case of the switch at the start of code, by key. Labels
+/// falling through to the same code share it.
+fn switch_cases(code: &str) -> Vec<(String, String)> {
+    let switch = Regex::new(r"\bswitch\s*\([^{]*\{").unwrap();
+    let start = match switch.find(code) {
+        Some(m) => m.end(),
+        None => return vec![],
+    };
+    let body = &code[start..start + balanced(&code[start..]) - 1];
+    let label = Regex::new(r"\bcase\s+('(?:[^'\\]|\\.)'|[^:']+?)\s*:|\bdefault\s*:").unwrap();
+    let labels: Vec<_> = label.captures_iter(body).collect();
+    let mut cases = vec![];
+    let mut pending = vec![];
+    for (i, caps) in labels.iter().enumerate() {
+        if let Some(val) = caps.get(1) {
+            pending.push(val_key(val.as_str()));
+        }
+        let end = labels
+            .get(i + 1)
+            .map_or(body.len(), |next| next.get(0).unwrap().start());
+        let case = body[caps.get(0).unwrap().end()..end].trim();
+        if !case.is_empty() {
+            for key in pending.drain(..) {
+                cases.push((key, case.to_owned()));
+            }
+        }
+    }
+    cases
+}
+
+/// The variable assigned to, from the left side of an assignment, such as
+/// `*opts->verbose`.
+fn assigned_var(lhs: &str) -> String {
+    let lhs = lhs
+        .trim()
+        .trim_start_matches(['*', '('])
+        .trim_end_matches(')');
+    let last = lhs.rsplit(['.', '>']).next().unwrap_or(lhs);
+    c_var(last.trim())
+}
+
+/// Fills in an option's variable and type from the code of its case.
+fn read_case(npi: &mut NonPositionalItem, has_arg: HasArg, case: &str, todos: &mut Vec<String>) {
+    let name = format!("--{}", npi.long);
+    let lhs = r"([*(]*\s*[A-Za-z_]\w*(?:\s*(?:\.|->)\s*[A-Za-z_]\w*)*\)?)";
+    if has_arg == HasArg::No {
+        let set = Regex::new(&format!(r"{}\s*=\s*(?:1|true)\s*;", lhs)).unwrap();
+        let count = Regex::new(&format!(r"{}\s*(?:\+\+|\+=\s*1\b)", lhs)).unwrap();
+        if let Some(caps) = set.captures(case) {
+            npi.c_var = assigned_var(&caps[1]);
+        } else if let Some(caps) = count.captures(case) {
+            npi.c_var = assigned_var(&caps[1]);
+            todos.push(format!(
+                "{} counts how often it is given, which argen doesn't; it is a flag",
+                name
+            ));
+        } else {
+            todos.push(format!(
+                "{} does something other than set a flag, which was not imported",
+                name
+            ));
+        }
+        return;
+    }
+    let convert = Regex::new(&format!(
+        r"{}\s*=\s*(?:\([^()]*\)\s*)?(\w+)\s*\(\s*optarg\b",
+        lhs
+    ))
+    .unwrap();
+    let assign = Regex::new(&format!(r"{}\s*=\s*(?:\([^()]*\)\s*)?optarg\s*;", lhs)).unwrap();
+    if let Some(caps) = assign.captures(case) {
+        npi.c_var = assigned_var(&caps[1]);
+    } else if let Some(caps) = convert.captures(case) {
+        npi.c_var = assigned_var(&caps[1]);
+        match &caps[2] {
+            "atoi" | "atol" | "atoll" | "strtol" | "strtoll" | "strtoul" | "strtoull" => {
+                npi.c_type = CType::Int
+            }
+            "strdup" | "xstrdup" => {}
+            other => todos.push(format!(
+                "{} reads its argument with {}, which argen doesn't; it is a char*",
+                name, other
+            )),
+        }
+    } else {
+        todos.push(format!(
+            "what {} does with its argument was not recognized; it is a char*",
+            name
+        ));
+    }
+}
+
+/// Drafts a spec from C code using getopt or getopt_long.
+pub(super) fn import(text: &str) -> Draft {
+    let code = strip_comments(text);
+    let mut todos = vec![];
+    let mut options = longopts(&code, &mut todos);
+    let call = Regex::new(r"\bgetopt(?:_long(?:_only)?)?\s*\(").unwrap();
+    let (shorts, rest) = match call.find(&code) {
+        Some(m) => {
+            let args = &code[m.end()..m.end() + balanced(&code[m.end()..])];
+            match string_literal(args) {
+                Some(s) => (optstring(&s), &code[m.end()..]),
+                None => {
+                    todos.push(String::from(
+                        "the optstring is not a string literal, so no short options were imported",
+                    ));
+                    (vec![], &code[m.end()..])
+                }
+            }
+        }
+        None => {
+            todos.push(String::from("no call to getopt or getopt_long was found"));
+            (vec![], &code[..])
+        }
+    };
+    // a long option returning a short one's char is the same option
+    for short in shorts {
+        let c = short.short;
+        match options
+            .iter_mut()
+            .find(|o| o.flag_var.is_none() && key_char(&o.val) == c)
+        {
+            Some(long) => long.short = c,
+            None => options.push(short),
+        }
+    }
+    let cases = switch_cases(rest);
+
+    let literals = string_literals(&code).concat().replace('\t', "        ");
+    let lines: Vec<&str> = literals.lines().collect();
+    let mut help = help_options(&lines, &mut vec![]);
+    let patterns = usage_patterns(&lines);
+    let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+    let positional = if !patterns.is_empty() {
+        usage_positionals(&patterns, &mut help, &mut todos)
+    } else {
+        if code.contains("optind") {
+            todos.push(String::from(
+                "the arguments after the options (from argv[optind]) were not imported; add a [[positional]] for each",
+            ));
+        }
+        vec![]
+    };
+
+    let mut non_positional = vec![];
+    for opt in options {
+        let long = opt.long.as_deref();
+        if matches!(long, Some("help") | Some("version"))
+            || (long.is_none() && opt.short == Some('h'))
+        {
+            continue;
+        }
+        let name = opt.name();
+        let short = opt.short.map(String::from);
+        let mut npi = option(
+            long.or(short.as_deref()).unwrap_or_default(),
+            long.or(short.as_deref()).unwrap_or_default(),
+        );
+        if long.is_none() {
+            todos.push(format!("{} has no long name; one was made up", name));
+        }
+        npi.short = short;
+        if opt.has_arg == HasArg::Optional {
+            todos.push(format!(
+                "{} takes an optional argument, which argen makes required",
+                name
+            ));
+        }
+        match (&opt.flag_var, opt.has_arg) {
+            (Some(var), _) => {
+                npi.c_var = assigned_var(var);
+                npi.c_type = CType::Int;
+                npi.flag = Some(true);
+                if opt.val != "1" {
+                    todos.push(format!(
+                        "{} sets {} to {}, where argen sets 1",
+                        name, var, opt.val
+                    ));
+                }
+            }
+            (None, has_arg) => {
+                if has_arg == HasArg::No {
+                    npi.c_type = CType::Int;
+                    npi.flag = Some(true);
+                }
+                match cases.iter().find(|(key, _)| *key == opt.val) {
+                    Some((_, case)) => read_case(&mut npi, has_arg, case, &mut todos),
+                    None => todos.push(format!("no case for {} was found", name)),
+                }
+            }
+        }
+        let described = help.iter().find(|h| {
+            (h.long.is_some() && h.long == opt.long)
+                || (h.short.is_some() && h.short == opt.short.map(String::from))
+        });
+        if let Some(described) = described {
+            npi.help_descr = described.help.clone().filter(|h| !h.starts_with('-'));
+            if npi.flag.is_none() {
+                npi.help_name = described
+                    .arg
+                    .as_deref()
+                    .map(|arg| arg.trim_start_matches('<').trim_end_matches('>').to_owned());
+                npi.default = described.default.clone();
+            }
+        }
+        non_positional.push(npi);
+    }
+    Draft::new(positional, non_positional, todos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::Spec;
+
+    const SOURCE: &str = r#"
+#include <getopt.h>
+
+static const char *usage_text =
+    "usage: frob [-v] [-n NUM] SOURCE [DEST...]\n"
+    "  -n, --lines NUM   frobnicate NUM lines (default: 10)\n"
+    "  -o FILE           write to FILE\n";
+
+static int brief;
+
+int main(int argc, char **argv) {
+    int verbose = 0, lines = 10;
+    char *out = NULL, *color = NULL;
+    static struct option longopts[] = {
+        {"verbose", no_argument, 0, 'v'},
+        {"lines", required_argument, NULL, 'n'},
+        {"color", optional_argument, 0, 256},
+        {"brief", no_argument, &brief, 1},
+        {"help", no_argument, 0, 'h'},
+        {0, 0, 0, 0}
+    };
+    int ch;
+    while ((ch = getopt_long(argc, argv, "vn:o:h", longopts, NULL)) != -1) {
+        switch (ch) {
+        case 'v':
+            verbose = 1;
+            break;
+        case 'n': /* how many */
+            lines = atoi(optarg);
+            break;
+        case 'o':
+            out = optarg;
+            break;
+        case 256:
+            color = optarg;
+            break;
+        case 0:
+            break;
+        case 'h':
+        default:
+            fputs(usage_text, stderr);
+            return 1;
+        }
+    }
+    return 0;
+}
+"#;
+
+    #[test]
+    fn getopt() {
+        let toml = Spec::import("getopt", SOURCE).unwrap().to_toml();
+        assert!(toml.starts_with(
+            "# TODO: --color takes an optional argument, which argen makes required\n\
+             # TODO: -o has no long name; one was made up\n\n"
+        ));
+        assert!(toml.contains(
+            "c_type = \"int\"\n\
+             c_var = \"verbose\"\n\
+             flag = true\n\
+             long = \"verbose\"\n\
+             short = \"v\"\n"
+        ));
+        assert!(toml.contains(
+            "c_type = \"int\"\n\
+             c_var = \"lines\"\n\
+             default = \"10\"\n\
+             help_descr = \"frobnicate NUM lines (default: 10)\"\n\
+             help_name = \"NUM\"\n\
+             long = \"lines\"\n\
+             short = \"n\"\n"
+        ));
+        assert!(toml.contains("c_var = \"color\"\nlong = \"color\"\n"));
+        assert!(toml.contains("c_var = \"brief\"\nflag = true\n"));
+        assert!(toml.contains(
+            "c_type = \"char*\"\n\
+             c_var = \"out\"\n\
+             help_descr = \"write to FILE\"\n\
+             help_name = \"FILE\"\n\
+             long = \"o\"\n\
+             short = \"o\"\n"
+        ));
+        assert!(toml.contains(
+            "[[positional]]\n\
+             c_type = \"char*\"\n\
+             c_var = \"SOURCE\"\n\
+             help_name = \"SOURCE\"\n\
+             required = true\n"
+        ));
+        assert!(!toml.contains("\"help\""));
+    }
+}
@@ -0,0 +1,189 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Drafts from what a program prints for --help, by guesswork: a line
+//! starting with a dash is an option, described after a run of spaces and
+//! on the more indented lines under it, and the usage lines give the
+//! positional arguments. The usage is read as docopt reads it.
+
+use super::docopt::{draft_options, parse_option, usage_positionals, DocOption};
+use super::{CType, Draft};
+use regex::Regex;
+
+/// Argument names that are numbers, as --help output tends to call them.
+const INT_NAMES: [&str; 7] = ["N", "NUM", "NUMBER", "COUNT", "INT", "INTEGER", "LINES"];
+
+/// The usage patterns: what follows "Usage:", and each "or:" line after it,
+/// or in docopt's style, the indented lines under a bare "Usage:".
+pub(super) fn usage_patterns(lines: &[&str]) -> Vec<String> {
+    let mut patterns = vec![];
+    let mut in_usage = false;
+    let mut bare = false;
+    for line in lines {
+        let trimmed = line.trim();
+        let lower = trimmed.to_ascii_lowercase();
+        if lower.starts_with("usage:") {
+            let rest = trimmed["usage:".len()..].trim();
+            in_usage = true;
+            bare = rest.is_empty();
+            if !bare {
+                patterns.push(rest.to_owned());
+            }
+        } else if in_usage && lower.starts_with("or:") {
+            patterns.push(trimmed["or:".len()..].trim().to_owned());
+        } else if in_usage && bare && line.starts_with(' ') && !trimmed.starts_with('-') {
+            patterns.push(trimmed.to_owned());
+        } else {
+            in_usage = false;
+        }
+    }
+    patterns
+}
+
+/// An option line, with the optional argument of --name[=ARG] made a
+/// required one.
+fn help_option(line: &str, todos: &mut Vec<String>) -> DocOption {
+    let optional = Regex::new(r"\[=([^\]\s]+)\]").unwrap();
+    let names_end = line.find("  ").unwrap_or(line.len());
+    let line = if optional.is_match(&line[..names_end]) {
+        let fixed = optional.replace(&line[..names_end], "=$1");
+        todos.push(format!(
+            "{} takes an optional argument, which argen makes required",
+            fixed
+                .split('=')
+                .next()
+                .and_then(|names| names.trim().rsplit([' ', ',']).next())
+                .unwrap_or_default()
+        ));
+        format!("{}{}", fixed, &line[names_end..])
+    } else {
+        line.to_owned()
+    };
+    parse_option(&line)
+}
+
+/// The options described in help text, each on a line starting with a
+/// dash, with defaults found in their descriptions.
+pub(super) fn help_options(lines: &[&str], todos: &mut Vec<String>) -> Vec<DocOption> {
+    let mut options: Vec<DocOption> = vec![];
+    // the indent of the last option line, while lines under it describe it
+    let mut option_indent: Option<usize> = None;
+    for line in lines {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+        if trimmed.starts_with('-') && trimmed.len() > 1 && !trimmed.starts_with("--- ") {
+            options.push(help_option(trimmed.trim_end(), todos));
+            option_indent = Some(indent);
+        } else if trimmed.is_empty() || option_indent.is_none_or(|i| indent <= i) {
+            option_indent = None;
+        } else if let Some(opt) = options.last_mut() {
+            let help = opt.help.get_or_insert_with(String::new);
+            if !help.is_empty() {
+                help.push(' ');
+            }
+            help.push_str(trimmed.trim_end());
+        }
+    }
+    let default = Regex::new(
+        r#"(?i)\(default:?\s*([^)]*)\)|\bdefaults? (?:is|to) ['"]?([^\s'",;)]+?)['"]?[.,;)]?(?:\s|$)"#,
+    )
+    .unwrap();
+    for opt in options.iter_mut().filter(|opt| opt.arg.is_some()) {
+        if let (None, Some(help)) = (&opt.default, &opt.help) {
+            if let Some(caps) = default.captures(help) {
+                let value = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+                opt.default = Some(value.trim().to_owned());
+            }
+        }
+    }
+    options
+}
+
+/// Drafts a spec from a program's --help output.
+pub(super) fn import(text: &str) -> Draft {
+    let text = text.replace('\t', "        ");
+    let lines: Vec<&str> = text.lines().collect();
+    let mut todos = vec![];
+    let mut options = help_options(&lines, &mut todos);
+    let patterns = usage_patterns(&lines);
+    if patterns.is_empty() {
+        todos.push(String::from(
+            "no usage line was found, so no positional arguments were",
+        ));
+    }
+    let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+    let positionals = usage_positionals(&patterns, &mut options, &mut todos);
+    let mut non_positional = draft_options(options, &mut todos);
+    for npi in &mut non_positional {
+        let int = npi.help_name.as_deref().is_some_and(|name| {
+            INT_NAMES.contains(
+                &name
+                    .trim_matches(|c| c == '<' || c == '>')
+                    .to_ascii_uppercase()
+                    .as_str(),
+            )
+        });
+        if int {
+            npi.c_type = CType::Int;
+        }
+    }
+    Draft::new(positionals, non_positional, todos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::Spec;
+
+    const HELP: &str = "Usage: frob [OPTION]... SOURCE [DEST]...
+  or:  frob --list
+Frobnicate SOURCE into each DEST.
+
+  -a, --all                  frobnicate hidden files too
+      --color[=WHEN]         colorize the output; WHEN can be 'always',
+                               'auto', or 'never' (default: auto)
+  -n, --lines=NUM            frobnicate NUM lines
+  -o FILE                    write to FILE
+  -h, --help                 display this help and exit
+";
+
+    #[test]
+    fn help() {
+        let toml = Spec::import("help", HELP).unwrap().to_toml();
+        assert!(toml.starts_with(
+            "# TODO: --color takes an optional argument, which argen makes required\n\
+             # TODO: -o has no long name; one was made up\n"
+        ));
+        assert!(toml.contains(
+            "c_var = \"color\"\n\
+             default = \"auto\"\n\
+             help_descr = \"colorize the output; WHEN can be 'always', 'auto', or 'never' (default: auto)\"\n\
+             help_name = \"WHEN\"\n"
+        ));
+        assert!(toml.contains("c_type = \"int\"\nc_var = \"lines\"\n"));
+        assert!(toml.contains(
+            "[[positional]]\n\
+             c_type = \"char*\"\n\
+             c_var = \"SOURCE\"\n\
+             help_name = \"SOURCE\"\n\
+             required = true\n\n\
+             [[positional]]\n\
+             c_type = \"char*\"\n\
+             c_var = \"DEST\"\n\
+             help_name = \"DEST\"\n\
+             multi = true\n"
+        ));
+    }
+}
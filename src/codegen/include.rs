@@ -0,0 +1,415 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `include = ["common.toml"]`: the positional and non_positional items of
+//! other spec files, merged into a spec before it is deserialized, so a
+//! suite of programs can share its common options. Named option sets, in
+//! the spec or the files it includes, are merged in with `use`.
+
+use super::{json, json_to_toml, ValidationError};
+use crate::log;
+use std::path::{Path, PathBuf};
+
+/// The tables an included file may hold, and nothing else.
+const INCLUDED: [&str; 2] = ["positional", "non_positional"];
+
+/// The items gathered so far, with the file each came from.
+struct Merged {
+    positional: Vec<toml::Value>,
+    non_positional: Vec<toml::Value>,
+    /// (c_var or long, name, file) of every item.
+    names: Vec<(&'static str, String, String)>,
+    /// Every file included, which a second include of leaves alone.
+    seen: Vec<PathBuf>,
+    /// (name, items, file) of every option set.
+    sets: Vec<(String, toml::Value, String)>,
+}
+impl Merged {
+    /// Adds a file's items, refusing a c_var or long another file has.
+    fn add(&mut self, table: &mut toml::value::Table, file: &str) -> Result<(), ValidationError> {
+        for key in &INCLUDED {
+            let items = match table.remove(*key) {
+                Some(toml::Value::Array(items)) => items,
+                Some(_) => {
+                    return Err(ValidationError::SpecInclude(
+                        file.to_owned(),
+                        format!("{} must be an array of tables", key),
+                    ))
+                }
+                None => continue,
+            };
+            for item in &items {
+                for field in &["c_var", "long"] {
+                    let name = match item.get(field).and_then(|v| v.as_str()) {
+                        Some(name) => name,
+                        None => continue,
+                    };
+                    if let Some((_, _, other)) = self
+                        .names
+                        .iter()
+                        .find(|(f, n, other)| f == field && n == name && other != file)
+                    {
+                        return Err(ValidationError::IncludeConflict(
+                            field.to_string(),
+                            name.to_owned(),
+                            other.to_owned(),
+                            file.to_owned(),
+                        ));
+                    }
+                    self.names.push((field, name.to_owned(), file.to_owned()));
+                }
+            }
+            match *key {
+                "positional" => self.positional.extend(items),
+                _ => self.non_positional.extend(items),
+            }
+        }
+        Ok(())
+    }
+    /// Keeps a file's option sets, refusing one another file has.
+    fn add_sets(
+        &mut self,
+        table: &mut toml::value::Table,
+        file: &str,
+    ) -> Result<(), ValidationError> {
+        let sets = match table.remove("option_sets") {
+            Some(toml::Value::Table(sets)) => sets,
+            Some(_) => {
+                return Err(ValidationError::SpecInclude(
+                    file.to_owned(),
+                    String::from("option_sets must be a table of option sets"),
+                ))
+            }
+            None => return Ok(()),
+        };
+        for (name, set) in sets {
+            if let Some((_, _, other)) = self.sets.iter().find(|(n, _, _)| *n == name) {
+                return Err(ValidationError::IncludeConflict(
+                    String::from("option set"),
+                    name,
+                    other.to_owned(),
+                    file.to_owned(),
+                ));
+            }
+            self.sets.push((name, set, file.to_owned()));
+        }
+        Ok(())
+    }
+    /// Adds the items of the option sets a spec uses, each either a set's
+    /// name or a table of the set's name and defaults to override, by c_var.
+    fn use_sets(&mut self, table: &mut toml::value::Table) -> Result<(), ValidationError> {
+        let uses = match table.remove("use") {
+            Some(toml::Value::Array(uses)) => uses,
+            Some(_) => {
+                return Err(ValidationError::OptionSet(
+                    String::from("use"),
+                    String::from("must be an array of option set names"),
+                ))
+            }
+            None => return Ok(()),
+        };
+        for used in uses {
+            let (name, defaults) = match used {
+                toml::Value::String(name) => (name, Default::default()),
+                toml::Value::Table(mut used) => match (used.remove("set"), used.remove("defaults"))
+                {
+                    (Some(toml::Value::String(name)), Some(toml::Value::Table(defaults))) => {
+                        (name, defaults)
+                    }
+                    (Some(toml::Value::String(name)), None) => (name, Default::default()),
+                    _ => {
+                        return Err(ValidationError::OptionSet(
+                            String::from("use"),
+                            String::from(
+                                "expected { set = \"name\", defaults = { c_var = \"value\" } }",
+                            ),
+                        ))
+                    }
+                },
+                other => {
+                    return Err(ValidationError::OptionSet(
+                        other.to_string(),
+                        String::from("not an option set name"),
+                    ))
+                }
+            };
+            log::debug(format_args!("using option set {}", name));
+            let err = |reason: String| ValidationError::OptionSet(name.clone(), reason);
+            let mut set = match self.sets.iter().find(|(n, _, _)| *n == name) {
+                Some((_, toml::Value::Table(set), _)) => set.clone(),
+                Some(_) => return Err(err(String::from("not a table"))),
+                None => return Err(err(String::from("no such option set"))),
+            };
+            for (c_var, default) in defaults {
+                let item = set
+                    .iter_mut()
+                    .filter(|(key, _)| INCLUDED.contains(&&key[..]))
+                    .filter_map(|(_, items)| items.as_array_mut())
+                    .flatten()
+                    .filter_map(|item| item.as_table_mut())
+                    .find(|item| item.get("c_var").and_then(|v| v.as_str()) == Some(&c_var));
+                match item {
+                    Some(item) => item.insert(String::from("default"), default),
+                    None => return Err(err(format!("no item with c_var \"{}\"", c_var))),
+                };
+            }
+            self.add(&mut set, &format!("option set {}", name))?;
+            if let Some(key) = set.keys().next() {
+                return Err(err(format!(
+                    "option sets hold positional and non_positional, not {}",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+    /// Reads the files a spec includes, and theirs in turn, into the merge.
+    /// stack holds the files being included, to catch an include cycle.
+    fn include(
+        &mut self,
+        table: &mut toml::value::Table,
+        dir: &Path,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), ValidationError> {
+        let includes = match table.remove("include") {
+            Some(toml::Value::Array(includes)) => includes,
+            Some(_) => {
+                return Err(ValidationError::SpecInclude(
+                    String::from("include"),
+                    String::from("must be an array of file names"),
+                ))
+            }
+            None => return Ok(()),
+        };
+        for include in includes {
+            let name = match include {
+                toml::Value::String(name) => name,
+                other => {
+                    return Err(ValidationError::SpecInclude(
+                        other.to_string(),
+                        String::from("not a file name"),
+                    ))
+                }
+            };
+            let path = dir.join(&name);
+            let err = |reason: String| ValidationError::SpecInclude(name.clone(), reason);
+            let canonical = path.canonicalize().map_err(|e| err(e.to_string()))?;
+            if stack.contains(&canonical) {
+                return Err(err(String::from("includes itself")));
+            }
+            if self.seen.contains(&canonical) {
+                log::debug(format_args!("'{}' is already included", path.display()));
+                continue;
+            }
+            log::info(format_args!("including '{}'", path.display()));
+            self.seen.push(canonical.clone());
+            let text = std::fs::read_to_string(&path).map_err(|e| err(e.to_string()))?;
+            let value = match path.extension().and_then(|e| e.to_str()) {
+                Some("json") => json::parse(&text)
+                    .map(|v| {
+                        json_to_toml(v).unwrap_or_else(|| toml::Value::Table(Default::default()))
+                    })
+                    .map_err(|e| err(e.to_string()))?,
+                _ => toml::from_str(&text).map_err(|e: toml::de::Error| err(e.to_string()))?,
+            };
+            let mut table = match value {
+                toml::Value::Table(table) => table,
+                _ => return Err(err(String::from("not a table"))),
+            };
+            stack.push(canonical);
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+            self.include(&mut table, dir, stack)?;
+            stack.pop();
+            self.add(&mut table, &name)?;
+            self.add_sets(&mut table, &name)?;
+            if let Some(key) = table.keys().next() {
+                return Err(err(format!(
+                    "only positional, non_positional and option_sets can be included, not {}",
+                    key
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merges the items of the files a spec includes, found relative to dir,
+/// and of the option sets it uses into it, with every file it read. Included
+/// items come first, then those of the option sets, then the spec's own.
+/// Without a dir, nothing is read, and a spec including files is refused.
+pub(super) fn resolve(
+    value: toml::Value,
+    dir: Option<&Path>,
+) -> Result<(toml::Value, Vec<PathBuf>), ValidationError> {
+    let mut table = match value {
+        toml::Value::Table(table)
+            if ["include", "option_sets", "use"]
+                .iter()
+                .any(|key| table.contains_key(*key)) =>
+        {
+            table
+        }
+        value => return Ok((value, vec![])),
+    };
+    let mut merged = Merged {
+        positional: vec![],
+        non_positional: vec![],
+        names: vec![],
+        seen: vec![],
+        sets: vec![],
+    };
+    match dir {
+        Some(dir) => merged.include(&mut table, dir, &mut vec![])?,
+        None => {
+            if let Some(include) = table.get("include") {
+                return Err(ValidationError::SpecInclude(
+                    include.to_string(),
+                    String::from(
+                        "files can't be included without a spec file to find them next to",
+                    ),
+                ));
+            }
+        }
+    }
+    merged.add_sets(&mut table, "the spec")?;
+    merged.use_sets(&mut table)?;
+    merged.add(&mut table, "the spec")?;
+    table.insert(
+        String::from("positional"),
+        toml::Value::Array(merged.positional),
+    );
+    table.insert(
+        String::from("non_positional"),
+        toml::Value::Array(merged.non_positional),
+    );
+    Ok((toml::Value::Table(table), merged.seen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    fn write(dir: &std::path::Path, name: &str, contents: &str) {
+        std::fs::write(dir.join(name), contents).unwrap();
+    }
+
+    #[test]
+    fn include() {
+        let dir = std::env::temp_dir().join("argen_include");
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        write(
+            &dir,
+            "common/logging.toml",
+            r#"
+            include = ["quiet.json"]
+
+            [[non_positional]]
+            c_var = "verbose"
+            c_type = "int"
+            long = "verbose"
+            flag = true
+            "#,
+        );
+        write(
+            &dir,
+            "common/quiet.json",
+            r#"{"non_positional": [{"c_var": "quiet", "c_type": "int", "long": "quiet", "flag": true}]}"#,
+        );
+        let spec = r#"
+            include = ["common/logging.toml"]
+            positional = []
+
+            [[non_positional]]
+            c_var = "out"
+            c_type = "char*"
+            long = "out"
+            "#;
+        let s = Spec::from_str_in(spec, &dir).unwrap();
+        let longs: Vec<_> = s.non_positional.iter().map(|npi| &npi.long[..]).collect();
+        assert_eq!(longs, ["quiet", "verbose", "out"]);
+
+        let dup = spec.replace("c_var = \"out\"", "c_var = \"verbose\"");
+        let e = Spec::from_str_in(&dup, &dir).err().unwrap();
+        assert_eq!(
+            e.0[0].message,
+            "c_var \"verbose\" is in both common/logging.toml and the spec"
+        );
+
+        write(
+            &dir,
+            "common/quiet.json",
+            r#"{"include": ["logging.toml"]}"#,
+        );
+        let e = Spec::from_str_in(spec, &dir).err().unwrap();
+        assert_eq!(e.0[0].message, "in include logging.toml: includes itself");
+
+        write(&dir, "common/quiet.json", r#"{"parser": "posix"}"#);
+        let e = Spec::from_str_in(spec, &dir).err().unwrap();
+        assert_eq!(
+            e.0[0].message,
+            "in include quiet.json: only positional, non_positional and option_sets can be included, not parser"
+        );
+    }
+
+    #[test]
+    fn option_sets() {
+        let dir = std::env::temp_dir().join("argen_option_sets");
+        std::fs::create_dir_all(&dir).unwrap();
+        write(
+            &dir,
+            "sets.toml",
+            r#"
+            [[option_sets.logging.non_positional]]
+            c_var = "log_level"
+            c_type = "char*"
+            long = "log-level"
+            default = "\"warn\""
+            "#,
+        );
+        let spec = r#"
+            include = ["sets.toml"]
+            use = [{ set = "logging", defaults = { log_level = "\"debug\"" } }, "output"]
+            positional = []
+
+            [[option_sets.output.non_positional]]
+            c_var = "out"
+            c_type = "char*"
+            long = "out"
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            "#;
+        let s = Spec::from_str_in(spec, &dir).unwrap();
+        let longs: Vec<_> = s.non_positional.iter().map(|npi| &npi.long[..]).collect();
+        assert_eq!(longs, ["log-level", "out", "size"]);
+        assert_eq!(s.non_positional[0].default.as_deref(), Some("\"debug\""));
+
+        let e = Spec::from_str_in(&spec.replace("\"output\"]", "\"outptu\"]"), &dir)
+            .err()
+            .unwrap();
+        assert_eq!(e.0[0].message, "in option set outptu: no such option set");
+
+        let e = Spec::from_str_in(&spec.replace("c_var = \"size\"", "c_var = \"out\""), &dir)
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.0[0].message,
+            "c_var \"out\" is in both option set output and the spec"
+        );
+    }
+}
@@ -0,0 +1,360 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! What is decided about a spec before any code is written for it: the val
+//! getopt returns for each option, the optstring, the C initializer of each
+//! default and the text of the help. The analysis makes these decisions once,
+//! and the emitters only lay them out, so every backend shows the same help
+//! and the C parser's pieces agree on the vals.
+
+use super::{
+    c_quote, help_line, help_with_default, CType, HelpEntry, NonPositionalItem, PositionalItem,
+    Spec, ValidationError, HELP_PREFIX,
+};
+use std::collections::BTreeSet;
+
+/// An option, with what the parser does with it decided.
+pub(super) struct Opt<'s> {
+    pub(super) item: &'s NonPositionalItem,
+    /// The val getopt returns for the option: its short name, or a byte no
+    /// short name takes.
+    pub(super) val: u8,
+    /// The C initializer of the option's __default, if it has a default.
+    pub(super) default: Option<String>,
+}
+
+/// A positional argument, with what the parser does with it decided.
+pub(super) struct Arg<'s> {
+    pub(super) item: &'s PositionalItem,
+    /// The C initializer of the argument's __default, if it has a default.
+    pub(super) default: Option<String>,
+}
+
+/// A spec analyzed for the emitters.
+pub(super) struct Ir<'s> {
+    /// The options, as the spec has them.
+    pub(super) options: Vec<Opt<'s>>,
+    /// The positional arguments, in order.
+    pub(super) arguments: Vec<Arg<'s>>,
+    /// The val of --config, or 0 without it.
+    pub(super) config_val: u8,
+    /// The val of --dump-args, or 0 without it.
+    pub(super) dump_val: u8,
+    /// The short options, as getopt takes them.
+    pub(super) optstring: String,
+    /// The subcommand named after the program in the usage line, starting
+    /// with a space if there is one.
+    pub(super) command: String,
+    /// The positional part of the usage line, starting with a space if
+    /// anything.
+    pub(super) synopsis: String,
+    /// The entries of the usage's Arguments section.
+    pub(super) help_arguments: Vec<HelpEntry>,
+    /// The entries of the usage's Options section, in the order the help
+    /// shows them.
+    pub(super) help_options: Vec<HelpEntry>,
+    /// The width of the label column both sections share.
+    pub(super) help_width: usize,
+}
+
+/// The C initializer of a default: a string literal for a char*, or the
+/// int as the spec writes it.
+fn c_default(default: &Option<String>, c_type: CType) -> Option<String> {
+    let default = default.as_ref()?;
+    Some(match c_type {
+        CType::Chars => format!("\"{}\"", c_quote(default)),
+        CType::Int => default.to_owned(),
+    })
+}
+
+impl Spec {
+    /// The bytes getopt can return for an option without a short name: any
+    /// but those of short names, the parser's own options and its errors.
+    fn free_vals(&self) -> BTreeSet<u8> {
+        let mut all_bytes: BTreeSet<u8> = (2..255).collect();
+        for npi in &self.non_positional {
+            if let Some(s) = &npi.short {
+                all_bytes.remove(&s.as_bytes()[0]);
+            }
+        }
+        for short in self.builtin_names().0 {
+            all_bytes.remove(&short.as_bytes()[0]);
+        }
+        all_bytes.remove(&b'?');
+        all_bytes.remove(&b':');
+        all_bytes
+    }
+    /// How many options need a val of free_vals: those without a short
+    /// name, and --config and --dump-args if the spec adds them.
+    fn free_vals_needed(&self) -> usize {
+        self.non_positional
+            .iter()
+            .filter(|npi| npi.short.is_none())
+            .count()
+            + self.has_config_option() as usize
+            + self.has_dump_args() as usize
+    }
+    /// An error if there are more options without a short name than bytes
+    /// for getopt to return for them.
+    pub(super) fn vals_problem(&self) -> Option<ValidationError> {
+        let (needed, free) = (self.free_vals_needed(), self.free_vals().len());
+        if needed > free {
+            Some(ValidationError::TooManyOptions(needed, free))
+        } else {
+            None
+        }
+    }
+    /// The val of each option, its short name if it has one, followed by
+    /// those of --config and --dump-args, if the spec adds them.
+    fn option_vals(&self) -> Vec<u8> {
+        // ordered, so the same spec always gets the same bytes
+        let mut next_free_shortname = self.free_vals().into_iter().rev();
+        let mut next_free = || {
+            next_free_shortname
+                .next()
+                .expect("no more options without a short name than vals_problem allows")
+        };
+        let mut vals: Vec<u8> = self
+            .non_positional
+            .iter()
+            .map(|npi| match &npi.short {
+                Some(s) => s.as_bytes()[0],
+                None => next_free(),
+            })
+            .collect();
+        vals.push(if self.has_config_option() {
+            next_free()
+        } else {
+            0
+        });
+        vals.push(if self.has_dump_args() { next_free() } else { 0 });
+        vals
+    }
+    /// The short options of the optstring, each followed by ':' if it takes
+    /// a value, then -h.
+    fn optstring(&self) -> String {
+        let mut optstring = String::new();
+        if self.is_strict_posix() {
+            // a leading '+' makes getopt_long stop at the first non-option
+            optstring.push('+');
+        }
+        for npi in &self.non_positional {
+            if let Some(short) = &npi.short {
+                optstring.push_str(short);
+                if !npi.is_flag() {
+                    optstring.push(':');
+                }
+            }
+        }
+        optstring.push('h');
+        optstring
+    }
+    /// The Arguments and Options sections of the usage, and the width of the
+    /// label column.
+    fn help_entries(&self) -> (Vec<HelpEntry>, Vec<HelpEntry>, usize) {
+        let arguments: Vec<HelpEntry> = self
+            .positional
+            .iter()
+            .map(|pi| (pi.help_label(), pi.help_text()))
+            .collect();
+        let long = self.has_long_options();
+        let mut options = vec![(
+            String::from(if long { "-h, --help" } else { "-h" }),
+            String::from("print this usage and exit"),
+        )];
+        options.extend(
+            self.help_options()
+                .iter()
+                .map(|npi| (npi.help_label(long), npi.help_text(long))),
+        );
+        if self.has_config_option() {
+            options.push((
+                String::from("    --config <file>"),
+                help_with_default(
+                    String::from("read option defaults from file"),
+                    &self.config_file,
+                    None,
+                ),
+            ));
+        }
+        // both sections share one description column
+        let width = arguments
+            .iter()
+            .chain(options.iter())
+            .map(|(label, _)| label.len() + 2)
+            .max()
+            .unwrap_or(0);
+        (arguments, options, width)
+    }
+    /// Makes every decision the emitters rely on.
+    pub(super) fn analyze(&self) -> Ir<'_> {
+        let vals = self.option_vals();
+        let (help_arguments, help_options, help_width) = self.help_entries();
+        Ir {
+            options: self
+                .non_positional
+                .iter()
+                .zip(&vals)
+                .map(|(item, &val)| Opt {
+                    item,
+                    val,
+                    default: c_default(&item.default, item.c_type),
+                })
+                .collect(),
+            arguments: self
+                .positional
+                .iter()
+                .map(|item| Arg {
+                    item,
+                    default: c_default(&item.default, item.c_type),
+                })
+                .collect(),
+            config_val: vals[vals.len() - 2],
+            dump_val: vals[vals.len() - 1],
+            optstring: self.optstring(),
+            command: self.usage_command(),
+            synopsis: self.usage_synopsis(),
+            help_arguments,
+            help_options,
+            help_width,
+        }
+    }
+}
+
+impl Ir<'_> {
+    /// The Arguments and Options sections of the usage, as lines of string
+    /// literals to be concatenated.
+    pub(super) fn usage_help(&self) -> String {
+        let mut help = String::new();
+        if !self.help_arguments.is_empty() {
+            help.push_str(&format!("{}\\nArguments:\\n\"\n", HELP_PREFIX));
+            for (label, descr) in &self.help_arguments {
+                help.push_str(&help_line(label, descr, self.help_width));
+            }
+        }
+        help.push_str(&format!("{}\\nOptions:\\n\"\n", HELP_PREFIX));
+        for (label, descr) in &self.help_options {
+            help.push_str(&help_line(label, descr, self.help_width));
+        }
+        help
+    }
+    /// The usage exactly as the generated usage function prints it, for a
+    /// program of the given name.
+    pub(super) fn usage_text(&self, progname: &str) -> String {
+        let width = self.help_width;
+        let mut text = format!(
+            "usage: {}{} [options]{}\n",
+            progname, self.command, self.synopsis
+        );
+        let plain_line = |label: &str, descr: &str| {
+            let indent = format!("\n{}", " ".repeat(width + 2));
+            let descr = descr.lines().collect::<Vec<_>>().join(&indent);
+            format!("  {:<width$}{}", label, descr, width = width)
+                .trim_end()
+                .to_owned()
+                + "\n"
+        };
+        if !self.help_arguments.is_empty() {
+            text.push_str("\nArguments:\n");
+            for (label, descr) in &self.help_arguments {
+                text.push_str(&plain_line(label, descr));
+            }
+        }
+        text.push_str("\nOptions:\n");
+        for (label, descr) in &self.help_options {
+            text.push_str(&plain_line(label, descr));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    #[test]
+    fn analyze() {
+        let s = Spec::from_str(
+            r#"
+            strict_posix = true
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            short = "s"
+            default = "12"
+
+            [[non_positional]]
+            c_var = "name"
+            c_type = "char*"
+            long = "name"
+            default = "anon"
+
+            [[non_positional]]
+            c_var = "verbose"
+            c_type = "int"
+            long = "verbose"
+            short = "v"
+            flag = true
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "INPUT"
+            "#,
+        )
+        .unwrap();
+        let ir = s.analyze();
+        let vals: Vec<u8> = ir.options.iter().map(|opt| opt.val).collect();
+        // shorts are their own vals, and the rest take the highest free bytes
+        assert_eq!(vals, [b's', 254, b'v']);
+        assert_eq!((ir.config_val, ir.dump_val), (0, 0));
+        assert_eq!(ir.optstring, "+s:vh");
+        assert_eq!(ir.options[0].default.as_deref(), Some("12"));
+        assert_eq!(ir.options[1].default.as_deref(), Some("\"anon\""));
+        assert_eq!(ir.arguments[0].default, None);
+        assert_eq!(ir.synopsis, " [INPUT]");
+        assert_eq!(ir.help_options[2].1, "(default: anon)");
+        // the C usage prints what the text says
+        assert!(ir
+            .usage_text("prog")
+            .starts_with("usage: prog [options] [INPUT]\n\nArguments:\n  INPUT\n"));
+        assert!(s.gen().contains(&ir.usage_help()));
+        // no val is one getopt returns for -h or an error, and running out
+        // of them is an error in the spec
+        let options = |n: usize| {
+            (0..n)
+                .map(|i| {
+                    format!(
+                        "[[non_positional]]\nc_var = \"o{0}\"\nc_type = \"int\"\nlong = \"o{0}\"\nflag = true\n",
+                        i
+                    )
+                })
+                .collect::<String>()
+        };
+        let s = Spec::from_str(&options(250)).unwrap();
+        let vals: Vec<u8> = s.analyze().options.iter().map(|opt| opt.val).collect();
+        assert!(!vals.iter().any(|val| b"h?:".contains(val)));
+        let errors = Spec::from_str(&format!("dump_args = true\n{}", options(250)))
+            .err()
+            .unwrap();
+        assert_eq!(errors.0[0].code, "E050");
+        assert!(errors.0[0]
+            .message
+            .starts_with("251 options have no short name"));
+    }
+}
@@ -0,0 +1,253 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Lints: things a valid spec probably didn't mean. They are warnings,
+//! printed when generating and by `argen check`, which only fail generation
+//! with --strict.
+
+use super::{int_literal, schema, CType, Spec};
+use std::fmt;
+
+/// Something a spec that generates probably didn't mean.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Warning {
+    /// An item has no help text.
+    NoHelp(String),
+    /// An int's default is not a number.
+    IntDefaultNotNumber(String, String),
+    /// A field, of an item or of the spec, that argen doesn't know.
+    UnusedField(Option<String>, String),
+    /// A flag has a help_name.
+    FlagHelpName(String),
+    /// show_default is set without a default.
+    ShowDefaultWithoutDefault(String),
+    /// A setting given with -D is one only parser = "argp" uses, with
+    /// another parser.
+    IgnoredDefine(String),
+}
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::NoHelp(param) => write!(f, "{} has no help text", param),
+            Warning::IntDefaultNotNumber(param, default) => write!(
+                f,
+                "{} is an int, but its default is not a number: {}",
+                param, default
+            ),
+            Warning::UnusedField(Some(param), field) => {
+                write!(f, "in param {}: unknown field {} is ignored", param, field)
+            }
+            Warning::UnusedField(None, field) => write!(f, "unknown field {} is ignored", field),
+            Warning::FlagHelpName(param) => write!(
+                f,
+                "in param {}: help_name is unused, as a flag takes no argument",
+                param
+            ),
+            Warning::ShowDefaultWithoutDefault(param) => write!(
+                f,
+                "in param {}: show_default is set, but there is no default to show",
+                param
+            ),
+            Warning::IgnoredDefine(define) => write!(
+                f,
+                "-D {} is ignored, as only parser = \"argp\" uses it",
+                define
+            ),
+        }
+    }
+}
+
+/// The warnings' codes, in order, with what each warns about. A code stays
+/// the same from one version of argen to the next, for --deny to name.
+pub const CODES: [(&str, &str); 6] = [
+    ("W001", "an item without help text"),
+    ("W002", "an int whose default is not a number"),
+    ("W003", "a field argen doesn't know, and so ignores"),
+    ("W004", "a help_name on a flag, which takes no argument"),
+    ("W005", "show_default without a default to show"),
+    ("W006", "a -D setting the spec's parser ignores"),
+];
+
+impl Warning {
+    /// The warning's code, as in CODES.
+    pub fn code(&self) -> &'static str {
+        let n = match self {
+            Warning::NoHelp(_) => 0,
+            Warning::IntDefaultNotNumber(_, _) => 1,
+            Warning::UnusedField(_, _) => 2,
+            Warning::FlagHelpName(_) => 3,
+            Warning::ShowDefaultWithoutDefault(_) => 4,
+            Warning::IgnoredDefine(_) => 5,
+        };
+        CODES[n].0
+    }
+}
+
+/// The fields of the tables in a spec which aren't in the spec format, and
+/// so are ignored, such as a misspelt `requird`.
+pub(super) fn unused_fields(value: &toml::Value) -> Vec<Warning> {
+    let mut unused = vec![];
+    if let Some(table) = value.as_table() {
+        unused_in(table, "Spec", None, &mut unused);
+    }
+    unused
+}
+
+fn unused_in(
+    table: &toml::value::Table,
+    format: &str,
+    param: Option<&str>,
+    unused: &mut Vec<Warning>,
+) {
+    let known = schema::fields(format);
+    for (key, value) in table {
+        if !known.contains(&key.as_str()) {
+            unused.push(Warning::UnusedField(
+                param.map(str::to_owned),
+                key.to_owned(),
+            ));
+            continue;
+        }
+        let inner = match key.as_str() {
+            "positional" => "PositionalItem",
+            "non_positional" => "NonPositionalItem",
+            "subcommand" => "Subcommand",
+            "global" => "Global",
+            _ => continue,
+        };
+        let tables: Vec<_> = match value {
+            toml::Value::Array(items) => items.iter().filter_map(|v| v.as_table()).collect(),
+            toml::Value::Table(table) => vec![table],
+            _ => continue,
+        };
+        for table in tables {
+            let name = table.get("c_var").or_else(|| table.get("name"));
+            let name = name.and_then(|v| v.as_str()).or(param);
+            unused_in(table, inner, name, unused);
+        }
+    }
+}
+
+impl Spec {
+    /// What looks wrong in the spec, though it generates.
+    pub fn lints(&self) -> Vec<Warning> {
+        let mut lints = self.read_warnings.clone();
+        let merged;
+        let s = if self.has_subcommands() {
+            merged = self.merged_spec();
+            &merged
+        } else {
+            self
+        };
+        let items = s
+            .positional
+            .iter()
+            .map(|pi| {
+                (
+                    pi.help_name.to_owned(),
+                    pi.c_type,
+                    &pi.help_descr,
+                    &pi.default,
+                )
+            })
+            .chain(s.non_positional.iter().map(|npi| {
+                (
+                    format!("--{}", npi.long),
+                    npi.c_type,
+                    &npi.help_descr,
+                    &npi.default,
+                )
+            }));
+        for (name, c_type, help_descr, default) in items {
+            if help_descr.as_deref().is_none_or(str::is_empty) {
+                lints.push(Warning::NoHelp(name.to_owned()));
+            }
+            if let (CType::Int, Some(default)) = (c_type, default) {
+                if int_literal(default).is_none() {
+                    lints.push(Warning::IntDefaultNotNumber(name, default.to_owned()));
+                }
+            }
+        }
+        let shows_default = s
+            .positional
+            .iter()
+            .map(|pi| (&pi.c_var, pi.show_default, &pi.default))
+            .chain(
+                s.non_positional
+                    .iter()
+                    .map(|npi| (&npi.c_var, npi.show_default, &npi.default)),
+            );
+        for (c_var, show_default, default) in shows_default {
+            if show_default == Some(true) && default.is_none() {
+                lints.push(Warning::ShowDefaultWithoutDefault(c_var.to_owned()));
+            }
+        }
+        for npi in &s.non_positional {
+            if npi.is_flag() && npi.help_name.is_some() {
+                lints.push(Warning::FlagHelpName(npi.c_var.to_owned()));
+            }
+        }
+        lints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    #[test]
+    fn lints() {
+        let s = Spec::from_str(
+            r#"
+            [[positional]]
+            c_var = "count"
+            c_type = "int"
+            help_name = "COUNT"
+            help_descr = "how many"
+            default = "0x10"
+            requird = true
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            default = "\"12\""
+
+            [[non_positional]]
+            c_var = "verbose"
+            c_type = "int"
+            long = "verbose"
+            help_descr = "say more"
+            help_name = "N"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let lints: Vec<String> = s.lints().iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            lints,
+            [
+                "in param count: unknown field requird is ignored",
+                "--size has no help text",
+                "--size is an int, but its default is not a number: \"12\"",
+                "in param verbose: help_name is unused, as a flag takes no argument",
+            ]
+        );
+        let codes: Vec<&str> = s.lints().iter().map(|w| w.code()).collect();
+        assert_eq!(codes, ["W003", "W001", "W002", "W004"]);
+    }
+}
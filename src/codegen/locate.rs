@@ -0,0 +1,491 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Where in a spec's source an error is. Specs are deserialized after
+//! includes and variables are resolved, which loses their positions, so the
+//! source is searched instead: for the item an error names, by its `long`
+//! or `help_name`, and in it for the field or value at fault.
+
+use super::ValidationError;
+use std::ops::Range;
+
+/// A part of a spec's source an error is about: a line, counting from 1, the
+/// bytes of it at fault, and what to say of them, if anything more than the
+/// error does.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Label {
+    /// The line, counting from 1.
+    pub line: usize,
+    /// The bytes of the line at fault.
+    pub columns: Range<usize>,
+    /// What to say of them.
+    pub note: Option<String>,
+}
+
+/// What to look for on the line at fault.
+enum Anchor {
+    /// A field, by name, or a table of that name.
+    Key(String),
+    /// A string, quoted.
+    Value(String),
+    /// A string, quoted, where it is last given, as a name given twice is.
+    Repeated(String),
+    /// Any text.
+    Text(String),
+}
+
+impl Anchor {
+    fn key(key: &str) -> Option<Anchor> {
+        Some(Anchor::Key(key.to_owned()))
+    }
+    fn value(value: &str) -> Option<Anchor> {
+        Some(Anchor::Value(value.to_owned()))
+    }
+    /// The field a feature such as `parser = "argp"` is set in.
+    fn feature(feature: &str) -> Option<Anchor> {
+        Anchor::key(feature.split([' ', '=']).next().unwrap_or(feature))
+    }
+    /// Where in the line the anchor is: the key's name, the quoted value or
+    /// the text, but not in a comment.
+    fn find(&self, line: &str) -> Option<Range<usize>> {
+        let line = code(line);
+        let text = match self {
+            Anchor::Key(key) => {
+                let start = key_set(line, key).or_else(|| table_named(line, key))?;
+                return Some(start..start + key.len());
+            }
+            Anchor::Value(value) | Anchor::Repeated(value) => format!("\"{}\"", value),
+            Anchor::Text(text) => text.to_owned(),
+        };
+        let start = line.find(&text)?;
+        Some(start..start + text.len())
+    }
+}
+
+/// The line up to a comment, if it has one: a `#` outside a string. A line
+/// that is only a comment has nothing left.
+pub(super) fn code(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some('"') if escaped => escaped = false,
+            Some('"') if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' => return &line[..i],
+            None => {}
+        }
+    }
+    line
+}
+
+/// The text after a field's name, the closing quote of a JSON key and the
+/// `=` or `:` setting it, if that is what follows the name.
+fn setting(after: &str) -> Option<&str> {
+    let after = after.strip_prefix('"').unwrap_or(after).trim_start();
+    Some(after.strip_prefix(['=', ':'])?.trim_start())
+}
+
+/// Where on the line the key is set, as `key =` or `"key":`, at the start
+/// of the line or after a space, a `{` or a `,`.
+fn key_set(line: &str, key: &str) -> Option<usize> {
+    line.match_indices(key).map(|(at, _)| at).find(|&at| {
+        let before = &line[..at];
+        let before = before.strip_suffix('"').unwrap_or(before);
+        let bounded = before.is_empty()
+            || before.ends_with(|c: char| c.is_whitespace() || c == '{' || c == ',');
+        bounded && setting(&line[at + key.len()..]).is_some()
+    })
+}
+
+/// Where on the line the key is named as the last part of a table header,
+/// as `[key]` or `[[subcommand.key]]`.
+fn table_named(line: &str, key: &str) -> Option<usize> {
+    let header = line.trim_start().strip_prefix('[')?;
+    let header = header.trim_start_matches('[').trim_start();
+    let path_len = header
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+        .unwrap_or(header.len());
+    let (path, rest) = header.split_at(path_len);
+    if !rest.trim_start().starts_with(']') {
+        return None;
+    }
+    let prefix = path.strip_suffix(key)?;
+    if !(prefix.is_empty() || prefix.len() > 1 && prefix.ends_with('.')) {
+        return None;
+    }
+    Some(line.len() - header.len() + prefix.len())
+}
+
+/// A line naming an item, by its long or help_name, or a subcommand by name,
+/// outside of a comment.
+fn names_item(line: &str, name: &str) -> bool {
+    let line = code(line);
+    let quoted = format!("\"{}\"", name);
+    ["long", "help_name", "name"].iter().any(|field| {
+        line.match_indices(field).any(|(at, _)| {
+            setting(&line[at + field.len()..]).is_some_and(|value| value.starts_with(&quoted))
+        })
+    })
+}
+
+/// A line starting a table, or in JSON, an object.
+fn starts_table(line: &str) -> bool {
+    let line = code(line);
+    line.trim_start().starts_with('[') || line.contains('{')
+}
+
+impl ValidationError {
+    /// The item the error is in, by its long or help_name, and what to look
+    /// for in it, or in the whole spec without an item.
+    fn anchor(&self) -> (Option<String>, Option<Anchor>) {
+        use ValidationError::*;
+        let item = |param: &str| Some(param.to_owned());
+        // a subcommand's path is named by its last word
+        let sub = |path: &str| item(path.rsplit(' ').next().unwrap_or(path));
+        match self {
+            TomlError(_) | JsonError(_) | BadDefine(_, _) => (None, None),
+            UnknownSchema(_) => (None, Anchor::key("schema")),
+            TooManyOptions(_, _) => (None, None),
+            BadIdent(param, ident) | ReservedWord(param, ident) | GeneratedName(param, ident) => (
+                item(param.trim_end_matches(" handler")),
+                Anchor::value(ident),
+            ),
+            RequiredHasDefault(param) | FlagHasDefault(param) => {
+                (item(param), Anchor::key("default"))
+            }
+            MultiNotChars(param) | FlagMustBeInt(param) => (item(param), Anchor::key("c_type")),
+            InvalidLong(long) => (None, Anchor::value(long)),
+            InvalidShort(param, value) | InvalidAlias(param, value) | InvalidEnv(param, value) => {
+                (item(param), Anchor::value(value))
+            }
+            FlagCannotBeRequired(param) | RequiredPositionalGoesBeforeOptionPositional(param) => {
+                (item(param), Anchor::key("required"))
+            }
+            MultiMustBeLast(param) => (item(param), Anchor::key("multi")),
+            PosixNeedsShort(param) => (item(param), None),
+            PortableNeedsScanner => (None, Anchor::key("portable")),
+            PosixCaseInsensitive => (None, Anchor::key("case_insensitive")),
+            PosixConfigOption => (None, Anchor::key("config")),
+            PosixDumpArgs => (None, Anchor::key("dump_args")),
+            BadIndent(_) => (None, Anchor::key("indent")),
+            UnknownStyle(style) => (None, Anchor::value(style)),
+            ThreadSafeNeedsScanner | ThreadSafeAccessors | ThreadSafeKeeps(_) => {
+                (None, Anchor::key("thread_safe"))
+            }
+            BackendUnsupported(_, feature)
+            | ArgpUnsupported(feature)
+            | SubcommandsUnsupported(feature) => (None, Anchor::feature(feature)),
+            BadInclude(include) | SpecInclude(include, _) => (None, Anchor::value(include)),
+            IncludeConflict(_, name, _, _) => (None, Anchor::value(name)),
+            OptionSet(set, _) => (None, Anchor::key(set)),
+            UnknownVar(name) => (None, Some(Anchor::Text(format!("${{{}}}", name)))),
+            BadVar(name) => (None, Anchor::key(name)),
+            BadSubcommand(path) | DuplicateSubcommand(path) | SubcommandHasItems(path) => {
+                (sub(path), None)
+            }
+            HandlerNeedsStruct(path) => (sub(path), Anchor::key("handler")),
+            GlobalConflict(path, name) => (sub(path), Anchor::value(name)),
+            GlobalNeedsSubcommands => (None, Anchor::key("global")),
+            SuffixCollision(var, _) => (None, Anchor::value(var)),
+            DuplicateCVar(name) | DuplicateLong(name) => {
+                (None, Some(Anchor::Repeated(name.to_owned())))
+            }
+            DuplicateShort(param, name, _) | AliasConflict(param, name, _) => {
+                (item(param), Anchor::value(name))
+            }
+            BuiltinConflict(param, name) => {
+                (item(param), Anchor::value(name.trim_start_matches('-')))
+            }
+        }
+    }
+
+    /// The other item the error is about, if any, by its long, what to look
+    /// for in it, and what to say of it.
+    fn related(&self) -> Option<(Option<String>, Anchor, String)> {
+        use ValidationError::*;
+        match self {
+            DuplicateShort(_, short, other) => Some((
+                Some(other.to_owned()),
+                Anchor::Value(short.to_owned()),
+                format!("the short name of {}", other),
+            )),
+            AliasConflict(_, alias, other) => Some((
+                Some(other.to_owned()),
+                Anchor::Value(alias.to_owned()),
+                format!("a long name of {}", other),
+            )),
+            DuplicateCVar(name) | DuplicateLong(name) => Some((
+                None,
+                Anchor::Value(name.to_owned()),
+                String::from("first given here"),
+            )),
+            _ => None,
+        }
+    }
+
+    /// The parts of the spec's source that the error is about, where they
+    /// can be found, the one at fault first. Items brought in by an include
+    /// aren't in the source, and aren't found.
+    pub fn labels(&self, source: &str) -> Vec<Label> {
+        let lines: Vec<&str> = source.lines().collect();
+        match self {
+            ValidationError::TomlError(e) => {
+                return e
+                    .line_col()
+                    .map(|(line, column)| Label {
+                        line: line + 1,
+                        columns: column..column,
+                        note: None,
+                    })
+                    .into_iter()
+                    .collect()
+            }
+            ValidationError::JsonError(e) => {
+                // the column is counted in characters
+                let text = lines.get(e.line() - 1).copied().unwrap_or_default();
+                let column = text
+                    .char_indices()
+                    .nth(e.column() - 1)
+                    .map_or(text.len(), |(i, _)| i);
+                return vec![Label {
+                    line: e.line(),
+                    columns: column..column,
+                    note: None,
+                }];
+            }
+            _ => {}
+        }
+        let (item, anchor) = self.anchor();
+        let (at, columns) = match search(&lines, item.as_deref(), anchor.as_ref()) {
+            Some(found) => found,
+            None => return vec![],
+        };
+        let mut labels = vec![label(&lines, at, columns, None)];
+        if let Some((item, anchor, note)) = self.related() {
+            let found =
+                search(&lines, item.as_deref(), Some(&anchor)).filter(|&(other, _)| other != at);
+            if let Some((other, columns)) = found {
+                if item.is_none() {
+                    labels[0].note = Some(String::from("given again here"));
+                }
+                labels.push(label(&lines, other, columns, Some(note)));
+            }
+        }
+        labels
+    }
+}
+
+/// Where the anchor is, by its index in the lines and its columns: in the
+/// item's table, if it is named, or else the whole source. An item whose
+/// table doesn't have the anchor is found at the line naming it.
+fn search(
+    lines: &[&str],
+    item: Option<&str>,
+    anchor: Option<&Anchor>,
+) -> Option<(usize, Option<Range<usize>>)> {
+    let find = |range: Range<usize>| {
+        let anchor = anchor?;
+        let mut found = range.filter_map(|i| Some((i, Some(anchor.find(lines[i])?))));
+        match anchor {
+            Anchor::Repeated(_) => found.next_back(),
+            _ => found.next(),
+        }
+    };
+    let at = item.and_then(|item| lines.iter().position(|line| names_item(line, item)));
+    match at {
+        Some(at) => {
+            let start = (0..=at)
+                .rev()
+                .find(|&i| starts_table(lines[i]))
+                .unwrap_or(0);
+            let end = (at + 1..lines.len())
+                .find(|&i| starts_table(lines[i]))
+                .unwrap_or(lines.len());
+            find(start..end).or(Some((at, None)))
+        }
+        None => find(0..lines.len()),
+    }
+}
+
+/// A label for a line found, underlining its columns, or without them, all
+/// of the line but its indentation.
+fn label(lines: &[&str], at: usize, columns: Option<Range<usize>>, note: Option<String>) -> Label {
+    let columns = columns.unwrap_or_else(|| {
+        let text = lines[at].trim_end();
+        text.len() - text.trim_start().len()..text.len()
+    });
+    Label {
+        line: at + 1,
+        columns,
+        note,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+    use super::super::ValidationError;
+    use super::Label;
+
+    fn line_of(e: &ValidationError, source: &str) -> Option<usize> {
+        e.labels(source).first().map(|label| label.line)
+    }
+
+    #[test]
+    fn line() {
+        let toml = r#"
+parser = "getopt"
+
+[[positional]]
+c_var = "input"
+c_type = "char*"
+help_name = "IN"
+
+[[non_positional]]
+c_var = "2out"
+c_type = "char*"
+long = "output"
+
+[[non_positional]]
+c_var = "verbose"
+long = "verbose"
+c_type = "char*"
+flag = true
+"#;
+        let s = Spec::parse_str_in(toml, std::path::Path::new("."))
+            .ok()
+            .unwrap();
+        let lines: Vec<_> = s
+            .problems()
+            .iter()
+            .map(|e| (line_of(e, toml), e.to_string()))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (
+                    Some(10),
+                    String::from("in param output: invalid c variable \"2out\"")
+                ),
+                (
+                    Some(17),
+                    String::from("in param verbose: options that are flags must be of c_type int")
+                ),
+            ]
+        );
+        let e = Spec::from_str("parser = \n").err().unwrap();
+        assert_eq!(e.0[0].labels[0].line, 1);
+        let json = "{\n  \"non_positional\": [\n    {\"c_var\": \"x\", \"long\": \"a b\", \"c_type\": \"int\"}\n  ]\n}";
+        let e = Spec::from_json(json).err().unwrap();
+        assert_eq!(e.0[0].labels[0].line, 3);
+    }
+
+    #[test]
+    fn labels() {
+        let toml = r#"
+[[non_positional]]
+c_var = "verbose"
+c_type = "int"
+long = "verbose"
+short = "v"
+flag = true
+
+[[non_positional]]
+c_var = "version"
+c_type = "int"
+long = "version"
+short = "v"
+flag = true
+"#;
+        let s = Spec::parse_str_in(toml, std::path::Path::new("."))
+            .ok()
+            .unwrap();
+        let e = s.problems().remove(0);
+        assert_eq!(
+            e.labels(toml),
+            [
+                Label {
+                    line: 13,
+                    columns: 8..11,
+                    note: None,
+                },
+                Label {
+                    line: 6,
+                    columns: 8..11,
+                    note: Some(String::from("the short name of verbose")),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let toml = r#"
+[[non_positional]]
+c_var = "level"
+c_type = "int"
+long = "level"
+help_descr = "how loud # of dB"
+
+[[non_positional]]
+c_var = "level" # to be renamed
+c_type = "int"
+long = "volume"
+# TODO: c_var = "level" again {once} the old name is gone
+"#;
+        let s = Spec::parse_str_in(toml, std::path::Path::new("."))
+            .ok()
+            .unwrap();
+        let e = s.problems().remove(0);
+        assert_eq!(
+            e.labels(toml),
+            [
+                Label {
+                    line: 9,
+                    columns: 8..15,
+                    note: Some(String::from("given again here")),
+                },
+                Label {
+                    line: 3,
+                    columns: 8..15,
+                    note: Some(String::from("first given here")),
+                },
+            ]
+        );
+        // nor is an item where a comment names it
+        let toml = r#"
+[[non_positional]]
+c_var = "verbose"
+c_type = "int"
+long = "verbose" # was long = "output"
+flag = true
+
+[[non_positional]]
+c_var = "output"
+c_type = "char*"
+long = "output"
+short = "out"
+"#;
+        let s = Spec::parse_str_in(toml, std::path::Path::new("."))
+            .ok()
+            .unwrap();
+        let e = s.problems().remove(0);
+        assert_eq!(line_of(&e, toml), Some(12));
+    }
+}
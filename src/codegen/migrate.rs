@@ -0,0 +1,173 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `argen migrate`: specs in the JSON of the old args.rs, which wrote
+//! booleans as strings and int defaults as numbers, brought to the current
+//! format.
+
+use super::{json_to_toml, schema, Spec, ValidationError};
+use crate::json::{self, Json};
+use std::path::Path;
+
+/// The boolean a legacy string stood for.
+fn legacy_bool(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "true" | "yes" | "1" => Some(true),
+        "false" | "no" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
+/// An item in the current format, noting what couldn't be kept.
+fn migrate_item(item: Json, kind: &str, known: &[&str], notes: &mut Vec<String>) -> Json {
+    let pairs = match item {
+        Json::Obj(pairs) => pairs,
+        _ => {
+            notes.push(format!("dropped a {} item that is not an object", kind));
+            return Json::Null;
+        }
+    };
+    let name = pairs
+        .iter()
+        .find(|(k, _)| k == "c_var")
+        .and_then(|(_, v)| match v {
+            Json::Str(s) => Some(s.to_owned()),
+            _ => None,
+        })
+        .unwrap_or_else(|| String::from("?"));
+    let mut out = vec![];
+    for (key, value) in pairs {
+        let value = match (key.as_str(), value) {
+            (_, Json::Null) => continue,
+            ("required" | "multi" | "flag" | "show_default", Json::Str(s)) => {
+                match legacy_bool(&s) {
+                    Some(b) => Json::Bool(b),
+                    None => {
+                        notes.push(format!(
+                            "{} {}: dropped {} = \"{}\", which is not a boolean",
+                            kind, name, key, s
+                        ));
+                        continue;
+                    }
+                }
+            }
+            ("default", Json::Num(n)) => Json::Str(n.to_string()),
+            ("default", Json::Bool(b)) => Json::Str(String::from(if b { "1" } else { "0" })),
+            (key, value) if known.contains(&key) => value,
+            (key, _) => {
+                notes.push(format!(
+                    "{} {}: dropped {}, which argen no longer has",
+                    kind, name, key
+                ));
+                continue;
+            }
+        };
+        out.push((key, value));
+    }
+    Json::Obj(out)
+}
+
+impl Spec {
+    /// Reads a spec in the legacy args.rs JSON format, with notes on what
+    /// couldn't be translated.
+    pub fn migrate_legacy(legacy: &str) -> Result<(Spec, Vec<String>), ValidationError> {
+        let pairs = match json::parse(legacy)? {
+            Json::Obj(pairs) => pairs,
+            // not a spec, which deserializing says
+            other => {
+                let spec = Spec::parse_json_in(&other.to_string(), Path::new("."))?;
+                spec.validate()?;
+                return Ok((spec, vec![]));
+            }
+        };
+        let settings = schema::fields("Spec");
+        let mut notes = vec![];
+        let mut out = vec![];
+        for (key, value) in pairs {
+            let value = match (key.as_str(), value) {
+                ("positional" | "non_positional", Json::Arr(items)) => {
+                    let (kind, known) = if key == "positional" {
+                        ("positional", schema::fields("PositionalItem"))
+                    } else {
+                        ("option", schema::fields("NonPositionalItem"))
+                    };
+                    Json::Arr(
+                        items
+                            .into_iter()
+                            .map(|item| migrate_item(item, kind, &known, &mut notes))
+                            .filter(|item| !matches!(item, Json::Null))
+                            .collect(),
+                    )
+                }
+                (key, value) if settings.contains(&key) => value,
+                (key, _) => {
+                    notes.push(format!("dropped {}, which argen no longer has", key));
+                    continue;
+                }
+            };
+            out.push((key, value));
+        }
+        let value =
+            json_to_toml(Json::Obj(out)).unwrap_or_else(|| toml::Value::Table(Default::default()));
+        let s = Spec::from_value(value, Some(Path::new(".")), &[])?;
+        s.validate()?;
+        Ok((s, notes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    #[test]
+    fn migrate_legacy() {
+        let (s, notes) = Spec::migrate_legacy(
+            r#"{
+                "c_file": "args.c",
+                "positional": [
+                    {"c_var": "input", "c_type": "char*", "help_name": "FILE", "required": "true"}
+                ],
+                "non_positional": [
+                    {"c_var": "size", "c_type": "int", "long": "size", "default": 12,
+                     "required": "false", "color": "blue"}
+                ]
+            }"#,
+        )
+        .ok()
+        .unwrap();
+        assert_eq!(
+            notes,
+            ["option size: dropped color, which argen no longer has"]
+        );
+        assert_eq!(
+            s.to_toml(),
+            "c_file = \"args.c\"\n\
+             \n\
+             [[non_positional]]\n\
+             c_type = \"int\"\n\
+             c_var = \"size\"\n\
+             default = \"12\"\n\
+             long = \"size\"\n\
+             required = false\n\
+             \n\
+             [[positional]]\n\
+             c_type = \"char*\"\n\
+             c_var = \"input\"\n\
+             help_name = \"FILE\"\n\
+             required = true\n"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The PowerShell backend: a script skeleton whose param block mirrors the
+//! spec, leaving the binding itself to PowerShell.
+
+use super::{
+    camel_case, int_literal, marker_text, CType, NonPositionalItem, PositionalItem, Spec,
+    ValidationError, USER_BEGIN, USER_END,
+};
+
+/// PowerShell's automatic variables, which a parameter mustn't be named.
+const AUTOMATIC: &[&str] = &[
+    "args",
+    "error",
+    "event",
+    "false",
+    "home",
+    "host",
+    "input",
+    "matches",
+    "myinvocation",
+    "null",
+    "pid",
+    "profile",
+    "psitem",
+    "pwd",
+    "sender",
+    "this",
+    "true",
+];
+
+/// A c_var as a PowerShell parameter name.
+fn ps_name(c_var: &str) -> String {
+    let name = camel_case(c_var);
+    if AUTOMATIC.contains(&name.to_ascii_lowercase().as_str()) {
+        name + "Value"
+    } else {
+        name
+    }
+}
+
+/// A string as a single-quoted PowerShell literal.
+fn ps_str(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn ps_type(c_type: CType) -> &'static str {
+    match c_type {
+        CType::Chars => "[string]",
+        CType::Int => "[int]",
+    }
+}
+
+fn ps_default(c_type: CType, default: &str) -> String {
+    match (c_type, int_literal(default)) {
+        (CType::Int, Some(n)) => format!(" = {}", n),
+        (CType::Int, None) => format!(" = {}", default),
+        (CType::Chars, _) => format!(" = {}", ps_str(default)),
+    }
+}
+
+impl NonPositionalItem {
+    fn ps_param(&self) -> String {
+        let name = ps_name(&self.c_var);
+        let mut param = String::new();
+        // with env, a missing value is only an error once the environment is checked
+        if self.is_required() && self.env.is_none() {
+            param.push_str("\t[Parameter(Mandatory = $true)]\n");
+        }
+        // PowerShell matches names regardless of case, and can't take hyphens
+        let mut aliases: Vec<String> = Vec::new();
+        let names = self.short.iter().chain(std::iter::once(&self.long));
+        for alias in names.chain(self.aliases.iter().flatten()) {
+            let alias = alias.replace('-', "");
+            let taken = alias.eq_ignore_ascii_case(&name)
+                || aliases.iter().any(|a| a.eq_ignore_ascii_case(&alias));
+            if !taken {
+                aliases.push(alias);
+            }
+        }
+        if !aliases.is_empty() {
+            let aliases: Vec<String> = aliases.iter().map(|a| ps_str(a)).collect();
+            param.push_str(&format!("\t[Alias({})]\n", aliases.join(", ")));
+        }
+        if self.is_flag() {
+            param.push_str(&format!("\t[switch]${}", name));
+        } else {
+            param.push_str(&format!("\t{}${}", ps_type(self.c_type), name));
+            if let Some(default) = &self.default {
+                param.push_str(&ps_default(self.c_type, default));
+            }
+        }
+        param
+    }
+    /// Falls back on the environment variable, if any, when the option
+    /// wasn't given.
+    fn ps_env(&self) -> String {
+        let env = match &self.env {
+            Some(env) => env,
+            None => return String::new(),
+        };
+        let name = ps_name(&self.c_var);
+        let value = if self.is_flag() {
+            format!("[switch]($env:{} -in '1', 'true', 'yes', 'on')", env)
+        } else {
+            match self.c_type {
+                CType::Chars => format!("$env:{}", env),
+                CType::Int => format!("[int]$env:{}", env),
+            }
+        };
+        let mut code = format!(
+            "if (-not $PSBoundParameters.ContainsKey({})) {{\n\
+             \tif ($env:{}) {{\n\
+             \t\t${} = {}\n\
+             \t}}",
+            ps_str(&name),
+            env,
+            name,
+            value
+        );
+        if self.is_required() {
+            code.push_str(&format!(
+                " else {{\n\
+                 \t\tthrow {}\n\
+                 \t}}",
+                ps_str(&format!("missing required option '--{}'", self.long))
+            ));
+        }
+        code.push_str("\n}\n");
+        code
+    }
+}
+
+impl PositionalItem {
+    fn ps_param(&self, position: usize) -> String {
+        let mut attrs = Vec::new();
+        if self.is_required() {
+            attrs.push(String::from("Mandatory = $true"));
+        }
+        attrs.push(format!("Position = {}", position));
+        if self.is_multi() {
+            attrs.push(String::from("ValueFromRemainingArguments = $true"));
+        }
+        let mut param = format!("\t[Parameter({})]\n", attrs.join(", "));
+        if self.is_multi() {
+            param.push_str(&format!("\t[string[]]${}", ps_name(&self.c_var)));
+            if let Some(default) = &self.default {
+                param.push_str(&format!(" = @({})", ps_str(default)));
+            }
+        } else {
+            param.push_str(&format!(
+                "\t{}${}",
+                ps_type(self.c_type),
+                ps_name(&self.c_var)
+            ));
+            if let Some(default) = &self.default {
+                param.push_str(&ps_default(self.c_type, default));
+            }
+        }
+        param
+    }
+}
+
+impl Spec {
+    /// Checks that the spec only uses what the PowerShell backend supports.
+    pub fn check_powershell(&self) -> Result<(), ValidationError> {
+        let unsupported = [
+            ("capture_rest", self.capture_rest.is_some()),
+            ("strict_posix", self.is_strict_posix()),
+            ("response_files", self.has_response_files()),
+            ("config", self.has_config()),
+            ("pre_parse_fn", self.pre_parse_fn.is_some()),
+            ("post_parse_fn", self.post_parse_fn.is_some()),
+            ("subcommands", self.has_subcommands()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((feature, _)) => Err(ValidationError::BackendUnsupported(
+                String::from("powershell"),
+                String::from(*feature),
+            )),
+            None => Ok(()),
+        }
+    }
+    /// Comment-based help, so Get-Help describes the script's parameters.
+    fn ps_help(&self, progname: &str) -> String {
+        let mut help = format!(
+            "<#\n.SYNOPSIS\n{} [options]{}\n",
+            progname,
+            self.usage_synopsis()
+        );
+        let npis = self
+            .help_options()
+            .into_iter()
+            .map(|npi| (&npi.c_var, npi.help_text(false)));
+        let pis = self.positional.iter().map(|pi| (&pi.c_var, pi.help_text()));
+        for (c_var, text) in npis.chain(pis) {
+            help.push_str(&format!("\n.PARAMETER {}\n", ps_name(c_var)));
+            if !text.is_empty() {
+                help.push_str(&text.replace("#>", "# >"));
+                help.push('\n');
+            }
+        }
+        help.push_str("#>\n");
+        help
+    }
+    /// Generates a PowerShell script of the given name whose param block
+    /// takes the spec's options and arguments, with the user region as its
+    /// body.
+    pub fn gen_powershell(&self, progname: &str) -> String {
+        let mut code = self.stamp_with("#", "");
+        code.push_str(&self.ps_help(progname));
+        code.push_str("[CmdletBinding(PositionalBinding = $false)]\nparam(\n");
+        let params: Vec<String> = self
+            .non_positional
+            .iter()
+            .map(|npi| npi.ps_param())
+            .chain(
+                self.positional
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pi)| pi.ps_param(i)),
+            )
+            .collect();
+        code.push_str(&params.join(",\n\n"));
+        if !params.is_empty() {
+            code.push('\n');
+        }
+        code.push_str(")\n");
+        let env: String = self.non_positional.iter().map(|npi| npi.ps_env()).collect();
+        if !env.is_empty() {
+            code.push('\n');
+            code.push_str(&env);
+        }
+        code.push_str(&format!(
+            "\n# {}\n# call your code here\n# {}\n",
+            marker_text(USER_BEGIN),
+            marker_text(USER_END)
+        ));
+        // PowerShell's style is four spaces
+        code.replace('\t', "    ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    #[test]
+    fn powershell() {
+        let s = Spec::from_str(
+            r#"
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            help_descr = "the input"
+            required = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            default = "it's"
+
+            [[non_positional]]
+            c_var = "block_size"
+            c_type = "int"
+            long = "block-size"
+            short = "b"
+            aliases = ["bs"]
+            default = "12"
+            help_descr = "set the block size"
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            required = true
+            env = "PROG_PORT"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "silent"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let ps = s.gen_powershell("prog");
+        assert!(ps.starts_with("<#\n.SYNOPSIS\nprog [options] FILE [WORD...]\n"));
+        assert!(ps.contains("\n.PARAMETER BlockSize\nset the block size (default: 12)\n"));
+        assert!(ps.contains(
+            "[CmdletBinding(PositionalBinding = $false)]\n\
+             param(\n\
+             \x20   [Alias('b', 'bs')]\n\
+             \x20   [int]$BlockSize = 12,\n\
+             \n\
+             \x20   [int]$Port,\n\
+             \n\
+             \x20   [Alias('silent')]\n\
+             \x20   [switch]$Quiet,\n\
+             \n\
+             \x20   [Parameter(Mandatory = $true, Position = 0)]\n\
+             \x20   [string]$InputValue,\n\
+             \n\
+             \x20   [Parameter(Position = 1, ValueFromRemainingArguments = $true)]\n\
+             \x20   [string[]]$Words = @('it''s')\n\
+             )\n"
+        ));
+        assert!(ps.contains(
+            "if (-not $PSBoundParameters.ContainsKey('Port')) {\n\
+             \x20   if ($env:PROG_PORT) {\n\
+             \x20       $Port = [int]$env:PROG_PORT\n\
+             \x20   } else {\n\
+             \x20       throw 'missing required option ''--port'''\n\
+             \x20   }\n\
+             }\n"
+        ));
+        assert!(ps.ends_with("# argen:begin user\n# call your code here\n# argen:end user\n"));
+        assert!(
+            Spec::from_str("capture_rest = \"r\"\npositional = []\nnon_positional = []")
+                .unwrap()
+                .check_powershell()
+                .is_err()
+        );
+    }
+}
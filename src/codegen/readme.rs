@@ -0,0 +1,187 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The README backend: Markdown with the usage the generated parser prints,
+//! and tables of its options and arguments, to embed in documentation.
+
+use super::{NonPositionalItem, PositionalItem, Spec};
+
+/// Text for a Markdown table cell, which can't hold a newline or a bare |.
+fn cell(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace('|', "\\|")
+}
+
+/// Text as inline code in a table cell.
+fn code(text: &str) -> String {
+    format!("`{}`", cell(text))
+}
+
+impl NonPositionalItem {
+    fn readme_row(&self, long: bool) -> String {
+        let mut names = Vec::new();
+        if let Some(short) = &self.short {
+            names.push(code(&format!("-{}", short)));
+        }
+        if long {
+            names.push(code(&format!("--{}", self.long)));
+            for alias in self.aliases.iter().flatten() {
+                names.push(code(&format!("--{}", alias)));
+            }
+        }
+        let value = if self.is_flag() {
+            String::new()
+        } else {
+            code(&format!("<{}>", self.help_name.as_deref().unwrap_or("arg")))
+        };
+        let default = match &self.default {
+            _ if self.is_required() => String::from("*required*"),
+            Some(default) => code(default),
+            None => String::new(),
+        };
+        let mut descr = cell(self.help_descr.as_deref().unwrap_or(""));
+        if let Some(env) = &self.env {
+            if !descr.is_empty() {
+                descr.push(' ');
+            }
+            descr.push_str(&format!("(env: {})", code(env)));
+        }
+        format!(
+            "| {} | {} | {} | {} |\n",
+            names.join(", "),
+            value,
+            default,
+            descr
+        )
+    }
+}
+
+impl PositionalItem {
+    fn readme_row(&self) -> String {
+        let default = match &self.default {
+            _ if self.is_required() => String::from("*required*"),
+            Some(default) => code(default),
+            None => String::new(),
+        };
+        format!(
+            "| {} | {} | {} |\n",
+            code(&self.help_label()),
+            default,
+            cell(self.help_descr.as_deref().unwrap_or(""))
+        )
+    }
+}
+
+impl Spec {
+    /// Generates Markdown for a README: the usage of a program of the given
+    /// name in a fenced code block, followed by tables of its arguments and
+    /// options.
+    pub fn gen_readme(&self, progname: &str) -> String {
+        // the stamp, as HTML comments so it isn't rendered
+        let mut md = self.stamp_with("<!--", " -->");
+        if !md.is_empty() {
+            md.push('\n');
+        }
+        md.push_str(&format!(
+            "```text\n{}```\n",
+            self.analyze().usage_text(progname)
+        ));
+        if !self.positional.is_empty() {
+            md.push_str("\n| Argument | Default | Description |\n|---|---|---|\n");
+            for pi in &self.positional {
+                md.push_str(&pi.readme_row());
+            }
+        }
+        let long = self.has_long_options();
+        md.push_str("\n| Option | Value | Default | Description |\n|---|---|---|---|\n");
+        md.push_str(&format!(
+            "| {} |  |  | print this usage and exit |\n",
+            if long { "`-h`, `--help`" } else { "`-h`" }
+        ));
+        for npi in self.help_options() {
+            md.push_str(&npi.readme_row(long));
+        }
+        if self.has_config_option() {
+            md.push_str(&format!(
+                "| `--config` | `<file>` | {} | read option defaults from file |\n",
+                self.config_file.as_deref().map(code).unwrap_or_default()
+            ));
+        }
+        md
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    #[test]
+    fn readme() {
+        let s = Spec::from_str(
+            r#"
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            help_descr = "the input"
+            required = true
+
+            [[non_positional]]
+            c_var = "block_size"
+            c_type = "int"
+            long = "block-size"
+            short = "b"
+            help_name = "num"
+            aliases = ["bs"]
+            default = "12"
+            help_descr = "set the block size\nin bytes"
+            env = "BLOCK_SIZE"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+            help_descr = "a | b"
+            "#,
+        )
+        .unwrap();
+        let md = s.gen_readme("prog");
+        assert!(md.starts_with(
+            "```text\n\
+             usage: prog [options] FILE\n\
+             \n\
+             Arguments:\n\
+             \x20 FILE                    the input\n\
+             \n\
+             Options:\n\
+             \x20 -h, --help              print this usage and exit\n\
+             \x20 -b, --block-size <num>  set the block size\n\
+             \x20                         in bytes (default: 12) (aliased: --bs)\n\
+             \x20     --quiet             a | b\n\
+             ```\n"
+        ));
+        assert!(md.contains("| `FILE` | *required* | the input |\n"));
+        assert!(md.contains(
+            "| `-b`, `--block-size`, `--bs` | `<num>` | `12` | \
+             set the block size in bytes (env: `BLOCK_SIZE`) |\n"
+        ));
+        assert!(md.contains("| `--quiet` |  |  | a \\| b |\n"));
+    }
+}
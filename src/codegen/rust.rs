@@ -0,0 +1,301 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Rust backend: a struct deriving clap's Parser, with a field for every
+//! c_var, for use with clap 4 (and its "env" feature if any option has env).
+
+use super::{
+    camel_case, int_literal, CType, NonPositionalItem, PositionalItem, Spec, ValidationError,
+    USER_BEGIN, USER_END,
+};
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "box", "break", "const", "continue", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move",
+    "mut", "priv", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type",
+    "unsafe", "use", "where", "while", "yield",
+];
+
+/// A c_var as a Rust field name, which is raw if it is a keyword.
+pub(super) fn rust_ident(c_var: &str) -> String {
+    if KEYWORDS.contains(&c_var) {
+        format!("r#{}", c_var)
+    } else {
+        c_var.to_owned()
+    }
+}
+
+/// A string as a Rust string literal.
+fn rust_str(s: &str) -> String {
+    format!("{:?}", s)
+}
+
+/// A default as the argument to #[arg], so that it parses as the field's type.
+fn rust_default(c_type: CType, default: &str) -> String {
+    match (c_type, int_literal(default)) {
+        (CType::Int, Some(n)) => format!("default_value_t = {}", n),
+        (CType::Int, None) => format!("default_value_t = {}", default),
+        (CType::Chars, _) => format!("default_value = {}", rust_str(default)),
+    }
+}
+
+fn rust_type(c_type: CType) -> &'static str {
+    match c_type {
+        CType::Chars => "String",
+        CType::Int => "i32",
+    }
+}
+
+/// Doc comments, which clap shows as the help, from a description.
+pub(super) fn rust_doc(descr: &Option<String>) -> String {
+    descr
+        .iter()
+        .flat_map(|descr| descr.lines())
+        .map(|line| match line.trim_end() {
+            "" => String::from("    ///\n"),
+            line => format!("    /// {}\n", line),
+        })
+        .collect()
+}
+
+impl NonPositionalItem {
+    /// This option's field of the Parser struct.
+    fn rust_field(&self) -> String {
+        let mut attrs = Vec::new();
+        if let Some(short) = &self.short {
+            attrs.push(format!("short = '{}'", short));
+        }
+        attrs.push(format!("long = {}", rust_str(&self.long)));
+        if let Some(help_name) = self.help_name.as_ref().filter(|_| !self.is_flag()) {
+            attrs.push(format!("value_name = {}", rust_str(help_name)));
+        }
+        if let Some(aliases) = self.aliases.as_ref().filter(|a| !a.is_empty()) {
+            let aliases: Vec<String> = aliases.iter().map(|a| rust_str(a)).collect();
+            attrs.push(format!("visible_aliases = [{}]", aliases.join(", ")));
+        }
+        if let Some(default) = &self.default {
+            attrs.push(rust_default(self.c_type, default));
+            if !self.show_default.unwrap_or(true) {
+                attrs.push(String::from("hide_default_value = true"));
+            }
+        }
+        if let Some(env) = &self.env {
+            attrs.push(format!("env = {}", rust_str(env)));
+        }
+        let ty = if self.is_flag() {
+            String::from("bool")
+        } else if self.has_default() || self.is_required() {
+            rust_type(self.c_type).to_owned()
+        } else {
+            format!("Option<{}>", rust_type(self.c_type))
+        };
+        format!(
+            "{}    #[arg({})]\n    pub {}: {},\n",
+            rust_doc(&self.help_descr),
+            attrs.join(", "),
+            rust_ident(&self.c_var),
+            ty
+        )
+    }
+}
+
+impl PositionalItem {
+    /// This argument's field of the Parser struct. With strict_posix, the
+    /// last argument takes everything from the first operand on.
+    fn rust_field(&self, strict_posix: bool) -> String {
+        let mut attrs = vec![format!("value_name = {}", rust_str(&self.help_name))];
+        if let Some(default) = &self.default {
+            attrs.push(rust_default(self.c_type, default));
+            if !self.show_default.unwrap_or(true) {
+                attrs.push(String::from("hide_default_value = true"));
+            }
+        }
+        let ty = if self.is_multi() {
+            if self.is_required() {
+                attrs.push(String::from("required = true"));
+            }
+            if strict_posix {
+                attrs.push(String::from("trailing_var_arg = true"));
+                attrs.push(String::from("allow_hyphen_values = true"));
+            }
+            format!("Vec<{}>", rust_type(self.c_type))
+        } else if self.has_default() || self.is_required() {
+            rust_type(self.c_type).to_owned()
+        } else {
+            format!("Option<{}>", rust_type(self.c_type))
+        };
+        format!(
+            "{}    #[arg({})]\n    pub {}: {},\n",
+            rust_doc(&self.help_descr),
+            attrs.join(", "),
+            rust_ident(&self.c_var),
+            ty
+        )
+    }
+}
+
+impl Spec {
+    /// Checks that the spec only uses what the Rust backend supports.
+    pub fn check_rust(&self) -> Result<(), ValidationError> {
+        let strict_posix =
+            self.is_strict_posix() && !self.positional.last().is_some_and(|pi| pi.is_multi());
+        let unsupported = [
+            ("case_insensitive", self.is_case_insensitive()),
+            ("strict_posix without a multi positional", strict_posix),
+            ("response_files", self.has_response_files()),
+            ("config", self.has_config()),
+            ("pre_parse_fn", self.pre_parse_fn.is_some()),
+            ("post_parse_fn", self.post_parse_fn.is_some()),
+            ("subcommands", self.has_subcommands()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((feature, _)) => Err(ValidationError::BackendUnsupported(
+                String::from("rust"),
+                String::from(*feature),
+            )),
+            None => Ok(()),
+        }
+    }
+    pub(super) fn rust_struct_name(&self) -> String {
+        camel_case(self.struct_name().unwrap_or("args"))
+    }
+    /// Generates a struct deriving clap's Parser for the spec, and a main
+    /// parsing into it unless emit_main is off.
+    pub fn gen_rust(&self) -> String {
+        let name = self.rust_struct_name();
+        let mut code = self.cgen_stamp();
+        code.push_str("use clap::Parser;\n\n#[derive(Parser, Debug)]\n");
+        if self.allows_abbrev() {
+            code.push_str("#[command(infer_long_args = true)]\n");
+        }
+        code.push_str(&format!("pub struct {} {{\n", name));
+        // clap shows options in the order of their fields
+        let fields: Vec<String> = self
+            .help_options()
+            .iter()
+            .map(|npi| npi.rust_field())
+            .chain(
+                self.positional
+                    .iter()
+                    .map(|pi| pi.rust_field(self.is_strict_posix())),
+            )
+            .collect();
+        code.push_str(&fields.join("\n"));
+        if let Some(rest) = &self.capture_rest {
+            if !fields.is_empty() {
+                code.push('\n');
+            }
+            code.push_str(&format!(
+                "    /// Everything after \"--\"\n    #[arg(last = true, value_name = \"ARG\")]\n    pub {}: Vec<String>,\n",
+                rust_ident(rest)
+            ));
+        }
+        code.push_str("}\n");
+        if self.emits_main() {
+            code.push_str(&format!(
+                "\n\
+                 fn main() {{\n\
+                 \x20   let args = {}::parse();\n\
+                 \n\
+                 \x20   {}\n\
+                 \x20   /* call your code here */\n\
+                 \x20   let _ = args;\n\
+                 \x20   {}\n\
+                 }}\n",
+                name, USER_BEGIN, USER_END
+            ));
+        }
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    #[test]
+    fn rust_clap() {
+        let s = Spec::from_str(
+            r#"
+            capture_rest = "rest"
+            args_struct = "program_args"
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            help_descr = "the input"
+            required = true
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            default = "w"
+
+            [[non_positional]]
+            c_var = "size"
+            c_type = "int"
+            long = "size"
+            short = "s"
+            help_name = "num"
+            aliases = ["sz"]
+            default = "0xDEADBEEF"
+
+            [[non_positional]]
+            c_var = "type"
+            c_type = "char*"
+            long = "type"
+            env = "PROG_TYPE"
+
+            [[non_positional]]
+            c_var = "quiet"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let r = s.gen_rust();
+        assert!(r.contains("#[command(infer_long_args = true)]\npub struct ProgramArgs {\n"));
+        assert!(r.contains(
+            "    #[arg(short = 's', long = \"size\", value_name = \"num\", \
+             visible_aliases = [\"sz\"], default_value_t = -559038737)]\n    pub size: i32,\n"
+        ));
+        assert!(r.contains(
+            "    #[arg(long = \"type\", env = \"PROG_TYPE\")]\n    pub r#type: Option<String>,\n"
+        ));
+        assert!(r.contains("    #[arg(long = \"quiet\")]\n    pub quiet: bool,\n"));
+        assert!(r.contains(
+            "    /// the input\n    #[arg(value_name = \"FILE\")]\n    pub input: String,\n"
+        ));
+        assert!(r.contains(
+            "    #[arg(value_name = \"WORD\", default_value = \"w\")]\n    pub words: Vec<String>,\n"
+        ));
+        assert!(r.contains(
+            "    #[arg(last = true, value_name = \"ARG\")]\n    pub rest: Vec<String>,\n"
+        ));
+        assert!(r.contains("    let args = ProgramArgs::parse();\n"));
+        assert!(s.check_rust().is_ok());
+        assert!(
+            Spec::from_str("case_insensitive = true\npositional = []\nnon_positional = []")
+                .unwrap()
+                .check_rust()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,499 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A JSON Schema of the spec format, for editors to validate and complete
+//! specs with. It is traced from Spec's Deserialize implementation: a
+//! deserializer hands Spec every field it asks for, noting what each field
+//! asked to be deserialized as, so the schema can't drift from the types.
+
+use super::Spec;
+use crate::json::Json;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+/// What a value was deserialized as.
+#[derive(Clone)]
+enum Format {
+    Unknown,
+    Bool,
+    Integer,
+    String,
+    Option(Box<Format>),
+    Seq(Box<Format>),
+    /// A struct, by name, with its fields kept in Registry.
+    Struct(&'static str),
+    /// An enum of unit variants, by their names in a spec.
+    Enum(&'static [&'static str]),
+}
+
+/// The fields of every struct traced, in the order they were first seen.
+#[derive(Default)]
+struct Registry {
+    structs: Vec<(&'static str, Vec<(&'static str, Format)>)>,
+    /// Structs being traced, to stop at one containing itself.
+    stack: Vec<&'static str>,
+    /// A field to leave out, to find whether it is required.
+    omit: Option<(&'static str, &'static str)>,
+}
+
+#[derive(Debug)]
+struct TraceError(String);
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+impl std::error::Error for TraceError {}
+impl de::Error for TraceError {
+    fn custom<T: fmt::Display>(msg: T) -> TraceError {
+        TraceError(msg.to_string())
+    }
+}
+/// Raised on reaching a struct already being traced.
+const RECURSION: &str = "recursion";
+
+/// A deserializer noting what it is asked for in format.
+struct Tracer {
+    registry: Rc<RefCell<Registry>>,
+    format: Rc<RefCell<Format>>,
+}
+impl Tracer {
+    fn child(&self) -> Tracer {
+        Tracer {
+            registry: self.registry.clone(),
+            format: Rc::new(RefCell::new(Format::Unknown)),
+        }
+    }
+    fn set(&self, format: Format) {
+        *self.format.borrow_mut() = format;
+    }
+}
+
+macro_rules! trace {
+    ($($method:ident => $format:expr, $visit:ident($value:expr);)*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+            self.set($format);
+            visitor.$visit($value)
+        })*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Tracer {
+    type Error = TraceError;
+
+    trace! {
+        deserialize_bool => Format::Bool, visit_bool(false);
+        deserialize_i8 => Format::Integer, visit_i8(0);
+        deserialize_i16 => Format::Integer, visit_i16(0);
+        deserialize_i32 => Format::Integer, visit_i32(0);
+        deserialize_i64 => Format::Integer, visit_i64(0);
+        deserialize_u8 => Format::Integer, visit_u8(0);
+        deserialize_u16 => Format::Integer, visit_u16(0);
+        deserialize_u32 => Format::Integer, visit_u32(0);
+        deserialize_u64 => Format::Integer, visit_u64(0);
+        deserialize_char => Format::String, visit_char('a');
+        deserialize_str => Format::String, visit_str("");
+        deserialize_string => Format::String, visit_string(String::new());
+        deserialize_identifier => Format::String, visit_str("");
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, TraceError> {
+        Err(TraceError(String::from(
+            "cannot trace a self-describing type",
+        )))
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let inner = self.child();
+        let format = inner.format.clone();
+        let value = visitor.visit_some(inner);
+        self.set(Format::Option(Box::new(format.borrow().clone())));
+        value
+    }
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, TraceError> {
+        let element = self.child();
+        let format = element.format.clone();
+        let value = visitor.visit_seq(Elements(Some(element)));
+        self.set(Format::Seq(Box::new(format.borrow().clone())));
+        value
+    }
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.set(Format::Struct(name));
+        if self.registry.borrow().stack.contains(&name) {
+            return Err(TraceError(String::from(RECURSION)));
+        }
+        self.registry.borrow_mut().stack.push(name);
+        let omit = self.registry.borrow().omit;
+        let fields: Vec<(&'static str, Tracer)> = fields
+            .iter()
+            .filter(|field| omit != Some((name, field)))
+            .map(|field| (*field, self.child()))
+            .collect();
+        let formats: Vec<(&'static str, Rc<RefCell<Format>>)> = fields
+            .iter()
+            .map(|(field, tracer)| (*field, tracer.format.clone()))
+            .collect();
+        let value = visitor.visit_map(Fields(fields.into_iter(), None));
+        let mut registry = self.registry.borrow_mut();
+        registry.stack.pop();
+        if omit.is_none() && !registry.structs.iter().any(|(n, _)| *n == name) {
+            let fields = formats
+                .into_iter()
+                .map(|(field, format)| (field, format.borrow().clone()))
+                .collect();
+            registry.structs.push((name, fields));
+        }
+        value
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, TraceError> {
+        self.set(Format::Enum(variants));
+        visitor.visit_enum(variants[0].into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 f32 f64 bytes byte_buf unit unit_struct newtype_struct tuple
+        tuple_struct map ignored_any
+    }
+}
+
+/// A sequence of one traced element, or none if the element is a struct
+/// already being traced.
+struct Elements(Option<Tracer>);
+impl<'de> de::SeqAccess<'de> for Elements {
+    type Error = TraceError;
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, TraceError> {
+        match self.0.take() {
+            Some(tracer) => match seed.deserialize(tracer) {
+                Ok(value) => Ok(Some(value)),
+                Err(TraceError(e)) if e == RECURSION => Ok(None),
+                Err(e) => Err(e),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Every field of a struct, each with a tracer for its value.
+struct Fields(std::vec::IntoIter<(&'static str, Tracer)>, Option<Tracer>);
+impl<'de> de::MapAccess<'de> for Fields {
+    type Error = TraceError;
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, TraceError> {
+        match self.0.next() {
+            Some((field, tracer)) => {
+                self.1 = Some(tracer);
+                seed.deserialize(field.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, TraceError> {
+        seed.deserialize(self.1.take().expect("a key before its value"))
+    }
+}
+
+/// Deserializes a Spec through a tracer, leaving out the given field.
+fn trace(omit: Option<(&'static str, &'static str)>) -> Result<Registry, TraceError> {
+    let registry = Rc::new(RefCell::new(Registry {
+        omit,
+        ..Default::default()
+    }));
+    let tracer = Tracer {
+        registry: registry.clone(),
+        format: Rc::new(RefCell::new(Format::Unknown)),
+    };
+    Spec::deserialize(tracer)?;
+    drop(registry.borrow_mut().stack.drain(..));
+    Ok(Rc::try_unwrap(registry)
+        .ok()
+        .expect("tracers dropped")
+        .into_inner())
+}
+
+/// The fields of a struct of the spec format, by its name, such as "Spec"
+/// or "NonPositionalItem".
+pub(super) fn fields(name: &str) -> Vec<&'static str> {
+    let registry = trace(None).expect("trace the spec format");
+    let fields = registry.structs.iter().find(|(n, _)| *n == name);
+    fields
+        .map(|(_, fields)| fields.iter().map(|(field, _)| *field).collect())
+        .unwrap_or_default()
+}
+
+/// A value for a field of a struct of the spec format, from text as a command
+/// line gives it: a boolean, an integer, a string, or strings separated by
+/// commas, as the field takes.
+pub(super) fn parse_field(name: &str, field: &str, text: &str) -> Result<toml::Value, String> {
+    let registry = trace(None).expect("trace the spec format");
+    let format = registry
+        .structs
+        .iter()
+        .find(|(n, _)| *n == name)
+        .and_then(|(_, fields)| fields.iter().find(|(f, _)| *f == field))
+        .map(|(_, format)| format)
+        .ok_or_else(|| format!("there is no field {}", field))?;
+    let mut format = format;
+    while let Format::Option(inner) = format {
+        format = inner;
+    }
+    match format {
+        Format::Bool => text
+            .parse()
+            .map(toml::Value::Boolean)
+            .map_err(|_| format!("{} is true or false", field)),
+        Format::Integer => text
+            .parse()
+            .map(toml::Value::Integer)
+            .map_err(|_| format!("{} is an integer", field)),
+        Format::String | Format::Enum(_) => Ok(toml::Value::String(text.to_owned())),
+        Format::Seq(element) if matches!(**element, Format::String) => Ok(toml::Value::Array(
+            text.split(',')
+                .map(|s| toml::Value::String(s.to_owned()))
+                .collect(),
+        )),
+        _ => Err(format!("{} can't be set on the command line", field)),
+    }
+}
+
+/// The schema of a value of the format.
+fn schema(format: &Format) -> Json {
+    match format {
+        Format::Unknown => Json::obj(vec![]),
+        Format::Bool => Json::obj(vec![("type", Json::Str(String::from("boolean")))]),
+        Format::Integer => Json::obj(vec![("type", Json::Str(String::from("integer")))]),
+        Format::String => Json::obj(vec![("type", Json::Str(String::from("string")))]),
+        Format::Option(inner) => schema(inner),
+        Format::Seq(inner) => Json::obj(vec![
+            ("type", Json::Str(String::from("array"))),
+            ("items", schema(inner)),
+        ]),
+        Format::Struct(name) => {
+            Json::obj(vec![("$ref", Json::Str(format!("#/definitions/{}", name)))])
+        }
+        Format::Enum(variants) => Json::obj(vec![(
+            "enum",
+            Json::Arr(variants.iter().map(|v| Json::Str(v.to_string())).collect()),
+        )]),
+    }
+}
+
+/// The schema of a struct's fields, the required ones listed.
+fn object_schema(fields: &[(&'static str, Format)], required: Vec<&str>) -> Vec<(String, Json)> {
+    let mut object = vec![
+        (String::from("type"), Json::Str(String::from("object"))),
+        (
+            String::from("properties"),
+            Json::obj(
+                fields
+                    .iter()
+                    .map(|(field, format)| (*field, schema(format))),
+            ),
+        ),
+    ];
+    if !required.is_empty() {
+        object.push((
+            String::from("required"),
+            Json::Arr(
+                required
+                    .into_iter()
+                    .map(|f| Json::Str(f.to_owned()))
+                    .collect(),
+            ),
+        ));
+    }
+    object
+}
+
+/// The settings read before a spec is deserialized, which Spec doesn't
+/// have fields for: see include.rs and vars.rs.
+fn preprocessed() -> Vec<(&'static str, Json)> {
+    let strings = || {
+        Json::obj(vec![
+            ("type", Json::Str(String::from("array"))),
+            ("items", schema(&Format::String)),
+        ])
+    };
+    let items = || {
+        Json::obj(vec![
+            ("type", Json::Str(String::from("object"))),
+            (
+                "properties",
+                Json::obj(vec![
+                    (
+                        "positional",
+                        schema(&Format::Seq(Box::new(Format::Struct("PositionalItem")))),
+                    ),
+                    (
+                        "non_positional",
+                        schema(&Format::Seq(Box::new(Format::Struct("NonPositionalItem")))),
+                    ),
+                ]),
+            ),
+        ])
+    };
+    let used = Json::obj(vec![
+        ("type", Json::Str(String::from("object"))),
+        (
+            "properties",
+            Json::obj(vec![
+                ("set", schema(&Format::String)),
+                (
+                    "defaults",
+                    Json::obj(vec![
+                        ("type", Json::Str(String::from("object"))),
+                        ("additionalProperties", schema(&Format::String)),
+                    ]),
+                ),
+            ]),
+        ),
+        ("required", Json::Arr(vec![Json::Str(String::from("set"))])),
+    ]);
+    vec![
+        ("include", strings()),
+        (
+            "option_sets",
+            Json::obj(vec![
+                ("type", Json::Str(String::from("object"))),
+                ("additionalProperties", items()),
+            ]),
+        ),
+        (
+            "use",
+            Json::obj(vec![
+                ("type", Json::Str(String::from("array"))),
+                (
+                    "items",
+                    Json::obj(vec![(
+                        "anyOf",
+                        Json::Arr(vec![schema(&Format::String), used]),
+                    )]),
+                ),
+            ]),
+        ),
+        (
+            "vars",
+            Json::obj(vec![
+                ("type", Json::Str(String::from("object"))),
+                ("additionalProperties", schema(&Format::String)),
+            ]),
+        ),
+    ]
+}
+
+impl Spec {
+    /// A JSON Schema (draft-07) of specs, in TOML or JSON.
+    pub fn json_schema() -> String {
+        let registry = trace(None).expect("trace the spec format");
+        let required = |name: &'static str, fields: &[(&'static str, Format)]| -> Vec<&str> {
+            fields
+                .iter()
+                .map(|(field, _)| *field)
+                .filter(|field| trace(Some((name, field))).is_err())
+                .collect()
+        };
+        let mut definitions = vec![];
+        let mut root = vec![];
+        for (name, fields) in &registry.structs {
+            let object = object_schema(fields, required(name, fields));
+            if *name == "Spec" {
+                root = object;
+            } else {
+                definitions.push((name.to_string(), Json::Obj(object)));
+            }
+        }
+        if let Some((_, Json::Obj(properties))) = root.iter_mut().find(|(k, _)| k == "properties") {
+            properties.extend(preprocessed().into_iter().map(|(k, v)| (k.to_owned(), v)));
+        }
+        let mut schema = vec![
+            (
+                String::from("$schema"),
+                Json::Str(String::from("http://json-schema.org/draft-07/schema#")),
+            ),
+            (String::from("title"), Json::Str(String::from("argen spec"))),
+        ];
+        schema.extend(root);
+        schema.push((String::from("definitions"), Json::Obj(definitions)));
+        format!("{}\n", Json::Obj(schema))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+    use crate::json::{parse, Json};
+
+    /// The value at a path of object keys.
+    fn get<'a>(json: &'a Json, path: &[&str]) -> &'a Json {
+        path.iter().fold(json, |json, key| match json {
+            Json::Obj(pairs) => &pairs.iter().find(|(k, _)| k == key).expect(key).1,
+            _ => panic!("{} is not in an object", key),
+        })
+    }
+
+    fn strs(json: &Json) -> Vec<&str> {
+        match json {
+            Json::Arr(items) => items
+                .iter()
+                .map(|item| match item {
+                    Json::Str(s) => &s[..],
+                    _ => panic!("not a string"),
+                })
+                .collect(),
+            _ => panic!("not an array"),
+        }
+    }
+
+    #[test]
+    fn json_schema() {
+        let schema = parse(&Spec::json_schema()).unwrap();
+        let props = get(&schema, &["properties"]);
+        assert_eq!(
+            strs(get(props, &["parser", "enum"])),
+            ["getopt", "reentrant", "posix", "argp"]
+        );
+        assert!(matches!(get(props, &["include", "type"]), Json::Str(t) if t == "array"));
+        let item = get(&schema, &["definitions", "NonPositionalItem"]);
+        assert_eq!(
+            strs(get(item, &["properties", "c_type", "enum"])),
+            ["char*", "int"]
+        );
+        assert_eq!(strs(get(item, &["required"])), ["c_var", "c_type", "long"]);
+        assert!(matches!(
+            get(&schema, &["definitions", "Subcommand", "properties", "subcommand", "items", "$ref"]),
+            Json::Str(r) if r == "#/definitions/Subcommand"
+        ));
+    }
+}
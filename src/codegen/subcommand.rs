@@ -0,0 +1,890 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `[[subcommand]]`: git-style commands chosen by the first argument, each
+//! with its own items, usage and parse_args, and parse_command to choose.
+//! `[global]` options are taken by every subcommand, before or after its
+//! name.
+
+use super::{
+    help_line, is_identifier, CStd, LaidOut, NonPositionalItem, OnError, Parser, PositionalItem,
+    Spec, ValidationError, HELP_PREFIX, USER_BEGIN, USER_END,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// A command of a git-style program, chosen by the first argument, which
+/// may have subcommands of its own instead of items.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Subcommand {
+    name: String,
+    /// Describes the command in the program's usage.
+    about: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    positional: Vec<PositionalItem>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    non_positional: Vec<NonPositionalItem>,
+    subcommand: Option<Vec<Subcommand>>,
+    /// Called by the generated main with the parsed args struct, returning
+    /// the program's exit status.
+    handler: Option<String>,
+}
+impl Subcommand {
+    /// The name the command line gives the subcommand by.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// What the program's usage says of the subcommand, if anything.
+    pub fn about(&self) -> Option<&str> {
+        self.about.as_deref()
+    }
+    /// The function the generated main calls with the parsed arguments, if
+    /// any.
+    pub fn handler(&self) -> Option<&str> {
+        self.handler.as_deref()
+    }
+    /// The subcommand's positional arguments, in order.
+    pub fn positional(&self) -> &[PositionalItem] {
+        &self.positional
+    }
+    /// The subcommand's options, without the global ones.
+    pub fn non_positional(&self) -> &[NonPositionalItem] {
+        &self.non_positional
+    }
+    /// The name as it goes into C identifiers.
+    fn ident(&self) -> String {
+        self.name.replace('-', "_")
+    }
+    /// The subcommand's own subcommands, if it has them instead of items.
+    pub fn subcommands(&self) -> &[Subcommand] {
+        self.subcommand.as_deref().unwrap_or(&[])
+    }
+}
+
+/// Options every subcommand takes.
+#[derive(Clone, Deserialize, Serialize)]
+pub(super) struct Global {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    non_positional: Vec<NonPositionalItem>,
+}
+
+/// A subcommand, after those it is under, outermost first. The empty path
+/// is the program itself.
+type Path<'a> = Vec<&'a Subcommand>;
+
+/// The path's names joined for a C identifier.
+fn path_ident(path: &[&Subcommand]) -> String {
+    path.iter()
+        .map(|sub| sub.ident())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The path's names as typed on the command line.
+fn path_words(path: &[&Subcommand]) -> String {
+    path.iter()
+        .map(|sub| &sub.name[..])
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a subcommand's name is a letter followed by letters, digits,
+/// dashes and underscores.
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c == '-' || c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Every line of code, indented one level further.
+fn indent(code: &str) -> String {
+    code.split_inclusive('\n')
+        .map(|line| match line {
+            "\n" => line.to_owned(),
+            _ => format!("\t{}", line),
+        })
+        .collect()
+}
+
+impl Spec {
+    pub(super) fn subcommands(&self) -> &[Subcommand] {
+        self.subcommand.as_deref().unwrap_or(&[])
+    }
+    pub(super) fn globals(&self) -> &[NonPositionalItem] {
+        match &self.global {
+            Some(global) => &global.non_positional,
+            None => &[],
+        }
+    }
+    /// Whether the program's first argument chooses a subcommand.
+    pub fn has_subcommands(&self) -> bool {
+        !self.subcommands().is_empty()
+    }
+    /// The subcommands of the program, or of the subcommand at the end of
+    /// the path.
+    fn children<'a>(&'a self, path: &[&'a Subcommand]) -> &'a [Subcommand] {
+        match path.last() {
+            Some(sub) => sub.subcommands(),
+            None => self.subcommands(),
+        }
+    }
+    /// The path to every subcommand, each before its own subcommands.
+    fn command_paths(&self) -> Vec<Path<'_>> {
+        let mut paths = vec![];
+        let mut pending: Vec<Path> = self
+            .subcommands()
+            .iter()
+            .rev()
+            .map(|sub| vec![sub])
+            .collect();
+        while let Some(path) = pending.pop() {
+            let last = path[path.len() - 1];
+            pending.extend(last.subcommands().iter().rev().map(|sub| {
+                let mut path = path.clone();
+                path.push(sub);
+                path
+            }));
+            paths.push(path);
+        }
+        paths
+    }
+    /// The paths to the subcommands that parse arguments, rather than
+    /// choose a subcommand of their own.
+    pub(super) fn leaf_paths(&self) -> Vec<Path<'_>> {
+        let mut paths = self.command_paths();
+        paths.retain(|path| path[path.len() - 1].subcommands().is_empty());
+        paths
+    }
+    /// The spec of a subcommand's parser: this spec's settings with the
+    /// subcommand's items, its functions and struct named after it.
+    pub(super) fn subcommand_spec(&self, path: &[&Subcommand]) -> Spec {
+        let sub = path[path.len() - 1];
+        let mut spec = self.clone();
+        spec.subcommand = None;
+        spec.global = None;
+        spec.positional = sub.positional.clone();
+        spec.non_positional = sub.non_positional.clone();
+        spec.non_positional.extend(self.globals().iter().cloned());
+        spec.prefix = Some(self.sym(&path_ident(path)));
+        spec.args_struct = self
+            .args_struct
+            .as_ref()
+            .map(|name| format!("{}_{}", path_ident(path), name));
+        spec.emit_main = Some(false);
+        spec.command = Some(path_words(path));
+        spec
+    }
+    /// The spec with the items of every subcommand, for what their parsers
+    /// share: the headers and helpers.
+    pub(super) fn merged_spec(&self) -> Spec {
+        let mut spec = self.clone();
+        spec.subcommand = None;
+        spec.global = None;
+        spec.non_positional.extend(self.globals().iter().cloned());
+        for path in self.leaf_paths() {
+            let sub = path[path.len() - 1];
+            spec.positional.extend(sub.positional.iter().cloned());
+            spec.non_positional
+                .extend(sub.non_positional.iter().cloned());
+        }
+        spec
+    }
+    pub(super) fn subcommand_problems(&self) -> Vec<ValidationError> {
+        let mut errors = vec![];
+        let unsupported = [
+            (
+                "positional or non_positional items outside a subcommand",
+                !self.positional.is_empty() || !self.non_positional.is_empty(),
+            ),
+            ("accessors", self.has_accessors()),
+            ("parser = \"argp\"", self.parser() == Parser::Argp),
+            ("post_parse_fn", self.post_parse_fn.is_some()),
+        ];
+        for (feature, _) in unsupported.iter().filter(|(_, used)| *used) {
+            errors.push(ValidationError::SubcommandsUnsupported(String::from(
+                *feature,
+            )));
+        }
+        let paths = self.command_paths();
+        for (i, path) in paths.iter().enumerate() {
+            let sub = path[path.len() - 1];
+            if !is_name(&sub.name) {
+                errors.push(ValidationError::BadSubcommand(path_words(path)));
+            }
+            // siblings must differ, and so must the C names of all of them
+            if paths[..i]
+                .iter()
+                .any(|other| path_ident(other) == path_ident(path))
+            {
+                errors.push(ValidationError::DuplicateSubcommand(path_words(path)));
+            }
+            if let Some(handler) = &sub.handler {
+                if !is_identifier(handler) {
+                    errors.push(ValidationError::BadIdent(
+                        format!("{} handler", path_words(path)),
+                        handler.to_owned(),
+                    ));
+                }
+                if self.args_struct.is_none() {
+                    errors.push(ValidationError::HandlerNeedsStruct(path_words(path)));
+                }
+            }
+            for npi in &sub.non_positional {
+                let clash = self.globals().iter().find_map(|global| {
+                    if global.c_var == npi.c_var {
+                        Some(&npi.c_var)
+                    } else if global.long == npi.long {
+                        Some(&npi.long)
+                    } else if global.short.is_some() && global.short == npi.short {
+                        npi.short.as_ref()
+                    } else {
+                        None
+                    }
+                });
+                if let Some(name) = clash {
+                    errors.push(ValidationError::GlobalConflict(
+                        path_words(path),
+                        name.to_owned(),
+                    ));
+                }
+            }
+            if sub.subcommands().is_empty() {
+                // a global's problems are every leaf's, and reported once
+                for e in self.subcommand_spec(path).problems() {
+                    if !errors.iter().any(|seen| seen.to_string() == e.to_string()) {
+                        errors.push(e);
+                    }
+                }
+            } else if !sub.positional.is_empty()
+                || !sub.non_positional.is_empty()
+                || sub.handler.is_some()
+            {
+                errors.push(ValidationError::SubcommandHasItems(path_words(path)));
+            }
+        }
+        errors
+    }
+    /// The subcommand's constant in the command enum.
+    fn command_const(&self, path: &[&Subcommand]) -> String {
+        format!("{}_{}", self.sym("command"), path_ident(path)).to_uppercase()
+    }
+    /// The enum of subcommands parse_command returns.
+    fn cgen_command_enum(&self) -> String {
+        let consts: Vec<String> = self
+            .leaf_paths()
+            .iter()
+            .map(|path| format!("\t{}", self.command_const(path)))
+            .collect();
+        format!(
+            "enum {} {{\n{}\n}};\n",
+            self.sym("command"),
+            consts.join(",\n")
+        )
+    }
+    /// The structs of every subcommand that has one.
+    fn cgen_command_structs(&self) -> String {
+        self.leaf_paths()
+            .iter()
+            .map(|path| self.subcommand_spec(path).cgen_struct())
+            .filter(|st| !st.is_empty())
+            .map(|st| format!("\n{}", st))
+            .collect()
+    }
+    /// Prototypes of the subcommands' handlers.
+    fn cgen_handler_decls(&self) -> String {
+        let decls: String = self
+            .leaf_paths()
+            .iter()
+            .filter_map(|path| {
+                let handler = path[path.len() - 1].handler.as_ref()?;
+                let spec = self.subcommand_spec(path);
+                Some(format!(
+                    "int {}(struct {} *args);\n",
+                    handler,
+                    spec.struct_name()?
+                ))
+            })
+            .collect();
+        match decls.is_empty() {
+            true => decls,
+            false => format!("\n{}", decls),
+        }
+    }
+    fn cgen_command_signature(&self) -> String {
+        format!("int {}(int *argc, char ***argv)", self.sym("parse_command"))
+    }
+    /// The usage of the program, or of a subcommand with subcommands.
+    fn command_usage_name(&self, path: &[&Subcommand]) -> String {
+        match path {
+            [] => self.sym("usage"),
+            _ => self.sym(&format!("{}_usage", path_ident(path))),
+        }
+    }
+    fn cgen_command_usage_signature(&self, path: &[&Subcommand]) -> String {
+        format!(
+            "void {}(const char *progname)",
+            self.command_usage_name(path)
+        )
+    }
+    /// The usage of the program, or of a subcommand with subcommands,
+    /// listing the subcommands.
+    fn cgen_command_usage(&self, path: &[&Subcommand]) -> String {
+        let commands: Vec<(String, String)> = self
+            .children(path)
+            .iter()
+            .map(|sub| (sub.name.clone(), sub.about.clone().unwrap_or_default()))
+            .collect();
+        let long = self.has_long_options();
+        let mut options = vec![(
+            String::from(if long { "-h, --help" } else { "-h" }),
+            String::from("print this usage and exit"),
+        )];
+        let globals = self.globals();
+        options.extend(self.help_order(globals).into_iter().map(|i| {
+            let npi = &globals[i];
+            (npi.help_label(long), npi.help_text(long))
+        }));
+        let width = commands
+            .iter()
+            .chain(options.iter())
+            .map(|(label, _)| label.len() + 2)
+            .max()
+            .unwrap_or(0);
+        let mut lines = format!("{}\\nCommands:\\n\"\n", HELP_PREFIX);
+        for (name, about) in &commands {
+            lines.push_str(&help_line(name, about, width));
+        }
+        lines.push_str(&format!("{}\\nOptions:\\n\"\n", HELP_PREFIX));
+        for (label, descr) in &options {
+            lines.push_str(&help_line(label, descr, width));
+        }
+        let words = match path {
+            [] => String::new(),
+            _ => format!(" {}", path_words(path)),
+        };
+        if self.std() == CStd::C89 {
+            // C89 compilers need only support 509 characters in a string
+            // literal, so print each line of help on its own.
+            let lines: String = lines
+                .lines()
+                .map(|line| format!("\tfputs({}, stdout);\n", line.trim_start()))
+                .collect();
+            return format!(
+                "{}{} {{\n\
+                 \tprintf(\"usage: %s{} COMMAND [options] [ARG...]\\n\", progname);\n\
+                 {}\
+                 }}\n",
+                self.usage_linkage().specifier(),
+                self.cgen_command_usage_signature(path),
+                words,
+                lines
+            );
+        }
+        format!(
+            "{}{} {{\n\
+             \tprintf(\"usage: %s{} COMMAND [options] [ARG...]\\n%s\", progname,\n\
+             {}\t       );\n\
+             }}\n",
+            self.usage_linkage().specifier(),
+            self.cgen_command_usage_signature(path),
+            words,
+            lines
+        )
+    }
+    /// The part of parse_command choosing among the subcommands of the
+    /// program, or of the subcommand at the end of the path, named by
+    /// args[depth + 1].
+    fn cgen_dispatch(&self, path: &[&Subcommand]) -> String {
+        let depth = path.len() + 1;
+        let mut code = String::new();
+        if !self.globals().is_empty() {
+            code.push_str(&format!("args_global_first(*argc, args, {});\n", depth));
+        }
+        code.push_str(&format!("if (*argc > {}) {{\n", depth));
+        for sub in self.children(path) {
+            let mut path = path.to_vec();
+            path.push(sub);
+            let chosen = if sub.subcommands().is_empty() {
+                format!(
+                    "args[{0}] = args[0];\n\
+                     *argc -= {0};\n\
+                     *argv += {0};\n\
+                     return {1};\n",
+                    depth,
+                    self.command_const(&path)
+                )
+            } else {
+                self.cgen_dispatch(&path)
+            };
+            code.push_str(&format!(
+                "\tif (strcmp(args[{}], \"{}\") == 0) {{\n{}\t}}\n",
+                depth,
+                sub.name,
+                indent(&indent(&chosen))
+            ));
+        }
+        code.push_str(&format!(
+            "}}\n{}(args[0]);\n{}\n",
+            self.command_usage_name(path),
+            match self.on_error() {
+                OnError::Exit => "exit(1);",
+                OnError::Return => "return -1;",
+            }
+        ));
+        code
+    }
+    /// Creates args_global_first, which moves the first argument from
+    /// argv[first] on that is neither a global option nor an option's value
+    /// to argv[first], so that global options can come before a
+    /// subcommand's name.
+    fn cgen_global_first(&self) -> String {
+        let valued: Vec<&NonPositionalItem> =
+            self.globals().iter().filter(|npi| !npi.is_flag()).collect();
+        let mut code = String::new();
+        if !valued.is_empty() {
+            // whether the option's value is the next argument
+            code.push_str("static int args_global_value(const char *arg) {\n");
+            if self.has_long_options() {
+                code.push_str("\tif (arg[1] == '-') {\n");
+                let longs = valued
+                    .iter()
+                    .flat_map(|npi| std::iter::once(&npi.long).chain(npi.aliases.iter().flatten()));
+                for long in longs {
+                    code.push_str(&if self.allows_abbrev() {
+                        format!(
+                            "\t\tif (strncmp(arg + 2, \"{0}\", strlen(arg + 2)) == 0) {{\n\t\t\treturn 1;\n\t\t}}\n",
+                            long
+                        )
+                    } else {
+                        format!(
+                            "\t\tif (strcmp(arg + 2, \"{}\") == 0) {{\n\t\t\treturn 1;\n\t\t}}\n",
+                            long
+                        )
+                    });
+                }
+                code.push_str("\t\treturn 0;\n\t}\n");
+            }
+            let shorts: String = valued
+                .iter()
+                .filter_map(|npi| npi.short.as_deref())
+                .collect();
+            if shorts.is_empty() {
+                code.push_str("\treturn 0;\n}\n\n");
+            } else {
+                code.push_str(&format!(
+                    "\tfor (arg++; *arg; arg++) {{\n\
+                     \t\tif (strchr(\"{}\", *arg) != NULL) {{\n\
+                     \t\t\treturn arg[1] == '\\0';\n\
+                     \t\t}}\n\
+                     \t}}\n\
+                     \treturn 0;\n\
+                     }}\n\n",
+                    shorts
+                ));
+            }
+        }
+        code.push_str(&format!(
+            "static void args_global_first(int argc, char **argv, int first) {{\n\
+             \tint i = first;\n\
+             \tchar *name;\n\
+             \twhile (i < argc && argv[i][0] == '-' && argv[i][1] != '\\0' && strcmp(argv[i], \"--\") != 0) {{\n\
+             \t\t{}\n\
+             \t}}\n\
+             \tif (i >= argc || i == first) {{\n\
+             \t\treturn;\n\
+             \t}}\n\
+             \tname = argv[i];\n\
+             \tmemmove(&argv[first + 1], &argv[first], (size_t)(i - first) * sizeof *argv);\n\
+             \targv[first] = name;\n\
+             }}\n\n",
+            if valued.is_empty() {
+                "i++;"
+            } else {
+                "i += args_global_value(argv[i]) ? 2 : 1;"
+            }
+        ));
+        code
+    }
+    /// Creates parse_command, which picks the subcommand the first arguments
+    /// name and leaves argc and argv as the subcommand's parse_args takes
+    /// them: the arguments after its name, with the program name before.
+    fn cgen_parse_command(&self) -> String {
+        format!(
+            "{}{} {{\n\tchar **args = *argv;\n{}}}\n",
+            self.parse_args_linkage().specifier(),
+            self.cgen_command_signature(),
+            indent(&self.cgen_dispatch(&[]))
+        )
+    }
+    /// Creates main, parsing each subcommand's arguments into variables of
+    /// its own, then calling its handler if it has one.
+    fn cgen_command_main(&self) -> String {
+        let mut main = format!(
+            "int main(int argc, char **argv) {{\n\tswitch ({}(&argc, &argv)) {{\n",
+            self.sym("parse_command")
+        );
+        for path in self.leaf_paths() {
+            let spec = self.subcommand_spec(&path);
+            let mut case = match spec.struct_name() {
+                Some(name) => format!("\tstruct {} args;\n\n", name),
+                None => format!("{}\n", spec.cgen_vars()),
+            };
+            case.push_str(&spec.cgen_main_call(spec.cgen_call()));
+            match &path[path.len() - 1].handler {
+                Some(handler) => case.push_str(&format!("\treturn {}(&args);\n", handler)),
+                None => case.push_str(&format!(
+                    "\t{}\n\t/* call your {} code here */\n\t{}\n\tbreak;\n",
+                    USER_BEGIN,
+                    path_words(&path),
+                    USER_END
+                )),
+            }
+            main.push_str(&format!(
+                "\tcase {}: {{\n{}\t}}\n",
+                self.command_const(&path),
+                indent(&case)
+            ));
+        }
+        main.push_str("\tdefault:\n\t\treturn 1;\n\t}\n\treturn 0;\n}\n");
+        main
+    }
+    /// What a companion header declares for a program with subcommands.
+    pub(super) fn cgen_command_header_decls(&self) -> String {
+        let mut decls = format!(
+            "{}{}{}\n",
+            self.cgen_command_enum(),
+            self.cgen_command_structs(),
+            self.cgen_handler_decls()
+        );
+        if self.usage_linkage() == super::Linkage::Extern {
+            decls.push_str(&format!("{};\n", self.cgen_command_usage_signature(&[])));
+            for path in self.command_paths() {
+                if !path[path.len() - 1].subcommands().is_empty() {
+                    decls.push_str(&format!("{};\n", self.cgen_command_usage_signature(&path)));
+                }
+            }
+        }
+        if self.parse_args_linkage() == super::Linkage::Extern {
+            decls.push_str(&format!("{};\n", self.cgen_command_signature()));
+        }
+        for path in self.leaf_paths() {
+            decls.push_str(&self.subcommand_spec(&path).cgen_header_decls());
+        }
+        decls
+    }
+    /// Generates the usage of the program and of every subcommand with
+    /// subcommands, parse_command, and every other subcommand's usage and
+    /// parse_args, as write_source does for a spec without subcommands: a
+    /// part at a time, each laid out as it is generated.
+    pub(super) fn write_commands<W: Write + ?Sized>(
+        &self,
+        header: Option<&str>,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let merged = self.merged_spec();
+        let mut h = self.cgen_stamp();
+        h.push_str(&merged.cgen_headers());
+        if let Some(header) = header {
+            h.push_str(&format!("#include \"{}\"\n", header));
+        }
+        let asserts = merged.cgen_static_asserts();
+        if !asserts.is_empty() {
+            h.push_str(&format!("\n{}", asserts));
+        }
+        if header.is_none() {
+            h.push_str(&format!(
+                "\n{}{}{}",
+                self.cgen_command_enum(),
+                self.cgen_command_structs(),
+                self.cgen_handler_decls()
+            ));
+        }
+        let mut out = LaidOut::new(self.cgen(), out);
+        out.write(&format!("{}\n\n", h))?;
+        let mut inner = vec![vec![]];
+        inner.extend(
+            self.command_paths()
+                .into_iter()
+                .filter(|path| !path[path.len() - 1].subcommands().is_empty()),
+        );
+        for path in &inner {
+            out.write(&if self.emits_usage() {
+                self.cgen_command_usage(path)
+            } else {
+                format!(
+                    "{}{};\n",
+                    self.usage_linkage().specifier(),
+                    self.cgen_command_usage_signature(path)
+                )
+            })?;
+            out.write("\n")?;
+        }
+        if self.emits_parse_args() {
+            out.write(&merged.cgen_helpers())?;
+        }
+        for path in self.leaf_paths() {
+            let spec = self.subcommand_spec(&path);
+            let ir = spec.analyze();
+            if spec.emits_usage() {
+                out.write(&spec.cgen_usage(&ir))?;
+            } else {
+                out.write(&format!(
+                    "{}{};\n",
+                    spec.usage_linkage().specifier(),
+                    spec.cgen_usage_signature()
+                ))?;
+            }
+            out.write("\n")?;
+            if spec.emits_parse_args() {
+                out.write(&spec.cgen_decl(&ir))?;
+            } else {
+                out.write(&format!(
+                    "{}{}{};\n",
+                    spec.cgen_signature_doc(),
+                    spec.parse_args_linkage().specifier(),
+                    spec.cgen_signature()
+                ))?;
+            }
+            if spec.has_cleanup() {
+                out.write(&format!("\n{}", spec.cgen_cleanup()))?;
+            }
+            if spec.has_dump() {
+                out.write(&format!("\n{}", spec.cgen_dump()))?;
+            }
+            out.write("\n")?;
+        }
+        if self.emits_parse_args() {
+            if !self.globals().is_empty() {
+                out.write(&self.cgen_global_first())?;
+            }
+            out.write(&self.cgen_parse_command())?;
+        } else {
+            out.write(&format!(
+                "{}{};\n",
+                self.parse_args_linkage().specifier(),
+                self.cgen_command_signature()
+            ))?;
+        }
+        if self.emits_main() {
+            out.write(&format!("\n{}", self.cgen_command_main()))?;
+        }
+        out.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+    use std::process::Command;
+
+    const SPEC: &str = r#"
+        [[subcommand]]
+        name = "fetch"
+        about = "download objects"
+
+        [[subcommand.positional]]
+        c_var = "remote"
+        c_type = "char*"
+        help_name = "REMOTE"
+        required = true
+
+        [[subcommand.non_positional]]
+        c_var = "depth"
+        c_type = "int"
+        long = "depth"
+        default = "1"
+
+        [[subcommand]]
+        name = "cherry-pick"
+
+        [[subcommand.non_positional]]
+        c_var = "no_commit"
+        c_type = "int"
+        long = "no-commit"
+        short = "n"
+        flag = true
+
+        [[subcommand]]
+        name = "remote"
+        about = "manage remotes"
+
+        [[subcommand.subcommand]]
+        name = "add"
+
+        [[subcommand.subcommand.positional]]
+        c_var = "name"
+        c_type = "char*"
+        help_name = "NAME"
+        required = true
+        "#;
+
+    #[test]
+    fn subcommands() {
+        let c = Spec::from_str(SPEC).unwrap().gen();
+        assert!(c.contains(
+            "enum command {\n\tCOMMAND_FETCH,\n\tCOMMAND_CHERRY_PICK,\n\tCOMMAND_REMOTE_ADD\n};\n"
+        ));
+        assert!(c.contains("\t       \"  fetch        download objects\\n\"\n"));
+        assert!(c.contains(
+            "\nvoid fetch_parse_args(int argc, char **argv, int *depth, char* *remote) {\n"
+        ));
+        assert!(c.contains("\tprintf(\"usage: %s cherry-pick [options]\\n%s\", progname,\n"));
+        assert!(c.contains(
+            "\tprintf(\"usage: %s remote COMMAND [options] [ARG...]\\n%s\", progname,\n"
+        ));
+        assert!(c.contains("\tprintf(\"usage: %s remote add [options] NAME\\n%s\", progname,\n"));
+        assert!(c.contains(
+            "\t\tif (strcmp(args[1], \"cherry-pick\") == 0) {\n\
+             \t\t\targs[1] = args[0];\n\
+             \t\t\t*argc -= 1;\n\
+             \t\t\t*argv += 1;\n\
+             \t\t\treturn COMMAND_CHERRY_PICK;\n"
+        ));
+        assert!(c.contains("\t\t\tremote_usage(args[0]);\n\t\t\texit(1);\n"));
+        assert!(c.contains("\t\tcherry_pick_parse_args(argc, argv, &no_commit);\n"));
+        assert!(c.contains("\t\tremote_add_parse_args(argc, argv, &name);\n"));
+
+        let e = Spec::from_str(&format!("{}\n[[subcommand]]\nname = \"fetch\"\n", SPEC))
+            .err()
+            .unwrap();
+        assert_eq!(e.0[0].message, "duplicate subcommand: \"fetch\"");
+        let e = Spec::from_str(&format!(
+            "{}\n[[subcommand.subcommand]]\nname = \"add\"\n",
+            SPEC
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(e.0[0].message, "duplicate subcommand: \"remote add\"");
+        let e = Spec::from_str(&format!(
+            "{}\n[[subcommand]]\nname = \"remote_add\"\n",
+            SPEC
+        ))
+        .err()
+        .unwrap();
+        assert_eq!(e.0[0].message, "duplicate subcommand: \"remote_add\"");
+    }
+
+    #[test]
+    fn handlers() {
+        let spec = SPEC.replace(
+            "name = \"cherry-pick\"\n",
+            "name = \"cherry-pick\"\nhandler = \"cherry_pick\"\n",
+        );
+        let e = Spec::from_str(&spec).err().unwrap();
+        assert_eq!(
+            e.0[0].message,
+            "subcommand \"cherry-pick\" has a handler, which is given its args_struct, but there is none"
+        );
+        let c = Spec::from_str(&format!("args_struct = \"args\"\n{}", spec))
+            .unwrap()
+            .gen();
+        assert!(c.contains("\nint cherry_pick(struct cherry_pick_args *args);\n"));
+        assert!(c.contains(
+            "\t\tcherry_pick_parse_args(argc, argv, &args);\n\n\t\treturn cherry_pick(&args);\n"
+        ));
+        assert!(c.contains("\t\t/* call your remote add code here */\n"));
+    }
+
+    #[test]
+    fn globals() {
+        let spec = format!(
+            "{}\n\
+             [[global.non_positional]]\n\
+             c_var = \"log\"\n\
+             c_type = \"char*\"\n\
+             long = \"log\"\n\
+             short = \"l\"\n\
+             default = \"\\\"warn\\\"\"\n",
+            SPEC
+        );
+        let c = Spec::from_str(&spec).unwrap().gen();
+        assert!(c.contains("\t       \"  -l, --log <arg>  (default: \\\"warn\\\")\\n\"\n"));
+        assert!(c.contains(
+            "void fetch_parse_args(int argc, char **argv, int *depth, char* *log, char* *remote) {\n"
+        ));
+        assert!(c.contains("\tchar **args = *argv;\n\targs_global_first(*argc, args, 1);\n"));
+        assert!(c.contains("\t\t\targs_global_first(*argc, args, 2);\n"));
+
+        let e = Spec::from_str(&spec.replace("c_var = \"depth\"", "c_var = \"log\""))
+            .err()
+            .unwrap();
+        assert_eq!(
+            e.0[0].message,
+            "subcommand \"fetch\" has an option named log, as a global option is"
+        );
+    }
+
+    #[test]
+    fn subcommands_run() {
+        let dir = std::env::temp_dir();
+        let src = dir.join("argen_subcommands.c");
+        let bin = dir.join("argen_subcommands");
+        let mut s = Spec::from_str(SPEC).unwrap();
+        s.set_emit_main(false);
+        let c = format!(
+            "{}\n\
+             int main(int argc, char **argv) {{\n\
+             \tint depth, no_commit;\n\
+             \tchar *remote, *name;\n\
+             \tswitch (parse_command(&argc, &argv)) {{\n\
+             \tcase COMMAND_FETCH:\n\
+             \t\tfetch_parse_args(argc, argv, &depth, &remote);\n\
+             \t\tprintf(\"fetch %s %d\\n\", remote, depth);\n\
+             \t\tbreak;\n\
+             \tcase COMMAND_CHERRY_PICK:\n\
+             \t\tcherry_pick_parse_args(argc, argv, &no_commit);\n\
+             \t\tprintf(\"cherry-pick %d\\n\", no_commit);\n\
+             \t\tbreak;\n\
+             \tcase COMMAND_REMOTE_ADD:\n\
+             \t\tremote_add_parse_args(argc, argv, &name);\n\
+             \t\tprintf(\"remote add %s\\n\", name);\n\
+             \t\tbreak;\n\
+             \t}}\n\
+             \treturn 0;\n\
+             }}\n",
+            s.gen()
+        );
+        std::fs::write(&src, c).unwrap();
+        let status = Command::new("cc")
+            .args(["-Wall", "-Wextra", "-Werror", "-o"])
+            .arg(&bin)
+            .arg(&src)
+            .status();
+        match status {
+            Ok(status) => assert!(status.success(), "cc rejected {}", src.display()),
+            Err(_) => return, // no compiler to run the program with
+        }
+        let run = |args: &[&str]| Command::new(&bin).args(args).output().unwrap();
+        let out = run(&["fetch", "--depth", "3", "origin"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "fetch origin 3\n");
+        let out = run(&["cherry-pick", "-n"]);
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "cherry-pick 1\n");
+        let out = run(&["remote", "add", "upstream"]);
+        assert_eq!(
+            String::from_utf8_lossy(&out.stdout),
+            "remote add upstream\n"
+        );
+        let out = run(&["fetch"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains(" fetch [options] REMOTE\n"));
+        assert!(!out.status.success());
+        let out = run(&["remote"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains(" remote COMMAND [options]"));
+        assert!(!out.status.success());
+        let out = run(&["merge"]);
+        assert!(String::from_utf8_lossy(&out.stdout).contains("\nCommands:\n"));
+        assert!(!out.status.success());
+    }
+}
@@ -0,0 +1,481 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `--template`: text of any kind rendered from a spec by a template in the
+//! part of Handlebars' syntax that text needs: values, `#if`, `#unless` and
+//! `#each` blocks with `else`, comments, and `~` to trim whitespace. What a
+//! template sees is the spec's description, as `--describe-json` writes it,
+//! with the program's name as `program`. Nothing is escaped, as the output
+//! is not HTML, and a name that isn't there is an error rather than nothing,
+//! so a misspelling shows.
+
+use super::Spec;
+use crate::json::Json;
+use std::error::Error;
+use std::fmt;
+
+/// Why a template couldn't be read or rendered, at a line of it, counting
+/// from 1.
+#[derive(Debug, PartialEq)]
+pub struct TemplateError {
+    /// The line of the template.
+    pub line: usize,
+    /// What is wrong there.
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for TemplateError {}
+
+fn error(line: usize, message: String) -> TemplateError {
+    TemplateError { line, message }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Block {
+    If,
+    Unless,
+    Each,
+}
+
+impl Block {
+    fn from_name(name: &str) -> Option<Block> {
+        match name {
+            "if" => Some(Block::If),
+            "unless" => Some(Block::Unless),
+            "each" => Some(Block::Each),
+            _ => None,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Block::If => "if",
+            Block::Unless => "unless",
+            Block::Each => "each",
+        }
+    }
+}
+
+enum Node {
+    Text(String),
+    Value {
+        path: String,
+        line: usize,
+    },
+    Block {
+        block: Block,
+        path: String,
+        line: usize,
+        body: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+/// A block whose closing tag is still to come.
+struct Open {
+    block: Block,
+    path: String,
+    line: usize,
+    body: Vec<Node>,
+    /// What comes after its {{else}}, once there is one.
+    otherwise: Option<Vec<Node>>,
+}
+
+/// Where the next node goes: in the innermost open block, or at the top.
+fn current<'a>(root: &'a mut Vec<Node>, open: &'a mut [Open]) -> &'a mut Vec<Node> {
+    match open.last_mut() {
+        Some(Open {
+            otherwise: Some(otherwise),
+            ..
+        }) => otherwise,
+        Some(open) => &mut open.body,
+        None => root,
+    }
+}
+
+/// A template, read and ready to render.
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    /// Reads a template, finding what is wrong with its tags.
+    pub fn parse(source: &str) -> Result<Template, TemplateError> {
+        let line_of = |at: usize| source[..at].matches('\n').count() + 1;
+        let mut root = vec![];
+        let mut open: Vec<Open> = vec![];
+        let mut pos = 0;
+        let mut trim_next = false;
+        while let Some(found) = source[pos..].find("{{") {
+            let start = pos + found;
+            let line = line_of(start);
+            let rest = &source[start..];
+            let (skip, close) = match () {
+                _ if rest.starts_with("{{{") => (3, "}}}"),
+                _ if rest.starts_with("{{!--") || rest.starts_with("{{~!--") => (2, "--}}"),
+                _ => (2, "}}"),
+            };
+            let end = source[start + skip..]
+                .find(close)
+                .map(|n| start + skip + n)
+                .ok_or_else(|| error(line, String::from("a tag is not closed")))?;
+            let after = end + close.len();
+            let inner = &source[start + skip..end];
+            let trim_before = inner.starts_with('~');
+            let trim_after = inner.ends_with('~');
+            let tag = inner.trim_start_matches('~').trim_end_matches('~').trim();
+            // a block's tag alone on its line takes the line with it
+            let mut text = &source[pos..start];
+            let mut next = after;
+            if tag.starts_with(['#', '/', '!']) || tag == "else" {
+                let line_start = source[..start].rfind('\n').map_or(0, |n| n + 1);
+                let line_end = source[after..]
+                    .find('\n')
+                    .map_or(source.len(), |n| after + n + 1);
+                if line_start >= pos
+                    && source[line_start..start].trim().is_empty()
+                    && source[after..line_end].trim().is_empty()
+                {
+                    text = &source[pos..line_start];
+                    next = line_end;
+                }
+            }
+            if trim_next {
+                text = text.trim_start();
+            }
+            if trim_before {
+                text = text.trim_end();
+            }
+            if !text.is_empty() {
+                current(&mut root, &mut open).push(Node::Text(text.to_owned()));
+            }
+            trim_next = trim_after;
+            pos = next;
+            if tag.starts_with('!') {
+                continue;
+            } else if let Some(opening) = tag.strip_prefix('#') {
+                let (name, path) = opening
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((opening, ""));
+                let block = Block::from_name(name).ok_or_else(|| {
+                    error(
+                        line,
+                        format!("there is no block named {}, only if, unless and each", name),
+                    )
+                })?;
+                let path = path.trim();
+                if path.is_empty() || path.contains(char::is_whitespace) {
+                    return Err(error(line, format!("{{{{#{}}}}} takes one name", name)));
+                }
+                open.push(Open {
+                    block,
+                    path: path.to_owned(),
+                    line,
+                    body: vec![],
+                    otherwise: None,
+                });
+            } else if tag == "else" {
+                match open.last_mut() {
+                    Some(block) if block.otherwise.is_none() => block.otherwise = Some(vec![]),
+                    _ => return Err(error(line, String::from("{{else}} is not in a block"))),
+                }
+            } else if let Some(name) = tag.strip_prefix('/') {
+                let block = open
+                    .pop()
+                    .ok_or_else(|| error(line, format!("{{{{/{}}}}} closes no block", name)))?;
+                if name.trim() != block.block.name() {
+                    return Err(error(
+                        line,
+                        format!(
+                            "{{{{/{}}}}} closes the {{{{#{}}}}} on line {}",
+                            name.trim(),
+                            block.block.name(),
+                            block.line
+                        ),
+                    ));
+                }
+                current(&mut root, &mut open).push(Node::Block {
+                    block: block.block,
+                    path: block.path,
+                    line: block.line,
+                    body: block.body,
+                    otherwise: block.otherwise.unwrap_or_default(),
+                });
+            } else if tag.is_empty() || tag.contains(char::is_whitespace) {
+                return Err(error(line, format!("{{{{{}}}}} is not a name", tag)));
+            } else {
+                current(&mut root, &mut open).push(Node::Value {
+                    path: tag.to_owned(),
+                    line,
+                });
+            }
+        }
+        let text = match trim_next {
+            true => source[pos..].trim_start(),
+            false => &source[pos..],
+        };
+        if !text.is_empty() {
+            current(&mut root, &mut open).push(Node::Text(text.to_owned()));
+        }
+        if let Some(block) = open.last() {
+            return Err(error(
+                block.line,
+                format!(
+                    "{{{{#{} {}}}}} is not closed",
+                    block.block.name(),
+                    block.path
+                ),
+            ));
+        }
+        Ok(Template { nodes: root })
+    }
+    /// Renders the template with a JSON value as what it sees.
+    pub fn render(&self, context: &Json) -> Result<String, TemplateError> {
+        let mut out = String::new();
+        let mut scopes = vec![Scope {
+            value: context,
+            index: None,
+        }];
+        render(&self.nodes, &mut scopes, &mut out)?;
+        Ok(out)
+    }
+}
+
+/// What names are looked up in: the value, and its place in the array an
+/// #each is going through, with the array's length.
+struct Scope<'a> {
+    value: &'a Json,
+    index: Option<(usize, usize)>,
+}
+
+/// What a name stands for.
+enum Value<'a> {
+    Json(&'a Json),
+    Index(usize),
+    Bool(bool),
+}
+
+impl Value<'_> {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Json(Json::Null) | Value::Json(Json::Bool(false)) | Value::Bool(false) => false,
+            Value::Json(Json::Str(s)) => !s.is_empty(),
+            Value::Json(Json::Num(n)) => *n != 0,
+            Value::Json(Json::Arr(items)) => !items.is_empty(),
+            Value::Json(_) | Value::Index(_) | Value::Bool(true) => true,
+        }
+    }
+}
+
+/// A field of an object, or an element of an array by its index.
+fn member<'a>(value: &'a Json, name: &str) -> Option<&'a Json> {
+    match value {
+        Json::Obj(pairs) => pairs.iter().find(|(k, _)| k == name).map(|(_, v)| v),
+        Json::Arr(items) => items.get(name.parse::<usize>().ok()?),
+        _ => None,
+    }
+}
+
+/// What a dotted name stands for: `this`, `@index`, `@first` or `@last`, or
+/// a field of the innermost value that has it, and a field of that, and so
+/// on. `../` looks from the scope outside.
+fn lookup<'a>(path: &str, scopes: &[Scope<'a>], line: usize) -> Result<Value<'a>, TemplateError> {
+    let missing = || error(line, format!("there is no {} here", path));
+    let mut path = path;
+    let mut scopes = scopes;
+    while let Some(rest) = path.strip_prefix("../") {
+        path = rest;
+        scopes = &scopes[..scopes.len().saturating_sub(1).max(1)];
+    }
+    let each = scopes.iter().rev().find_map(|scope| scope.index);
+    match (path, each) {
+        ("@index", Some((i, _))) => return Ok(Value::Index(i)),
+        ("@first", Some((i, _))) => return Ok(Value::Bool(i == 0)),
+        ("@last", Some((i, n))) => return Ok(Value::Bool(i + 1 == n)),
+        _ => {}
+    }
+    let innermost = scopes.last().expect("a scope").value;
+    let mut names = path.split('.');
+    let first = names.next().unwrap_or_default();
+    let mut value = match first {
+        "this" => innermost,
+        _ => scopes
+            .iter()
+            .rev()
+            .find_map(|scope| member(scope.value, first))
+            .ok_or_else(missing)?,
+    };
+    for name in names {
+        value = member(value, name).ok_or_else(missing)?;
+    }
+    Ok(Value::Json(value))
+}
+
+fn render<'a>(
+    nodes: &'a [Node],
+    scopes: &mut Vec<Scope<'a>>,
+    out: &mut String,
+) -> Result<(), TemplateError> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { path, line } => match lookup(path, scopes, *line)? {
+                Value::Json(Json::Str(s)) => out.push_str(s),
+                Value::Json(Json::Null) => {}
+                Value::Json(Json::Num(n)) => out.push_str(&n.to_string()),
+                Value::Json(Json::Bool(b)) => out.push_str(&b.to_string()),
+                Value::Bool(b) => out.push_str(&b.to_string()),
+                Value::Json(value) => out.push_str(&value.line()),
+                Value::Index(i) => out.push_str(&i.to_string()),
+            },
+            Node::Block {
+                block,
+                path,
+                line,
+                body,
+                otherwise,
+            } => {
+                let value = lookup(path, scopes, *line)?;
+                match (block, value) {
+                    (Block::Each, Value::Json(Json::Arr(items))) if !items.is_empty() => {
+                        for (i, item) in items.iter().enumerate() {
+                            scopes.push(Scope {
+                                value: item,
+                                index: Some((i, items.len())),
+                            });
+                            let rendered = render(body, scopes, out);
+                            scopes.pop();
+                            rendered?;
+                        }
+                    }
+                    (Block::Each, Value::Json(Json::Arr(_)) | Value::Json(Json::Null)) => {
+                        render(otherwise, scopes, out)?
+                    }
+                    (Block::Each, _) => {
+                        return Err(error(
+                            *line,
+                            format!("{} is not a list to go through", path),
+                        ))
+                    }
+                    (Block::If, value) if value.is_truthy() => render(body, scopes, out)?,
+                    (Block::Unless, value) if !value.is_truthy() => render(body, scopes, out)?,
+                    _ => render(otherwise, scopes, out)?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Spec {
+    /// Renders a template with the spec's description, as describe_json
+    /// writes it, and the program's name as `program`.
+    pub fn render_template(&self, template: &str, program: &str) -> Result<String, TemplateError> {
+        let template = Template::parse(template)?;
+        let context = match self.describe() {
+            Json::Obj(mut pairs) => {
+                pairs.insert(0, (String::from("program"), Json::Str(program.to_owned())));
+                Json::Obj(pairs)
+            }
+            other => other,
+        };
+        template.render(&context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+    use super::Template;
+
+    const SPEC: &str = r#"
+        [[non_positional]]
+        c_var = "verbose"
+        c_type = "int"
+        long = "verbose"
+        short = "v"
+        flag = true
+        help_descr = "say more"
+
+        [[non_positional]]
+        c_var = "out"
+        c_type = "char*"
+        long = "out"
+        help_descr = "where to write"
+
+        [[positional]]
+        c_var = "input"
+        c_type = "char*"
+        help_name = "INPUT"
+        required = true
+        "#;
+
+    #[test]
+    fn template() {
+        let s = Spec::from_str(SPEC).unwrap();
+        let out = s
+            .render_template(
+                "#!/bin/sh\n\
+                 {{! a wrapper for the program }}\n\
+                 # {{program}} {{synopsis}}\n\
+                 {{#each options}}\n\
+                 {{#unless builtin}}\n\
+                 # {{@index}}: --{{long}}{{#if short}}, -{{short}}{{/if}}: {{help}}\n\
+                 {{/unless}}\n\
+                 {{/each}}\n\
+                 {{#each positional}}{{value_name}}{{#unless @last}} {{/unless}}{{/each}}\n\
+                 {{#if settings.capture_rest}}rest{{else}}no rest{{/if}}\n\
+                 {{~#each settings.config_file}}x{{/each}}\n",
+                "frob",
+            )
+            .unwrap();
+        assert_eq!(
+            out,
+            "#!/bin/sh\n\
+             # frob [options] INPUT\n\
+             # 1: --verbose, -v: say more\n\
+             # 2: --out: where to write\n\
+             INPUT\n\
+             no rest\n"
+        );
+        let wrong = |template: &str| Template::parse(template).err();
+        assert_eq!(
+            wrong("{{#each options}}").unwrap().to_string(),
+            "line 1: {{#each options}} is not closed"
+        );
+        assert_eq!(
+            wrong("\n{{#if x}}{{/each}}").unwrap().to_string(),
+            "line 2: {{/each}} closes the {{#if}} on line 2"
+        );
+        assert_eq!(
+            wrong("{{#with x}}{{/with}}").unwrap().to_string(),
+            "line 1: there is no block named with, only if, unless and each"
+        );
+        assert_eq!(
+            s.render_template("{{#each options}}{{lung}}{{/each}}", "")
+                .unwrap_err()
+                .to_string(),
+            "line 1: there is no lung here"
+        );
+        assert!(wrong("{{else}}").is_some());
+        assert!(wrong("{{program").is_some());
+    }
+}
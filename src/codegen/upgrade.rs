@@ -0,0 +1,328 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `argen upgrade`: a spec written in an older schema of the spec format
+//! rewritten in the current one, a step at a time, so it goes on meaning
+//! what it did. Specs of older schemas are still read as they were written;
+//! upgrading is for taking up what newer schemas change.
+
+use super::canonical::{toml_to_json, write_toml};
+use super::locate::code;
+use super::{json_to_toml, Spec, ValidationError};
+use crate::json;
+
+/// The schema of the spec format argen writes, and the newest it reads.
+pub const SCHEMA: u32 = 2;
+
+type Table = toml::value::Table;
+
+/// A step from one schema to the next, rewriting a spec's table to say in
+/// the next schema what it said in its own, noting what it changed.
+type Step = fn(&mut Table, &mut Vec<String>);
+
+/// The step from each schema to the next, from schema 1 on. Steps are for
+/// fields renamed or restructured; a default stays the same across schemas.
+const STEPS: [Step; SCHEMA as usize - 1] = [to_2];
+
+/// Schema 2 is the first to name itself, and means what schema 1 does.
+fn to_2(_spec: &mut Table, _notes: &mut Vec<String>) {}
+
+/// The schema a spec says it is written in, if it is one argen reads.
+pub(super) fn schema(spec: &toml::Value) -> Result<u32, ValidationError> {
+    match spec.get("schema") {
+        None => Ok(1),
+        Some(toml::Value::Integer(n)) if (1..=i64::from(SCHEMA)).contains(n) => Ok(*n as u32),
+        Some(other) => Err(ValidationError::UnknownSchema(other.to_string())),
+    }
+}
+
+/// What upgrading a spec's table with the steps from its schema on comes
+/// to. The files it includes are left as they are: they are upgraded on
+/// their own, if they are specs.
+enum Upgraded {
+    /// The spec is already in the newest schema, or isn't a spec, which
+    /// reading it says.
+    Unchanged,
+    /// Only the schema it names changes, to this one.
+    Schema(u32),
+    /// The spec's table changes.
+    Table(toml::Value),
+}
+
+fn upgrade(
+    spec: toml::Value,
+    steps: &[Step],
+    notes: &mut Vec<String>,
+) -> Result<Upgraded, ValidationError> {
+    let from = schema(&spec)? as usize;
+    let newest = steps.len() + 1;
+    let mut table = match spec {
+        toml::Value::Table(table) if from < newest => table,
+        _ => return Ok(Upgraded::Unchanged),
+    };
+    let before = table.clone();
+    for step in &steps[from - 1..] {
+        step(&mut table, notes);
+    }
+    if table == before {
+        return Ok(Upgraded::Schema(newest as u32));
+    }
+    table.insert(String::from("schema"), toml::Value::Integer(newest as i64));
+    Ok(Upgraded::Table(toml::Value::Table(table)))
+}
+
+/// A spec's TOML with its schema set, where it already sets one, or else
+/// first, after any comment heading the spec.
+fn set_schema_toml(toml: &str, schema: u32) -> String {
+    let lines: Vec<&str> = toml.split_inclusive('\n').collect();
+    let tables = lines
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .unwrap_or(lines.len());
+    for (i, line) in lines[..tables].iter().enumerate() {
+        let code = code(line);
+        let value = code
+            .trim_start()
+            .strip_prefix("schema")
+            .and_then(|rest| rest.trim_start().strip_prefix('='));
+        if let Some(value) = value {
+            let start = code.len() - value.trim_start().len();
+            let end = code.trim_end().len();
+            let line = format!("{}{}{}", &line[..start], schema, &line[end..]);
+            return [&lines[..i], &[line.as_str()], &lines[i + 1..]]
+                .concat()
+                .concat();
+        }
+    }
+    let heading = lines
+        .iter()
+        .take_while(|line| line.trim_start().starts_with('#'))
+        .count();
+    let at = match lines.get(heading) {
+        Some(line) if heading > 0 && line.trim().is_empty() => heading + 1,
+        _ => 0,
+    };
+    let mut out = lines[..at].concat();
+    out.push_str(&format!("schema = {}\n", schema));
+    // set apart from a table, or the comment above it, with no settings
+    // between them
+    let settings = lines[at..tables.max(at)].iter().any(|line| {
+        let line = line.trim_start();
+        !line.is_empty() && !line.starts_with('#')
+    });
+    if !settings && lines.get(at).is_some_and(|line| !line.trim().is_empty()) {
+        out.push('\n');
+    }
+    out.push_str(&lines[at..].concat());
+    out
+}
+
+/// A spec's JSON with its schema set, where it already sets one, or else as
+/// its first key.
+fn set_schema_json(json: &str, schema: u32) -> String {
+    let mut depth = 0;
+    let mut string = None;
+    let mut escaped = false;
+    // whether the last string read is the spec's "schema" key
+    let mut key = false;
+    for (i, c) in json.char_indices() {
+        if let Some(start) = string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => {
+                    string = None;
+                    key = depth == 1 && &json[start..=i] == "\"schema\"";
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if c != ':' && !c.is_whitespace() {
+            key = false;
+        }
+        match c {
+            '"' => string = Some(i),
+            '{' | '[' => depth += 1,
+            '}' | ']' => depth -= 1,
+            ':' if key => {
+                let value = json[i + 1..].trim_start();
+                let start = json.len() - value.len();
+                let end = start
+                    + value
+                        .find(|c: char| c == ',' || c == '}' || c.is_whitespace())
+                        .unwrap_or(value.len());
+                return format!("{}{}{}", &json[..start], schema, &json[end..]);
+            }
+            _ => {}
+        }
+    }
+    // the spec has been read, so it is an object
+    let open = json.find('{').map_or(0, |open| open + 1);
+    let rest = &json[open..];
+    let space = &rest[..rest.len() - rest.trim_start().len()];
+    let first = match rest.trim_start().starts_with('}') {
+        true => format!("{}\"schema\": {}", space, schema),
+        false if space.is_empty() => format!("\"schema\": {}, ", schema),
+        false => format!("{}\"schema\": {},", space, schema),
+    };
+    format!("{}{}{}", &json[..open], first, rest)
+}
+
+/// A spec's TOML upgraded with the steps.
+fn upgrade_toml(toml: &str, steps: &[Step]) -> Result<(String, Vec<String>), ValidationError> {
+    let mut notes = vec![];
+    let upgraded = match upgrade(toml::from_str(toml)?, steps, &mut notes)? {
+        Upgraded::Unchanged => toml.to_owned(),
+        Upgraded::Schema(schema) => set_schema_toml(toml, schema),
+        Upgraded::Table(spec) => write_toml(&spec),
+    };
+    Ok((upgraded, notes))
+}
+
+/// A spec's JSON upgraded with the steps.
+fn upgrade_json(json: &str, steps: &[Step]) -> Result<(String, Vec<String>), ValidationError> {
+    let mut notes = vec![];
+    let spec =
+        json_to_toml(json::parse(json)?).unwrap_or_else(|| toml::Value::Table(Default::default()));
+    let upgraded = match upgrade(spec, steps, &mut notes)? {
+        Upgraded::Unchanged => json.to_owned(),
+        Upgraded::Schema(schema) => set_schema_json(json, schema),
+        Upgraded::Table(spec) => format!("{}\n", toml_to_json(spec)),
+    };
+    Ok((upgraded, notes))
+}
+
+impl Spec {
+    /// A spec's TOML rewritten in the current schema, with notes on what
+    /// was changed to keep its meaning. A spec for which that only means
+    /// setting `schema` is otherwise left as it is written; one with more
+    /// to change is written in canonical form.
+    pub fn upgrade_toml(toml: &str) -> Result<(String, Vec<String>), ValidationError> {
+        upgrade_toml(toml, &STEPS)
+    }
+    /// A spec's JSON rewritten in the current schema, as upgrade_toml does.
+    pub fn upgrade_json(json: &str) -> Result<(String, Vec<String>), ValidationError> {
+        upgrade_json(json, &STEPS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Spec, ValidationError};
+    use super::{set_schema_json, set_schema_toml, to_2, upgrade_json, upgrade_toml, Table};
+    use super::{SCHEMA, STEPS};
+
+    const OLD: &str = r#"# an old spec
+args_struct = "args"
+
+[[non_positional]]
+c_var = "verbose"
+c_type = "int"
+long = "verbose"
+flag = true
+"#;
+
+    #[test]
+    fn upgrade() {
+        let (toml, notes) = Spec::upgrade_toml(OLD).unwrap();
+        assert_eq!(toml, format!("schema = 2\n{}", OLD));
+        assert!(notes.is_empty());
+        // an old spec means what it did, before and after upgrading
+        assert!(Spec::from_str(OLD).unwrap().allows_abbrev());
+        assert!(Spec::from_str(&toml).unwrap().allows_abbrev());
+        let new = Spec::from_str(&format!("schema = {}\n{}", SCHEMA, OLD)).unwrap();
+        assert!(new.allows_abbrev());
+        // a current spec is left as it is
+        assert_eq!(Spec::upgrade_toml(&toml).unwrap(), (toml.clone(), vec![]));
+        let (json, notes) = Spec::upgrade_json("{\"allow_abbrev\": false}").unwrap();
+        assert_eq!(json, "{\"schema\": 2, \"allow_abbrev\": false}");
+        assert!(notes.is_empty());
+        for schema in ["3", "0", "\"2\""] {
+            let spec = format!("schema = {}\n", schema);
+            assert!(matches!(
+                Spec::upgrade_toml(&spec),
+                Err(ValidationError::UnknownSchema(_))
+            ));
+            assert_eq!(Spec::from_str(&spec).err().unwrap().0[0].code, "E049");
+        }
+    }
+
+    #[test]
+    fn set_schema() {
+        // where the spec sets it, or first, after a comment heading the spec
+        assert_eq!(
+            set_schema_toml("# a spec\n  schema=1 # old\nlong = 1\n", 2),
+            "# a spec\n  schema=2 # old\nlong = 1\n"
+        );
+        assert_eq!(
+            set_schema_toml("# a spec\n\nparser = \"argp\"\n", 2),
+            "# a spec\n\nschema = 2\nparser = \"argp\"\n"
+        );
+        assert_eq!(
+            set_schema_toml("# the input\n[[positional]]\nschema = 1\n", 2),
+            "schema = 2\n\n# the input\n[[positional]]\nschema = 1\n"
+        );
+        assert_eq!(set_schema_toml("", 2), "schema = 2\n");
+        assert_eq!(
+            set_schema_json("{\n  \"a\": {\"schema\": 1},\n  \"schema\" : 1\n}\n", 2),
+            "{\n  \"a\": {\"schema\": 1},\n  \"schema\" : 2\n}\n"
+        );
+        assert_eq!(
+            set_schema_json("{\n  \"positional\": []\n}\n", 2),
+            "{\n  \"schema\": 2,\n  \"positional\": []\n}\n"
+        );
+        assert_eq!(set_schema_json(" { } ", 2), " { \"schema\": 2 } ");
+    }
+
+    /// A step as a schema 3 might take, renaming args_struct.
+    fn to_3(spec: &mut Table, notes: &mut Vec<String>) {
+        if let Some(name) = spec.remove("args_struct") {
+            spec.insert(String::from("struct_name"), name);
+            notes.push(String::from("args_struct is renamed struct_name"));
+        }
+    }
+
+    #[test]
+    fn steps() {
+        assert_eq!(STEPS.len() + 1, SCHEMA as usize);
+        let steps = [to_2, to_3];
+        let (toml, notes) = upgrade_toml(OLD, &steps).unwrap();
+        assert_eq!(
+            toml,
+            "schema = 3\n\
+             struct_name = \"args\"\n\
+             \n\
+             [[non_positional]]\n\
+             c_type = \"int\"\n\
+             c_var = \"verbose\"\n\
+             flag = true\n\
+             long = \"verbose\"\n"
+        );
+        assert_eq!(notes, ["args_struct is renamed struct_name"]);
+        // each step is taken from the schema the spec names on
+        let (toml, notes) = upgrade_toml("schema = 2\nargs_struct = \"a\"\n", &steps).unwrap();
+        assert_eq!(toml, "schema = 3\nstruct_name = \"a\"\n");
+        assert_eq!(notes.len(), 1);
+        // a spec the steps don't change only has its schema set
+        let (toml, notes) = upgrade_toml("parser = \"argp\" # for --version\n", &steps).unwrap();
+        assert_eq!(toml, "schema = 3\nparser = \"argp\" # for --version\n");
+        assert!(notes.is_empty());
+        let (json, notes) = upgrade_json("{\"args_struct\": \"a\"}", &steps).unwrap();
+        assert_eq!(json, "{\n  \"schema\": 3,\n  \"struct_name\": \"a\"\n}\n");
+        assert_eq!(notes.len(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `[vars]`: strings a spec names once and refers to as `${name}` in the
+//! text of its items and settings, substituted before it is deserialized.
+
+use super::ValidationError;
+
+/// The item fields variables are substituted in.
+const ITEM_FIELDS: [&str; 4] = ["default", "help_name", "help_descr", "env"];
+/// The settings variables are substituted in.
+const SETTINGS: [&str; 3] = ["version", "bug_address", "config_file"];
+
+/// text with each ${name} replaced by the variable's value, and $$ by $.
+fn substitute(text: &str, vars: &toml::value::Table) -> Result<String, ValidationError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('{')
+            .and_then(|braced| braced.split_once('}'))
+        {
+            out.push_str(&value(name, vars)?);
+            rest = after;
+        } else {
+            // a $ starting neither is left as it is
+            out.push('$');
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn value(name: &str, vars: &toml::value::Table) -> Result<String, ValidationError> {
+    match vars.get(name) {
+        Some(toml::Value::String(value)) => Ok(value.to_owned()),
+        Some(_) => Err(ValidationError::BadVar(name.to_owned())),
+        None => Err(ValidationError::UnknownVar(name.to_owned())),
+    }
+}
+
+/// Substitutes the variables of a spec's [vars] table into the given fields
+/// of a table.
+fn substitute_fields(
+    table: &mut toml::value::Table,
+    fields: &[&str],
+    vars: &toml::value::Table,
+) -> Result<(), ValidationError> {
+    for field in fields {
+        if let Some(toml::Value::String(text)) = table.get_mut(*field) {
+            *text = substitute(text, vars)?;
+        }
+    }
+    Ok(())
+}
+
+/// Substitutes a spec's variables, taking its [vars] table out of it.
+pub(super) fn interpolate(value: toml::Value) -> Result<toml::Value, ValidationError> {
+    let mut table = match value {
+        toml::Value::Table(table) => table,
+        value => return Ok(value),
+    };
+    let vars = match table.remove("vars") {
+        Some(toml::Value::Table(vars)) => vars,
+        Some(_) => return Err(ValidationError::BadVar(String::from("vars"))),
+        None => Default::default(),
+    };
+    substitute_fields(&mut table, &SETTINGS, &vars)?;
+    for key in &["positional", "non_positional"] {
+        let items = table.get_mut(*key).and_then(|items| items.as_array_mut());
+        for item in items.into_iter().flatten() {
+            if let Some(item) = item.as_table_mut() {
+                substitute_fields(item, &ITEM_FIELDS, &vars)?;
+            }
+        }
+    }
+    Ok(toml::Value::Table(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Spec;
+
+    const SPEC: &str = r#"
+        config_file = "/etc/${product}/config"
+        positional = []
+
+        [vars]
+        product = "frob"
+
+        [[non_positional]]
+        c_var = "dir"
+        c_type = "char*"
+        long = "dir"
+        default = "\"/var/lib/${product}\""
+        help_descr = "where ${product} keeps its state, or $$FROB_DIR, $ or {}"
+        env = "FROB_DIR"
+        "#;
+
+    #[test]
+    fn vars() {
+        let s = Spec::from_str(SPEC).unwrap();
+        assert_eq!(s.config_file.as_deref(), Some("/etc/frob/config"));
+        let npi = &s.non_positional[0];
+        assert_eq!(npi.default.as_deref(), Some("\"/var/lib/frob\""));
+        assert_eq!(
+            npi.help_descr.as_deref(),
+            Some("where frob keeps its state, or $FROB_DIR, $ or {}")
+        );
+
+        let e = Spec::from_str(&SPEC.replace("${product}/config", "${prodcut}/config"))
+            .err()
+            .unwrap();
+        assert_eq!(e.0[0].message, "undefined variable ${prodcut}");
+    }
+}
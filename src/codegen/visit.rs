@@ -0,0 +1,257 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A spec's items read by a SpecVisitor, as `Spec::visit` walks them, so a
+//! linter, docs generator or migrator built on argen reads items through
+//! their accessors rather than the spec's layout. A visitor overriding a
+//! method that walks further calls the walk function of the same name to go
+//! on walking.
+
+use super::{CType, NonPositionalItem, PositionalItem, Spec, Subcommand};
+
+/// What reads a spec's items, each method called as `Spec::visit` comes to
+/// an item of its kind. Every method does nothing more than walk on unless
+/// it is overridden.
+pub trait SpecVisitor {
+    /// Visits a positional argument, of the program or of a subcommand.
+    fn visit_positional(&mut self, _item: &PositionalItem) {}
+    /// Visits an option, of the program, a subcommand or a group.
+    fn visit_option(&mut self, _item: &NonPositionalItem) {}
+    /// Visits a named group of options taken together, as `global` is of
+    /// the options every subcommand takes.
+    fn visit_group(&mut self, name: &str, options: &[NonPositionalItem]) {
+        walk_group(self, name, options)
+    }
+    /// Visits a subcommand, with its items and its own subcommands.
+    fn visit_subcommand(&mut self, subcommand: &Subcommand) {
+        walk_subcommand(self, subcommand)
+    }
+}
+
+/// Visits the spec's positional arguments, in order, its options, its
+/// groups, and then its subcommands.
+pub fn walk_spec<V: SpecVisitor + ?Sized>(visitor: &mut V, spec: &Spec) {
+    for item in &spec.positional {
+        visitor.visit_positional(item);
+    }
+    for item in &spec.non_positional {
+        visitor.visit_option(item);
+    }
+    if !spec.globals().is_empty() {
+        visitor.visit_group("global", spec.globals());
+    }
+    for subcommand in spec.subcommands() {
+        visitor.visit_subcommand(subcommand);
+    }
+}
+
+/// Visits each option of a group.
+pub fn walk_group<V: SpecVisitor + ?Sized>(
+    visitor: &mut V,
+    _name: &str,
+    options: &[NonPositionalItem],
+) {
+    for item in options {
+        visitor.visit_option(item);
+    }
+}
+
+/// Visits a subcommand's positional arguments, its options and then its own
+/// subcommands.
+pub fn walk_subcommand<V: SpecVisitor + ?Sized>(visitor: &mut V, subcommand: &Subcommand) {
+    for item in subcommand.positional() {
+        visitor.visit_positional(item);
+    }
+    for item in subcommand.non_positional() {
+        visitor.visit_option(item);
+    }
+    for subcommand in subcommand.subcommands() {
+        visitor.visit_subcommand(subcommand);
+    }
+}
+
+impl Spec {
+    /// Walks the spec's items with a visitor, as walk_spec does.
+    pub fn visit<V: SpecVisitor + ?Sized>(&self, visitor: &mut V) {
+        walk_spec(visitor, self)
+    }
+}
+
+impl PositionalItem {
+    /// The C variable the argument is parsed into.
+    pub fn c_var(&self) -> &str {
+        &self.c_var
+    }
+    /// The C type the argument is parsed into.
+    pub fn c_type(&self) -> CType {
+        self.c_type
+    }
+    /// What the usage calls the argument.
+    pub fn help_name(&self) -> &str {
+        &self.help_name
+    }
+    /// What the help says of the argument, if anything.
+    pub fn help(&self) -> Option<&str> {
+        self.help_descr.as_deref()
+    }
+    /// Whether the argument must be given.
+    pub fn required(&self) -> bool {
+        self.is_required()
+    }
+    /// The value when the argument is left out, as C would write it.
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+    /// Whether the argument takes the rest of the command line.
+    pub fn multi(&self) -> bool {
+        self.is_multi()
+    }
+}
+
+impl NonPositionalItem {
+    /// The C variable the option is parsed into.
+    pub fn c_var(&self) -> &str {
+        &self.c_var
+    }
+    /// The C type the option is parsed into.
+    pub fn c_type(&self) -> CType {
+        self.c_type
+    }
+    /// The option's long name, without the --.
+    pub fn long(&self) -> &str {
+        &self.long
+    }
+    /// The option's short name, without the -, if it has one.
+    pub fn short(&self) -> Option<char> {
+        self.short.as_ref()?.chars().next()
+    }
+    /// The option's other long names.
+    pub fn aliases(&self) -> &[String] {
+        self.aliases.as_deref().unwrap_or(&[])
+    }
+    /// What the help says of the option, if anything.
+    pub fn help(&self) -> Option<&str> {
+        self.help_descr.as_deref()
+    }
+    /// What the help calls the option's value, if the spec names it.
+    pub fn help_name(&self) -> Option<&str> {
+        self.help_name.as_deref()
+    }
+    /// Whether the option is a flag, taking no value.
+    pub fn flag(&self) -> bool {
+        self.is_flag()
+    }
+    /// Whether the option must be given.
+    pub fn required(&self) -> bool {
+        self.is_required()
+    }
+    /// The value when the option isn't given, as C would write it.
+    pub fn default(&self) -> Option<&str> {
+        self.default.as_deref()
+    }
+    /// The environment variable the value is taken from when the option
+    /// isn't given, if any.
+    pub fn env(&self) -> Option<&str> {
+        self.env.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{NonPositionalItem, PositionalItem, Spec, Subcommand};
+    use super::{walk_subcommand, SpecVisitor};
+
+    /// A docs generator, listing each item under the command taking it.
+    #[derive(Default)]
+    struct Outline {
+        command: Vec<String>,
+        lines: Vec<String>,
+    }
+
+    impl Outline {
+        fn line(&mut self, item: String) {
+            self.lines
+                .push(format!("{}: {}", self.command.join(" "), item));
+        }
+    }
+
+    impl SpecVisitor for Outline {
+        fn visit_positional(&mut self, item: &PositionalItem) {
+            self.line(item.help_name().to_owned());
+        }
+        fn visit_option(&mut self, item: &NonPositionalItem) {
+            self.line(format!("--{}", item.long()));
+        }
+        fn visit_group(&mut self, name: &str, options: &[NonPositionalItem]) {
+            self.line(format!("{} {}", name, options.len()));
+        }
+        fn visit_subcommand(&mut self, subcommand: &Subcommand) {
+            self.command.push(subcommand.name().to_owned());
+            walk_subcommand(self, subcommand);
+            self.command.pop();
+        }
+    }
+
+    #[test]
+    fn visit() {
+        let s = Spec::from_str(
+            r#"
+            [[subcommand]]
+            name = "fetch"
+
+            [[subcommand.positional]]
+            c_var = "remote"
+            c_type = "char*"
+            help_name = "REMOTE"
+
+            [[subcommand]]
+            name = "remote"
+
+            [[subcommand.subcommand]]
+            name = "add"
+
+            [[subcommand.subcommand.non_positional]]
+            c_var = "track"
+            c_type = "char*"
+            long = "track"
+            short = "t"
+
+            [[global.non_positional]]
+            c_var = "verbose"
+            c_type = "int"
+            long = "verbose"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let mut outline = Outline::default();
+        s.visit(&mut outline);
+        assert_eq!(
+            outline.lines,
+            [": global 1", "fetch: REMOTE", "remote add: --track"]
+        );
+        // the walk goes on into a group unless the visitor stops it
+        struct Shorts(Vec<Option<char>>);
+        impl SpecVisitor for Shorts {
+            fn visit_option(&mut self, item: &NonPositionalItem) {
+                self.0.push(item.short());
+            }
+        }
+        let mut shorts = Shorts(vec![]);
+        s.visit(&mut shorts);
+        assert_eq!(shorts.0, [None, Some('t')]);
+    }
+}
@@ -0,0 +1,817 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The Zig backend: an Args struct, and a parseArgs function filling it in
+//! from the arguments std.process hands main, for Zig 0.13.
+
+use super::{
+    camel_case, int_literal, marker_text, CType, NonPositionalItem, PositionalItem, Spec,
+    ValidationError, USER_BEGIN, USER_END,
+};
+
+/// Zig's keywords and primitive values and types, which need @"" to be
+/// used as names.
+const RESERVED: &[&str] = &[
+    "addrspace",
+    "align",
+    "allowzero",
+    "and",
+    "anyerror",
+    "anyframe",
+    "anyopaque",
+    "anytype",
+    "asm",
+    "async",
+    "await",
+    "bool",
+    "break",
+    "callconv",
+    "catch",
+    "comptime",
+    "const",
+    "continue",
+    "defer",
+    "else",
+    "enum",
+    "errdefer",
+    "error",
+    "export",
+    "extern",
+    "f16",
+    "f32",
+    "f64",
+    "f80",
+    "f128",
+    "false",
+    "fn",
+    "for",
+    "if",
+    "inline",
+    "isize",
+    "linksection",
+    "noalias",
+    "noinline",
+    "noreturn",
+    "nosuspend",
+    "null",
+    "opaque",
+    "or",
+    "orelse",
+    "packed",
+    "pub",
+    "resume",
+    "return",
+    "struct",
+    "suspend",
+    "switch",
+    "test",
+    "threadlocal",
+    "true",
+    "try",
+    "type",
+    "undefined",
+    "union",
+    "unreachable",
+    "usingnamespace",
+    "usize",
+    "var",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// A c_var as a Zig field name.
+fn zig_field(c_var: &str) -> String {
+    // i32, u8 and the like are types
+    let int_type = c_var.len() > 1
+        && (c_var.starts_with('i') || c_var.starts_with('u'))
+        && c_var[1..].bytes().all(|b| b.is_ascii_digit());
+    if RESERVED.contains(&c_var) || int_type {
+        format!("@\"{}\"", c_var)
+    } else {
+        c_var.to_owned()
+    }
+}
+
+/// A string as a Zig string literal.
+fn zig_str(s: &str) -> String {
+    let mut lit = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => lit.push_str("\\\""),
+            '\\' => lit.push_str("\\\\"),
+            '\n' => lit.push_str("\\n"),
+            '\t' => lit.push_str("\\t"),
+            c if (c as u32) < 0x20 => lit.push_str(&format!("\\x{:02x}", c as u32)),
+            c => lit.push(c),
+        }
+    }
+    lit.push('"');
+    lit
+}
+
+fn zig_type(c_type: CType) -> &'static str {
+    match c_type {
+        CType::Chars => "[]const u8",
+        CType::Int => "i32",
+    }
+}
+
+/// A default as a Zig value of the field's type.
+fn zig_default(c_type: CType, default: &str) -> String {
+    match (c_type, int_literal(default)) {
+        (CType::Int, Some(n)) => n.to_string(),
+        (CType::Int, None) => default.to_owned(),
+        (CType::Chars, _) => zig_str(default),
+    }
+}
+
+/// What a required field holds until parseArgs fills it in.
+fn zig_zero(c_type: CType) -> &'static str {
+    match c_type {
+        CType::Chars => "\"\"",
+        CType::Int => "0",
+    }
+}
+
+/// A Zig string value converted to the given type, failing with what
+/// named in the error if it isn't a number.
+fn zig_convert(c_type: CType, value: &str, what: &str) -> String {
+    match c_type {
+        CType::Chars => value.to_owned(),
+        CType::Int => format!("try argsInt(progname, {}, {})", value, what),
+    }
+}
+
+impl NonPositionalItem {
+    /// This option's field of the struct, after the name. Options with
+    /// neither a default nor required are optional.
+    fn zig_field(&self) -> String {
+        let ty = zig_type(self.c_type);
+        match &self.default {
+            _ if self.is_flag() => String::from("bool = false"),
+            _ if self.is_required() => format!("{} = {}", ty, zig_zero(self.c_type)),
+            Some(default) => format!("{} = {}", ty, zig_default(self.c_type, default)),
+            None => format!("?{} = null", ty),
+        }
+    }
+    /// The prong of argsSet's switch taking this option's value.
+    fn zig_prong(&self, id: usize) -> String {
+        let field = zig_field(&self.c_var);
+        if self.is_flag() {
+            format!("\t\t{} => args.{} = true,\n", id, field)
+        } else {
+            format!(
+                "\t\t{} => args.{} = {},\n",
+                id,
+                field,
+                zig_convert(self.c_type, "value", "name")
+            )
+        }
+    }
+    /// Falls back on the environment and checks required options were given.
+    fn zig_post_loop(&self, id: usize, exposed: bool) -> String {
+        let mut post = String::new();
+        if let Some(env) = &self.env {
+            let set = format!(
+                "try argsSet(&args, &seen, progname, {}, {}, env);",
+                id,
+                zig_str(&format!("${}", env))
+            );
+            let set = if self.is_flag() {
+                format!(
+                    "if (argsTruthy(env)) {{\n\
+                     \t\t\t\t{}\n\
+                     \t\t\t}}",
+                    set
+                )
+            } else {
+                set
+            };
+            post.push_str(&format!(
+                "\tif (!seen[{}]) {{\n\
+                 \t\tif (std.posix.getenv({})) |env| {{\n\
+                 \t\t\t{}\n\
+                 \t\t}}\n\
+                 \t}}\n",
+                id,
+                zig_str(env),
+                set
+            ));
+        }
+        if self.is_required() {
+            post.push_str(&format!(
+                "\tif (!seen[{}]) {{\n\
+                 \t\treturn argsFail(progname, {}, .{{}});\n\
+                 \t}}\n",
+                id,
+                zig_str(&format!("missing required option '--{}'", self.long))
+            ));
+        }
+        if exposed {
+            post.push_str(&format!(
+                "\targs.{} = seen[{}];\n",
+                zig_field(&format!("{}_isset", self.c_var)),
+                id
+            ));
+        }
+        post
+    }
+}
+
+impl PositionalItem {
+    fn zig_field(&self) -> String {
+        let ty = zig_type(self.c_type);
+        if self.is_multi() {
+            return match &self.default {
+                Some(default) => format!("[]const {} = &.{{{}}}", ty, zig_str(default)),
+                None => format!("[]const {} = &.{{}}", ty),
+            };
+        }
+        match &self.default {
+            Some(default) => format!("{} = {}", ty, zig_default(self.c_type, default)),
+            None if self.is_required() => format!("{} = {}", ty, zig_zero(self.c_type)),
+            None => format!("?{} = null", ty),
+        }
+    }
+    /// Takes this argument from the operands.
+    fn zig_assign(&self, exposed: bool) -> String {
+        let field = zig_field(&self.c_var);
+        let mut assign = String::new();
+        if self.is_required() {
+            assign.push_str(&format!(
+                "\tif (next >= operands.items.len) {{\n\
+                 \t\treturn argsFail(progname, {}, .{{}});\n\
+                 \t}}\n",
+                zig_str(&format!("missing {}", self.help_name))
+            ));
+        }
+        // a required argument was just checked for, so needs no if
+        let checked = self.is_required() && !self.is_multi();
+        let indent = if checked { "\t" } else { "\t\t" };
+        if !checked {
+            assign.push_str("\tif (next < operands.items.len) {\n");
+        }
+        if self.is_multi() {
+            assign.push_str(&format!(
+                "{0}args.{1} = try allocator.dupe([]const u8, operands.items[next..]);\n\
+                 {0}next = operands.items.len;\n",
+                indent, field
+            ));
+        } else {
+            assign.push_str(&format!(
+                "{0}args.{1} = {2};\n\
+                 {0}next += 1;\n",
+                indent,
+                field,
+                zig_convert(
+                    self.c_type,
+                    "operands.items[next]",
+                    &zig_str(&self.help_name)
+                )
+            ));
+        }
+        if exposed {
+            assign.push_str(&format!(
+                "{}args.{} = true;\n",
+                indent,
+                zig_field(&format!("{}_isset", self.c_var))
+            ));
+        }
+        if !checked {
+            assign.push_str("\t}\n");
+        }
+        assign
+    }
+}
+
+impl Spec {
+    /// Checks that the spec only uses what the Zig backend supports.
+    pub fn check_zig(&self) -> Result<(), ValidationError> {
+        let unsupported = [
+            ("response_files", self.has_response_files()),
+            ("config", self.has_config()),
+            ("pre_parse_fn", self.pre_parse_fn.is_some()),
+            ("post_parse_fn", self.post_parse_fn.is_some()),
+            ("subcommands", self.has_subcommands()),
+        ];
+        match unsupported.iter().find(|(_, used)| *used) {
+            Some((feature, _)) => Err(ValidationError::BackendUnsupported(
+                String::from("zig"),
+                String::from(*feature),
+            )),
+            None => Ok(()),
+        }
+    }
+    fn zig_struct_name(&self) -> String {
+        camel_case(self.struct_name().unwrap_or("args"))
+    }
+    /// Functions are camelCase in Zig.
+    fn zig_fn_name(&self, name: &str) -> String {
+        let name = camel_case(&self.sym(name));
+        name[..1].to_ascii_lowercase() + &name[1..]
+    }
+    fn zig_struct(&self) -> String {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for npi in &self.non_positional {
+            fields.push((zig_field(&npi.c_var), npi.zig_field()));
+        }
+        for pi in &self.positional {
+            fields.push((zig_field(&pi.c_var), pi.zig_field()));
+        }
+        if let Some(rest) = &self.capture_rest {
+            fields.push((zig_field(rest), String::from("[]const []const u8 = &.{}")));
+        }
+        for var in self.exposed_issets() {
+            fields.push((
+                zig_field(&format!("{}_isset", var)),
+                String::from("bool = false"),
+            ));
+        }
+        let mut code = format!(
+            "/// Values parsed from the command line. Strings are slices of argv,\n\
+             /// which must outlive it.\n\
+             pub const {} = struct {{\n",
+            self.zig_struct_name()
+        );
+        for (name, field) in fields {
+            code.push_str(&format!("\t{}: {},\n", name, field));
+        }
+        code.push_str("};\n");
+        code
+    }
+    fn zig_usage(&self) -> String {
+        let text = self.analyze().usage_text("");
+        let help = text.split_once('\n').map_or("", |(_, help)| help);
+        let mut code = format!(
+            "/// Writes how to use the program to writer.\n\
+             pub fn {}(writer: anytype, progname: []const u8) !void {{\n\
+             \ttry writer.print({}, .{{progname}});\n\
+             \ttry writer.writeAll(\n",
+            self.zig_fn_name("usage"),
+            // braces are format specifiers
+            zig_str(&format!(
+                "usage: {{s}} [options]{}\n",
+                self.usage_synopsis().replace('{', "{{").replace('}', "}}")
+            ))
+        );
+        for line in help.split('\n') {
+            code.push_str(&format!("\t\t\\\\{}\n", line));
+        }
+        code.push_str("\t);\n}\n");
+        code
+    }
+    /// The option tables, and the helpers parseArgs uses.
+    fn zig_helpers(&self) -> String {
+        let help_id = self.non_positional.len();
+        let mut longopts = format!(
+            "\t.{{ .name = \"help\", .id = {}, .has_arg = false }},\n",
+            help_id
+        );
+        let mut shortopts = format!(
+            "\t.{{ .name = \"h\", .id = {}, .has_arg = false }},\n",
+            help_id
+        );
+        for (id, npi) in self.non_positional.iter().enumerate() {
+            let has_arg = !npi.is_flag();
+            for long in std::iter::once(&npi.long).chain(npi.aliases.iter().flatten()) {
+                longopts.push_str(&format!(
+                    "\t.{{ .name = {}, .id = {}, .has_arg = {} }},\n",
+                    zig_str(long),
+                    id,
+                    has_arg
+                ));
+            }
+            if let Some(short) = &npi.short {
+                shortopts.push_str(&format!(
+                    "\t.{{ .name = {}, .id = {}, .has_arg = {} }},\n",
+                    zig_str(short),
+                    id,
+                    has_arg
+                ));
+            }
+        }
+        let mut helpers = format!(
+            "pub const ParseError = error{{ Help, InvalidArgs }} || std.mem.Allocator.Error;\n\
+             \n\
+             const ArgsOption = struct {{\n\
+             \tname: []const u8,\n\
+             \tid: usize,\n\
+             \thas_arg: bool,\n\
+             }};\n\
+             \n\
+             const args_long_options = [_]ArgsOption{{\n{}}};\n\
+             \n\
+             const args_short_options = [_]ArgsOption{{\n{}}};\n\
+             \n\
+             /// Reports a bad command line, with the usage, on stderr.\n\
+             fn argsFail(progname: []const u8, comptime fmt: []const u8, fmt_args: anytype) error{{InvalidArgs}} {{\n\
+             \tconst stderr = std.io.getStdErr().writer();\n\
+             \tstderr.print(\"{{s}}: \", .{{progname}}) catch {{}};\n\
+             \tstderr.print(fmt ++ \"\\n\", fmt_args) catch {{}};\n\
+             \t{}(stderr, progname) catch {{}};\n\
+             \treturn error.InvalidArgs;\n\
+             }}\n",
+            longopts,
+            shortopts,
+            self.zig_fn_name("usage")
+        );
+        let (equal, prefix) = if self.is_case_insensitive() {
+            (
+                "std.ascii.eqlIgnoreCase(opt.name, name)",
+                "std.ascii.startsWithIgnoreCase(opt.name, name)",
+            )
+        } else {
+            (
+                "std.mem.eql(u8, opt.name, name)",
+                "std.mem.startsWith(u8, opt.name, name)",
+            )
+        };
+        if self.allows_abbrev() {
+            helpers.push_str(&format!(
+                "\n\
+                 fn argsFindLong(progname: []const u8, name: []const u8) error{{InvalidArgs}}!?ArgsOption {{\n\
+                 \tvar match: ?ArgsOption = null;\n\
+                 \tvar ambiguous = false;\n\
+                 \tfor (args_long_options) |opt| {{\n\
+                 \t\tif ({}) {{\n\
+                 \t\t\treturn opt;\n\
+                 \t\t}}\n\
+                 \t\tif (name.len < opt.name.len and {}) {{\n\
+                 \t\t\tif (match) |m| {{\n\
+                 \t\t\t\tif (m.id != opt.id) {{\n\
+                 \t\t\t\t\tambiguous = true;\n\
+                 \t\t\t\t}}\n\
+                 \t\t\t}}\n\
+                 \t\t\tmatch = opt;\n\
+                 \t\t}}\n\
+                 \t}}\n\
+                 \tif (ambiguous) {{\n\
+                 \t\treturn argsFail(progname, \"option '--{{s}}' is ambiguous\", .{{name}});\n\
+                 \t}}\n\
+                 \treturn match;\n\
+                 }}\n",
+                equal, prefix
+            ));
+        } else {
+            helpers.push_str(&format!(
+                "\n\
+                 fn argsFindLong(progname: []const u8, name: []const u8) error{{InvalidArgs}}!?ArgsOption {{\n\
+                 \t_ = progname;\n\
+                 \tfor (args_long_options) |opt| {{\n\
+                 \t\tif ({}) {{\n\
+                 \t\t\treturn opt;\n\
+                 \t\t}}\n\
+                 \t}}\n\
+                 \treturn null;\n\
+                 }}\n",
+                equal
+            ));
+        }
+        helpers.push_str(
+            "\n\
+             fn argsFindShort(c: u8) ?ArgsOption {\n\
+             \tfor (args_short_options) |opt| {\n\
+             \t\tif (opt.name[0] == c) {\n\
+             \t\t\treturn opt;\n\
+             \t\t}\n\
+             \t}\n\
+             \treturn null;\n\
+             }\n",
+        );
+        let ints = self
+            .non_positional
+            .iter()
+            .any(|npi| !npi.is_flag() && matches!(npi.c_type, CType::Int))
+            || self
+                .positional
+                .iter()
+                .any(|pi| matches!(pi.c_type, CType::Int));
+        if ints {
+            helpers.push_str(
+                "\n\
+                 fn argsInt(progname: []const u8, value: []const u8, what: []const u8) error{InvalidArgs}!i32 {\n\
+                 \treturn std.fmt.parseInt(i32, value, 10) catch\n\
+                 \t\treturn argsFail(progname, \"invalid number for {s}: '{s}'\", .{ what, value });\n\
+                 }\n",
+            );
+        }
+        if self
+            .non_positional
+            .iter()
+            .any(|npi| npi.is_flag() && npi.env.is_some())
+        {
+            helpers.push_str(
+                "\n\
+                 fn argsTruthy(value: []const u8) bool {\n\
+                 \tfor ([_][]const u8{ \"1\", \"true\", \"yes\", \"on\" }) |truthy| {\n\
+                 \t\tif (std.mem.eql(u8, value, truthy)) {\n\
+                 \t\t\treturn true;\n\
+                 \t\t}\n\
+                 \t}\n\
+                 \treturn false;\n\
+                 }\n",
+            );
+        }
+        helpers.push_str(&self.zig_set());
+        helpers
+    }
+    /// argsSet, which stores the value of the option with the given id, as
+    /// named on the command line, and notes that it was given.
+    fn zig_set(&self) -> String {
+        let n = self.non_positional.len();
+        let mut set = format!(
+            "\n\
+             fn argsSet(args: *{}, seen: *[{}]bool, progname: []const u8, id: usize, name: []const u8, value: []const u8) ParseError!void {{\n",
+            self.zig_struct_name(),
+            n + 1
+        );
+        // Zig rejects unused parameters
+        let has_values = self.non_positional.iter().any(|npi| !npi.is_flag());
+        let has_ints = self
+            .non_positional
+            .iter()
+            .any(|npi| !npi.is_flag() && matches!(npi.c_type, CType::Int));
+        if n == 0 {
+            set.push_str("\t_ = args;\n");
+        }
+        if !has_ints {
+            set.push_str("\t_ = name;\n");
+        }
+        if !has_values {
+            set.push_str("\t_ = value;\n");
+        }
+        set.push_str("\tseen[id] = true;\n\tswitch (id) {\n");
+        for (id, npi) in self.non_positional.iter().enumerate() {
+            set.push_str(&npi.zig_prong(id));
+        }
+        set.push_str(&format!(
+            "\t\telse => {{\n\
+             \t\t\t{}(std.io.getStdOut().writer(), progname) catch {{}};\n\
+             \t\t\treturn error.Help;\n\
+             \t\t}},\n\
+             \t}}\n\
+             }}\n",
+            self.zig_fn_name("usage")
+        ));
+        set
+    }
+    fn zig_parse_args(&self) -> String {
+        let exposed = self.exposed_issets();
+        let n = self.non_positional.len();
+        let mut code = format!(
+            "/// Parses argv, starting with the program name, into {0}. Multi-valued\n\
+             /// arguments are allocated with allocator. Returns error.Help after\n\
+             /// printing the usage for --help, and error.InvalidArgs after reporting\n\
+             /// a bad command line.\n\
+             pub fn {1}(allocator: std.mem.Allocator, argv: []const []const u8) ParseError!{0} {{\n\
+             \tconst progname = argv[0];\n\
+             \tvar args = {0}{{}};\n\
+             \tvar seen = [_]bool{{false}} ** {2};\n\
+             \tvar operands = std.ArrayList([]const u8).init(allocator);\n\
+             \tdefer operands.deinit();\n",
+            self.zig_struct_name(),
+            self.zig_fn_name("parse_args"),
+            n + 1
+        );
+        match &self.capture_rest {
+            Some(rest) => code.push_str(&format!(
+                "\tvar end = argv.len;\n\
+                 \tfor (argv[1..], 1..) |arg, k| {{\n\
+                 \t\tif (std.mem.eql(u8, arg, \"--\")) {{\n\
+                 \t\t\targs.{} = argv[k + 1 ..];\n\
+                 \t\t\tend = k;\n\
+                 \t\t\tbreak;\n\
+                 \t\t}}\n\
+                 \t}}\n",
+                zig_field(rest)
+            )),
+            None => code.push_str("\tconst end = argv.len;\n"),
+        }
+        code.push_str(
+            "\tvar i: usize = 1;\n\
+             \twhile (i < end) : (i += 1) {\n\
+             \t\tconst arg = argv[i];\n\
+             \t\tif (std.mem.eql(u8, arg, \"--\")) {\n\
+             \t\t\ti += 1;\n\
+             \t\t\tbreak;\n\
+             \t\t}\n\
+             \t\tif (arg.len > 2 and std.mem.startsWith(u8, arg, \"--\")) {\n\
+             \t\t\tvar name = arg[2..];\n\
+             \t\t\tvar value: ?[]const u8 = null;\n\
+             \t\t\tif (std.mem.indexOfScalar(u8, name, '=')) |eq| {\n\
+             \t\t\t\tvalue = name[eq + 1 ..];\n\
+             \t\t\t\tname = name[0..eq];\n\
+             \t\t\t}\n\
+             \t\t\tconst found = try argsFindLong(progname, name);\n\
+             \t\t\tconst opt = found orelse return argsFail(progname, \"unrecognized option '--{s}'\", .{name});\n\
+             \t\t\tif (opt.has_arg and value == null) {\n\
+             \t\t\t\ti += 1;\n\
+             \t\t\t\tif (i >= end) {\n\
+             \t\t\t\t\treturn argsFail(progname, \"option '--{s}' requires an argument\", .{name});\n\
+             \t\t\t\t}\n\
+             \t\t\t\tvalue = argv[i];\n\
+             \t\t\t} else if (!opt.has_arg and value != null) {\n\
+             \t\t\t\treturn argsFail(progname, \"option '--{s}' doesn't allow an argument\", .{name});\n\
+             \t\t\t}\n\
+             \t\t\ttry argsSet(&args, &seen, progname, opt.id, arg[0 .. name.len + 2], value orelse \"\");\n\
+             \t\t} else if (arg.len > 1 and arg[0] == '-') {\n\
+             \t\t\tvar j: usize = 1;\n\
+             \t\t\twhile (j < arg.len) : (j += 1) {\n\
+             \t\t\t\tconst opt = argsFindShort(arg[j]) orelse\n\
+             \t\t\t\t\treturn argsFail(progname, \"invalid option '-{c}'\", .{arg[j]});\n\
+             \t\t\t\tconst name = [_]u8{ '-', arg[j] };\n\
+             \t\t\t\tif (!opt.has_arg) {\n\
+             \t\t\t\t\ttry argsSet(&args, &seen, progname, opt.id, &name, \"\");\n\
+             \t\t\t\t} else if (j + 1 < arg.len) {\n\
+             \t\t\t\t\ttry argsSet(&args, &seen, progname, opt.id, &name, arg[j + 1 ..]);\n\
+             \t\t\t\t\tbreak;\n\
+             \t\t\t\t} else {\n\
+             \t\t\t\t\ti += 1;\n\
+             \t\t\t\t\tif (i >= end) {\n\
+             \t\t\t\t\t\treturn argsFail(progname, \"option '-{c}' requires an argument\", .{arg[j]});\n\
+             \t\t\t\t\t}\n\
+             \t\t\t\t\ttry argsSet(&args, &seen, progname, opt.id, &name, argv[i]);\n\
+             \t\t\t\t}\n\
+             \t\t\t}\n\
+             \t\t} else {\n",
+        );
+        if self.is_strict_posix() {
+            code.push_str("\t\t\tbreak;\n");
+        } else {
+            code.push_str("\t\t\ttry operands.append(arg);\n");
+        }
+        code.push_str("\t\t}\n\t}\n\ttry operands.appendSlice(argv[i..end]);\n");
+        for (id, npi) in self.non_positional.iter().enumerate() {
+            code.push_str(&npi.zig_post_loop(id, exposed.contains(&npi.c_var.as_str())));
+        }
+        if !self.positional.is_empty() {
+            code.push_str("\tvar next: usize = 0;\n");
+            for pi in &self.positional {
+                code.push_str(&pi.zig_assign(exposed.contains(&pi.c_var.as_str())));
+            }
+        }
+        code.push_str("\treturn args;\n}\n");
+        code
+    }
+    fn zig_main(&self) -> String {
+        format!(
+            "pub fn main() !void {{\n\
+             \tvar arena = std.heap.ArenaAllocator.init(std.heap.page_allocator);\n\
+             \tdefer arena.deinit();\n\
+             \tconst allocator = arena.allocator();\n\
+             \tvar argv = std.ArrayList([]const u8).init(allocator);\n\
+             \tvar it = try std.process.argsWithAllocator(allocator);\n\
+             \twhile (it.next()) |arg| {{\n\
+             \t\ttry argv.append(arg);\n\
+             \t}}\n\
+             \tconst args = {}(allocator, argv.items) catch |err| switch (err) {{\n\
+             \t\terror.Help => std.process.exit(0),\n\
+             \t\telse => std.process.exit(1),\n\
+             \t}};\n\
+             \n\
+             \t// {}\n\
+             \t// call your code here\n\
+             \t_ = args;\n\
+             \t// {}\n\
+             }}\n",
+            self.zig_fn_name("parse_args"),
+            marker_text(USER_BEGIN),
+            marker_text(USER_END)
+        )
+    }
+    /// Generates Zig for the spec: the struct, usage and parseArgs, and
+    /// unless disabled a main reading std.process's arguments.
+    pub fn gen_zig(&self) -> String {
+        let mut code = self.stamp_with("//", "");
+        code.push_str("const std = @import(\"std\");\n\n");
+        code.push_str(&self.zig_struct());
+        code.push('\n');
+        code.push_str(&self.zig_usage());
+        code.push('\n');
+        code.push_str(&self.zig_helpers());
+        code.push('\n');
+        code.push_str(&self.zig_parse_args());
+        if self.emits_main() {
+            code.push('\n');
+            code.push_str(&self.zig_main());
+        }
+        // zig fmt indents with four spaces
+        code.replace('\t', "    ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Spec;
+
+    #[test]
+    fn zig() {
+        let s = Spec::from_str(
+            r#"
+            capture_rest = "rest"
+            expose_isset = true
+
+            [[positional]]
+            c_var = "input"
+            c_type = "char*"
+            help_name = "FILE"
+            required = true
+
+            [[positional]]
+            c_var = "count"
+            c_type = "int"
+            help_name = "N"
+
+            [[positional]]
+            c_var = "words"
+            c_type = "char*"
+            help_name = "WORD"
+            multi = true
+            default = "w"
+
+            [[non_positional]]
+            c_var = "block_size"
+            c_type = "int"
+            long = "block-size"
+            short = "b"
+            aliases = ["bs"]
+            default = "0x10"
+
+            [[non_positional]]
+            c_var = "port"
+            c_type = "int"
+            long = "port"
+            required = true
+            env = "PROG_PORT"
+
+            [[non_positional]]
+            c_var = "error"
+            c_type = "int"
+            long = "quiet"
+            flag = true
+            "#,
+        )
+        .unwrap();
+        let z = s.gen_zig().replace("    ", "\t");
+        assert!(z.contains(
+            "pub const Args = struct {\n\
+             \tblock_size: i32 = 16,\n\
+             \tport: i32 = 0,\n\
+             \t@\"error\": bool = false,\n\
+             \tinput: []const u8 = \"\",\n\
+             \tcount: ?i32 = null,\n\
+             \twords: []const []const u8 = &.{\"w\"},\n\
+             \trest: []const []const u8 = &.{},\n\
+             \tblock_size_isset: bool = false,\n\
+             \tcount_isset: bool = false,\n\
+             \twords_isset: bool = false,\n\
+             };\n"
+        ));
+        assert!(z.contains(
+            "\t.{ .name = \"block-size\", .id = 0, .has_arg = true },\n\
+             \t.{ .name = \"bs\", .id = 0, .has_arg = true },\n"
+        ));
+        assert!(z.contains("\t\t\\\\Options:\n\t\t\\\\  -h, --help"));
+        assert!(z.contains("\t\t0 => args.block_size = try argsInt(progname, value, name),\n"));
+        assert!(z.contains("\t\t2 => args.@\"error\" = true,\n"));
+        assert!(z.contains(
+            "\tif (!seen[1]) {\n\
+             \t\tif (std.posix.getenv(\"PROG_PORT\")) |env| {\n\
+             \t\t\ttry argsSet(&args, &seen, progname, 1, \"$PROG_PORT\", env);\n\
+             \t\t}\n\
+             \t}\n\
+             \tif (!seen[1]) {\n\
+             \t\treturn argsFail(progname, \"missing required option '--port'\", .{});\n\
+             \t}\n"
+        ));
+        assert!(z.contains(
+            "\tif (next >= operands.items.len) {\n\
+             \t\treturn argsFail(progname, \"missing FILE\", .{});\n\
+             \t}\n\
+             \targs.input = operands.items[next];\n\
+             \tnext += 1;\n\
+             \tif (next < operands.items.len) {\n\
+             \t\targs.count = try argsInt(progname, operands.items[next], \"N\");\n\
+             \t\tnext += 1;\n\
+             \t\targs.count_isset = true;\n\
+             \t}\n"
+        ));
+        assert!(z.contains("\t\t\targs.rest = argv[k + 1 ..];\n"));
+        assert!(z.contains("\t// argen:begin user\n\t// call your code here\n\t_ = args;\n"));
+        assert!(s.check_zig().is_ok());
+    }
+}
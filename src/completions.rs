@@ -0,0 +1,255 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Completion scripts for argen's own command line, made from its table of
+//! options and subcommands so they never fall behind it.
+
+use super::{Flag, Subcommand};
+
+/// The shells there are completion scripts for.
+pub const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+/// What an option is called on the command line, with one dash or two.
+fn names(flag: &Flag) -> Vec<String> {
+    let mut names = vec![];
+    if !flag.short.is_empty() {
+        names.push(format!("-{}", flag.short));
+    }
+    if !flag.long.is_empty() {
+        names.push(format!("--{}", flag.long));
+    }
+    names
+}
+
+/// Whether the word after an option is its argument.
+fn takes_next(flag: &Flag) -> bool {
+    flag.hint.is_some() && !flag.optional
+}
+
+fn bash(flags: &[Flag], subcommands: &[Subcommand], sources: &[&str]) -> String {
+    let mut cases = String::new();
+    for flag in flags.iter().filter(|flag| takes_next(flag)) {
+        let reply = match (flag.values, flag.hint) {
+            ([], Some("DIR")) => String::from("compgen -d -- \"$cur\""),
+            ([], _) => String::from("compgen -f -- \"$cur\""),
+            (values, _) => format!("compgen -W \"{}\" -- \"$cur\"", values.join(" ")),
+        };
+        cases.push_str(&format!(
+            "        {})\n            COMPREPLY=($({}))\n            return\n            ;;\n",
+            names(flag).join("|"),
+            reply
+        ));
+    }
+    let all: Vec<String> = flags.iter().flat_map(names).collect();
+    let commands: Vec<&str> = subcommands.iter().map(|sub| sub.name).collect();
+    format!(
+        "# bash completion for argen\n\
+         _argen() {{\n    \
+             local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"\n    \
+             local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n    \
+             case \"$prev\" in\n\
+         {}        \
+                 import)\n            \
+                     COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            \
+                     return\n            \
+                     ;;\n        \
+                 completions)\n            \
+                     COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            \
+                     return\n            \
+                     ;;\n    \
+             esac\n    \
+             if [[ \"$cur\" == -* ]]; then\n        \
+                 COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n        \
+                 return\n    \
+             fi\n    \
+             COMPREPLY=($(compgen -f -- \"$cur\"))\n    \
+             if [[ \"$COMP_CWORD\" -eq 1 ]]; then\n        \
+                 COMPREPLY+=($(compgen -W \"{}\" -- \"$cur\"))\n    \
+             fi\n\
+         }}\n\
+         complete -o filenames -F _argen argen\n",
+        cases,
+        sources.join(" "),
+        SHELLS.join(" "),
+        all.join(" "),
+        commands.join(" ")
+    )
+}
+
+/// Text quoted for zsh, in single quotes, and escaped for _arguments.
+fn zsh_quote(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+        .replace('\'', "'\\''")
+}
+
+fn zsh(flags: &[Flag], subcommands: &[Subcommand], sources: &[&str]) -> String {
+    let mut specs = String::new();
+    for flag in flags {
+        let action = match (flag.values, flag.hint) {
+            (_, None) => String::new(),
+            ([], Some("DIR")) => String::from(":DIR:_files -/"),
+            ([], Some(hint)) => format!(":{}:_files", hint),
+            (values, Some(hint)) => format!(":{}:({})", hint, values.join(" ")),
+        };
+        for name in names(flag) {
+            // an optional argument is only given after an =
+            let name = match flag.optional {
+                true => format!("{}=-", name),
+                false => name,
+            };
+            specs.push_str(&format!(
+                "        '{}[{}]{}' \\\n",
+                name,
+                zsh_quote(flag.help),
+                action
+            ));
+        }
+    }
+    let commands: Vec<String> = subcommands
+        .iter()
+        .map(|sub| format!("        '{}:{}'\n", sub.name, zsh_quote(sub.help)))
+        .collect();
+    format!(
+        "#compdef argen\n\
+         # zsh completion for argen\n\
+         _argen() {{\n    \
+             local -a commands\n    \
+             commands=(\n\
+         {}    \
+             )\n    \
+             local state\n    \
+             _arguments -s \\\n\
+         {}        \
+                 '1: :->first' \\\n        \
+                 '2: :->second' \\\n        \
+                 '*:spec:_files'\n    \
+             case $state in\n        \
+                 first)\n            \
+                     _describe -t commands command commands\n            \
+                     _files\n            \
+                     ;;\n        \
+                 second)\n            \
+                     case $words[2] in\n                \
+                         import) compadd {} ;;\n                \
+                         completions) compadd {} ;;\n                \
+                         *) _files ;;\n            \
+                     esac\n            \
+                     ;;\n    \
+             esac\n\
+         }}\n\
+         _argen \"$@\"\n",
+        commands.concat(),
+        specs,
+        sources.join(" "),
+        SHELLS.join(" ")
+    )
+}
+
+/// Text quoted for fish, in single quotes.
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(flags: &[Flag], subcommands: &[Subcommand], sources: &[&str]) -> String {
+    let mut script = String::from("# fish completion for argen\n");
+    for flag in flags {
+        let mut line = String::from("complete -c argen");
+        if !flag.short.is_empty() {
+            line.push_str(&format!(" -s {}", flag.short));
+        }
+        if !flag.long.is_empty() {
+            line.push_str(&format!(" -l {}", flag.long));
+        }
+        match (flag.values, flag.hint) {
+            (_, None) => {}
+            ([], Some("DIR")) => line.push_str(" -x -a '(__fish_complete_directories)'"),
+            ([], Some(_)) if !flag.optional => line.push_str(" -r -F"),
+            ([], Some(_)) => {}
+            (values, Some(_)) => {
+                line.push_str(&format!(" -x -a {}", fish_quote(&values.join(" "))))
+            }
+        }
+        line.push_str(&format!(" -d {}\n", fish_quote(flag.help)));
+        script.push_str(&line);
+    }
+    for sub in subcommands {
+        script.push_str(&format!(
+            "complete -c argen -n __fish_use_subcommand -a {} -d {}\n",
+            sub.name,
+            fish_quote(sub.help)
+        ));
+    }
+    script.push_str(&format!(
+        "complete -c argen -n '__fish_seen_subcommand_from import' -x -a {}\n\
+         complete -c argen -n '__fish_seen_subcommand_from completions' -x -a {}\n",
+        fish_quote(&sources.join(" ")),
+        fish_quote(&SHELLS.join(" "))
+    ));
+    script
+}
+
+/// The completion script for a shell, if it is one of SHELLS, given argen's
+/// options, its subcommands with what they do, and what it imports from.
+pub fn script(
+    shell: &str,
+    flags: &[Flag],
+    subcommands: &[Subcommand],
+    sources: &[&str],
+) -> Option<String> {
+    match shell {
+        "bash" => Some(bash(flags, subcommands, sources)),
+        "zsh" => Some(zsh(flags, subcommands, sources)),
+        "fish" => Some(fish(flags, subcommands, sources)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::script;
+    use crate::{FLAGS, SUBCOMMANDS};
+
+    #[test]
+    fn completions() {
+        let sources = ["clap", "docopt"];
+        let bash = script("bash", FLAGS, SUBCOMMANDS, &sources).unwrap();
+        assert!(bash.contains(
+            "        --backend)\n            \
+             COMPREPLY=($(compgen -W \"c c++ rust go powershell zig readme json tests fuzz eval\" -- \"$cur\"))\n"
+        ));
+        assert!(bash
+            .contains("        --out-dir)\n            COMPREPLY=($(compgen -d -- \"$cur\"))\n"));
+        assert!(bash.contains("--compile-check"));
+        assert!(!bash.contains("--format)"));
+        let zsh = script("zsh", FLAGS, SUBCOMMANDS, &sources).unwrap();
+        assert!(zsh.contains(
+            "'--out-dir[write each spec'\\''s output, named as with --auto-name, to a directory]:DIR:_files -/'"
+        ));
+        assert!(zsh.contains("'--format=-["));
+        assert!(zsh
+            .contains("        'import:draft a spec from another definition of a command line'\n"));
+        let fish = script("fish", FLAGS, SUBCOMMANDS, &sources).unwrap();
+        assert!(fish
+            .contains("complete -c argen -s o -r -F -d 'set output file name, or - for stdout'\n"));
+        assert!(fish.contains(
+            "complete -c argen -n '__fish_seen_subcommand_from import' -x -a 'clap docopt'\n"
+        ));
+        assert!(script("tcsh", FLAGS, SUBCOMMANDS, &sources).is_none());
+    }
+}
@@ -0,0 +1,209 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Defaults for argen's options, from the user's config file and the
+//! project's `.argen.toml`. Each key is an option's long name, and stands for
+//! that option given on the command line before any that are. An option given
+//! once wins over the same option from a config file, and one from the
+//! project's file wins over the user's; values of an option given more than
+//! once, like -D, add up, the user's first and the command line's last.
+
+use super::{log, CliError, Flag};
+use std::env;
+use std::iter;
+use std::path::{Path, PathBuf};
+
+/// Options a config file can't set, as they are about one run of argen.
+const PER_RUN: [&str; 8] = [
+    "help",
+    "version",
+    "header",
+    "inplace",
+    "diff",
+    "force",
+    "check-output",
+    "no-config",
+];
+
+/// Options given on the command line that a config file's option gives way
+/// to, beyond itself.
+fn overridden_by(long: &str) -> &'static [&'static str] {
+    match long {
+        "backend" => &["target"],
+        "target" => &["backend"],
+        "out-dir" => &["o", "inplace", "auto-name"],
+        "auto-name" => &["o", "inplace", "out-dir"],
+        _ => &[],
+    }
+}
+
+/// The user's config file, in $XDG_CONFIG_HOME or ~/.config.
+fn user_config() -> Option<PathBuf> {
+    let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(dir.join("argen").join("config.toml"))
+}
+
+/// The project's config file: the nearest .argen.toml in the current
+/// directory or one above it.
+fn project_config() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(".argen.toml"))
+        .find(|file| file.is_file())
+}
+
+/// A config file's options, as arguments, leaving out those the command
+/// line gives.
+fn file_args(
+    path: &Path,
+    flags: &[Flag],
+    given: &dyn Fn(&str) -> bool,
+) -> Result<Vec<String>, CliError> {
+    let name = path.to_string_lossy();
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| CliError::Io(format!("cannot read config file '{}'", name), e))?;
+    let table: toml::value::Table =
+        toml::from_str(&contents).map_err(|e| CliError::spec(&name, &contents, e.into()))?;
+    let mut args = vec![];
+    for (key, value) in table {
+        let long = key.replace('_', "-");
+        let flag = flags
+            .iter()
+            .find(|flag| flag.long == long && !PER_RUN.contains(&flag.long))
+            .ok_or_else(|| {
+                CliError::Failed(format!("{}: argen has no option {} to set", name, key))
+            })?;
+        let bad = || {
+            CliError::Failed(format!(
+                "{}: {} is {}",
+                name,
+                key,
+                match (flag.hint, flag.multi) {
+                    (None, _) => "true or false",
+                    (Some(_), true) => "a string or a list of strings",
+                    (Some(_), false) if flag.optional => "true or a string",
+                    (Some(_), false) => "a string",
+                }
+            ))
+        };
+        if !flag.multi
+            && overridden_by(&long)
+                .iter()
+                .chain(iter::once(&flag.long))
+                .any(|o| given(o))
+        {
+            continue;
+        }
+        let values = match value {
+            toml::Value::Array(values) if flag.multi => values,
+            value => vec![value],
+        };
+        for value in values {
+            match (value, flag.hint) {
+                (toml::Value::Boolean(true), None) => args.push(format!("--{}", long)),
+                (toml::Value::Boolean(true), Some(_)) if flag.optional => {
+                    args.push(format!("--{}", long))
+                }
+                (toml::Value::Boolean(false), None) => {}
+                (toml::Value::String(s), Some(_)) => args.push(format!("--{}={}", long, s)),
+                (toml::Value::Integer(n), Some(_)) => args.push(format!("--{}={}", long, n)),
+                _ => return Err(bad()),
+            }
+        }
+    }
+    Ok(args)
+}
+
+/// The options the config files set, as arguments to go before the command
+/// line's, leaving out those it gives. given says whether the command line
+/// gives an option, by its long or short name.
+pub fn args(flags: &[Flag], given: &dyn Fn(&str) -> bool) -> Result<Vec<String>, CliError> {
+    let mut args: Vec<String> = vec![];
+    // the project's file first, so the user's leaves out what it sets
+    for path in vec![project_config(), user_config()].into_iter().flatten() {
+        if path.is_file() {
+            log::debug(format_args!("reading defaults from '{}'", path.display()));
+            let set: Vec<&str> = args
+                .iter()
+                .map(|arg| arg[2..].split('=').next().unwrap_or_default())
+                .collect();
+            let given = |name: &str| given(name) || set.contains(&name);
+            let mut file = file_args(&path, flags, &given)?;
+            log::trace(format_args!("defaults: {}", file.join(" ")));
+            file.append(&mut args);
+            args = file;
+        }
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::file_args;
+    use crate::FLAGS;
+
+    #[test]
+    fn config() {
+        let dir = std::env::temp_dir().join("argen_config");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        let args = |contents: &str, given: &[&str]| {
+            std::fs::write(&path, contents).unwrap();
+            file_args(&path, FLAGS, &|name| given.contains(&name))
+        };
+        let config = r#"
+backend = "rust"
+strict = true
+no_provenance = false
+out_dir = "gen"
+format = true
+define = ["version=2.0", "vars.product=frob"]
+"#;
+        assert_eq!(
+            args(config, &[]).ok().unwrap(),
+            [
+                "--backend=rust",
+                "--define=version=2.0",
+                "--define=vars.product=frob",
+                "--format",
+                "--out-dir=gen",
+                "--strict",
+            ]
+        );
+        // the command line's options win, and its -D values come after
+        assert_eq!(
+            args(config, &["target", "o", "format", "define"])
+                .ok()
+                .unwrap(),
+            [
+                "--define=version=2.0",
+                "--define=vars.product=frob",
+                "--strict",
+            ]
+        );
+        let error = |contents: &str| args(contents, &[]).err().unwrap().to_string();
+        assert!(
+            error("colour = \"red\"").ends_with("config.toml: argen has no option colour to set")
+        );
+        assert!(error("inplace = \"args.c\"").ends_with("argen has no option inplace to set"));
+        assert!(error("strict = \"yes\"").ends_with("config.toml: strict is true or false"));
+        assert!(error("backend = [\"c\"]").ends_with("backend is a string"));
+        assert!(error("backend =").contains("config.toml:1"));
+    }
+}
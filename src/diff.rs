@@ -0,0 +1,178 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Unified diffs, line by line, just enough to show how a generated file
+//! is out of date.
+
+use std::cmp;
+
+/// Unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+
+/// The most cells of the table of common subsequences worth filling in; past
+/// it, the lines between the first and last change are all shown as changed.
+const MAX_CELLS: usize = 4_000_000;
+
+/// What became of a line.
+#[derive(Clone, Copy, PartialEq)]
+enum Edit {
+    Same,
+    Removed,
+    Added,
+}
+
+/// The edits turning old into new, keeping a longest common subsequence of
+/// their lines.
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // lines in common at the start and end need no table
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+    let mut edits = vec![Edit::Same; prefix];
+    let width = b.len() + 1;
+    if (a.len() + 1) * width > MAX_CELLS {
+        edits.extend(a.iter().map(|_| Edit::Removed));
+        edits.extend(b.iter().map(|_| Edit::Added));
+    } else {
+        // common[i * width + j]: the longest common subsequence of a[i..] and b[j..]
+        let mut common = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                common[i * width + j] = if a[i] == b[j] {
+                    common[(i + 1) * width + j + 1] + 1
+                } else {
+                    cmp::max(common[(i + 1) * width + j], common[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                edits.push(Edit::Same);
+                i += 1;
+                j += 1;
+            } else if common[(i + 1) * width + j] >= common[i * width + j + 1] {
+                edits.push(Edit::Removed);
+                i += 1;
+            } else {
+                edits.push(Edit::Added);
+                j += 1;
+            }
+        }
+        edits.extend(a[i..].iter().map(|_| Edit::Removed));
+        edits.extend(b[j..].iter().map(|_| Edit::Added));
+    }
+    edits.extend((0..suffix).map(|_| Edit::Same));
+    edits
+}
+
+/// A hunk's range of lines, as `@@` gives it: where it starts, counting from
+/// 1, or the line before it when it is empty, and how many lines it has.
+fn range(before: usize, count: usize) -> String {
+    match count {
+        0 => format!("{},0", before),
+        1 => format!("{}", before + 1),
+        _ => format!("{},{}", before + 1, count),
+    }
+}
+
+/// The unified diff from old to new, both of the file named, or None when
+/// they are the same.
+pub fn unified(old: &str, new: &str, name: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+    let old: Vec<&str> = old.split_inclusive('\n').collect();
+    let new: Vec<&str> = new.split_inclusive('\n').collect();
+    let edits = edits(&old, &new);
+    // where each edit is in old and new, before it is made
+    let mut at = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in &edits {
+        at.push((i, j));
+        match edit {
+            Edit::Same => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Removed => i += 1,
+            Edit::Added => j += 1,
+        }
+    }
+    at.push((i, j));
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&n| edits[n] != Edit::Same)
+        .collect();
+    let mut diff = format!("--- {}\n+++ {}\n", name, name);
+    let mut n = 0;
+    while n < changes.len() {
+        // changes closer together than twice the context share a hunk
+        let mut last = n;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT {
+            last += 1;
+        }
+        let start = changes[n].saturating_sub(CONTEXT);
+        let end = cmp::min(changes[last] + CONTEXT + 1, edits.len());
+        let ((i, j), (i_end, j_end)) = (at[start], at[end]);
+        diff.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(i, i_end - i),
+            range(j, j_end - j)
+        ));
+        for (edit, &(i, j)) in edits[start..end].iter().zip(&at[start..end]) {
+            let (mark, line) = match edit {
+                Edit::Same => (' ', old[i]),
+                Edit::Removed => ('-', old[i]),
+                Edit::Added => ('+', new[j]),
+            };
+            diff.push(mark);
+            diff.push_str(line);
+            if !line.ends_with('\n') {
+                diff.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        n = last + 1;
+    }
+    Some(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unified;
+
+    #[test]
+    fn diffs() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "f"), None);
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13";
+        assert_eq!(
+            unified(old, new, "f.c").unwrap(),
+            "--- f.c\n+++ f.c\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n\\ No newline at end of file\n"
+        );
+        assert_eq!(
+            unified("", "a\n", "f.c").unwrap(),
+            "--- f.c\n+++ f.c\n@@ -0,0 +1 @@\n+a\n"
+        );
+    }
+}
@@ -0,0 +1,394 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! JSON values, just enough to write them out and to read specs.
+
+use std::error::Error;
+use std::fmt;
+
+/// A JSON value. Objects keep their keys in the order given.
+pub enum Json {
+    /// null.
+    Null,
+    /// true or false.
+    Bool(bool),
+    /// A number, which in specs is always an integer.
+    Num(i64),
+    /// A string.
+    Str(String),
+    /// An array.
+    Arr(Vec<Json>),
+    /// An object, as its keys and values.
+    Obj(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// A string, or null.
+    pub fn opt_str(s: Option<&str>) -> Json {
+        match s {
+            Some(s) => Json::Str(s.to_owned()),
+            None => Json::Null,
+        }
+    }
+    /// An object from (key, value) pairs.
+    pub fn obj<'a, I>(pairs: I) -> Json
+    where
+        I: IntoIterator<Item = (&'a str, Json)>,
+    {
+        Json::Obj(pairs.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+    /// The value on one line, with no spaces, as JSON Lines want each value.
+    pub fn line(&self) -> String {
+        struct Line<'a>(&'a Json);
+        impl fmt::Display for Line<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self.0 {
+                    Json::Arr(items) => {
+                        write!(f, "[")?;
+                        for (i, item) in items.iter().enumerate() {
+                            write!(f, "{}{}", if i > 0 { "," } else { "" }, Line(item))?;
+                        }
+                        write!(f, "]")
+                    }
+                    Json::Obj(pairs) => {
+                        write!(f, "{{")?;
+                        for (i, (key, value)) in pairs.iter().enumerate() {
+                            write!(f, "{}", if i > 0 { "," } else { "" })?;
+                            write_str(f, key)?;
+                            write!(f, ":{}", Line(value))?;
+                        }
+                        write!(f, "}}")
+                    }
+                    value => value.write(f, 0),
+                }
+            }
+        }
+        Line(self).to_string()
+    }
+    /// Writes the value, indenting nested arrays and objects by two spaces a level.
+    fn write(&self, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+        let indent = "  ".repeat(level + 1);
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Num(n) => write!(f, "{}", n),
+            Json::Str(s) => write_str(f, s),
+            Json::Arr(items) if items.is_empty() => write!(f, "[]"),
+            Json::Arr(items) => {
+                writeln!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{}", indent)?;
+                    item.write(f, level + 1)?;
+                    writeln!(f, "{}", if i + 1 < items.len() { "," } else { "" })?;
+                }
+                write!(f, "{}]", "  ".repeat(level))
+            }
+            Json::Obj(pairs) if pairs.is_empty() => write!(f, "{{}}"),
+            Json::Obj(pairs) => {
+                writeln!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    write!(f, "{}", indent)?;
+                    write_str(f, key)?;
+                    write!(f, ": ")?;
+                    value.write(f, level + 1)?;
+                    writeln!(f, "{}", if i + 1 < pairs.len() { "," } else { "" })?;
+                }
+                write!(f, "{}}}", "  ".repeat(level))
+            }
+        }
+    }
+}
+
+/// Why a JSON document couldn't be read, and where, counting from 1.
+#[derive(Debug)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    message: String,
+}
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {} column {}",
+            self.message, self.line, self.column
+        )
+    }
+}
+impl Error for ParseError {}
+impl ParseError {
+    /// The line the error is on, counting from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    /// The column the error is at, in characters, counting from 1.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+/// Reads a JSON document. Numbers must be integers, which is all a spec has.
+pub fn parse(text: &str) -> Result<Json, ParseError> {
+    let mut p = Parser {
+        text,
+        chars: text.char_indices().peekable(),
+    };
+    let value = p.value()?;
+    p.skip_space();
+    match p.chars.peek() {
+        Some(_) => Err(p.error("trailing characters")),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    /// An error at the next character.
+    fn error(&mut self, message: &str) -> ParseError {
+        let at = self.chars.peek().map_or(self.text.len(), |&(i, _)| i);
+        let before = &self.text[..at];
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column: before.rsplit('\n').next().unwrap_or("").chars().count() + 1,
+            message: message.to_owned(),
+        }
+    }
+    fn skip_space(&mut self) {
+        while let Some((_, ' ')) | Some((_, '\t')) | Some((_, '\n')) | Some((_, '\r')) =
+            self.chars.peek()
+        {
+            self.chars.next();
+        }
+    }
+    /// Consumes c, after any whitespace, or fails.
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        self.skip_space();
+        match self.chars.peek() {
+            Some(&(_, next)) if next == c => {
+                self.chars.next();
+                Ok(())
+            }
+            _ => Err(self.error(&format!("expected '{}'", c))),
+        }
+    }
+    /// Consumes a keyword such as true, or fails.
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, ParseError> {
+        for expected in word.chars() {
+            match self.chars.peek() {
+                Some(&(_, c)) if c == expected => {
+                    self.chars.next();
+                }
+                _ => return Err(self.error(&format!("expected {}", word))),
+            }
+        }
+        Ok(value)
+    }
+    fn value(&mut self) -> Result<Json, ParseError> {
+        self.skip_space();
+        match self.chars.peek().map(|&(_, c)| c) {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Json::Str(self.string()?)),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+    fn object(&mut self) -> Result<Json, ParseError> {
+        self.expect('{')?;
+        let mut pairs = Vec::new();
+        self.skip_space();
+        if let Some((_, '}')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Json::Obj(pairs));
+        }
+        loop {
+            self.skip_space();
+            if !matches!(self.chars.peek(), Some((_, '"'))) {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            pairs.push((key, self.value()?));
+            self.skip_space();
+            match self.chars.next() {
+                Some((_, ',')) => {}
+                Some((_, '}')) => return Ok(Json::Obj(pairs)),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+    fn array(&mut self) -> Result<Json, ParseError> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_space();
+        if let Some((_, ']')) = self.chars.peek() {
+            self.chars.next();
+            return Ok(Json::Arr(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_space();
+            match self.chars.next() {
+                Some((_, ',')) => {}
+                Some((_, ']')) => return Ok(Json::Arr(items)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => {
+                    let c = match self.chars.next() {
+                        Some((_, '"')) => '"',
+                        Some((_, '\\')) => '\\',
+                        Some((_, '/')) => '/',
+                        Some((_, 'b')) => '\u{8}',
+                        Some((_, 'f')) => '\u{c}',
+                        Some((_, 'n')) => '\n',
+                        Some((_, 'r')) => '\r',
+                        Some((_, 't')) => '\t',
+                        Some((_, 'u')) => self.escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    s.push(c);
+                }
+                Some((_, c)) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string"))
+                }
+                Some((_, c)) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+    /// The character of a \u escape, whose \u has been read, joining
+    /// surrogate pairs.
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.chars.next().map(|(_, c)| c) != Some('\\')
+                || self.chars.next().map(|(_, c)| c) != Some('u')
+            {
+                return Err(self.error("unpaired surrogate"));
+            }
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        std::char::from_u32(code).ok_or_else(|| self.error("invalid escape"))
+    }
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            match self.chars.next().and_then(|(_, c)| c.to_digit(16)) {
+                Some(d) => code = code * 16 + d,
+                None => return Err(self.error("invalid escape")),
+            }
+        }
+        Ok(code)
+    }
+    fn number(&mut self) -> Result<Json, ParseError> {
+        let start = self.chars.peek().map_or(0, |&(i, _)| i);
+        let mut end = start;
+        while let Some(&(i, c)) = self.chars.peek() {
+            if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                break;
+            }
+            end = i + c.len_utf8();
+            self.chars.next();
+        }
+        match self.text[start..end].parse() {
+            Ok(n) => Ok(Json::Num(n)),
+            Err(_) => Err(self.error("expected an integer")),
+        }
+    }
+}
+
+fn write_str(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Json};
+
+    #[test]
+    fn pretty() {
+        let v = Json::obj(vec![
+            ("a", Json::Arr(vec![Json::Num(1), Json::Null])),
+            ("b", Json::Str(String::from("q\"\n\u{1}"))),
+            ("c", Json::Arr(vec![])),
+            ("d", Json::obj(vec![("e", Json::Bool(true))])),
+        ]);
+        assert_eq!(
+            v.to_string(),
+            "{\n  \"a\": [\n    1,\n    null\n  ],\n  \"b\": \"q\\\"\\n\\u0001\",\n  \
+             \"c\": [],\n  \"d\": {\n    \"e\": true\n  }\n}"
+        );
+        assert_eq!(
+            v.line(),
+            "{\"a\":[1,null],\"b\":\"q\\\"\\n\\u0001\",\"c\":[],\"d\":{\"e\":true}}"
+        );
+    }
+
+    #[test]
+    fn read() {
+        let v = parse(" {\"a\": [1, -2, null], \"b\": \"q\\\"\\u00e9\\ud83d\\ude00\", \"c\": {}, \"d\": true} ")
+            .unwrap();
+        assert_eq!(
+            v.to_string(),
+            "{\n  \"a\": [\n    1,\n    -2,\n    null\n  ],\n  \"b\": \"q\\\"\u{e9}\u{1f600}\",\n  \
+             \"c\": {},\n  \"d\": true\n}"
+        );
+        let e = parse("{\n  \"a\": 1.5\n}").err().unwrap();
+        assert_eq!(e.to_string(), "expected an integer at line 2 column 11");
+        assert!(parse("[1, 2").is_err());
+        assert!(parse("{} x").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! argen's core: reading specs and generating code from them. It opens no
+//! files but those a spec includes, runs no programs, and prints nothing, so
+//! it builds for `wasm32-unknown-unknown`, where [`generate`] turns a spec
+//! into C for a web playground, and can be called from build scripts and
+//! other tools rather than running the `argen` binary. Only the [`build`]
+//! module, for build scripts, writes files, into `OUT_DIR`, and prints
+//! cargo's instructions.
+//!
+//! The `cli` feature, on by default, is the binary's: it brings in getopts
+//! and regex, and importing specs from other tools. Built with
+//! `default-features = false`, the library depends only on serde and toml.
+//! The `cc` feature adds `build::compile`, compiling the C it generates.
+//!
+//! A [`Spec`] is read from TOML or JSON, checked, and generated from:
+//!
+//! ```
+//! let spec = argen::Spec::from_str(
+//!     r#"
+//!     [[non_positional]]
+//!     c_var = "verbose"
+//!     c_type = "int"
+//!     long = "verbose"
+//!     flag = true
+//!     help_descr = "say more"
+//!     "#,
+//! )?;
+//! assert!(spec.lints().is_empty());
+//! let c = spec.gen();
+//! assert!(c.contains("void parse_args(int argc, char **argv"));
+//! # Ok::<(), argen::Diagnostics>(())
+//! ```
+//!
+//! `from_str` returns [`Diagnostics`] listing every problem the spec has,
+//! each with its severity, code, message, lines and notes;
+//! [`Spec::diagnostics`] adds the lints of a spec that reads, as warnings.
+//! [`Spec::parse_str_in`] reads a spec without checking it.
+//!
+//! What is exported here follows semantic versioning: a release that isn't
+//! a new major version removes nothing and changes no signature, though
+//! [`ValidationError`] and [`Warning`] may gain kinds of problem. Their codes
+//! never change meaning. The hidden `cli` module, there only with the `cli`
+//! feature, is the `argen` binary's, and isn't part of this API: it may
+//! change in any release.
+
+#![warn(missing_docs)]
+// without the binary, the parts of the library only it uses go unused
+#![cfg_attr(not(feature = "cli"), allow(dead_code, unused_imports))]
+
+pub mod build;
+mod codegen;
+mod json;
+mod log;
+mod sha256;
+
+pub use codegen::backend;
+pub use codegen::visit;
+pub use codegen::{
+    generate, CType, Change, CodeGenerator, Define, Diagnostic, Diagnostics, Impact, Label,
+    NonPositionalItem, PositionalItem, Severity, Spec, SpecVisitor, Subcommand, Template,
+    TemplateError, ValidationError, Warning, SCHEMA,
+};
+pub use json::{Json, ParseError as JsonError};
+
+/// What the `argen` binary uses of the library beyond its API.
+#[cfg(feature = "cli")]
+#[doc(hidden)]
+pub mod cli {
+    #[allow(missing_docs)]
+    pub mod codegen {
+        pub use crate::codegen::*;
+    }
+    #[allow(missing_docs)]
+    pub mod json {
+        pub use crate::json::*;
+    }
+    #[allow(missing_docs)]
+    pub mod log {
+        pub use crate::log::*;
+    }
+}
@@ -0,0 +1,167 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! What argen is doing: the specs it reads, the files they include, and the
+//! files it writes, as much as -v, --quiet or `RUST_LOG` asks for. Log lines
+//! go to the logger set, which for argen's command line prints them as
+//! diagnostics are; without one, they go nowhere.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+
+/// How much argen says, each level saying what those before it do. Errors
+/// are always printed.
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Level {
+    /// What stops argen doing what it was asked.
+    Error,
+    /// What a spec probably didn't mean.
+    Warn,
+    /// What argen does: the specs it reads and files it writes.
+    Info,
+    /// How it does it.
+    Debug,
+    /// Everything it knows.
+    Trace,
+}
+
+impl Level {
+    const ALL: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    /// The level by its name in `RUST_LOG`, where off is as quiet as argen
+    /// gets.
+    fn from_name(name: &str) -> Option<Level> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" | "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+
+static LOGGER: OnceLock<fn(Level, fmt::Arguments)> = OnceLock::new();
+
+/// Sets where log lines go, once.
+pub fn set_logger(logger: fn(Level, fmt::Arguments)) {
+    let _ = LOGGER.set(logger);
+}
+
+/// Sets how much argen says.
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Whether argen says what is at the level.
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// The level `RUST_LOG` asks of argen, reading it as env_logger does: a
+/// list of directives, each a level, a module and its level, or a module
+/// alone, for all of it. Directives for argen win over one for everything,
+/// and those for other crates don't matter.
+pub fn from_env(rust_log: &str) -> Option<Level> {
+    let mut everything = None;
+    let mut argen = None;
+    // a /regex filtering messages is left out
+    let directives = rust_log.split('/').next().unwrap_or_default();
+    for directive in directives.split(',').map(str::trim) {
+        match directive.split_once('=') {
+            Some((module, level)) if is_argen(module.trim()) => {
+                argen = Level::from_name(level.trim()).or(argen)
+            }
+            Some(_) => {}
+            None if is_argen(directive) => argen = Some(Level::Trace),
+            None => everything = Level::from_name(directive).or(everything),
+        }
+    }
+    argen.or(everything)
+}
+
+/// Whether a module named in `RUST_LOG` is argen or one of its own.
+fn is_argen(module: &str) -> bool {
+    module == "argen" || module.starts_with("argen::")
+}
+
+/// The level asked for on the command line, with -v given some number of
+/// times or --quiet, or otherwise by `RUST_LOG`: warnings and errors unless
+/// something asks for more or less.
+pub fn level(verbose: usize, quiet: bool, rust_log: Option<&str>) -> Level {
+    match (verbose, quiet) {
+        (0, true) => Level::Error,
+        (0, false) => rust_log.and_then(from_env).unwrap_or(Level::Warn),
+        (n, _) => Level::ALL[(Level::Warn as usize + n).min(Level::ALL.len() - 1)],
+    }
+}
+
+fn log(level: Level, message: fmt::Arguments) {
+    if let Some(logger) = LOGGER.get().filter(|_| enabled(level)) {
+        logger(level, message)
+    }
+}
+
+/// Says what argen does, with --verbose.
+pub fn info(message: fmt::Arguments) {
+    log(Level::Info, message)
+}
+
+/// Says how argen does it, with --verbose twice.
+pub fn debug(message: fmt::Arguments) {
+    log(Level::Debug, message)
+}
+
+/// Says everything argen knows, with --verbose three times.
+pub fn trace(message: fmt::Arguments) {
+    log(Level::Trace, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_env, level, Level};
+
+    #[test]
+    fn levels() {
+        assert_eq!(from_env("debug"), Some(Level::Debug));
+        assert_eq!(from_env("warn,argen=trace"), Some(Level::Trace));
+        assert_eq!(from_env("argen::include=info,error"), Some(Level::Info));
+        assert_eq!(from_env("info,hyper=debug"), Some(Level::Info));
+        assert_eq!(from_env("argen"), Some(Level::Trace));
+        assert_eq!(from_env("OFF"), Some(Level::Error));
+        assert_eq!(from_env("debug/spec"), Some(Level::Debug));
+        assert_eq!(from_env("hyper=debug"), None);
+        assert_eq!(from_env(""), None);
+        assert_eq!(level(0, false, None), Level::Warn);
+        assert_eq!(level(0, false, Some("debug")), Level::Debug);
+        assert_eq!(level(0, false, Some("nonsense")), Level::Warn);
+        // the command line wins over RUST_LOG
+        assert_eq!(level(0, true, Some("debug")), Level::Error);
+        assert_eq!(level(1, false, Some("trace")), Level::Info);
+        assert_eq!(level(2, false, None), Level::Debug);
+        assert_eq!(level(5, false, None), Level::Trace);
+    }
+}
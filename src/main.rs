@@ -14,82 +14,2118 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-mod codegen;
+mod annotate;
+mod completions;
+mod config;
+mod diff;
+mod man;
+mod scaffold;
 
-use codegen::Spec;
+use annotate::{Diagnostic, Severity};
+use argen::cli::{codegen, json, log};
+use codegen::{
+    backend, replace_generated, splice_user_regions, CodeGenerator, Define, Impact, Spec,
+    ValidationError, Warning,
+};
 use getopts::Options;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io;
-use std::io::{Read, Write};
+use std::io::{BufWriter, IsTerminal, Read, Write};
 use std::path::Path;
-use std::process;
+use std::process::{self, Command, Stdio};
 
-const VERSION: &str = "1.0.0";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} [options] SPEC.toml", program);
-    print!("{}", opts.usage(&brief));
+/// An error in a spec: the spec's name and source, and the error.
+#[derive(Debug)]
+struct Located {
+    name: String,
+    source: String,
+    error: ValidationError,
 }
 
-fn codegen(filename: String, output: Option<String>) {
-    let path = Path::new(&filename);
-    let mut f = File::open(&path).expect("open input toml");
+/// Why argen stopped, each kind with its own exit status.
+#[derive(Debug)]
+enum CliError {
+    /// The command line is wrong.
+    Usage(String),
+    /// A file couldn't be read or written: what was being done, and why.
+    Io(String, io::Error),
+    /// A spec isn't TOML or JSON.
+    Parse(Box<Located>),
+    /// A spec was read, but can't be generated from.
+    Invalid(Box<Located>),
+    /// Problems that were already reported, as `argen check` does.
+    Reported,
+    /// Something else went wrong, such as clang-format.
+    Failed(String),
+}
+impl CliError {
+    /// A spec's error, at its location in the spec.
+    fn spec(name: &str, contents: &str, e: ValidationError) -> CliError {
+        let parse = matches!(
+            e,
+            ValidationError::TomlError(_) | ValidationError::JsonError(_)
+        );
+        let located = Box::new(Located {
+            name: name.to_owned(),
+            source: contents.to_owned(),
+            error: e,
+        });
+        match parse {
+            true => CliError::Parse(located),
+            false => CliError::Invalid(located),
+        }
+    }
+    fn exit_status(&self) -> i32 {
+        match self {
+            CliError::Invalid(_) | CliError::Reported | CliError::Failed(_) => 1,
+            CliError::Usage(_) => 2,
+            CliError::Parse(_) => 3,
+            CliError::Io(_, _) => 4,
+        }
+    }
+    /// The error as argen reports it, unless it already was.
+    fn diagnostic(&self) -> Option<Diagnostic<'_>> {
+        let error = |message| Diagnostic::new(Severity::Error, message);
+        match self {
+            CliError::Usage(message) => Some(Diagnostic {
+                hint: Some("run with --help for how to use argen"),
+                ..error(message.to_owned())
+            }),
+            CliError::Io(doing, e) => {
+                // without the "(os error 2)" that the OS's message comes with
+                let message = e.to_string();
+                let message = match message.find(" (os error") {
+                    Some(i) => &message[..i],
+                    None => &message,
+                };
+                Some(error(format!("{}: {}", doing, message)))
+            }
+            CliError::Parse(located) | CliError::Invalid(located) => {
+                let Located {
+                    name,
+                    source,
+                    error: e,
+                } = &**located;
+                Some(Diagnostic {
+                    code: Some(e.code()),
+                    file: Some(name),
+                    source,
+                    labels: e.labels(source),
+                    ..error(e.to_string())
+                })
+            }
+            CliError::Reported => None,
+            CliError::Failed(message) => Some(error(message.to_owned())),
+        }
+    }
+}
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.diagnostic() {
+            Some(diagnostic) => write!(f, "{}", diagnostic.render()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The contents of a file, or of stdin for "-".
+fn read_input(filename: &str, what: &str) -> Result<String, CliError> {
     let mut contents = String::new();
-    f.read_to_string(&mut contents).expect("read input toml");
-    let s = Spec::from_str(&contents);
-    if let Err(e) = s {
-        writeln!(&mut io::stderr(), "Spec Parse Error: {}", e).unwrap();
-        process::exit(1);
+    let read = if filename == "-" {
+        io::stdin().read_to_string(&mut contents)
+    } else {
+        File::open(filename)
+            .map_err(|e| CliError::Io(format!("cannot open {} '{}'", what, filename), e))?
+            .read_to_string(&mut contents)
+    };
+    match read {
+        Ok(_) => Ok(contents),
+        Err(e) if filename == "-" => Err(CliError::Io(
+            format!("cannot read {} from standard input", what),
+            e,
+        )),
+        Err(e) => Err(CliError::Io(
+            format!("cannot read {} '{}'", what, filename),
+            e,
+        )),
     }
-    let s = s.unwrap();
+}
+
+/// Writes text to a file, or to stdout without one.
+fn write_output(output: Option<&str>, text: &str) -> Result<(), CliError> {
     match output {
-        Some(f) => {
-            let p = Path::new(&f);
-            let mut f = File::create(&p).expect("open output file");
-            s.writeout(&mut f)
+        Some(p) => {
+            std::fs::write(p, text)
+                .map_err(|e| CliError::Io(format!("cannot write output file '{}'", p), e))?;
+            log::info(format_args!("wrote '{}'", p));
+            Ok(())
+        }
+        None => io::stdout()
+            .write_all(text.as_bytes())
+            .map_err(|e| CliError::Io(String::from("cannot write to standard output"), e)),
+    }
+}
+
+/// An option of argen's own command line.
+struct Flag {
+    short: &'static str,
+    long: &'static str,
+    help: &'static str,
+    /// The name of the option's argument, if it takes one.
+    hint: Option<&'static str>,
+    /// Whether the argument, given with `=`, may be left out.
+    optional: bool,
+    /// Whether the option may be given more than once.
+    multi: bool,
+    /// The values the argument may take, for completion; without any, it
+    /// names a file.
+    values: &'static [&'static str],
+}
+
+impl Flag {
+    const fn switch(short: &'static str, long: &'static str, help: &'static str) -> Flag {
+        Flag {
+            short,
+            long,
+            help,
+            hint: None,
+            optional: false,
+            multi: false,
+            values: &[],
+        }
+    }
+    const fn takes(
+        short: &'static str,
+        long: &'static str,
+        help: &'static str,
+        hint: &'static str,
+        values: &'static [&'static str],
+    ) -> Flag {
+        Flag {
+            short,
+            long,
+            help,
+            hint: Some(hint),
+            optional: false,
+            multi: false,
+            values,
+        }
+    }
+}
+
+/// argen's options, from which both its parsing and its shell completions
+/// are made.
+const FLAGS: &[Flag] = &[
+    Flag::takes("o", "", "set output file name, or - for stdout", "NAME", &[]),
+    Flag::switch(
+        "",
+        "auto-name",
+        "name the output after the spec, or as its c_file says",
+    ),
+    Flag::switch(
+        "",
+        "diff",
+        "write nothing, but print how the output files would change, failing if they would",
+    ),
+    Flag::switch("", "check-output", "the same as --diff"),
+    Flag::switch(
+        "",
+        "compile-check",
+        "compile the output with $CC -fsyntax-only -Wall (or $CXX for c++), failing on any diagnostic",
+    ),
+    Flag::takes(
+        "",
+        "out-dir",
+        "write each spec's output, named as with --auto-name, to a directory",
+        "DIR",
+        &[],
+    ),
+    Flag::takes("", "header", "also write a companion header file", "NAME", &[]),
+    Flag::takes(
+        "",
+        "rust-ffi",
+        "also write Rust bindings to the C parser",
+        "FILE",
+        &[],
+    ),
+    Flag::switch("", "no-main", "do not generate a main function"),
+    Flag::switch(
+        "",
+        "force",
+        "overwrite output files that argen did not generate",
+    ),
+    Flag::switch(
+        "",
+        "backup",
+        "overwrite output files that argen did not generate, keeping each as FILE.bak",
+    ),
+    Flag::takes(
+        "",
+        "inplace",
+        "replace the code between /* argen:generated */ markers in a file",
+        "FILE",
+        &[],
+    ),
+    Flag::switch(
+        "",
+        "no-provenance",
+        "leave out the comment naming the argen version and spec file",
+    ),
+    Flag {
+        optional: true,
+        ..Flag::takes(
+            "",
+            "format",
+            "reformat output with clang-format, or a built-in approximation of the style",
+            "STYLE",
+            &[
+                "file",
+                "llvm",
+                "google",
+                "chromium",
+                "mozilla",
+                "webkit",
+                "gnu",
+                "microsoft",
+            ],
+        )
+    },
+    Flag::takes(
+        "",
+        "backend",
+        "what to generate: a parser in c (the default), c++, rust, go, powershell or zig, readme for Markdown usage docs, json as --describe-json does, or tests, fuzz or eval",
+        "LANG",
+        TARGETS,
+    ),
+    Flag::takes("", "target", "the same as --backend", "LANG", TARGETS),
+    Flag {
+        multi: true,
+        ..Flag::takes(
+            "",
+            "backend-opt",
+            "set a setting of the spec for the backend, or with BACKEND: in front, only when generating for that backend",
+            "[BACKEND:]KEY=VALUE",
+            &[],
+        )
+    },
+    Flag {
+        multi: true,
+        ..Flag::takes(
+            "D",
+            "define",
+            "set a value of the spec: a setting, vars.NAME, or ITEM.FIELD of an item by its c_var or long",
+            "KEY=VALUE",
+            &[],
+        )
+    },
+    Flag::takes(
+        "",
+        "spec-format",
+        "read the spec as toml or json, rather than by its file extension",
+        "FORMAT",
+        &["toml", "json"],
+    ),
+    Flag::switch(
+        "",
+        "describe-json",
+        "describe the spec's options and arguments as JSON instead of generating code",
+    ),
+    Flag::takes(
+        "",
+        "template",
+        "render a Handlebars template with the spec's description instead of generating code",
+        "FILE",
+        &[],
+    ),
+    Flag::switch(
+        "",
+        "strict",
+        "fail on warnings about the spec, such as options without help text",
+    ),
+    Flag {
+        multi: true,
+        ..Flag::takes(
+            "",
+            "deny",
+            "fail on the warnings with these codes, such as W001,W005",
+            "CODES",
+            &[],
+        )
+    },
+    Flag::switch(
+        "",
+        "no-config",
+        "ignore the defaults in ~/.config/argen/config.toml and .argen.toml",
+    ),
+    Flag::takes(
+        "",
+        "color",
+        "color diagnostics: auto (on a terminal, unless NO_COLOR is set), always or never",
+        "WHEN",
+        &annotate::WHEN,
+    ),
+    Flag::takes(
+        "",
+        "message-format",
+        "print diagnostics for people to read (human, the default), or each as a line of JSON",
+        "FORMAT",
+        &annotate::FORMATS,
+    ),
+    Flag {
+        multi: true,
+        ..Flag::switch(
+            "",
+            "verbose",
+            "say what argen does: the specs it reads and files it writes, more when given twice or three times",
+        )
+    },
+    Flag::switch(
+        "q",
+        "quiet",
+        "print only errors, leaving out warnings",
+    ),
+    Flag::switch("h", "help", "print this help menu"),
+    Flag::switch("v", "version", "show version"),
+];
+
+/// What --backend can generate.
+const TARGETS: &[&str] = &[
+    "c",
+    "c++",
+    "rust",
+    "go",
+    "powershell",
+    "zig",
+    "readme",
+    "json",
+    "tests",
+    "fuzz",
+    "eval",
+];
+
+/// One of argen's commands, by the word starting its command line: what it
+/// does, how it is used, and the options it takes, beyond those every
+/// command takes.
+struct Subcommand {
+    name: &'static str,
+    help: &'static str,
+    usage: &'static str,
+    flags: &'static [&'static str],
+}
+
+/// The options every command takes, which say how argen runs.
+const COMMON_FLAGS: &[&str] = &[
+    "no-config",
+    "color",
+    "message-format",
+    "verbose",
+    "quiet",
+    "help",
+    "version",
+];
+
+/// The options of the commands that generate from a spec.
+const GEN_FLAGS: &[&str] = &[
+    "o",
+    "auto-name",
+    "diff",
+    "check-output",
+    "compile-check",
+    "out-dir",
+    "header",
+    "rust-ffi",
+    "no-main",
+    "force",
+    "backup",
+    "inplace",
+    "no-provenance",
+    "format",
+    "backend",
+    "target",
+    "backend-opt",
+    "define",
+    "spec-format",
+    "describe-json",
+    "template",
+    "strict",
+    "deny",
+];
+
+/// argen's commands; a command line starting with none of them is gen's.
+const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand {
+        name: "gen",
+        help: "generate a parser, or what --backend names (the default)",
+        usage: "[options] SPEC.toml...",
+        flags: GEN_FLAGS,
+    },
+    Subcommand {
+        name: "tests",
+        help: "generate unit tests for the C parser",
+        usage: "[options] SPEC.toml...",
+        flags: GEN_FLAGS,
+    },
+    Subcommand {
+        name: "fuzz",
+        help: "generate a libFuzzer target for the C parser",
+        usage: "[options] SPEC.toml...",
+        flags: GEN_FLAGS,
+    },
+    Subcommand {
+        name: "eval",
+        help: "generate a program printing the parsed values for a shell to eval",
+        usage: "[options] SPEC.toml...",
+        flags: GEN_FLAGS,
+    },
+    Subcommand {
+        name: "new",
+        help: "ask about a new program, and write a starter spec and its parser",
+        usage: "[--force] [NAME]",
+        flags: &["force"],
+    },
+    Subcommand {
+        name: "check",
+        help: "list everything wrong with a spec",
+        usage: "[--strict] [--deny CODES] [--backend BACKEND] SPEC.toml",
+        flags: &[
+            "backend",
+            "target",
+            "backend-opt",
+            "define",
+            "spec-format",
+            "strict",
+            "deny",
+        ],
+    },
+    Subcommand {
+        name: "fmt",
+        help: "rewrite a spec in canonical form",
+        usage: "[-o FILE] SPEC.toml",
+        flags: &["o", "define", "spec-format"],
+    },
+    Subcommand {
+        name: "explain",
+        help: "say what the parser generated from a spec does",
+        usage: "SPEC.toml",
+        flags: &["define", "spec-format"],
+    },
+    Subcommand {
+        name: "diff",
+        help: "say how a program's command line changes from one spec to another",
+        usage: "OLD.toml NEW.toml",
+        flags: &["define", "spec-format"],
+    },
+    Subcommand {
+        name: "upgrade",
+        help: "rewrite a spec in the current schema of the spec format",
+        usage: "[-o FILE] SPEC.toml",
+        flags: &["o", "spec-format"],
+    },
+    Subcommand {
+        name: "migrate",
+        help: "convert a legacy args.rs JSON spec to TOML",
+        usage: "[-o NEW.toml] OLD.json",
+        flags: &["o"],
+    },
+    Subcommand {
+        name: "import",
+        help: "draft a spec from another definition of a command line",
+        usage: "clap|docopt|getopt|help [-o SPEC.toml] FILE",
+        flags: &["o"],
+    },
+    Subcommand {
+        name: "schema",
+        help: "print a JSON Schema of specs",
+        usage: "[-o FILE]",
+        flags: &["o"],
+    },
+    Subcommand {
+        name: "man",
+        help: "print argen's man page",
+        usage: "[-o FILE]",
+        flags: &["o"],
+    },
+    Subcommand {
+        name: "completions",
+        help: "print a completion script for bash, zsh or fish",
+        usage: "bash|zsh|fish [-o FILE]",
+        flags: &["o"],
+    },
+];
+
+impl Flag {
+    /// The option's name, as Options knows it: its long name, or without one,
+    /// its short name.
+    fn name(&self) -> &'static str {
+        match self.long {
+            "" => self.short,
+            long => long,
+        }
+    }
+    /// The option as it is given on the command line.
+    fn spelling(&self) -> String {
+        match self.long {
+            "" => format!("-{}", self.short),
+            long => format!("--{}", long),
+        }
+    }
+}
+
+impl Subcommand {
+    /// Whether the command takes the option.
+    fn takes(&self, flag: &Flag) -> bool {
+        COMMON_FLAGS.contains(&flag.name()) || self.flags.contains(&flag.name())
+    }
+    /// The command named by the first of a command line's free arguments,
+    /// if it names one.
+    fn named(free: &[String]) -> Option<&'static Subcommand> {
+        let name = free.first()?;
+        SUBCOMMANDS.iter().find(|sub| sub.name == name)
+    }
+}
+
+/// The options argen parses its command line with: all of them, or those a
+/// command takes, for its help.
+fn options(only: Option<&Subcommand>) -> Options {
+    let mut opts = Options::new();
+    for flag in FLAGS
+        .iter()
+        .filter(|flag| only.is_none_or(|sub| sub.takes(flag)))
+    {
+        match flag.hint {
+            Some(hint) if flag.optional => opts.optflagopt(flag.short, flag.long, flag.help, hint),
+            Some(hint) if flag.multi => opts.optmulti(flag.short, flag.long, flag.help, hint),
+            Some(hint) => opts.optopt(flag.short, flag.long, flag.help, hint),
+            None if flag.multi => opts.optflagmulti(flag.short, flag.long, flag.help),
+            None => opts.optflag(flag.short, flag.long, flag.help),
+        };
+    }
+    opts
+}
+
+/// Prints how to use a command, with the options it takes. Run without one,
+/// argen also lists its commands.
+fn print_usage(program: &str, sub: &Subcommand, named: bool) {
+    let mut brief = format!("Usage: {} {} {}\n", program, sub.name, sub.usage);
+    if !named {
+        brief = format!(
+            "Usage: {0} [gen] [options] SPEC.toml...\n       {0} COMMAND [options] ...\n\nCommands:\n",
+            program
+        );
+        for sub in SUBCOMMANDS {
+            brief.push_str(&format!("    {:<13}{}\n", sub.name, sub.help));
+        }
+    }
+    if sub.usage.contains("SPEC") {
+        brief.push_str(
+            "\nA SPEC of - reads the spec from stdin, as does no SPEC with stdin piped.\n",
+        );
+    }
+    if !named {
+        brief.push_str(&format!(
+            "\nRun {} COMMAND --help for the options a command takes; those of gen follow.",
+            program
+        ));
+    }
+    print!("{}", options(Some(sub)).usage(brief.trim_end()));
+}
+
+fn has_clang_format() -> bool {
+    Command::new("clang-format")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// The languages argen generates parsers in, by --backend name.
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    C,
+    Cpp,
+    Rust,
+    Go,
+    PowerShell,
+    Readme,
+    Zig,
+}
+impl Backend {
+    fn from_name(name: &str) -> Option<Backend> {
+        match name {
+            "c" => Some(Backend::C),
+            "c++" | "cpp" => Some(Backend::Cpp),
+            "rust" => Some(Backend::Rust),
+            "go" => Some(Backend::Go),
+            "powershell" | "ps1" => Some(Backend::PowerShell),
+            "readme" | "markdown" => Some(Backend::Readme),
+            "zig" => Some(Backend::Zig),
+            _ => None,
+        }
+    }
+    /// What generates the language, for a program of the name.
+    fn generator(self, program: &str) -> Box<dyn CodeGenerator> {
+        let program = program.to_owned();
+        match self {
+            Backend::C => Box::new(backend::C),
+            Backend::Cpp => Box::new(backend::Cpp),
+            Backend::Rust => Box::new(backend::Rust),
+            Backend::Go => Box::new(backend::Go),
+            Backend::PowerShell => Box::new(backend::PowerShell { program }),
+            Backend::Readme => Box::new(backend::Readme { program }),
+            Backend::Zig => Box::new(backend::Zig),
+        }
+    }
+    /// The extension of a file in the language.
+    fn extension(self) -> &'static str {
+        match self {
+            Backend::C => "c",
+            Backend::Cpp => "cpp",
+            Backend::Rust => "rs",
+            Backend::Go => "go",
+            Backend::PowerShell => "ps1",
+            Backend::Readme => "md",
+            Backend::Zig => "zig",
+        }
+    }
+    /// A file name in the language, so clang-format knows what it is
+    /// formatting, if clang-format can format it.
+    fn example_file(self) -> Option<&'static str> {
+        match self {
+            Backend::C => Some("argen.c"),
+            Backend::Cpp => Some("argen.cpp"),
+            Backend::Rust | Backend::Go | Backend::PowerShell | Backend::Readme | Backend::Zig => {
+                None
+            }
+        }
+    }
+}
+
+/// The backend of a name, or a usage error naming the backends.
+fn backend_named(name: &str) -> Result<Backend, CliError> {
+    Backend::from_name(name).ok_or_else(|| {
+        CliError::Usage(format!(
+            "unknown backend: {}; argen generates: {}",
+            name,
+            TARGETS.join(", ")
+        ))
+    })
+}
+
+/// Runs code through clang-format in the given style.
+fn clang_format(code: &str, style: &str, backend: Backend) -> Result<String, CliError> {
+    log::debug(format_args!(
+        "formatting with clang-format --style={}",
+        style
+    ));
+    let failed = |e: io::Error| CliError::Failed(format!("cannot run clang-format: {}", e));
+    let mut child = Command::new("clang-format")
+        .arg(format!("--style={}", style))
+        .arg(format!(
+            "--assume-filename={}",
+            backend
+                .example_file()
+                .expect("a language clang-format knows")
+        ))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+    child
+        .stdin
+        .take()
+        .expect("clang-format stdin")
+        .write_all(code.as_bytes())
+        .map_err(failed)?;
+    let out = child.wait_with_output().map_err(failed)?;
+    if !out.status.success() {
+        return Err(CliError::Failed(String::from("clang-format failed")));
+    }
+    String::from_utf8(out.stdout)
+        .map_err(|_| CliError::Failed(String::from("clang-format wrote invalid UTF-8")))
+}
+
+/// Compiles generated code with -fsyntax-only -Wall, by $CC, or $CXX for
+/// C++, failing with the compiler's diagnostics, named for the file, if it
+/// has any. Headers the code includes are found in dir.
+fn compile_check(code: &str, backend: Backend, name: &str, dir: &Path) -> Result<(), CliError> {
+    let (var, default, language) = match backend {
+        Backend::Cpp => ("CXX", "c++", "c++"),
+        _ => ("CC", "cc", "c"),
+    };
+    let compiler = env::var(var).unwrap_or_else(|_| String::from(default));
+    let mut words = compiler.split_whitespace();
+    let program = words.next().unwrap_or(default);
+    let failed = |e: io::Error| CliError::Failed(format!("cannot run {}: {}", program, e));
+    let mut child = Command::new(program)
+        .args(words)
+        .args(["-fsyntax-only", "-Wall", "-x", language])
+        .arg("-I")
+        .arg(dir)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(failed)?;
+    child
+        .stdin
+        .take()
+        .expect("compiler stdin")
+        .write_all(code.as_bytes())
+        .map_err(failed)?;
+    let out = child.wait_with_output().map_err(failed)?;
+    let diagnostics = String::from_utf8_lossy(&out.stderr).replace("<stdin>", name);
+    if !out.status.success() || !diagnostics.trim().is_empty() {
+        return Err(CliError::Failed(format!(
+            "{} doesn't compile cleanly with {}:\n{}",
+            name,
+            compiler,
+            diagnostics.trim_end()
+        )));
+    }
+    Ok(())
+}
+
+/// The languages argen reads specs in, by --spec-format name.
+#[derive(Clone, Copy, PartialEq)]
+enum SpecFormat {
+    Toml,
+    Json,
+}
+impl SpecFormat {
+    const NAMES: &'static str = "toml, json";
+    fn from_name(name: &str) -> Option<SpecFormat> {
+        match name {
+            "toml" => Some(SpecFormat::Toml),
+            "json" => Some(SpecFormat::Json),
+            _ => None,
+        }
+    }
+    /// The format of a spec file, by its extension.
+    fn detect(path: &Path) -> Result<SpecFormat, String> {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match ext {
+            "yaml" | "yml" => Err(format!(
+                "{}: YAML specs are not supported; supported formats: {}",
+                path.display(),
+                SpecFormat::NAMES
+            )),
+            _ => SpecFormat::from_name(&ext.to_ascii_lowercase()).ok_or_else(|| {
+                format!(
+                    "{}: cannot tell the spec format from the file extension; supported formats: {} (use --spec-format to choose one)",
+                    path.display(),
+                    SpecFormat::NAMES
+                )
+            }),
+        }
+    }
+}
+
+/// Programs argen generates around the C parser, by subcommand name.
+#[derive(Clone, Copy, PartialEq)]
+enum Harness {
+    /// Unit tests, running the parser on canned command lines.
+    Tests,
+    /// A libFuzzer target.
+    Fuzz,
+    /// A program printing the parsed values as shell assignments.
+    Eval,
+}
+impl Harness {
+    fn from_name(name: &str) -> Option<Harness> {
+        match name {
+            "tests" => Some(Harness::Tests),
+            "fuzz" => Some(Harness::Fuzz),
+            "eval" => Some(Harness::Eval),
+            _ => None,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            Harness::Tests => "tests",
+            Harness::Fuzz => "fuzz",
+            Harness::Eval => "eval",
+        }
+    }
+}
+
+/// What to do with an output file that argen did not generate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Overwrite {
+    /// Fail, leaving the file alone.
+    Refuse,
+    /// Overwrite it anyway.
+    Force,
+    /// Overwrite it, keeping it as FILE.bak.
+    Backup,
+}
+
+/// What to generate from a spec, and where to, as asked on the command line.
+#[derive(Clone)]
+struct Invocation {
+    output: Option<String>,
+    header: Option<String>,
+    /// Also write Rust bindings to the C parser to this file.
+    rust_ffi: Option<String>,
+    no_main: bool,
+    format: Option<String>,
+    inplace: Option<String>,
+    provenance: bool,
+    /// The spec's format, if not told by its file extension.
+    spec_format: Option<SpecFormat>,
+    /// Values of the spec set on the command line.
+    defines: Vec<Define>,
+    backend: Backend,
+    /// Describe the command line as JSON instead of generating a parser.
+    describe_json: bool,
+    /// Render this template with the description instead of generating a
+    /// parser.
+    template: Option<String>,
+    /// Generate a program around the C parser instead of the parser alone.
+    harness: Option<Harness>,
+    /// The warnings about the spec that fail, as errors do.
+    deny: Deny,
+    /// Name the output after the spec, or as its c_file says.
+    auto_name: bool,
+    /// Write the output, named as with auto_name, to this directory.
+    out_dir: Option<String>,
+    /// Show how the output files would change instead of writing them.
+    diff: bool,
+    /// Compile the generated code, failing on any diagnostic.
+    compile_check: bool,
+    /// What to do with an output file that argen did not generate.
+    overwrite: Overwrite,
+}
+
+/// The output file `--auto-name` picks: the spec's c_file, next to the
+/// spec, for the C parser, or otherwise the spec's path with the extension
+/// of what is generated, as spec.toml gives spec.c, spec.rs or
+/// spec_tests.c. A template's output takes the extension the template's
+/// name has under .tmpl or .hbs, as man.1.tmpl gives spec.1.
+fn auto_output(
+    path: &Path,
+    s: &Spec,
+    backend: Backend,
+    describe_json: bool,
+    template: Option<&str>,
+    harness: Option<Harness>,
+) -> String {
+    let c_parser =
+        backend == Backend::C && !describe_json && template.is_none() && harness.is_none();
+    let file = match s.c_file() {
+        Some(c_file) if c_parser => path.with_file_name(c_file),
+        _ => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            let stem = match harness {
+                Some(harness) => format!("{}_{}", stem, harness.name()),
+                None => stem.into_owned(),
+            };
+            let extension = match template {
+                Some(template) => template_extension(template),
+                None if describe_json => String::from("json"),
+                None => String::from(backend.extension()),
+            };
+            path.with_file_name(stem).with_extension(extension)
+        }
+    };
+    file.to_string_lossy().into_owned()
+}
+
+/// The extension of a template's output: that of the template's name once
+/// .tmpl or .hbs is taken off, or txt.
+fn template_extension(template: &str) -> String {
+    let mut name = Path::new(template);
+    if let Some("tmpl" | "hbs") = name.extension().and_then(|e| e.to_str()) {
+        name = Path::new(name.file_stem().unwrap_or_default());
+    }
+    match name.extension() {
+        Some(extension) => extension.to_string_lossy().into_owned(),
+        None => String::from("txt"),
+    }
+}
+
+/// Which warnings about a spec fail, as errors do: all of them, with
+/// --strict, or those --deny names by code.
+#[derive(Clone, Debug, Default)]
+struct Deny {
+    all: bool,
+    codes: Vec<String>,
+}
+
+impl Deny {
+    /// The warnings denied by --strict, and by --deny's lists of codes.
+    fn new(strict: bool, lists: &[String]) -> Result<Deny, CliError> {
+        let mut codes = vec![];
+        for code in lists.iter().flat_map(|list| list.split(',')) {
+            let code = code.trim().to_uppercase();
+            if !codegen::WARNING_CODES
+                .iter()
+                .any(|(known, _)| *known == code)
+            {
+                return Err(CliError::Usage(format!(
+                    "--deny {}: there is no warning with that code",
+                    code
+                )));
+            }
+            codes.push(code);
+        }
+        Ok(Deny { all: strict, codes })
+    }
+    fn denies(&self, warning: &Warning) -> bool {
+        self.all || self.codes.iter().any(|code| code == warning.code())
+    }
+}
+
+/// Prints a warning about a spec.
+fn warn(name: &str, warning: &dyn fmt::Display) {
+    if !log::enabled(log::Level::Warn) {
+        return;
+    }
+    let warning = Diagnostic {
+        file: Some(name),
+        ..Diagnostic::new(Severity::Warning, warning.to_string())
+    };
+    writeln!(&mut io::stderr(), "{}", warning.render()).unwrap();
+}
+
+/// Prints the lints of a spec, as errors where they are denied, saying
+/// whether any were.
+fn lint(name: &str, lints: &[Warning], deny: &Deny) -> bool {
+    for lint in lints {
+        let severity = match deny.denies(lint) {
+            true => Severity::Error,
+            false if log::enabled(log::Level::Warn) => Severity::Warning,
+            false => continue,
+        };
+        let lint = Diagnostic {
+            code: Some(lint.code()),
+            file: Some(name),
+            ..Diagnostic::new(severity, lint.to_string())
+        };
+        writeln!(&mut io::stderr(), "{}", lint.render()).unwrap();
+    }
+    lints.iter().any(|lint| deny.denies(lint))
+}
+
+/// Reads a spec, and validates it if asked, with the source text and the
+/// format it was read in.
+fn load_spec(
+    filename: &str,
+    spec_format: Option<SpecFormat>,
+    defines: &[Define],
+    validate: bool,
+) -> Result<(Spec, String, SpecFormat), CliError> {
+    // a spec named "-" is read from stdin, as TOML unless told otherwise
+    let stdin = filename == "-";
+    let path = Path::new(if stdin { "stdin.toml" } else { filename });
+    let spec_format = spec_format
+        .map_or_else(|| SpecFormat::detect(path), Ok)
+        .map_err(CliError::Usage)?;
+    let spec = match stdin {
+        true => String::from("from standard input"),
+        false => format!("'{}'", filename),
+    };
+    log::info(format_args!("reading spec {}", spec));
+    let contents = read_input(filename, "spec")?;
+    // includes are found next to the spec, or for a spec from stdin in the
+    // current directory
+    let dir = path
+        .parent()
+        .filter(|dir| !stdin && *dir != Path::new(""))
+        .unwrap_or_else(|| Path::new("."));
+    log::debug(format_args!(
+        "parsing it as {}, with includes found in '{}'",
+        match spec_format {
+            SpecFormat::Json => "json",
+            SpecFormat::Toml => "toml",
+        },
+        dir.display()
+    ));
+    let s = match spec_format {
+        SpecFormat::Json => Spec::parse_json_with(&contents, dir, defines),
+        SpecFormat::Toml => Spec::parse_str_with(&contents, dir, defines),
+    };
+    let s = s.and_then(|s| match validate {
+        true => {
+            s.validate()?;
+            log::info(format_args!("validated spec {}", spec));
+            Ok(s)
+        }
+        false => Ok(s),
+    });
+    match s {
+        Ok(s) => Ok((s, contents, spec_format)),
+        Err(e) => Err(CliError::spec(display_name(filename), &contents, e)),
+    }
+}
+
+/// A spec's name in messages.
+fn display_name(filename: &str) -> &str {
+    if filename == "-" {
+        "standard input"
+    } else {
+        filename
+    }
+}
+
+/// Writes a spec back out in canonical form, in the format it was read in,
+/// as it is written: its comments, includes, option sets and variables are
+/// kept, and -D only says how to read it.
+fn fmt(
+    filename: &str,
+    spec_format: Option<SpecFormat>,
+    defines: &[Define],
+    output: Option<String>,
+) -> Result<(), CliError> {
+    let (_, contents, spec_format) = load_spec(filename, spec_format, defines, true)?;
+    let formatted = match spec_format {
+        SpecFormat::Toml => Spec::format_toml(&contents),
+        SpecFormat::Json => Spec::format_json(&contents),
+    };
+    let formatted = formatted.map_err(|e| CliError::spec(display_name(filename), &contents, e))?;
+    write_output(output.as_deref(), &formatted)
+}
+
+/// Rewrites a spec in the current schema, in the format it was read in,
+/// warning about what was changed to keep its meaning.
+fn upgrade(
+    filename: &str,
+    spec_format: Option<SpecFormat>,
+    output: Option<String>,
+) -> Result<(), CliError> {
+    let path = Path::new(if filename == "-" {
+        "stdin.toml"
+    } else {
+        filename
+    });
+    let spec_format = spec_format
+        .map_or_else(|| SpecFormat::detect(path), Ok)
+        .map_err(CliError::Usage)?;
+    let contents = read_input(filename, "spec")?;
+    let upgraded = match spec_format {
+        SpecFormat::Toml => Spec::upgrade_toml(&contents),
+        SpecFormat::Json => Spec::upgrade_json(&contents),
+    };
+    let (upgraded, notes) =
+        upgraded.map_err(|e| CliError::spec(display_name(filename), &contents, e))?;
+    for note in &notes {
+        warn(display_name(filename), note);
+    }
+    if upgraded == contents {
+        log::info(format_args!(
+            "{} is already schema {}",
+            display_name(filename),
+            codegen::SCHEMA
+        ));
+    }
+    write_output(output.as_deref(), &upgraded)
+}
+
+/// Drafts a spec from a command line defined for another library.
+fn import(from: &str, filename: &str, output: Option<String>) -> Result<(), CliError> {
+    let contents = read_input(filename, "definition")?;
+    let draft = Spec::import(from, &contents).ok_or_else(|| {
+        CliError::Usage(format!(
+            "cannot import from {}; argen imports from: {}",
+            from,
+            codegen::IMPORT_SOURCES
+        ))
+    })?;
+    write_output(output.as_deref(), &draft.to_toml())
+}
+
+/// Converts a spec in the legacy args.rs JSON to TOML, warning about what
+/// it couldn't.
+fn migrate(filename: &str, output: Option<String>) -> Result<(), CliError> {
+    let contents = read_input(filename, "spec")?;
+    let (s, notes) = Spec::migrate_legacy(&contents)
+        .map_err(|e| CliError::spec(display_name(filename), &contents, e))?;
+    for note in &notes {
+        warn(display_name(filename), note);
+    }
+    write_output(output.as_deref(), &s.to_toml())
+}
+
+/// Prints how the command line changes from one spec to another, one change
+/// a line. Exits non-zero if any change is breaking, so a release can be held
+/// back by one.
+fn compare(
+    old: &str,
+    new: &str,
+    spec_format: Option<SpecFormat>,
+    defines: &[Define],
+) -> Result<(), CliError> {
+    let (old, _, _) = load_spec(old, spec_format, defines, true)?;
+    let (new, _, _) = load_spec(new, spec_format, defines, true)?;
+    let changes = old.compare(&new);
+    let mut report = String::new();
+    for change in &changes {
+        match annotate::json() {
+            true => report.push_str(&change.json()),
+            false => report.push_str(&change.to_string()),
+        }
+        report.push('\n');
+    }
+    write_output(None, &report)?;
+    match changes.iter().any(|c| c.impact == Impact::Breaking) {
+        true => Err(CliError::Reported),
+        false => Ok(()),
+    }
+}
+
+/// Reports everything wrong with a spec, for generating with the backend,
+/// without generating anything. Exits non-zero if anything is, or if there
+/// are warnings it denies.
+fn check(
+    filename: &str,
+    spec_format: Option<SpecFormat>,
+    defines: &[Define],
+    backend: Backend,
+    deny: &Deny,
+) -> Result<(), CliError> {
+    let (s, contents, _) = load_spec(filename, spec_format, defines, false)?;
+    let mut errors = s.problems();
+    errors.extend(backend.generator("program").check(&s).err());
+    let lints = s.lints();
+    let name = display_name(filename);
+    let failed = !errors.is_empty();
+    for e in errors {
+        writeln!(&mut io::stderr(), "{}", CliError::spec(name, &contents, e)).unwrap();
+    }
+    let denied = lint(name, &lints, deny);
+    if failed || denied {
+        return Err(CliError::Reported);
+    }
+    Ok(())
+}
+
+/// Prints how a file would change to hold the text, saying whether it would.
+/// A file that doesn't exist yet is compared as empty.
+fn print_diff(file: &str, text: &str) -> Result<bool, CliError> {
+    let existing = match std::fs::read_to_string(file) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(CliError::Io(format!("cannot read '{}'", file), e)),
+    };
+    match diff::unified(&existing, text, file) {
+        Some(diff) => write_output(None, &diff).map(|_| true),
+        None => {
+            log::info(format_args!("'{}' is up to date", file));
+            Ok(false)
+        }
+    }
+}
+
+/// Whether a file's text is argen's output, by the spec's SHA-256 that all
+/// of it notes, with or without provenance.
+fn generated_by_argen(text: &str) -> bool {
+    text.contains("spec sha256: ") || text.contains("\"spec_sha256\": ")
+}
+
+/// Makes sure an output file may be written: it doesn't exist yet, is empty,
+/// or was generated by argen, unless told to overwrite it anyway.
+fn may_overwrite(file: &str, overwrite: Overwrite) -> Result<(), CliError> {
+    let existing = match std::fs::read(file) {
+        Ok(existing) => existing,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(CliError::Io(format!("cannot read '{}'", file), e)),
+    };
+    if existing.is_empty() || generated_by_argen(&String::from_utf8_lossy(&existing)) {
+        return Ok(());
+    }
+    match overwrite {
+        Overwrite::Refuse => Err(CliError::Failed(format!(
+            "'{}' was not generated by argen; use --force to overwrite it, or --backup to keep it as '{}.bak'",
+            file, file
+        ))),
+        Overwrite::Force => Ok(()),
+        Overwrite::Backup => {
+            let backup = format!("{}.bak", file);
+            std::fs::write(&backup, &existing)
+                .map_err(|e| CliError::Io(format!("cannot write backup file '{}'", backup), e))?;
+            log::info(format_args!("kept '{}' as '{}'", file, backup));
+            Ok(())
+        }
+    }
+}
+
+fn codegen(filename: String, invocation: Invocation) -> Result<(), CliError> {
+    let Invocation {
+        output,
+        header,
+        rust_ffi,
+        no_main,
+        format,
+        inplace,
+        provenance,
+        spec_format,
+        defines,
+        backend,
+        describe_json,
+        template,
+        harness,
+        deny,
+        auto_name,
+        out_dir,
+        diff,
+        compile_check,
+        overwrite,
+    } = invocation;
+    let stdin = filename == "-";
+    let path = Path::new(if stdin { "stdin.toml" } else { &filename });
+    let (mut s, contents, _) = load_spec(&filename, spec_format, &defines, true)?;
+    let name = display_name(&filename);
+    if lint(name, &s.lints(), &deny) {
+        return Err(CliError::Reported);
+    }
+    // the README and PowerShell script are for a program named after the spec,
+    // or "program" for a spec from stdin
+    let progname = Path::new(if stdin { "program" } else { &filename })
+        .file_stem()
+        .ok_or_else(|| CliError::Usage(format!("'{}' is not a spec file", filename)))?
+        .to_string_lossy()
+        .into_owned();
+    let generator = backend.generator(&progname);
+    generator
+        .check(&s)
+        .map_err(|e| CliError::spec(name, &contents, e))?;
+    if rust_ffi.is_some() {
+        s.check_rust_ffi()
+            .map_err(|e| CliError::spec(name, &contents, e))?;
+    }
+    let output = match (auto_name, &out_dir) {
+        (false, None) => output,
+        _ if stdin => {
+            return Err(CliError::Usage(String::from(
+                "--auto-name and --out-dir need a spec file to name the output after",
+            )))
+        }
+        (_, Some(dir)) => {
+            let file = auto_output(
+                path,
+                &s,
+                backend,
+                describe_json,
+                template.as_deref(),
+                harness,
+            );
+            let file = Path::new(&file).file_name().unwrap_or_default();
+            Some(Path::new(dir).join(file).to_string_lossy().into_owned())
+        }
+        (true, None) => Some(auto_output(
+            path,
+            &s,
+            backend,
+            describe_json,
+            template.as_deref(),
+            harness,
+        )),
+    };
+    if s.has_subcommands() && (backend == Backend::Readme || describe_json || harness.is_some()) {
+        return Err(CliError::Usage(String::from(
+            "subcommands are only for the c backend, without readme, --describe-json, tests, fuzz or eval",
+        )));
+    }
+    // values set on the command line change the output as the spec would
+    let mut source = contents.clone();
+    for define in &defines {
+        source.push_str(&format!("\n-D {}", define));
+    }
+    s.set_spec_hash(&source);
+    if provenance {
+        let spec = match stdin {
+            true => "standard input".into(),
+            false => path
+                .file_name()
+                .ok_or_else(|| CliError::Usage(format!("'{}' is not a spec file", filename)))?
+                .to_string_lossy(),
+        };
+        s.set_provenance(&format!("argen {}", VERSION), &spec);
+    }
+    // a file updated in place has its own main, and a harness its own
+    if no_main || inplace.is_some() || harness.is_some() {
+        s.set_emit_main(false);
+    }
+    match harness {
+        Some(Harness::Fuzz) => s.set_fuzzing(),
+        Some(Harness::Eval) => s.set_eval(),
+        _ => {}
+    }
+    // without clang-format, argen lays out the code itself
+    let clang = format.is_some() && has_clang_format();
+    if let Some(style) = &format {
+        if !clang {
+            let style = if style == "file" { "llvm" } else { style };
+            s.set_style(style)
+                .map_err(|e| CliError::Usage(e.to_string()))?;
+        }
+    }
+    let finish = |code: String| match &format {
+        Some(style) if clang => clang_format(&code, style, backend),
+        _ => Ok(code),
+    };
+    // a template is rendered once, as it may fail
+    let rendered = match &template {
+        Some(file) => {
+            let text = std::fs::read_to_string(file)
+                .map_err(|e| CliError::Io(format!("cannot read template '{}'", file), e))?;
+            let rendered = s
+                .render_template(&text, &progname)
+                .map_err(|e| CliError::Failed(format!("{}: {}", file, e)))?;
+            Some(rendered)
+        }
+        None => None,
+    };
+    let gen = || match (&rendered, harness) {
+        (Some(rendered), _) => rendered.clone(),
+        _ if describe_json => s.describe_json(),
+        (None, Some(Harness::Tests)) => s.gen_tests(),
+        (None, Some(Harness::Fuzz)) => s.gen_fuzz(),
+        (None, Some(Harness::Eval)) => s.gen_eval(),
+        (None, None) => {
+            let mut code = vec![];
+            generator
+                .generate(&s, &mut code)
+                .expect("generate into memory");
+            String::from_utf8(code).expect("generated code is UTF-8")
+        }
+    };
+    if let Some(f) = inplace {
+        let existing = std::fs::read_to_string(&f)
+            .map_err(|e| CliError::Io(format!("cannot read in-place file '{}'", f), e))?;
+        let updated = replace_generated(&existing, &finish(gen())?).ok_or_else(|| {
+            CliError::Failed(format!("{}: expected two /* argen:generated */ markers", f))
+        })?;
+        if diff {
+            return match print_diff(&f, &updated)? {
+                true => Err(CliError::Reported),
+                false => Ok(()),
+            };
+        }
+        std::fs::write(&f, &updated)
+            .map_err(|e| CliError::Io(format!("cannot write in-place file '{}'", f), e))?;
+        log::info(format_args!("updated the generated code in '{}'", f));
+        if compile_check {
+            compile_check_in(&updated, backend, &f)?;
+        }
+        return Ok(());
+    }
+    // hand-written regions of a file being regenerated are kept
+    let existing = output
+        .as_ref()
+        .and_then(|f| std::fs::read_to_string(f).ok());
+    let finish = |code: String| match &existing {
+        Some(existing) => Ok(splice_user_regions(&finish(code)?, existing)),
+        None => finish(code),
+    };
+    let header = match header {
+        Some(h) => {
+            let name = Path::new(&h)
+                .file_name()
+                .ok_or_else(|| CliError::Usage(format!("'{}' is not a header file name", h)))?
+                .to_string_lossy()
+                .into_owned();
+            Some((h, name))
+        }
+        None => None,
+    };
+    if diff {
+        let output = output.as_deref().expect("an output file to compare with");
+        let mut stale = false;
+        let code = match &header {
+            Some((h, name)) => {
+                stale |= print_diff(h, &finish(s.gen_header(name))?)?;
+                finish(s.gen_with_header(name))?
+            }
+            None => finish(gen())?,
+        };
+        if let Some(r) = &rust_ffi {
+            stale |= print_diff(r, &s.gen_rust_ffi())?;
+        }
+        stale |= print_diff(output, &code)?;
+        return match stale {
+            true => Err(CliError::Reported),
+            false => Ok(()),
+        };
+    }
+    // a mistyped -o mustn't cost a hand-written file
+    for file in output
+        .iter()
+        .chain(header.iter().map(|(h, _)| h))
+        .chain(rust_ffi.iter())
+    {
+        may_overwrite(file, overwrite)?;
+    }
+    if let Some(r) = &rust_ffi {
+        std::fs::write(r, s.gen_rust_ffi())
+            .map_err(|e| CliError::Io(format!("cannot write Rust bindings file '{}'", r), e))?;
+        log::info(format_args!("wrote '{}'", r));
+    }
+    let write_failed = |e: io::Error| match &output {
+        Some(f) => CliError::Io(format!("cannot write output file '{}'", f), e),
+        // whoever was reading stopped, as head does, so there is no one to tell
+        None if e.kind() == io::ErrorKind::BrokenPipe => CliError::Reported,
+        None => CliError::Io(String::from("cannot write to standard output"), e),
+    };
+    let wrote = || match &output {
+        Some(f) => log::info(format_args!("wrote '{}'", f)),
+        None => log::info(format_args!("wrote to standard output")),
+    };
+    let mut out: BufWriter<Box<dyn Write>> = BufWriter::new(match &output {
+        Some(f) => Box::new(
+            File::create(f)
+                .map_err(|e| CliError::Io(format!("cannot create output file '{}'", f), e))?,
+        ),
+        None => Box::new(io::stdout()),
+    });
+    let code = match header {
+        Some((h, name)) => {
+            let mut f = File::create(&h)
+                .map_err(|e| CliError::Io(format!("cannot create header file '{}'", h), e))?;
+            f.write_all(finish(s.gen_header(&name))?.as_bytes())
+                .map_err(|e| CliError::Io(format!("cannot write header file '{}'", h), e))?;
+            log::info(format_args!("wrote '{}'", h));
+            let code = finish(s.gen_with_header(&name))?;
+            out.write_all(code.as_bytes()).map_err(write_failed)?;
+            code
+        }
+        None if format.is_some()
+            || existing.is_some()
+            || backend != Backend::C
+            || describe_json
+            || rendered.is_some()
+            || harness.is_some()
+            || compile_check =>
+        {
+            let code = finish(gen())?;
+            out.write_all(code.as_bytes()).map_err(write_failed)?;
+            code
+        }
+        None => {
+            s.writeout(&mut out).map_err(write_failed)?;
+            out.flush().map_err(write_failed)?;
+            wrote();
+            return Ok(());
         }
-        None => s.writeout(&mut io::stdout()),
     };
+    out.flush().map_err(write_failed)?;
+    wrote();
+    if compile_check {
+        let name = output.as_deref().unwrap_or("generated code");
+        compile_check_in(&code, backend, name)?;
+    }
+    Ok(())
+}
+
+/// Asks about a new program, named already or after the current directory,
+/// and writes a starter spec for it, NAME.toml, and the C parser generated
+/// from that, NAME.c, overwriting neither unless forced to.
+fn new_program(name: Option<&str>, force: bool) -> Result<(), CliError> {
+    let default = env::current_dir()
+        .ok()
+        .and_then(|dir| {
+            dir.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| String::from("program"));
+    let answers = scaffold::questions(name, &default, &mut io::stdin().lock(), &mut io::stderr())
+        .map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidInput => CliError::Usage(e.to_string()),
+        _ => CliError::Io(String::from("cannot read the answers"), e),
+    })?;
+    let spec = format!("{}.toml", answers.name);
+    let code = format!("{}.c", answers.name);
+    if !force && Path::new(&spec).exists() {
+        return Err(CliError::Failed(format!(
+            "'{}' already exists; use --force to overwrite it",
+            spec
+        )));
+    }
+    write_output(Some(&spec), &scaffold::spec(&answers))?;
+    codegen(
+        spec.clone(),
+        Invocation {
+            output: Some(code.clone()),
+            header: None,
+            rust_ffi: None,
+            no_main: false,
+            format: None,
+            inplace: None,
+            provenance: true,
+            spec_format: None,
+            defines: vec![],
+            backend: Backend::C,
+            describe_json: false,
+            template: None,
+            harness: None,
+            deny: Deny::default(),
+            auto_name: false,
+            out_dir: None,
+            diff: false,
+            compile_check: false,
+            overwrite: match force {
+                true => Overwrite::Force,
+                false => Overwrite::Refuse,
+            },
+        },
+    )?;
+    println!(
+        "Wrote {0} and {1}. Build the program with
+    cc -o {2} {1}
+and after changing {0}, regenerate {1} with
+    argen -o {1} {0}",
+        spec, code, answers.name
+    );
+    Ok(())
+}
+
+/// Compiles generated code named for its file, finding the headers it
+/// includes next to the file.
+fn compile_check_in(code: &str, backend: Backend, file: &str) -> Result<(), CliError> {
+    log::info(format_args!("compiling {} to check it", file));
+    let dir = Path::new(file)
+        .parent()
+        .filter(|dir| *dir != Path::new(""))
+        .unwrap_or_else(|| Path::new("."));
+    compile_check(code, backend, file, dir)
 }
 
 fn main() {
+    if let Err(e) = run() {
+        if !matches!(e, CliError::Reported) {
+            writeln!(&mut io::stderr(), "{}", e).unwrap();
+        }
+        process::exit(e.exit_status());
+    }
+}
+
+/// Sets how much argen says, as --verbose, --quiet or RUST_LOG ask.
+fn set_log_level(matches: &getopts::Matches) -> Result<(), CliError> {
+    if matches.opt_present("verbose") && matches.opt_present("quiet") {
+        return Err(CliError::Usage(String::from(
+            "--verbose and --quiet cannot be combined",
+        )));
+    }
+    log::set_level(log::level(
+        matches.opt_count("verbose"),
+        matches.opt_present("quiet"),
+        env::var("RUST_LOG").ok().as_deref(),
+    ));
+    Ok(())
+}
+
+fn run() -> Result<(), CliError> {
     let args: Vec<String> = env::args().collect();
     let program = args[0].clone();
+    log::set_logger(annotate::log);
 
-    let mut opts = Options::new();
-    opts.optopt("o", "", "set output file name", "NAME");
-    opts.optflag("h", "help", "print this help menu");
-    opts.optflag("v", "version", "show version");
-    let matches = match opts.parse(&args[1..]) {
-        Ok(m) => m,
-        Err(f) => panic!(f.to_string()),
+    let opts = options(None);
+    // a command line that doesn't parse is still reported as JSON, if it
+    // asks for that
+    if args.iter().any(|arg| arg == "--message-format=json")
+        || args
+            .windows(2)
+            .any(|w| w[0] == "--message-format" && w[1] == "json")
+    {
+        annotate::set_json(true);
+    }
+    let mut matches = opts
+        .parse(&args[1..])
+        .map_err(|f| CliError::Usage(f.to_string()))?;
+    // without a command, argen generates
+    let named = Subcommand::named(&matches.free);
+    let sub = named.unwrap_or(&SUBCOMMANDS[0]);
+    let free = matches.free[named.iter().len()..].to_vec();
+    // a command only takes its own options on the command line; the config
+    // files' defaults for others don't matter to it
+    if let Some(flag) = FLAGS
+        .iter()
+        .find(|flag| matches.opt_present(flag.name()) && !sub.takes(flag))
+    {
+        return Err(CliError::Usage(format!(
+            "{} does not take {}",
+            sub.name,
+            flag.spelling()
+        )));
+    }
+    // the config files' defaults go before the command line, unless it gives
+    // the same options; reading them is logged as the command line asks
+    if !matches.opt_present("no-config") {
+        set_log_level(&matches)?;
+        let defaults = config::args(FLAGS, &|name| matches.opt_present(name))?;
+        if !defaults.is_empty() {
+            matches = opts
+                .parse(defaults.iter().chain(&args[1..]))
+                .map_err(|f| CliError::Usage(format!("with the config files' defaults, {}", f)))?;
+        }
+    }
+    let color = match matches.opt_str("color").as_deref() {
+        None | Some("auto") => {
+            io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+        Some("always") => true,
+        Some("never") => false,
+        Some(when) => {
+            return Err(CliError::Usage(format!(
+                "--color takes auto, always or never, not {}",
+                when
+            )))
+        }
     };
+    annotate::set_color(color);
+    set_log_level(&matches)?;
+    match matches.opt_str("message-format").as_deref() {
+        None | Some("human") => {}
+        Some("json") => annotate::set_json(true),
+        Some(format) => {
+            return Err(CliError::Usage(format!(
+                "--message-format takes human or json, not {}",
+                format
+            )))
+        }
+    }
     if matches.opt_present("h") {
-        print_usage(&program, opts);
-        return;
+        print_usage(&program, sub, named.is_some());
+        return Ok(());
     }
-    if matches.opt_present("v") {
+    if matches.opt_present("version") {
         println!("argen {}", VERSION);
-        return;
+        return Ok(());
+    }
+    // an output of - is stdout, as no output is
+    let output = matches.opt_str("o").filter(|o| o != "-");
+    if matches!(sub.name, "schema" | "man") && !free.is_empty() {
+        return Err(CliError::Usage(format!("{} takes no arguments", sub.name)));
+    }
+    // `argen schema` prints a JSON Schema of specs, for editors
+    if sub.name == "schema" {
+        return write_output(output.as_deref(), &Spec::json_schema());
+    }
+    // `argen man` prints argen's man page, made from its options
+    if sub.name == "man" {
+        return write_output(output.as_deref(), &man::page(VERSION, FLAGS, SUBCOMMANDS));
+    }
+    // `argen completions bash` prints a completion script for argen itself
+    if sub.name == "completions" {
+        let shell = match free.as_slice() {
+            [shell] => shell,
+            _ => {
+                return Err(CliError::Usage(format!(
+                    "completions takes a shell: {}",
+                    completions::SHELLS.join(", ")
+                )))
+            }
+        };
+        let sources: Vec<&str> = codegen::IMPORT_SOURCES.split(", ").collect();
+        let script = completions::script(shell, FLAGS, SUBCOMMANDS, &sources).ok_or_else(|| {
+            CliError::Usage(format!(
+                "no completions for {}; argen completes in: {}",
+                shell,
+                completions::SHELLS.join(", ")
+            ))
+        })?;
+        return write_output(output.as_deref(), &script);
+    }
+    // `argen new frob` writes frob.toml and frob.c for a new program
+    if sub.name == "new" {
+        return match free.as_slice() {
+            [] => new_program(None, matches.opt_present("force")),
+            [name] => new_program(Some(name), matches.opt_present("force")),
+            _ => Err(CliError::Usage(String::from(
+                "new takes at most the new program's name",
+            ))),
+        };
+    }
+    let header = matches.opt_str("header");
+    let inplace = matches.opt_str("inplace");
+    let auto_name = matches.opt_present("auto-name");
+    let out_dir = matches.opt_str("out-dir");
+    let diff = matches.opt_present("diff") || matches.opt_present("check-output");
+    let compile_check = matches.opt_present("compile-check");
+    if diff && output.is_none() && !auto_name && out_dir.is_none() && inplace.is_none() {
+        return Err(CliError::Usage(String::from(
+            "--diff needs output files to compare with: -o, --auto-name, --out-dir or --inplace",
+        )));
+    }
+    if (auto_name || out_dir.is_some()) && (matches.opt_present("o") || inplace.is_some()) {
+        return Err(CliError::Usage(String::from(
+            "--auto-name and --out-dir cannot be combined with -o or --inplace",
+        )));
+    }
+    if inplace.is_some() && (output.is_some() || header.is_some()) {
+        return Err(CliError::Usage(String::from(
+            "--inplace cannot be combined with -o or --header",
+        )));
+    }
+    // --backend also names the other kinds of output: json, as
+    // --describe-json, and the programs tests, fuzz and eval
+    if matches.opt_present("backend") && matches.opt_present("target") {
+        return Err(CliError::Usage(String::from(
+            "--target is another name for --backend; give one of them",
+        )));
+    }
+    let target = matches
+        .opt_str("backend")
+        .or_else(|| matches.opt_str("target"))
+        .unwrap_or_else(|| String::from("c"));
+    let (backend, target_json, target_harness) = match target.as_str() {
+        "json" => (Backend::C, true, None),
+        name => match Harness::from_name(name) {
+            Some(harness) => (Backend::C, false, Some(harness)),
+            None => (backend_named(name)?, false, None),
+        },
+    };
+    if backend != Backend::C && header.is_some() {
+        return Err(CliError::Usage(String::from(
+            "--header is only for the c backend",
+        )));
+    }
+    let spec_format = match matches.opt_str("spec-format") {
+        Some(name) => Some(SpecFormat::from_name(&name).ok_or_else(|| {
+            CliError::Usage(format!(
+                "unknown spec format: {}; supported formats: {}",
+                name,
+                SpecFormat::NAMES
+            ))
+        })?),
+        None => None,
+    };
+    // settings for the backend are set first, so -D can override them
+    let mut defines = vec![];
+    for opt in matches.opt_strs("backend-opt") {
+        let (only, setting) = match opt.split_once(':') {
+            Some((only, setting)) if !only.contains('=') => (Some(only), setting),
+            _ => (None, opt.as_str()),
+        };
+        if only
+            .map(backend_named)
+            .transpose()?
+            .is_some_and(|only| only != backend)
+        {
+            continue;
+        }
+        let define: Define = setting.parse().map_err(CliError::Usage)?;
+        if !define.is_setting() {
+            return Err(CliError::Usage(format!(
+                "--backend-opt {}: expected a setting, such as std=c89",
+                opt
+            )));
+        }
+        defines.push(define);
+    }
+    for define in matches.opt_strs("D") {
+        defines.push(define.parse().map_err(CliError::Usage)?);
+    }
+    let deny = Deny::new(matches.opt_present("strict"), &matches.opt_strs("deny"))?;
+    let describe_json = matches.opt_present("describe-json") || target_json;
+    if describe_json && (header.is_some() || inplace.is_some() || matches.opt_present("format")) {
+        return Err(CliError::Usage(String::from(
+            "--describe-json cannot be combined with --header, --inplace or --format",
+        )));
+    }
+    if compile_check && (!matches!(backend, Backend::C | Backend::Cpp) || describe_json) {
+        return Err(CliError::Usage(String::from(
+            "--compile-check is only for the c and c++ backends, without --describe-json",
+        )));
     }
-    let output = matches.opt_str("o");
-    let input = if !matches.free.is_empty() {
-        matches.free[0].clone()
+    if backend.example_file().is_none() && matches.opt_present("format") {
+        return Err(CliError::Usage(String::from(
+            "--format is only for the c and c++ backends",
+        )));
+    }
+    // `argen import clap app.rs` drafts a spec from another definition
+    if sub.name == "import" {
+        let (from, input) = match free.as_slice() {
+            [from] if !io::stdin().is_terminal() => (from, "-"),
+            [from, input] => (from, input.as_str()),
+            _ => {
+                return Err(CliError::Usage(String::from(
+                    "import takes what to import from and a file to import",
+                )))
+            }
+        };
+        return import(from, input, output);
+    }
+    // `argen diff OLD.toml NEW.toml` says how the command line changes
+    if sub.name == "diff" {
+        return match free.as_slice() {
+            [old, new] => compare(old, new, spec_format, &defines),
+            _ => Err(CliError::Usage(String::from(
+                "diff takes the old spec file and the new one",
+            ))),
+        };
+    }
+    // `argen fmt SPEC.toml` rewrites a spec in canonical form,
+    // `argen check SPEC.toml` lists its problems, `argen explain
+    // SPEC.toml` says what the parser generated from it does, `argen
+    // upgrade SPEC.toml` rewrites it in the current schema, and `argen
+    // migrate OLD.json` converts a legacy spec to TOML
+    if matches!(
+        sub.name,
+        "fmt" | "check" | "explain" | "upgrade" | "migrate"
+    ) {
+        let input = match free.as_slice() {
+            [] if !io::stdin().is_terminal() => String::from("-"),
+            [input] => input.clone(),
+            _ => return Err(CliError::Usage(format!("{} takes one spec file", sub.name))),
+        };
+        return match sub.name {
+            "fmt" => fmt(&input, spec_format, &defines, output),
+            "check" => check(&input, spec_format, &defines, backend, &deny),
+            "upgrade" => upgrade(&input, spec_format, output),
+            "migrate" => migrate(&input, output),
+            _ => write_output(
+                None,
+                &load_spec(&input, spec_format, &defines, true)?.0.explain(),
+            ),
+        };
+    }
+    // `argen tests SPEC.toml` generates tests for the C parser,
+    // `argen fuzz SPEC.toml` a fuzz target, and `argen eval SPEC.toml` a
+    // program for shell scripts to eval
+    let piped = !io::stdin().is_terminal();
+    let harness = Harness::from_name(sub.name).or(target_harness);
+    if harness.is_some()
+        && (backend != Backend::C || header.is_some() || inplace.is_some() || describe_json)
+    {
+        return Err(CliError::Usage(String::from(
+            "tests, fuzz and eval are only for the c backend, and cannot be combined with --header, --inplace or --describe-json",
+        )));
+    }
+    let template = matches.opt_str("template");
+    if template.is_some()
+        && (describe_json
+            || harness.is_some()
+            || compile_check
+            || header.is_some()
+            || inplace.is_some()
+            || matches.opt_present("format")
+            || matches.opt_present("backend"))
+    {
+        return Err(CliError::Usage(String::from(
+            "--template cannot be combined with --backend, --describe-json, --header, --inplace, --format, --compile-check, tests, fuzz or eval",
+        )));
+    }
+    let rust_ffi = matches.opt_str("rust-ffi");
+    if rust_ffi.is_some()
+        && (backend != Backend::C
+            || describe_json
+            || template.is_some()
+            || harness.is_some()
+            || inplace.is_some())
+    {
+        return Err(CliError::Usage(String::from(
+            "--rust-ffi is only for the c backend, and cannot be combined with --inplace, --describe-json, --template, tests, fuzz or eval",
+        )));
+    }
+    // without a spec file, a spec piped in is read from stdin
+    let inputs = match &free[..] {
+        [] if piped => vec![String::from("-")],
+        // run bare, argen says how it is used
+        [] if args.len() == 1 => {
+            print_usage(&program, sub, false);
+            return Ok(());
+        }
+        [] => return Err(CliError::Usage(String::from("no spec file given"))),
+        specs => specs.to_vec(),
+    };
+    if inputs.len() > 1 && !auto_name && out_dir.is_none() {
+        return Err(CliError::Usage(String::from(
+            "several specs need --out-dir or --auto-name to name their outputs",
+        )));
+    }
+    if inputs.len() > 1 && header.is_some() {
+        return Err(CliError::Usage(String::from(
+            "--header is for one spec at a time",
+        )));
+    }
+    if inputs.len() > 1 && rust_ffi.is_some() {
+        return Err(CliError::Usage(String::from(
+            "--rust-ffi is for one spec at a time",
+        )));
+    }
+    if let Some(dir) = out_dir.as_ref().filter(|_| !diff) {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| CliError::Io(format!("cannot create output directory '{}'", dir), e))?;
+    }
+
+    let format = if matches.opt_present("format") {
+        Some(
+            matches
+                .opt_str("format")
+                .unwrap_or_else(|| String::from("file")),
+        )
     } else {
-        print_usage(&program, opts);
-        return;
+        None
     };
 
-    codegen(input, output)
+    let invocation = Invocation {
+        output,
+        header,
+        rust_ffi,
+        no_main: matches.opt_present("no-main"),
+        format,
+        inplace,
+        provenance: !matches.opt_present("no-provenance"),
+        spec_format,
+        defines,
+        backend,
+        describe_json,
+        template,
+        harness,
+        deny,
+        auto_name,
+        out_dir,
+        diff,
+        compile_check,
+        overwrite: match (matches.opt_present("backup"), matches.opt_present("force")) {
+            (true, _) => Overwrite::Backup,
+            (false, true) => Overwrite::Force,
+            (false, false) => Overwrite::Refuse,
+        },
+    };
+    if let [input] = &inputs[..] {
+        return codegen(input.clone(), invocation);
+    }
+    // a batch carries on past a spec that fails, and fails once all are done
+    log::debug(format_args!("generating from {} specs", inputs.len()));
+    let mut failed = false;
+    for input in inputs {
+        if let Err(e) = codegen(input, invocation.clone()) {
+            if !matches!(e, CliError::Reported) {
+                writeln!(&mut io::stderr(), "{}", e).unwrap();
+            }
+            failed = true;
+        }
+    }
+    match failed {
+        true => Err(CliError::Reported),
+        false => Ok(()),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::codegen;
+    use super::{
+        codegen, may_overwrite, Backend, Deny, Harness, Invocation, Overwrite, Spec, SpecFormat,
+    };
+    use std::path::Path;
 
     #[test]
     fn it_works() {
-        codegen(String::from("examples/example_spec.toml"), None)
+        codegen(
+            String::from("examples/example_spec.toml"),
+            Invocation {
+                output: None,
+                header: None,
+                rust_ffi: None,
+                no_main: false,
+                format: None,
+                inplace: None,
+                provenance: true,
+                spec_format: None,
+                defines: vec![],
+                backend: Backend::C,
+                describe_json: false,
+                template: None,
+                harness: None,
+                deny: Deny::default(),
+                auto_name: false,
+                out_dir: None,
+                diff: false,
+                compile_check: false,
+                overwrite: Overwrite::Refuse,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn overwrite() {
+        let dir = std::env::temp_dir().join("argen_overwrite");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.c");
+        let file = file.to_str().unwrap();
+        let backup = format!("{}.bak", file);
+        let _ = std::fs::remove_file(&backup);
+        std::fs::write(file, "int main(void) { return 0; }\n").unwrap();
+        let error = may_overwrite(file, Overwrite::Refuse).err().unwrap();
+        assert!(error.to_string().contains("was not generated by argen"));
+        assert!(may_overwrite(file, Overwrite::Force).is_ok());
+        assert!(!Path::new(&backup).exists());
+        assert!(may_overwrite(file, Overwrite::Backup).is_ok());
+        assert_eq!(
+            std::fs::read_to_string(&backup).unwrap(),
+            "int main(void) { return 0; }\n"
+        );
+        // argen's own output, even without provenance, is regenerated
+        std::fs::write(file, "/* spec sha256: 0123abcd */\nint x;\n").unwrap();
+        assert!(may_overwrite(file, Overwrite::Refuse).is_ok());
+        std::fs::write(file, "").unwrap();
+        assert!(may_overwrite(file, Overwrite::Refuse).is_ok());
+        assert!(may_overwrite(dir.join("new.c").to_str().unwrap(), Overwrite::Refuse).is_ok());
+    }
+
+    #[test]
+    fn deny() {
+        let s = Spec::from_str(
+            "[[non_positional]]\nc_var = \"x\"\nc_type = \"int\"\nlong = \"x\"\nflag = true\n",
+        )
+        .unwrap();
+        let lints = s.lints();
+        let denies = |deny: Deny| lints.iter().any(|lint| deny.denies(lint));
+        assert!(!denies(Deny::default()));
+        assert!(denies(Deny::new(true, &[]).unwrap()));
+        assert!(denies(
+            Deny::new(false, &[String::from("W002, w001")]).unwrap()
+        ));
+        assert!(!denies(Deny::new(false, &[String::from("W005")]).unwrap()));
+        assert!(Deny::new(false, &[String::from("E001")]).is_err());
+    }
+
+    #[test]
+    fn auto_output() {
+        let s = Spec::from_str("").unwrap();
+        let path = Path::new("specs/prog.toml");
+        let name = |backend, describe_json, harness| {
+            super::auto_output(path, &s, backend, describe_json, None, harness)
+        };
+        assert_eq!(name(Backend::C, false, None), "specs/prog.c");
+        assert_eq!(name(Backend::Rust, false, None), "specs/prog.rs");
+        assert_eq!(name(Backend::C, true, None), "specs/prog.json");
+        let template =
+            |template| super::auto_output(path, &s, Backend::C, false, Some(template), None);
+        assert_eq!(template("man.1.tmpl"), "specs/prog.1");
+        assert_eq!(template("t/page.html.hbs"), "specs/prog.html");
+        assert_eq!(template("notes.tmpl"), "specs/prog.txt");
+        assert_eq!(
+            name(Backend::C, false, Some(Harness::Tests)),
+            "specs/prog_tests.c"
+        );
+        let s = Spec::from_str("c_file = \"args.c\"").unwrap();
+        assert_eq!(
+            super::auto_output(path, &s, Backend::C, false, None, None),
+            "specs/args.c"
+        );
+        assert_eq!(
+            super::auto_output(path, &s, Backend::Go, false, None, None),
+            "specs/prog.go"
+        );
+    }
+
+    #[test]
+    fn spec_format() {
+        let detect = |f: &str| SpecFormat::detect(Path::new(f));
+        assert!(detect("spec.toml") == Ok(SpecFormat::Toml));
+        assert!(detect("spec.JSON") == Ok(SpecFormat::Json));
+        assert!(detect("spec.yaml")
+            .err()
+            .unwrap()
+            .contains("supported formats: toml, json"));
+        assert!(detect("spec").err().unwrap().contains("--spec-format"));
     }
 }
@@ -0,0 +1,140 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! argen's man page, in troff, made from its table of options and commands
+//! as its completions are, so it never falls behind them.
+
+use super::{Flag, Subcommand};
+
+/// Text escaped for troff: backslashes and hyphens, and a line starting
+/// with what would be taken for a request.
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.starts_with(['.', '\'']) {
+        true => format!("\\&{}", text),
+        false => text,
+    }
+}
+
+/// How an option is given, in bold, with its argument in italics.
+fn synopsis(flag: &Flag) -> String {
+    let mut names = vec![];
+    if !flag.short.is_empty() {
+        names.push(format!("\\fB\\-{}\\fR", escape(flag.short)));
+    }
+    if !flag.long.is_empty() {
+        names.push(format!("\\fB\\-\\-{}\\fR", escape(flag.long)));
+    }
+    let names = names.join(", ");
+    match flag.hint {
+        None => names,
+        Some(hint) if flag.optional => format!("{}[=\\fI{}\\fR]", names, escape(hint)),
+        Some(hint) => format!("{} \\fI{}\\fR", names, escape(hint)),
+    }
+}
+
+/// The man page, for section 1, of this version of argen.
+pub fn page(version: &str, flags: &[Flag], subcommands: &[Subcommand]) -> String {
+    let mut page = format!(
+        ".TH ARGEN 1 \"\" \"argen {}\" \"User Commands\"\n\
+         .SH NAME\n\
+         argen \\- generate command\\-line parsers from a spec\n\
+         .SH SYNOPSIS\n\
+         .B argen\n\
+         [\\fBgen\\fR] [\\fIoptions\\fR] \\fISPEC.toml\\fR...\n\
+         .br\n\
+         .B argen\n\
+         \\fICOMMAND\\fR [\\fIoptions\\fR] ...\n\
+         .SH DESCRIPTION\n\
+         argen reads a spec of a program's options and arguments, in TOML or JSON, \
+         and generates code to parse them: in C by default, or in C++, Rust, Go, \
+         PowerShell or Zig, along with usage docs, tests and fuzz targets. \
+         A SPEC of \\- reads the spec from standard input, as does no SPEC with \
+         standard input piped.\n\
+         .SH COMMANDS\n",
+        escape(version)
+    );
+    for sub in subcommands {
+        page.push_str(&format!(
+            ".TP\n\\fBargen {}\\fR {}\n{}\n",
+            escape(sub.name),
+            escape(sub.usage),
+            escape(sub.help)
+        ));
+    }
+    page.push_str(".SH OPTIONS\n");
+    for flag in flags {
+        let takers: Vec<&str> = subcommands
+            .iter()
+            .filter(|sub| sub.takes(flag))
+            .map(|sub| sub.name)
+            .collect();
+        let takers = match takers.len() == subcommands.len() {
+            true => String::new(),
+            false => format!(" (for {})", takers.join(", ")),
+        };
+        page.push_str(&format!(
+            ".TP\n{}\n{}{}\n",
+            synopsis(flag),
+            escape(flag.help),
+            escape(&takers)
+        ));
+    }
+    page.push_str(
+        ".SH ENVIRONMENT\n\
+         .TP\n\
+         .B CC, CXX\n\
+         The compilers \\fB\\-\\-compile\\-check\\fR runs, cc and c++ by default.\n\
+         .TP\n\
+         .B NO_COLOR\n\
+         When set, diagnostics are not in color, unless \\fB\\-\\-color always\\fR is given.\n\
+         .TP\n\
+         .B XDG_CONFIG_HOME\n\
+         Where the user's config file is, instead of ~/.config.\n\
+         .SH FILES\n\
+         .TP\n\
+         .I ~/.config/argen/config.toml\n\
+         The user's defaults for argen's options, each by its long name.\n\
+         .TP\n\
+         .I .argen.toml\n\
+         A project's defaults, in the current directory or one above it, which win over the user's.\n\
+         .SH EXIT STATUS\n\
+         0 on success, 1 for a spec with problems or denied warnings, \
+         2 for a bad command line, 3 for a spec that isn't valid TOML or JSON, \
+         and 4 for a file that couldn't be read or written.\n",
+    );
+    page
+}
+
+#[cfg(test)]
+mod tests {
+    use super::page;
+    use crate::{FLAGS, SUBCOMMANDS};
+
+    #[test]
+    fn man() {
+        let page = page("1.0.0", FLAGS, SUBCOMMANDS);
+        assert!(page.starts_with(".TH ARGEN 1 \"\" \"argen 1.0.0\" \"User Commands\"\n"));
+        assert!(page.contains(
+            ".TP\n\\fBargen check\\fR [\\-\\-strict] [\\-\\-deny CODES] [\\-\\-backend BACKEND] SPEC.toml\n\
+             list everything wrong with a spec\n"
+        ));
+        assert!(page.contains(".TP\n\\fB\\-D\\fR, \\fB\\-\\-define\\fR \\fIKEY=VALUE\\fR\n"));
+        assert!(page.contains("\\fB\\-\\-format\\fR[=\\fISTYLE\\fR]\n"));
+        assert!(page.contains("(for gen, tests, fuzz, eval)\n"));
+        assert!(page.contains(".TP\n\\fB\\-\\-color\\fR \\fIWHEN\\fR\ncolor diagnostics"));
+    }
+}
@@ -0,0 +1,362 @@
+// Argen
+// Copyright (C) 2017 Matt Lee <matt@kynelee.com>, Lucas Morales <lucas@lucasem.com>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `argen new`: a starter spec for a new program, from a few questions
+//! about it, commented so a first-time user can see what to change.
+
+use crate::codegen::SCHEMA;
+use std::io::{self, BufRead, Write};
+
+/// An option most programs have, that a new spec can start with.
+struct Common {
+    long: &'static str,
+    short: &'static str,
+    /// The name of its argument, or None for a flag.
+    hint: Option<&'static str>,
+    c_type: &'static str,
+    default: Option<&'static str>,
+    help: &'static str,
+}
+
+impl Common {
+    const fn flag(long: &'static str, short: &'static str, help: &'static str) -> Common {
+        Common {
+            long,
+            short,
+            hint: None,
+            c_type: "int",
+            default: None,
+            help,
+        }
+    }
+}
+
+const COMMON: [Common; 5] = [
+    Common::flag("verbose", "v", "say more about what is going on"),
+    Common::flag("quiet", "q", "say only what goes wrong"),
+    Common {
+        hint: Some("FILE"),
+        c_type: "char*",
+        ..Common::flag("output", "o", "write to FILE instead of standard output")
+    },
+    Common::flag("dry-run", "n", "show what would be done, without doing it"),
+    Common {
+        hint: Some("N"),
+        default: Some("1"),
+        ..Common::flag("jobs", "j", "run N jobs at once")
+    },
+];
+
+/// The options a new spec starts with when asked for none in particular.
+const DEFAULT_OPTIONS: &str = "verbose";
+
+/// A positional argument, as given in answer: `FILE`, `FILE?` when it may be
+/// left out, or `FILE...` when it takes the rest.
+#[derive(Clone, Debug, PartialEq)]
+struct Positional {
+    name: String,
+    optional: bool,
+    multi: bool,
+}
+
+/// What a new program is, as answered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Answers {
+    pub name: String,
+    description: String,
+    positionals: Vec<Positional>,
+    options: Vec<&'static str>,
+}
+
+/// A name made into a C identifier: lowercase, with anything else as _.
+fn c_var(name: &str) -> String {
+    let var: String = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | '0'..='9' => c,
+            'A'..='Z' => c.to_ascii_lowercase(),
+            _ => '_',
+        })
+        .collect();
+    match var.starts_with(|c: char| c.is_ascii_digit()) {
+        true => format!("_{}", var),
+        false => var,
+    }
+}
+
+/// The positional arguments, from words such as `INPUT OUTPUT? FILES...`.
+fn positionals(answer: &str) -> Result<Vec<Positional>, String> {
+    let words: Vec<&str> = answer.split_whitespace().collect();
+    let mut positionals = vec![];
+    for (n, word) in words.iter().enumerate() {
+        let (name, optional, multi) = match (word.strip_suffix("..."), word.strip_suffix('?')) {
+            (Some(name), _) => (name, false, true),
+            (None, Some(name)) => (name, true, false),
+            (None, None) => (*word, false, false),
+        };
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(format!("{} is not a name for an argument", word));
+        }
+        if multi && n + 1 < words.len() {
+            return Err(format!(
+                "only the last argument can take several, not {}",
+                name
+            ));
+        }
+        if !optional && !multi && positionals.iter().any(|p: &Positional| p.optional) {
+            return Err(format!(
+                "{} is required, so it can't come after an argument that isn't",
+                name
+            ));
+        }
+        positionals.push(Positional {
+            name: name.to_ascii_uppercase(),
+            optional,
+            multi,
+        });
+    }
+    Ok(positionals)
+}
+
+/// The common options named, by long name, with commas or spaces between
+/// them, or none.
+fn options(answer: &str) -> Result<Vec<&'static str>, String> {
+    let mut options = vec![];
+    for word in answer.split([',', ' ']).filter(|word| !word.is_empty()) {
+        if word == "none" {
+            continue;
+        }
+        match COMMON.iter().find(|common| common.long == word) {
+            Some(common) if !options.contains(&common.long) => options.push(common.long),
+            Some(_) => {}
+            None => return Err(format!("{} is not one of the options offered", word)),
+        }
+    }
+    Ok(options)
+}
+
+/// Asks a question until the answer is one parse takes; an empty answer,
+/// or none at the end of the input, is the default.
+fn ask<T>(
+    input: &mut dyn BufRead,
+    prompts: &mut dyn Write,
+    question: &str,
+    default: &str,
+    parse: &dyn Fn(&str) -> Result<T, String>,
+) -> io::Result<T> {
+    loop {
+        match default {
+            "" => write!(prompts, "{}: ", question)?,
+            default => write!(prompts, "{} [{}]: ", question, default)?,
+        }
+        prompts.flush()?;
+        let mut line = String::new();
+        let answer = match input.read_line(&mut line)? {
+            0 => {
+                writeln!(prompts)?;
+                default
+            }
+            _ => match line.trim() {
+                "" => default,
+                answer => answer,
+            },
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            // with nothing more to read, there is no asking again
+            Err(e) if answer == default => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, e))
+            }
+            Err(e) => writeln!(prompts, "{}", e)?,
+        }
+    }
+}
+
+/// Asks what the new program is called, unless it is named already, what it
+/// does, what arguments it takes, and which common options it has.
+pub fn questions(
+    name: Option<&str>,
+    default_name: &str,
+    input: &mut dyn BufRead,
+    prompts: &mut dyn Write,
+) -> io::Result<Answers> {
+    let program = |answer: &str| match answer {
+        "" => Err(String::from("the program needs a name")),
+        name if name.contains(['/', '\\']) || name.starts_with('.') => {
+            Err(format!("{} is not a name for a program", name))
+        }
+        name => Ok(name.to_owned()),
+    };
+    let name = match name {
+        Some(name) => program(name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+        None => ask(input, prompts, "Program name", default_name, &program)?,
+    };
+    let description = ask(
+        input,
+        prompts,
+        "What does it do, in a line",
+        "",
+        &|answer| Ok(answer.to_owned()),
+    )?;
+    let positionals = ask(
+        input,
+        prompts,
+        "Arguments, in order (NAME, NAME? if it may be left out, NAME... for the rest)",
+        "",
+        &positionals,
+    )?;
+    let offered: Vec<&str> = COMMON.iter().map(|common| common.long).collect();
+    let options = ask(
+        input,
+        prompts,
+        &format!("Options, of {} (or none)", offered.join(", ")),
+        DEFAULT_OPTIONS,
+        &options,
+    )?;
+    Ok(Answers {
+        name,
+        description,
+        positionals,
+        options,
+    })
+}
+
+/// The starter spec: settings to consider, then the options and arguments
+/// asked for, with comments saying what their fields do.
+pub fn spec(answers: &Answers) -> String {
+    let mut spec = format!("# argen spec for {}", answers.name);
+    if !answers.description.is_empty() {
+        spec.push_str(&format!(": {}", answers.description));
+    }
+    spec.push_str(&format!(
+        "\n#\n\
+         # Generate the parser from it with\n\
+         #   argen -o {0}.c {0}.toml\n\
+         # and see argen's README for every field a spec can have.\n\
+         \n\
+         # Settings for the whole program go first, before any [[...]] table.\n\
+         # The version of the spec format, which `argen upgrade` keeps current:\n\
+         schema = {2}\n\
+         #args_struct = \"{1}_args\"  # parse into a struct, not globals\n\
+         #on_error = \"return\"  # let the caller handle a bad command line\n\
+         #emit_main = false  # leave main to you\n",
+        answers.name,
+        c_var(&answers.name),
+        SCHEMA
+    ));
+    for (n, long) in answers.options.iter().enumerate() {
+        let option = COMMON
+            .iter()
+            .find(|common| common.long == *long)
+            .expect("one of the common options");
+        spec.push('\n');
+        if n == 0 {
+            spec.push_str(
+                "# Options, each with a long name and, if you like, a short one. A\n\
+                 # flag takes no argument, and is 1 when given and 0 when not.\n",
+            );
+        }
+        spec.push_str(&format!(
+            "[[non_positional]]\n\
+             c_var = \"{}\"\n\
+             c_type = \"{}\"\n\
+             long = \"{}\"\n\
+             short = \"{}\"\n\
+             help_descr = \"{}\"\n",
+            c_var(long),
+            option.c_type,
+            long,
+            option.short,
+            option.help
+        ));
+        match option.hint {
+            Some(hint) => spec.push_str(&format!("help_name = \"{}\"\n", hint)),
+            None => spec.push_str("flag = true\n"),
+        }
+        if let Some(default) = option.default {
+            spec.push_str(&format!("default = \"{}\"\n", default));
+        }
+    }
+    for (n, positional) in answers.positionals.iter().enumerate() {
+        spec.push('\n');
+        if n == 0 {
+            spec.push_str(
+                "# Arguments, in order: a required one can't follow one that isn't,\n\
+                 # and only the last can take several (multi = true), as a char**\n\
+                 # with its count in <c_var>__size.\n",
+            );
+        }
+        spec.push_str(&format!(
+            "[[positional]]\n\
+             c_var = \"{}\"\n\
+             c_type = \"char*\"\n\
+             help_name = \"{}\"\n\
+             help_descr = \"the {}\"\n",
+            c_var(&positional.name),
+            positional.name,
+            positional
+                .name
+                .to_ascii_lowercase()
+                .replace(['_', '-'], " ")
+        ));
+        match (positional.optional, positional.multi) {
+            (false, false) => spec.push_str("required = true\n"),
+            (_, true) => spec.push_str("multi = true\n"),
+            (true, false) => {}
+        }
+    }
+    spec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{positionals, questions, spec};
+    use crate::codegen::Spec;
+
+    #[test]
+    fn scaffold() {
+        let mut input = "\nfrob things\nINPUT OUT? files...\njobs, quiet\n".as_bytes();
+        let mut prompts = vec![];
+        let answers = questions(None, "frob", &mut input, &mut prompts).unwrap();
+        assert_eq!(answers.name, "frob");
+        let prompts = String::from_utf8(prompts).unwrap();
+        assert!(prompts.starts_with("Program name [frob]: What does it do, in a line: "));
+        let spec = spec(&answers);
+        assert!(spec.starts_with("# argen spec for frob: frob things\n"));
+        let s = Spec::from_str(&spec).unwrap();
+        assert!(s.lints().is_empty());
+        let code = s.gen();
+        assert!(code.contains("--jobs"));
+        assert!(code.contains("INPUT [OUT [FILES...]]"));
+        // a bad answer is asked again; with no more input, the defaults do
+        let mut input = "thing\n\nA... B\nA B\n".as_bytes();
+        let mut prompts = vec![];
+        let answers = questions(None, "frob", &mut input, &mut prompts).unwrap();
+        assert!(String::from_utf8(prompts)
+            .unwrap()
+            .contains("only the last argument can take several, not A\n"));
+        assert_eq!(answers.name, "thing");
+        assert_eq!(answers.options, ["verbose"]);
+        assert!(positionals("A? B").is_err());
+        let s = Spec::from_str(&super::spec(&answers)).unwrap();
+        assert!(s.gen().contains("--verbose"));
+        assert!(questions(Some("../x"), "", &mut "".as_bytes(), &mut vec![]).is_err());
+    }
+}